@@ -63,6 +63,10 @@ def nc_to_dataframe(
     allow_undefined_variables: bool = False,
     flatten_tolerance: float | None = None,
     include_line_numbers: bool = False,
+    subprogram_dirs: "list[str | os.PathLike] | None" = None,
+    cycles: dict[str, str] | None = None,
+    keep_contour: bool = False,
+    unit: str = "mm",
 ) -> tuple[pl.DataFrame, dict]:
     """
     Parses Sinumerik-flavored NC G-code and converts it into a Polars DataFrame along with the final state.
@@ -91,10 +95,12 @@ def nc_to_dataframe(
     allow_undefined_variables: bool, optional
         If True, allows undefined variables to be used in expressions with a value of 0 [default: False].
     flatten_tolerance: float | None, optional
-        When set, flatten curved motions (G2/G3 arcs and ASPLINE/BSPLINE/CSPLINE
-        splines) into runs of G1 moves whose polyline stays within this maximum
-        deviation (in path units, i.e. mm) of the true curve. The interpolation
-        parameters (I/J/K/CR, PW/SD/PL) are consumed and do not appear in the
+        When set, flatten curved motions (G2/G3 arcs, CIP/CT circles,
+        ASPLINE/BSPLINE/CSPLINE splines, POLY polynomials and INVCW/INVCCW
+        involutes) into runs of G1 moves whose polyline
+        stays within this maximum deviation (in path units, i.e. mm) of the true
+        curve. The interpolation parameters (I/J/K/CR, I1/J1/K1, PW/SD/PL,
+        PO_...) are consumed and do not appear in the
         output. Default None (curves pass through untouched).
     include_line_numbers: bool, optional
         If True, prepend a `line_no` (Int64) column giving the 1-based source
@@ -102,6 +108,34 @@ def nc_to_dataframe(
         across a jump, matching the streaming `nc_to_rows`. Never
         forward-filled; `dataframe_to_nc` ignores it. Default False (column
         absent, output schema unchanged).
+    subprogram_dirs: list[str | os.PathLike] | None, optional
+        Directories searched, in order, for the subprograms the program calls
+        (`L100`, `CONTOUR(1, 2)`, `CALL "NAME"`): the first `NAME.SPF`, else
+        `NAME.MPF`, runs with its PROC parameters bound to the call's
+        arguments, and its rows appear in the output (carrying the
+        subprogram's own line numbers). A `CALL` or `L<number>` program that
+        is not found raises `NcError` with kind "subprogram_not_found"; other
+        unknown names stay in the `non_returning_function_call` column.
+        Default None (only the built-in cycles, see `cycles`, are expanded).
+    cycles: dict[str, str] | None, optional
+        Cycle implementations by name, each the NC source of a `PROC` (e.g.
+        ``{"CYCLE81": "PROC CYCLE81(REAL RTP, ...)\\n...\\nM17"}``). They take
+        precedence over the search path and over the built-in expansions of
+        the standard cycles (CYCLE81..CYCLE84, POCKET3, SLOT1, CYCLE800),
+        which run without any search path. Default None (built-ins only).
+    keep_contour: bool, optional
+        Under tool radius compensation (G41/G42 with a D whose radius the
+        initial state sets, e.g. ``$TC_DP6[1,1]=5``) the axis columns carry
+        the tool-centre path. If True, the programmed contour is kept in the
+        ``CONTOUR_X``/``CONTOUR_Y``/``CONTOUR_Z`` columns next to it (the
+        corner point on generated corner rows). Default False.
+    unit: str, optional
+        The unit of the lengths in the output, ``"mm"`` or ``"inch"``. Programs
+        switching to inch or metric (G70/G71, G700/G710) are converted to it:
+        G70/G71 convert the geometry (linear axes, I/J/K, CR, RP, TRANS, ...),
+        G700/G710 also the feed, the tool and settable offsets and the
+        ``$AA_IW``/``$AA_IM`` reads. The final state reports it as ``unit``.
+        Default "mm".
 
     Returns:
     --------
@@ -152,6 +186,10 @@ def nc_to_dataframe(
         input_is_path,
         flatten_tolerance,
         include_line_numbers,
+        subprogram_dirs=subprogram_dirs,
+        cycles=cycles,
+        keep_contour=keep_contour,
+        unit=unit,
     )
     # pl.DataFrame wraps each Arrow record batch via __arrow_c_array__ (polars
     # >= 1.3), no pyarrow needed. Exhaust the iterator before reading .state.
@@ -261,6 +299,9 @@ def sanitize_dataframe(
     # dataframe_to_nc.
     if "line_no" in df.columns:
         df = df.drop("line_no")
+    # The spindle columns are derived from S, M3/M4/M5 and G96 (which stay in
+    # the table), not words of their own.
+    df = df.drop([c for c in ("spindle_rpm", "spindle_dir") if c in df.columns])
 
     modal = [g["short_name"] for g in GGroups.g_groups if g["effectiveness"] == "modal"]
     non_modal = [g["short_name"] for g in GGroups.g_groups if g["effectiveness"] != "modal"]
@@ -270,12 +311,18 @@ def sanitize_dataframe(
     ]
     string_columns = {*modal, *non_modal, "T", "non_returning_function_call", "comment"}
     # Block addresses: value columns, but never forward-filled. These are the
-    # circular/helical interpolation parameters (I/J/K arc-centre offsets and
-    # the CR radius form) and the spline programming addresses (PW/SD/PL).
+    # circular/helical interpolation parameters (I/J/K arc-centre offsets,
+    # the I1/J1/K1 CIP intermediate point and the CR radius form), the
+    # spline programming addresses (PW/SD/PL), the resolved POLY
+    # polynomial coefficients (PO_X_1..PO_Z_5, denominator PO_1..PO_5) and
+    # the thread start angle SF and thread columns of G33/G34/G35 blocks.
     # Each value belongs only to the block that programs it, so it must not be
     # carried forward. Keep this list identical (and in the same order) to the
     # Rust BLOCK_ADDRESSES constant so both output layers agree on columns.
-    block_addresses = ["I", "J", "K", "CR", "PW", "SD", "PL"]
+    block_addresses = ["I", "J", "K", "I1", "J1", "K1", "CR", "PW", "SD", "PL"]
+    block_addresses += [f"PO_{axis}_{n}" for axis in "XYZ" for n in range(1, 6)]
+    block_addresses += [f"PO_{n}" for n in range(1, 6)]
+    block_addresses += ["SF", "THREAD_LEAD", "THREAD_LEAD_CHANGE", "THREAD_REVOLUTIONS"]
 
     # Value columns: anything that is not a known string/list column.
     value_columns = [
@@ -407,6 +454,10 @@ def nc_to_rows(
     axis_index_map: dict[str, int] | None = None,
     allow_undefined_variables: bool = False,
     flatten_tolerance: float | None = None,
+    subprogram_dirs: "list[str | os.PathLike] | None" = None,
+    cycles: dict[str, str] | None = None,
+    keep_contour: bool = False,
+    unit: str = "mm",
 ):
     """Interpret an NC program lazily, yielding one row at a time.
 
@@ -439,6 +490,11 @@ def nc_to_rows(
     promptly instead of finishing the rest of the program. There is no
     separate ``.cancel()``/``.close()`` - drop is the whole mechanism.
 
+    ``subprogram_dirs`` is the subprogram search path and ``cycles`` the
+    registered cycle implementations, ``keep_contour`` keeps the
+    programmed contour under tool radius compensation and ``unit`` is the
+    output unit, as for :func:`nc_to_dataframe`.
+
     Errors raise ``ValueError`` from ``next()`` when reached. After the
     iterator is exhausted, its ``state`` attribute holds the final
     interpreter state (axes, symbol_table, translation).
@@ -469,6 +525,10 @@ def nc_to_rows(
         allow_undefined_variables,
         input_is_path,
         flatten_tolerance,
+        subprogram_dirs,
+        cycles,
+        keep_contour,
+        unit,
     )
 
 
@@ -535,6 +595,10 @@ def nc_to_batches(
     flatten_tolerance: float | None = None,
     include_line_numbers: bool = False,
     include_variables: bool = False,
+    subprogram_dirs: "list[str | os.PathLike] | None" = None,
+    cycles: dict[str, str] | None = None,
+    keep_contour: bool = False,
+    unit: str = "mm",
 ) -> _BatchIterator:
     """Interpret an NC program into a stream of columnar polars DataFrames.
 
@@ -571,6 +635,11 @@ def nc_to_batches(
     batch-path equivalent of the per-row ``variables`` dict :func:`nc_to_rows`
     yields; replaying the events reconstructs the symbol table at any row.
 
+    ``subprogram_dirs`` is the subprogram search path and ``cycles`` the
+    registered cycle implementations, ``keep_contour`` keeps the
+    programmed contour under tool radius compensation and ``unit`` is the
+    output unit, as for :func:`nc_to_dataframe`.
+
     Example:
     --------
     >>> batches = nc_to_batches("G1 X10\\nX20 Y5", batch_size=1)
@@ -595,5 +664,9 @@ def nc_to_batches(
         flatten_tolerance,
         include_line_numbers,
         include_variables,
+        subprogram_dirs,
+        cycles,
+        keep_contour,
+        unit,
     )
     return _BatchIterator(inner)
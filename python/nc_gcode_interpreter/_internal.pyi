@@ -30,6 +30,10 @@ def nc_to_rows(
     allow_undefined_variables: bool = False,
     input_is_path: bool = False,
     flatten_tolerance: Optional[float] = None,
+    subprogram_dirs: Optional[List[Any]] = None,
+    cycles: Optional[Dict[str, str]] = None,
+    keep_contour: bool = False,
+    unit: str = "mm",
 ) -> Iterator[Tuple[Any, ...]]:
     """Interpret an NC program lazily into ``(line_no, row[, variables])`` tuples."""
     ...
@@ -48,11 +52,16 @@ def nc_to_batches(
     flatten_tolerance: Optional[float] = None,
     include_line_numbers: bool = False,
     include_variables: bool = False,
+    subprogram_dirs: Optional[List[Any]] = None,
+    cycles: Optional[Dict[str, str]] = None,
+    keep_contour: bool = False,
+    unit: str = "mm",
 ) -> Any:
     """Interpret an NC program into an iterator of columnar polars DataFrames.
 
     The returned iterator exposes ``state`` (dict with ``axes``,
-    ``symbol_table``, ``translation`` and ``string_table``) once exhausted, and
+    ``symbol_table``, ``translation``, ``string_table``, ``work_offset``,
+    ``work_offset_translation`` and ``unit``) once exhausted, and
     - when ``include_variables`` is set - ``variable_events`` (an Arrow batch of
     ``row_idx`` / ``name_id`` / ``value``) and ``variable_names`` (list[str]).
     """
@@ -148,7 +148,15 @@ def test_string_table_round_trips_through_state_dict():
     _df, state = nc_to_dataframe(program)
     assert state["string_table"] == {"MSG": "HELLO WORLD", "TAG": "ABC"}
     # The existing numeric sub-tables are untouched by the new key.
-    assert set(state) == {"axes", "symbol_table", "translation", "string_table"}
+    assert set(state) == {
+        "axes",
+        "symbol_table",
+        "translation",
+        "string_table",
+        "work_offset",
+        "work_offset_translation",
+        "unit",
+    }
     # The string variable never leaks into the numeric symbol table.
     assert "MSG" not in state["symbol_table"]
 
@@ -52,6 +52,70 @@ def test_arc_flattening_line_numbers_point_at_source_block():
     assert (df["F"] == 1000.0).all()
 
 
+def test_opening_angle_arc_gets_its_centre(capfd):
+    # A quarter circle (AR=90) from (0, 0) to (10, 0) turns about (5, 5).
+    df, _state = nc_to_dataframe("G1 X0 Y0 F100\nG3 X10 Y0 AR=90\n", disable_forward_fill=True)
+    err = capfd.readouterr().err
+    assert "'AR'" not in err, err
+    assert df["I"].to_list()[-1] == pytest.approx(5.0) and df["J"].to_list()[-1] == pytest.approx(5.0)
+
+
+def test_cip_circle_through_its_intermediate_point(capfd):
+    df, _state = nc_to_dataframe("G1 X0 Y0 F100\nCIP X20 Y0 I1=10 J1=5\n", flatten_tolerance=0.1)
+    assert capfd.readouterr().err == ""
+    assert df["X"].to_list()[-1] == 20.0 and df["Y"].max() == pytest.approx(5.0)
+    assert "I1" not in df.columns
+
+
+def test_ct_circle_continues_tangentially(capfd):
+    # After a move along +X the arc to (20, 10) is the quarter circle about
+    # (10, 10).
+    df, _state = nc_to_dataframe("G1 X0 Y0 F100\nX10\nCT X20 Y10\n", flatten_tolerance=0.1)
+    assert capfd.readouterr().err == ""
+    arc = df.filter(pl.col("flattened") == 1.0)
+    assert arc.height > 0
+    for x, y in zip(arc["X"].to_list(), arc["Y"].to_list()):
+        assert math.hypot(x - 10.0, y - 10.0) == pytest.approx(10.0)
+
+
+def test_involute_unwinds_from_its_base_circle(capfd):
+    # Base circle of radius 10 about the origin, the AR= form.
+    df, _state = nc_to_dataframe(
+        "G1 X10 Y0 F100\nINVCCW I=AC(0) J=AC(0) CR=10 AR=90\n", flatten_tolerance=0.1
+    )
+    assert capfd.readouterr().err == ""
+    assert "CR" not in df.columns
+    assert df["X"].to_list()[-1] == pytest.approx(5.0 * math.pi) and df["Y"].to_list()[-1] == pytest.approx(10.0)
+    for x, y in zip(df["X"].to_list()[1:], df["Y"].to_list()[1:]):
+        unwound = math.sqrt(max(math.hypot(x, y) ** 2 / 100.0 - 1.0, 0.0))
+        assert math.atan2(y, x) + math.atan(unwound) - unwound == pytest.approx(0.0, abs=1e-9)
+
+
+def test_g33_thread_flattens_as_a_straight_move(capfd):
+    # K is the lead, not an arc centre, and with the spindle turning the
+    # row's F is the thread feed.
+    df, _state = nc_to_dataframe(
+        "G1 X20 Z5 F300 S1000 M3\nG33 Z-30 K1.5\nG1 X30\n", flatten_tolerance=0.1
+    )
+    assert capfd.readouterr().err == ""
+    assert "K" not in df.columns
+    assert df["THREAD_LEAD"].to_list()[1] == 1.5 and df["F"].to_list()[1:] == [1500.0, 300.0]
+
+
+def test_polar_end_point_is_cartesian(capfd):
+    df, _state = nc_to_dataframe("G1 X0 Y0 F100\nG111 X20 Y20\nG1 AP=90 RP=30\n")
+    err = capfd.readouterr().err
+    assert "'AP'" not in err and "'RP'" not in err, err
+    assert df["X"].to_list()[-1] == pytest.approx(20.0) and df["Y"].to_list()[-1] == 50.0
+
+
+def test_g91_positions_accumulate(capfd):
+    df, _state = nc_to_dataframe("G1 X10 Y10 F100\nG91\nX5 Y5\nX5 Y5\n")
+    err = capfd.readouterr().err
+    assert "G91" not in err, err
+    assert df["X"].to_list()[-1] == 20.0 and df["Y"].to_list()[-1] == 20.0
+
+
 def test_spline_flattening_dataframe():
     df, _ = nc_to_dataframe(SPLINE_PROGRAM, flatten_tolerance=0.05)
     assert set(df["gg01_motion"].to_list()) == {"G1"}
@@ -63,6 +127,43 @@ def test_spline_flattening_dataframe():
     assert abs(spline_end["Y"][0]) < 1e-9
 
 
+def test_spline_end_conditions():
+    # BTAN leaves along the preceding move (+X), ETAN enters the following
+    # one (-Y); BNAT/ENAT start and end elsewhere.
+    def chords(conditions):
+        program = f"G1 X-10 Y0 F100\nX0\nCSPLINE {conditions} X10 Y10\nX20 Y0\nG1 Y-10\n"
+        df, _ = nc_to_dataframe(program, flatten_tolerance=0.001)
+        xs, ys = df["X"].to_list()[1:-1], df["Y"].to_list()[1:-1]
+        first = math.atan2(ys[1] - ys[0], xs[1] - xs[0])
+        last = math.atan2(ys[-1] - ys[-2], xs[-1] - xs[-2])
+        return first, last
+
+    first, last = chords("BTAN ETAN")
+    assert first == pytest.approx(0.0, abs=0.05) and last == pytest.approx(-math.pi / 2, abs=0.05)
+    first, last = chords("BNAT ENAT")
+    assert first > 0.3 and last > -1.3
+
+
+def test_splinepath_followers_move_linearly():
+    # X/Y form the spline path; Z is no path axis and follows linearly.
+    program = "SPLINEPATH(1, X, Y)\nG1 X0 Y0 Z0 F100\nCSPLINE X10 Y10 Z10\nX20 Y0 Z20\nG1 X30\n"
+    df, _ = nc_to_dataframe(program, flatten_tolerance=0.01)
+    spline = df.filter((pl.col("X") > 0.0) & (pl.col("X") < 20.0))
+    assert spline.height > 4
+    assert (spline["Z"] - spline["X"]).abs().max() > 1e-3  # Z follows the path, not X
+    assert (spline["Z"].diff().drop_nulls() >= 0.0).all()
+
+
+def test_rational_poly_traces_a_quarter_circle(capfd):
+    df, _state = nc_to_dataframe(
+        "G1 X10 Y0 F100\nPOLY PO[X]=(0,-10) PO[Y]=(10) PO[]=(2,1)\n", flatten_tolerance=0.1
+    )
+    assert capfd.readouterr().err == ""
+    assert not any(c.startswith("PO_") for c in df.columns)
+    for x, y in zip(df["X"].to_list()[1:], df["Y"].to_list()[1:]):
+        assert math.hypot(x, y) == pytest.approx(10.0)
+
+
 def test_tighter_tolerance_more_rows():
     coarse, _ = nc_to_dataframe(ARC_PROGRAM, flatten_tolerance=1.0)
     fine, _ = nc_to_dataframe(ARC_PROGRAM, flatten_tolerance=0.01)
@@ -344,35 +445,29 @@ def test_view_toolpath_nozzle_and_follow():
 
 
 def test_unsupported_constructs_warn_loudly(capfd):
-    # AR= (opening-angle arc) is not interpreted: it must warn, once per run,
-    # instead of silently producing a straight line.
-    nc_to_dataframe("G1 X0 Y0 F100\nG2 X50 Y0 AR=105\nG2 X100 Y0 AR=105\n")
-    err = capfd.readouterr().err
-    assert err.count("'AR'") == 1, err
-    assert "not interpreted" in err
-
-    # Polar coordinates (AP=/RP=) likewise.
-    nc_to_dataframe("G1 X0 Y0 F100\nG111 X20 Y20\nG1 AP=45 RP=30\n")
-    err = capfd.readouterr().err
-    assert "'AP'" in err and "'RP'" in err
-
-    # G91 incremental dimensioning is parsed but not applied: loud warning.
-    nc_to_dataframe("G1 X10 Y10 F100\nG91\nX5 Y5\nX5 Y5\n")
-    err = capfd.readouterr().err
-    assert err.count("G91 incremental dimensioning") == 1, err
-
-    # The CIP intermediate-point form (I1=/J1=) fails to parse - a loud
-    # error, not a silent misread.
-    with pytest.raises(ValueError, match="line 2"):
-        nc_to_dataframe("G1 X0 Y0 F100\nCIP X20 Y0 I1=10 J1=5\n")
-    capfd.readouterr()
-
-    # A CIP block that does parse passes through flattening with a warning
-    # naming the word.
+    # A CIP block without an intermediate point passes through flattening
+    # with a warning.
     nc_to_dataframe("G1 X0 Y0 F100\nCIP X20 Y0 I10 J5\n", flatten_tolerance=0.1)
     err = capfd.readouterr().err
-    assert "CIP" in err
+    assert "intermediate point" in err
 
     # Supported constructs stay quiet.
     nc_to_dataframe(ARC_PROGRAM, flatten_tolerance=0.1)
     assert capfd.readouterr().err == ""
+
+
+def test_tool_radius_compensation_keeps_the_contour():
+    # G42 on a clockwise arc: the tool centre runs 2 mm inside the
+    # programmed circle, which keep_contour keeps alongside.
+    df, _state = nc_to_dataframe(
+        "G0 X-20 Y0\nG42 D1 G1 X0 Y0 F100\nG2 X20 Y0 CR=10\nG40 G1 X40 Y0\n",
+        initial_state="$TC_DP6[1,1]=2\n",
+        flatten_tolerance=0.01,
+        keep_contour=True,
+    )
+    arc = df.filter(pl.col("flattened") == 1.0)
+    assert arc.height > 5
+    for x, y in arc.select("X", "Y").iter_rows():
+        assert math.hypot(x - 10.0, y) == pytest.approx(8.0, abs=0.01)
+    assert df["X"].to_list()[1] == pytest.approx(2.0)
+    assert df["CONTOUR_X"].to_list()[1] == 0.0 and df["CONTOUR_X"].to_list()[-1] == 40.0
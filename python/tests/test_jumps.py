@@ -1,5 +1,6 @@
 """Program jumps to jump markers: GOTOB, GOTOF, GOTO, GOTOC, GOTOS,
-IF ... GOTO, CASE ... OF ... DEFAULT (NC programming manual 4.1.5)."""
+IF ... GOTO, CASE ... OF ... DEFAULT and the program section repetitions
+REPEAT/REPEATB (NC programming manual 4.1.5)."""
 
 import pytest
 from nc_gcode_interpreter import nc_to_dataframe
@@ -200,3 +201,17 @@ def test_label_names_may_shadow_keyword_prefixes():
     """A label starting with a keyword (GOTO_END) must parse as a label."""
     df, _state = nc_to_dataframe("GOTOF GOTO_END\nX999\nGOTO_END: X1")
     assert df["X"].to_list() == [1.0]
+
+
+def test_section_repetition_returns_after_the_repeat():
+    """REPEAT <label> P=n reruns the blocks from the label up to the REPEAT (4.1.5.4)."""
+    program = "LAB1: X1\nX2\nREPEAT LAB1 P=2\nREPEATB LAB1\nX3"
+    df, _state = nc_to_dataframe(program)
+    assert df["X"].to_list() == [1.0, 2.0, 1.0, 2.0, 1.0, 2.0, 1.0, 3.0]
+
+
+def test_section_between_two_labels():
+    program = "R1=0\nSTART: R1=R1+1\nENDLABEL: X=R1\nY1\nREPEAT START ENDLABEL P=2\nN10 X9"
+    df, state = nc_to_dataframe(program, disable_forward_fill=True)
+    assert df["X"].drop_nulls().to_list() == [1.0, 2.0, 3.0, 9.0]
+    assert state["symbol_table"]["R1"] == 3.0
@@ -0,0 +1,21 @@
+"""DEFINE ... AS macros expand textually in the blocks that use them."""
+
+import pytest
+from nc_gcode_interpreter import NcError, nc_to_dataframe
+
+
+def test_macro_words_expand():
+    df, state = nc_to_dataframe(
+        "DEFINE M_SPIN AS M3 S1000\nDEFINE LIFT AS G0 Z=IC(5)\nG1 X0 Z0 F100\nX10 M_SPIN\nLIFT\n",
+        disable_forward_fill=True,
+    )
+    assert df["S"].to_list() == [None, 1000.0, None]
+    assert df["Z"].to_list() == [0.0, None, 5.0]
+    assert df["gg01_motion"].to_list()[-1] == "G0"
+
+
+def test_recursive_macro_raises():
+    with pytest.raises(NcError) as exc:
+        nc_to_dataframe("DEFINE LOOPY AS X1 LOOPY\n")
+    assert exc.value.kind == "recursive_macro"
+    assert exc.value.line == 1
@@ -88,6 +88,35 @@ def test_fast_path_matches_with_extra_axes_and_index_map(tmp_path):
     run_both(program, extra_axes=["ELX"], axis_index_map={"E": 4})
 
 
+def test_fast_path_matches_with_tool_length_compensation():
+    program = "G0 X0 Y0 Z10\nT2\nM6\nD1 Z10\nX5\nD0 Z10"
+    df = run_both(program, initial_state="$TC_DP3[2,1]=100\n", disable_forward_fill=True)
+    assert df["Z"].drop_nulls().to_list() == [10.0, 110.0, 10.0]
+    assert df["T"].drop_nulls().to_list() == ["2"]
+
+
+def test_fast_path_matches_with_inch_output():
+    program = "G0 X0\nG71 X25.4 F100\nG710 X50.8 F254\nG70 X3"
+    df = run_both(program, unit="inch")
+    assert df["X"].to_list() == pytest.approx([0.0, 1.0, 2.0, 3.0])
+    assert df["F"].drop_nulls().to_list() == pytest.approx([100.0, 10.0, 10.0])
+    _df, state = nc_to_dataframe(program, unit="inch")
+    assert state["unit"] == "inch"
+
+
+def test_fast_path_matches_with_diameter_programming():
+    program = "DIAMON G18 G1 X20 Z5\nG91 X4\nDIAM90 X2\nG90 X=RAC(30)\nDIAMOF X50"
+    df = run_both(program)
+    assert df["X"].to_list() == pytest.approx([10.0, 12.0, 14.0, 30.0, 50.0])
+
+
+def test_fast_path_matches_with_spindle_state():
+    program = "G0 X50 Z0\nG96 S200 LIMS=3000 M3\nG1 X10\nG97 S500\nSPOS=90\nM5"
+    df = run_both(program)
+    assert df["spindle_rpm"].to_list() == pytest.approx([None, 636.62, 3000.0, 500.0, 0.0, 0.0], abs=0.01)
+    assert df["spindle_dir"].to_list() == [None, "CW", "CW", "CW", "POS", "STOP"]
+
+
 def test_structured_programs_take_the_full_path():
     """Programs with IF/WHILE bodies bypass the fast path entirely and must
     still work (this exercises the shape gate)."""
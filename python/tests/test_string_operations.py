@@ -77,3 +77,16 @@ def test_whitespace_only_string_literal_keeps_its_content():
     exactly the space the date-zeroing idiom above searches for."""
     _df, state = nc_to_dataframe('DEF STRING[8] STR = " X"\nR1 = INDEX(STR, " ")\n')
     assert state["symbol_table"]["R1"] == 0.0
+
+
+def test_execstring_runs_a_concatenated_block():
+    """EXECSTRING parses its string as a block and runs it in place of the
+    calling one; errors name the generated text."""
+    df, _state = nc_to_dataframe(
+        'DEF REAL POS = 12.5\nEXECSTRING("G1 X" << POS << " F" << 100)\n',
+        disable_forward_fill=True,
+    )
+    assert df["X"].to_list() == [12.5]
+    assert df["F"].to_list() == [100.0]
+    with pytest.raises(ValueError, match="Generated by EXECSTRING: X=UNDEF1"):
+        nc_to_dataframe('EXECSTRING("X=" << "UNDEF1")\n')
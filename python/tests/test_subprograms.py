@@ -0,0 +1,66 @@
+import pytest
+from nc_gcode_interpreter import nc_to_dataframe, nc_to_rows
+
+
+def test_subprogram_call_with_parameters(tmp_path):
+    """A call resolved on `subprogram_dirs` runs the program file; VAR parameters write back."""
+    (tmp_path / "POCKET.SPF").write_text(
+        "PROC POCKET(REAL DEPTH, VAR REAL COUNT) SAVE\nG91 G1 Z=-DEPTH\nCOUNT=COUNT+1\nM17\n"
+    )
+    df, state = nc_to_dataframe(
+        "R1=0\nG90 G1 X0 Z0 F100\nPOCKET(2, R1)\nPOCKET(2, R1)\nX10\n",
+        subprogram_dirs=[tmp_path],
+    )
+    assert df["Z"].drop_nulls().to_list()[-1] == -4.0
+    assert df["X"].to_list()[-1] == 10.0
+    assert state["symbol_table"]["R1"] == 2.0
+
+
+def test_missing_subprogram_raises(tmp_path):
+    with pytest.raises(Exception, match="14011"):
+        nc_to_dataframe('CALL "NOPE"\n', subprogram_dirs=[tmp_path])
+
+
+def test_unknown_call_without_search_path_is_kept():
+    df, _ = nc_to_dataframe("L100\nX1\n")
+    assert "L100" in df["non_returning_function_call"].to_list()
+
+
+def test_modal_call_after_each_position(tmp_path):
+    """MCALL repeats the call after every block that positions an axis, until a bare MCALL."""
+    (tmp_path / "DRILL.SPF").write_text("PROC DRILL(REAL DEPTH)\nG1 Z=-DEPTH\nG0 Z2\nM17\n")
+    df, _ = nc_to_dataframe(
+        "G0 X0 Y0 Z2\nMCALL DRILL(4)\nX10\nY10\nMCALL\nX0 Y0\n",
+        subprogram_dirs=[tmp_path],
+        disable_forward_fill=True,
+    )
+    assert df["Z"].drop_nulls().to_list() == [2.0, -4.0, 2.0, -4.0, 2.0]
+
+
+def test_builtin_cycle_expands_without_search_path():
+    """The standard cycles expand natively; the calling block's line numbers carry over."""
+    df, _ = nc_to_dataframe(
+        "G0 X0 Y0 Z20\nCYCLE81(10, 0, 2, -5)\n",
+        disable_forward_fill=True,
+        include_line_numbers=True,
+    )
+    assert df["Z"].drop_nulls().to_list() == [20.0, 2.0, -5.0, 10.0]
+    assert set(df["line_no"].to_list()[1:]) == {2}
+
+
+def test_registered_cycle_replaces_builtin():
+    df, _ = nc_to_dataframe(
+        "G0 X0 Z20\nCYCLE81(10, 0, 2, -3)\n",
+        cycles={"CYCLE81": "PROC CYCLE81(REAL RTP, REAL RFP, REAL SDIS, REAL DP)\nG1 Z=DP\nM17\n"},
+        disable_forward_fill=True,
+    )
+    assert df["Z"].drop_nulls().to_list() == [20.0, -3.0]
+
+
+def test_registered_cycle_streams_through_nc_to_rows():
+    rows = nc_to_rows(
+        "G0 X0 Z20\nCYCLE81(10, 0, 2, -3)\n",
+        cycles={"CYCLE81": "PROC CYCLE81(REAL RTP, REAL RFP, REAL SDIS, REAL DP)\nG1 Z=DP\nM17\n"},
+        forward_fill=False,
+    )
+    assert [row["Z"] for _line, row in rows if "Z" in row] == [20.0, -3.0]
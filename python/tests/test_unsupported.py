@@ -2,27 +2,6 @@ import pytest
 from nc_gcode_interpreter import nc_to_dataframe
 
 
-@pytest.mark.parametrize(
-    "program",
-    [
-        "MIRROR X0\nX2",  # mirroring frame
-        "AMIRROR X0\nX2",  # additive mirroring frame
-        "ROT RPL=30\nX2",  # rotation frame
-        "AROT RPL=30\nX2",  # additive rotation frame
-        "SCALE X2 Y2\nX2",  # scaling frame
-        "ASCALE X2 Y2\nX2",  # additive scaling frame
-    ],
-    ids=lambda p: p.splitlines()[0],
-)
-def test_unsupported_statements_error_loudly(program):
-    """
-    Statements that would silently corrupt the output (unmodeled frame
-    transformations) must raise instead of being ignored.
-    """
-    with pytest.raises(ValueError, match="not supported"):
-        nc_to_dataframe(program)
-
-
 @pytest.mark.parametrize(
     "program",
     [
@@ -57,6 +36,53 @@ def test_bare_absolute_frame_instruction_deletes_frame():
     assert df["X"].to_list() == [102.0, 3.0]
 
 
+def test_rotation_frames_rotate_the_toolpath():
+    """AROT turns the coordinate system about its current origin: X moves
+    along the rotated axis (manual 3.12.4)."""
+    df, _state = nc_to_dataframe("TRANS X10\nAROT RPL=90\nX2 Y0")
+    assert df["X"][-1] == pytest.approx(10.0)
+    assert df["Y"][-1] == pytest.approx(2.0)
+
+
+def test_scale_and_mirror_frames_transform_the_toolpath():
+    """SCALE multiplies and MIRROR reverses the local coordinates, arc centre
+    offsets and radii included; mirroring one axis of the working plane
+    reverses the arc direction (manual 3.12.5, 3.12.6)."""
+    df, _state = nc_to_dataframe("SCALE X2 Y2\nX3 Y1\nMIRROR X0\nG2 X3 Y1 I1 J0")
+    assert df["X"].to_list() == [6.0, -3.0]
+    assert df["Y"].to_list() == [2.0, 1.0]
+    assert df["gg01_motion"][-1] == "G3"
+    assert df["I"].to_list() == [None, -1.0]
+    assert df["J"].to_list() == [None, 0.0]
+
+    df, _state = nc_to_dataframe("G1 X0 Y0\nSCALE X2 Y2\nG2 X20 Y0 I10 J0\nG3 X0 Y0 CR=10")
+    assert df["X"].to_list() == [0.0, 40.0, 0.0]
+    assert df["I"].to_list() == [None, 20.0, None]
+    assert df["CR"].to_list() == [None, None, 20.0]
+
+
+def test_work_offset_from_the_settable_frame_table():
+    """G54 selects settable frame 1 ($P_UIFR[1], set with CTRANS in the
+    initial state): its offset shifts the output until G500."""
+    df, state = nc_to_dataframe(
+        "G54 X1 Y1\nG500 X1", initial_state="$P_UIFR[1]=CTRANS(X,10,Y,20)"
+    )
+    assert df["X"].to_list() == [11.0, 1.0]
+    assert df["Y"].to_list() == [21.0, 21.0]
+    assert state["work_offset"] is None
+
+    _df, state = nc_to_dataframe("G54 X1", initial_state="$P_UIFR[1]=CTRANS(X,10)")
+    assert state["work_offset"] == "G54"
+    assert state["work_offset_translation"] == {"X": 10.0}
+
+
+def test_g53_retract_lands_in_machine_coordinates():
+    """G53/SUPA suppress the frames for their block only: a tool-change
+    retract is not shifted by the active TRANS (manual 3.12.7)."""
+    df, _state = nc_to_dataframe("TRANS Z10\nG0 Z5\nG53 Z0\nSUPA Z1\nZ5")
+    assert df["Z"].to_list() == [15.0, 0.0, 1.0, 15.0]
+
+
 def test_frame_instruction_mid_block_errors():
     """Frame instructions must be alone in the block; `G1 MIRROR X0` must
     not be interpreted as a G-command plus an axis move to X=0."""
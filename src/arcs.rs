@@ -0,0 +1,248 @@
+//! Circle forms that are resolved when the block ends (manual 3.9):
+//!
+//! * the opening angle `AR=` with either the end point (`G2 X.. Y.. AR=90`)
+//!   or the centre (`G3 I.. J.. AR=90`). The missing part is computed in the
+//!   working plane, so the row carries the usual end point and I/J/K centre
+//!   offsets (relative to the start point) that the flattener and any other
+//!   consumer understand; `AR` itself never appears as a column.
+//! * the intermediate point `I1=`/`J1=`/`K1=` of a `CIP` circle. It follows
+//!   G90/G91 and `AC()`/`IC()` like an end point, an unprogrammed component
+//!   is the start point's, and the row carries it in absolute machine
+//!   coordinates (frame applied), so the circle through start, intermediate
+//!   and end point can be rebuilt from the row alone.
+//!
+//! Under a rotated, scaled or mirrored frame the centre offsets `I`/`J`/`K`
+//! and the radius `CR` of a circle or involute row are output in machine
+//! coordinates too, like its end point: the frame turns the offset vector and
+//! scales the radius.
+
+use crate::errors::ParsingError;
+use crate::frame;
+use crate::output::{CellMap, OutputRows};
+use crate::state::{ColKind, Dimensioning, State};
+use crate::types::Value;
+
+/// The geometry axes by X/Y/Z slot, and their centre-offset and
+/// intermediate-point addresses.
+pub(crate) const GEOMETRY_AXES: [&str; 3] = ["X", "Y", "Z"];
+pub(crate) const CENTRE_OFFSETS: [&str; 3] = ["I", "J", "K"];
+pub(crate) const INTERMEDIATE_POINT: [&str; 3] = ["I1", "J1", "K1"];
+
+/// The circle words of the block being interpreted.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ArcWords {
+    /// `AR=`: the opening angle in degrees.
+    opening_angle: Option<f64>,
+    /// `I1=`/`J1=`/`K1=`: the CIP intermediate point in local coordinates.
+    intermediate: [Option<f64>; 3],
+}
+
+impl ArcWords {
+    /// Forget the previous block's circle words.
+    pub(crate) fn begin_block(&mut self) {
+        self.opening_angle = None;
+        self.intermediate = [None; 3];
+    }
+}
+
+/// Take a programmed `key = value` word that belongs to a circle form
+/// resolved at the end of the block. Returns `false` for any other word.
+pub(crate) fn take_word(key: &str, value: f64, dimensioning: Dimensioning, state: &mut State) -> bool {
+    if key.eq_ignore_ascii_case("AR") {
+        state.arc_words.opening_angle = Some(value);
+        return true;
+    }
+    let Some(slot) = INTERMEDIATE_POINT
+        .iter()
+        .position(|address| address.eq_ignore_ascii_case(key))
+    else {
+        return false;
+    };
+    let incremental = match dimensioning {
+        Dimensioning::Modal => state.incremental,
+        Dimensioning::Incremental => true,
+        Dimensioning::Absolute => false,
+        Dimensioning::Diameter { incremental } | Dimensioning::Radius { incremental } => incremental,
+    };
+    let value = state.as_radius(GEOMETRY_AXES[slot], value, dimensioning);
+    let value = if incremental {
+        state.block_start_local(GEOMETRY_AXES[slot]).unwrap_or(0.0) + value
+    } else {
+        value
+    };
+    state.arc_words.intermediate[slot] = Some(value);
+    true
+}
+
+/// Resolve the block's circle words once all its other words ran, then map
+/// the centre of a circle or involute row into machine coordinates.
+pub(crate) fn finish_block(line_no: usize, output: &mut OutputRows, state: &mut State) -> Result<(), ParsingError> {
+    resolve_words(line_no, output, state)?;
+    centre_to_machine(output, state);
+    Ok(())
+}
+
+/// Turn the local centre offsets `I`/`J`/`K` of a G2/G3 or involute row by
+/// the frame's linear part, and scale its `CR`. A machine offset is output
+/// where the frame couples a programmed one into it.
+fn centre_to_machine(output: &mut OutputRows, state: &State) {
+    let Some(linear) = state.frame_linear().filter(|_| !state.frame_suppressed) else {
+        return;
+    };
+    if state.arc_direction().or(state.involute_direction()).is_none() {
+        return;
+    }
+    let last = output.last_mut().expect("row started");
+    let offsets = CENTRE_OFFSETS.map(|address| match last.get(address) {
+        Some(Value::Float(value)) => Some(*value),
+        _ => None,
+    });
+    if offsets.iter().any(Option::is_some) {
+        for (row, address) in CENTRE_OFFSETS.into_iter().enumerate() {
+            // Like the axes (State::machine_outputs), round-off coefficients
+            // such as cos 90 do not couple an offset in.
+            let terms: Vec<f64> = (0..3)
+                .filter(|&slot| linear[row][slot].abs() >= frame::EPSILON)
+                .filter_map(|slot| offsets[slot].map(|offset| linear[row][slot] * offset))
+                .collect();
+            if terms.is_empty() {
+                last.remove(address);
+            } else {
+                // `+ 0.0` turns a mirrored zero into 0.
+                last.insert(address, Value::Float(terms.iter().sum::<f64>() + 0.0));
+            }
+        }
+    }
+    if let Some(Value::Float(radius)) = last.get("CR") {
+        let [abscissa, ordinate, _] = state.plane.axes();
+        let radius = radius * frame::radius_scale(&linear, [abscissa, ordinate]);
+        last.insert("CR", Value::Float(radius));
+    }
+}
+
+/// Resolve the opening angle `AR=` and the CIP intermediate point.
+fn resolve_words(line_no: usize, output: &mut OutputRows, state: &mut State) -> Result<(), ParsingError> {
+    if state.arc_words.intermediate.iter().any(Option::is_some) {
+        let point: [f64; 3] = std::array::from_fn(|slot| {
+            state.arc_words.intermediate[slot]
+                .or_else(|| state.block_start_local(GEOMETRY_AXES[slot]))
+                .unwrap_or(0.0)
+        });
+        let last = output.last_mut().expect("row started");
+        for (address, value) in INTERMEDIATE_POINT.into_iter().zip(state.machine_point(point)) {
+            last.insert(address, Value::Float(value));
+        }
+    }
+    // AR= of an involute has its own meaning (see crate::involute).
+    if state.involute_direction().is_some() {
+        return crate::involute::finish_block(state.arc_words.opening_angle, line_no, output, state);
+    }
+    let Some(opening_angle) = state.arc_words.opening_angle else {
+        return Ok(());
+    };
+    let error = |message: &str| {
+        ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or(""),
+            "opening-angle arc (AR=)",
+            message,
+        )
+    };
+    let Some(clockwise) = state.arc_direction() else {
+        return Err(error("AR= is only allowed with G2 or G3"));
+    };
+    if !(opening_angle > 0.0 && opening_angle < 360.0) {
+        return Err(error("the opening angle AR= must lie between 0 and 360 degrees"));
+    }
+    let [abscissa, ordinate, _] = state.plane.axes();
+    let last = output.last_mut().expect("row started");
+    if last.get("CR").is_some() {
+        return Err(error("AR= and CR= cannot be programmed in the same block"));
+    }
+    let offset = |slot: usize| match last.get(CENTRE_OFFSETS[slot]) {
+        Some(Value::Float(value)) => Some(*value),
+        _ => None,
+    };
+    let offsets = [offset(abscissa), offset(ordinate)];
+    let start = [abscissa, ordinate].map(|slot| state.block_start_local(GEOMETRY_AXES[slot]).unwrap_or(0.0));
+    let end_programmed = state.moved_in_block(abscissa) || state.moved_in_block(ordinate);
+    // Counter-clockwise angles are positive.
+    let sweep = if clockwise { -opening_angle } else { opening_angle }.to_radians();
+
+    match (end_programmed, offsets.iter().any(Option::is_some)) {
+        (true, true) => Err(error("AR= takes either the end point or the centre (I/J/K), not both")),
+        (false, false) => Err(error("AR= needs the end point or the centre (I/J/K) of the arc")),
+        (true, false) => {
+            let end = [abscissa, ordinate].map(|slot| state.get_axis_local(GEOMETRY_AXES[slot]).unwrap_or(0.0));
+            let (du, dv) = (end[0] - start[0], end[1] - start[1]);
+            let chord = du.hypot(dv);
+            if chord < 1e-9 {
+                return Err(error(
+                    "an arc given by AR= and its end point needs distinct start and end points",
+                ));
+            }
+            // The centre lies on the chord's bisector, left of the chord
+            // for a counter-clockwise arc below 180 degrees; the signed
+            // distance flips sides for the clockwise and the larger arcs.
+            let distance = (chord / 2.0) / (sweep / 2.0).tan();
+            let (nu, nv) = (-dv / chord, du / chord);
+            let centre = [
+                (start[0] + end[0]) / 2.0 + distance * nu,
+                (start[1] + end[1]) / 2.0 + distance * nv,
+            ];
+            last.insert(
+                CENTRE_OFFSETS[abscissa],
+                Value::Float(settle(centre[0] - start[0], chord)),
+            );
+            last.insert(
+                CENTRE_OFFSETS[ordinate],
+                Value::Float(settle(centre[1] - start[1], chord)),
+            );
+            Ok(())
+        }
+        (false, true) => {
+            let [ou, ov] = offsets.map(|offset| offset.unwrap_or(0.0));
+            let (sin, cos) = sweep.sin_cos();
+            // Rotate the start point about the centre by the opening angle.
+            let radius = ou.hypot(ov);
+            let end = [
+                (abscissa, settle(start[0] + ou - (ou * cos - ov * sin), radius)),
+                (ordinate, settle(start[1] + ov - (ou * sin + ov * cos), radius)),
+            ];
+            last.insert(CENTRE_OFFSETS[abscissa], Value::Float(ou));
+            last.insert(CENTRE_OFFSETS[ordinate], Value::Float(ov));
+            move_plane_axes(end, last, state)
+        }
+    }
+}
+
+/// `value`, computed from an arc of size `scale`, with round-off below
+/// [`frame::EPSILON`] of that size and a negative zero set to 0: the centre
+/// of `G2 X10 Y0 AR=180` lies on the chord, at `J` 0 rather than -0.000.
+fn settle(value: f64, scale: f64) -> f64 {
+    if value.abs() < frame::EPSILON * scale.max(1.0) {
+        0.0
+    } else {
+        value
+    }
+}
+
+/// Move the working-plane axes to the local end point `end` (X/Y/Z slot and
+/// value) computed at the end of a block, writing their machine coordinates
+/// into the block's row `last`.
+pub(crate) fn move_plane_axes(
+    end: [(usize, f64); 2],
+    last: &mut CellMap,
+    state: &mut State,
+) -> Result<(), ParsingError> {
+    for (slot, local_value) in end {
+        let Some((ColKind::Axis, key)) = state.resolve_output_key(GEOMETRY_AXES[slot]) else {
+            continue;
+        };
+        state.update_axis(key, local_value)?;
+        state.machine_outputs(key, local_value, |axis, machine_value| {
+            last.insert(axis, Value::Float(machine_value));
+        });
+    }
+    Ok(())
+}
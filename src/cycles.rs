@@ -0,0 +1,883 @@
+//! Built-in expansions of the standard SINUMERIK machining cycles, for
+//! programs calling them without the cycle sources on the subprogram search
+//! path. A call like `CYCLE81(10, 0, 2, -5)` is expanded into plain NC blocks
+//! (G0/G1/G2/G3, dwells, frame instructions) from the documented meaning of
+//! its parameters, and those blocks run like a subprogram. Their output rows
+//! carry the line number of the calling block.
+//!
+//! A subprogram of the same name on the search path, or a cycle registered
+//! with the interpreter, takes precedence over the built-in one.
+//!
+//! | Cycle      | Expansion |
+//! |------------|-----------|
+//! | `CYCLE81`  | drilling, centering: G0 to the safety plane, G1 to depth, G0 to the retraction plane |
+//! | `CYCLE82`  | as `CYCLE81`, with the dwell `DTB` at depth |
+//! | `CYCLE83`  | deep-hole drilling with degressive infeeds, chip breaking (`VARI=0`) or chip removal (`VARI=1`) |
+//! | `CYCLE84`  | rigid tapping as G1 moves at the feed of the pitch (`PIT`, or the metric thread `MPIT`) |
+//! | `POCKET3`  | rectangular pocket: rounded-rectangle rings from the centre outwards, per depth infeed |
+//! | `SLOT1`    | slots on a circle: per slot and infeed, along the slot axis and around its outline |
+//! | `CYCLE800` | swivel plane: `TRANS`/`ATRANS` and `AROT` frames for the axis-by-axis mode |
+//!
+//! The expansions are approximations of what the control does:
+//!
+//! * The drilling and pocketing cycles work along the applicate of the
+//!   active plane (Z for G17) and restore the caller's modal G functions,
+//!   like a `SAVE` subprogram. The machining direction is towards the
+//!   reference plane from the retraction plane.
+//! * There is no tool model, so the pocket and slot paths are those of a
+//!   tool of radius 0: the outline, less the finishing allowance `FAL`.
+//!   Without `MIDA` the pocket rings are a quarter of the width apart.
+//! * Helical and oscillating insertion are expanded as a perpendicular
+//!   infeed. `CYCLE800` does not retract (`_FR`), as the machine limits are
+//!   unknown, and supports only the axis-by-axis mode.
+//! * Tapping is expanded without the spindle reversal; the caller's feed is
+//!   restated after the cycle when it is known.
+
+use crate::frame::WorkingPlane;
+use crate::subprogram::Argument;
+
+/// A built-in cycle: its formal parameters (for the arity check and the
+/// omitted-argument defaults) and how it expands.
+#[derive(Debug)]
+pub(crate) struct Cycle {
+    pub(crate) name: &'static str,
+    pub(crate) parameters: &'static [&'static str],
+    /// The parameters of type STRING.
+    pub(crate) strings: &'static [&'static str],
+    /// Restore the caller's modal G functions and frame on return.
+    pub(crate) save: bool,
+    expand: fn(&Arguments, &CycleContext) -> Result<String, String>,
+}
+
+impl Cycle {
+    /// The NC blocks of a call with `arguments` (one per parameter).
+    pub(crate) fn expand(&self, arguments: &[Argument], context: &CycleContext) -> Result<String, String> {
+        (self.expand)(&Arguments(arguments), context).map_err(|message| format!("{}: {message}", self.name))
+    }
+}
+
+/// What an expansion needs to know about the calling program.
+pub(crate) struct CycleContext {
+    pub(crate) plane: WorkingPlane,
+    /// The active feed, restated after a cycle that programs its own.
+    pub(crate) feed: Option<f64>,
+    /// Upper bound on the infeeds, rings and slots one call generates.
+    pub(crate) iteration_limit: usize,
+}
+
+const CYCLES: &[Cycle] = &[
+    Cycle {
+        name: "CYCLE81",
+        parameters: &["RTP", "RFP", "SDIS", "DP", "DPR"],
+        strings: &[],
+        save: true,
+        expand: cycle81,
+    },
+    Cycle {
+        name: "CYCLE82",
+        parameters: &["RTP", "RFP", "SDIS", "DP", "DPR", "DTB"],
+        strings: &[],
+        save: true,
+        expand: cycle82,
+    },
+    Cycle {
+        name: "CYCLE83",
+        parameters: &[
+            "RTP", "RFP", "SDIS", "DP", "DPR", "FDEP", "FDPR", "DAM", "DTB", "DTS", "FRF", "VARI", "_AXN", "_MDEP",
+            "_VRT", "_DTD", "_DIS1",
+        ],
+        strings: &[],
+        save: true,
+        expand: cycle83,
+    },
+    Cycle {
+        name: "CYCLE84",
+        parameters: &[
+            "RTP", "RFP", "SDIS", "DP", "DPR", "DTB", "SDAC", "MPIT", "PIT", "POSS", "SST", "SST1", "_AXN", "_PTAB",
+            "_TECHNO", "_VARI", "_DAM", "_VRT",
+        ],
+        strings: &[],
+        save: true,
+        expand: cycle84,
+    },
+    Cycle {
+        name: "POCKET3",
+        parameters: &[
+            "_RTP", "_RFP", "_SDIS", "_DP", "_LENG", "_WID", "_CRAD", "_PA", "_PO", "_STA", "_MID", "_FAL", "_FALD",
+            "_FFP1", "_FFD", "_CDIR", "_VARI", "_MIDA", "_AP1", "_AP2", "_AD", "_RAD1", "_DP1",
+        ],
+        strings: &[],
+        save: true,
+        expand: pocket3,
+    },
+    Cycle {
+        name: "SLOT1",
+        parameters: &[
+            "RTP", "RFP", "SDIS", "DP", "DPR", "NUM", "LENG", "WID", "CPA", "CPO", "RAD", "STA1", "INDA", "FFD",
+            "FFP1", "MID", "CDIR", "FAL", "VARI", "MIDF", "FFP2", "SSF", "_FALD", "_STA2", "_DP1",
+        ],
+        strings: &[],
+        save: true,
+        expand: slot1,
+    },
+    Cycle {
+        name: "CYCLE800",
+        parameters: &[
+            "_FR", "_TC", "_ST", "_MODE", "_X0", "_Y0", "_Z0", "_A", "_B", "_C", "_X1", "_Y1", "_Z1", "_DIR", "_FR_I",
+            "_DMODE",
+        ],
+        strings: &["_TC"],
+        save: false,
+        expand: cycle800,
+    },
+];
+
+/// The built-in cycle called `name` (uppercased).
+pub(crate) fn builtin(name: &str) -> Option<&'static Cycle> {
+    CYCLES.iter().find(|cycle| cycle.name == name)
+}
+
+/// The arguments of a call, by parameter position; STRING arguments read as
+/// 0 where a number is wanted.
+struct Arguments<'a>(&'a [Argument]);
+
+impl Arguments<'_> {
+    fn number(&self, index: usize) -> f64 {
+        match self.0.get(index) {
+            Some(Argument::Number(value)) => *value,
+            _ => 0.0,
+        }
+    }
+}
+
+const AXES: [&str; 3] = ["X", "Y", "Z"];
+const CENTRE_WORDS: [&str; 3] = ["I", "J", "K"];
+
+/// A number as an NC value: six decimals at most, no trailing zeros.
+fn num(value: f64) -> String {
+    let text = format!("{value:.6}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// The blocks of an expansion, written in the active plane: positions are
+/// (abscissa, ordinate) pairs and depths are on the applicate.
+struct Blocks {
+    lines: Vec<String>,
+    plane: [usize; 3],
+    /// The (abscissa, ordinate) position reached, for the arc centres.
+    position: (f64, f64),
+    /// A feed to program with the next motion.
+    feed: Option<f64>,
+}
+
+impl Blocks {
+    fn new(plane: WorkingPlane) -> Self {
+        Blocks {
+            lines: Vec::new(),
+            plane: plane.axes(),
+            position: (0.0, 0.0),
+            feed: None,
+        }
+    }
+
+    fn push(&mut self, mut block: String) {
+        if let Some(feed) = self.feed.take() {
+            block.push_str(&format!(" F={}", num(feed)));
+        }
+        self.lines.push(block);
+    }
+
+    /// Program `feed` with the next motion; 0 keeps the active one.
+    fn set_feed(&mut self, feed: f64) {
+        if feed > 0.0 {
+            self.feed = Some(feed);
+        }
+    }
+
+    /// A G0/G1 move along the applicate.
+    fn depth(&mut self, motion: &str, value: f64) {
+        self.push(format!("{motion} {}={}", AXES[self.plane[2]], num(value)));
+    }
+
+    /// A G0/G1 move in the plane.
+    fn to(&mut self, motion: &str, (a, o): (f64, f64)) {
+        self.position = (a, o);
+        let [abscissa, ordinate, _] = self.plane;
+        self.push(format!(
+            "{motion} {}={} {}={}",
+            AXES[abscissa],
+            num(a),
+            AXES[ordinate],
+            num(o)
+        ));
+    }
+
+    /// A G2 (`clockwise`) or G3 arc in the plane, from the position reached.
+    fn arc(&mut self, clockwise: bool, (a, o): (f64, f64), (ca, co): (f64, f64)) {
+        let [abscissa, ordinate, _] = self.plane;
+        let (i, j) = (ca - self.position.0, co - self.position.1);
+        self.position = (a, o);
+        self.push(format!(
+            "{} {}={} {}={} {}={} {}={}",
+            if clockwise { "G2" } else { "G3" },
+            AXES[abscissa],
+            num(a),
+            AXES[ordinate],
+            num(o),
+            CENTRE_WORDS[abscissa],
+            num(i),
+            CENTRE_WORDS[ordinate],
+            num(j)
+        ));
+    }
+
+    /// `G4` for `time` seconds, or `-time` spindle revolutions.
+    fn dwell(&mut self, time: f64) {
+        if time > 0.0 {
+            self.lines.push(format!("G4 F={}", num(time)));
+        } else if time < 0.0 {
+            self.lines.push(format!("G4 S={}", num(-time)));
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        text
+    }
+}
+
+/// The planes of a drilling cycle along the applicate.
+struct Planes {
+    /// Retraction plane.
+    rtp: f64,
+    /// Reference plane.
+    rfp: f64,
+    /// Reference plane brought forward by the safety distance.
+    safety: f64,
+    /// Final depth: `DPR` below the reference plane if given, else `DP`.
+    depth: f64,
+    /// +1 when the retraction plane lies above the reference plane (towards
+    /// larger applicate values), -1 otherwise.
+    up: f64,
+}
+
+impl Planes {
+    /// From the leading `RTP, RFP, SDIS, DP, DPR` parameters.
+    fn new(args: &Arguments) -> Self {
+        let (rtp, rfp, sdis, dp, dpr) = (
+            args.number(0),
+            args.number(1),
+            args.number(2),
+            args.number(3),
+            args.number(4),
+        );
+        let up = if rtp < rfp { -1.0 } else { 1.0 };
+        Planes {
+            rtp,
+            rfp,
+            safety: rfp + up * sdis.abs(),
+            depth: if dpr != 0.0 { rfp - up * dpr.abs() } else { dp },
+            up,
+        }
+    }
+
+    /// Absolute dimensioning, then rapid to the safety plane.
+    fn approach(&self, blocks: &mut Blocks) {
+        blocks.depth("G90 G0", self.safety);
+    }
+}
+
+/// `count` equal infeeds from `start` to `end`, at most `max_step` apart (a
+/// single one when `max_step` is 0).
+fn levels(start: f64, end: f64, max_step: f64, context: &CycleContext) -> Result<Vec<f64>, String> {
+    let total = (end - start).abs();
+    let count = if max_step > 0.0 {
+        (total / max_step).ceil().max(1.0)
+    } else {
+        1.0
+    };
+    if count > context.iteration_limit as f64 {
+        return Err(format!(
+            "the infeed of {max_step} needs more than {} passes",
+            context.iteration_limit
+        ));
+    }
+    let count = count as usize;
+    Ok((1..=count)
+        .map(|k| start + (end - start) * k as f64 / count as f64)
+        .collect())
+}
+
+fn cycle81(args: &Arguments, context: &CycleContext) -> Result<String, String> {
+    drill(args, 0.0, context)
+}
+
+fn cycle82(args: &Arguments, context: &CycleContext) -> Result<String, String> {
+    drill(args, args.number(5), context)
+}
+
+fn drill(args: &Arguments, dwell: f64, context: &CycleContext) -> Result<String, String> {
+    let planes = Planes::new(args);
+    let mut blocks = Blocks::new(context.plane);
+    planes.approach(&mut blocks);
+    blocks.depth("G1", planes.depth);
+    blocks.dwell(dwell);
+    blocks.depth("G0", planes.rtp);
+    Ok(blocks.finish())
+}
+
+/// Deep-hole drilling. Each infeed after the first is `DAM` shorter than the
+/// one before, down to `_MDEP` (or `DAM`); when what is left is less than two
+/// infeeds it is split into two equal ones.
+fn cycle83(args: &Arguments, context: &CycleContext) -> Result<String, String> {
+    let planes = Planes::new(args);
+    let (fdep, fdpr, dam, dtb, dts, frf) = (
+        args.number(5),
+        args.number(6),
+        args.number(7),
+        args.number(8),
+        args.number(9),
+        args.number(10),
+    );
+    let chip_removal = args.number(11) == 1.0;
+    let (min_step, vrt, dtd, dis1) = (args.number(13), args.number(14), args.number(15), args.number(16));
+    let total = (planes.rfp - planes.depth).abs();
+    let first = if fdpr != 0.0 {
+        fdpr.abs()
+    } else if fdep != 0.0 {
+        (planes.rfp - fdep).abs()
+    } else {
+        total
+    };
+    let min_step = if min_step > 0.0 { min_step } else { dam.abs() };
+    let retract = if vrt > 0.0 { vrt } else { 1.0 };
+    let advance = if dis1 > 0.0 {
+        dis1
+    } else if total <= 30.0 {
+        0.6
+    } else {
+        (total / 50.0).min(7.0)
+    };
+
+    let mut blocks = Blocks::new(context.plane);
+    planes.approach(&mut blocks);
+    let mut reached = planes.rfp;
+    let mut step = if first > 0.0 { first } else { total };
+    let mut split = false;
+    for infeed in 0.. {
+        if infeed > context.iteration_limit {
+            return Err(format!("more than {} infeeds", context.iteration_limit));
+        }
+        let left = (reached - planes.depth).abs();
+        if infeed > 0 && !split && left > step && left < 2.0 * step {
+            step = left / 2.0;
+            split = true;
+        }
+        let stroke = step.min(left);
+        if chip_removal && infeed > 0 {
+            blocks.depth("G0", reached + planes.up * advance);
+        }
+        if infeed == 0 && frf > 0.0 && frf < 1.0 {
+            if let Some(feed) = context.feed {
+                blocks.set_feed(feed * frf);
+            }
+        } else if infeed == 1 && frf > 0.0 && frf < 1.0 {
+            if let Some(feed) = context.feed {
+                blocks.set_feed(feed);
+            }
+        }
+        reached -= planes.up * stroke;
+        if (reached - planes.depth).abs() < 1e-9 || stroke <= 0.0 {
+            blocks.depth("G1", planes.depth);
+            blocks.dwell(if dtd != 0.0 { dtd } else { dtb });
+            break;
+        }
+        blocks.depth("G1", reached);
+        blocks.dwell(dtb);
+        if chip_removal {
+            blocks.depth("G0", planes.safety);
+            blocks.dwell(dts);
+        } else {
+            blocks.depth("G1", reached + planes.up * retract);
+        }
+        if !split {
+            step = (step - dam.abs()).max(min_step).max(f64::EPSILON);
+        }
+    }
+    if frf > 0.0 && frf < 1.0 {
+        if let Some(feed) = context.feed {
+            blocks.set_feed(feed);
+        }
+    }
+    blocks.depth("G0", planes.rtp);
+    Ok(blocks.finish())
+}
+
+/// Pitches of the ISO metric coarse threads, by nominal diameter.
+const METRIC_PITCHES: &[(f64, f64)] = &[
+    (3.0, 0.5),
+    (4.0, 0.7),
+    (5.0, 0.8),
+    (6.0, 1.0),
+    (8.0, 1.25),
+    (10.0, 1.5),
+    (12.0, 1.75),
+    (14.0, 2.0),
+    (16.0, 2.0),
+    (18.0, 2.5),
+    (20.0, 2.5),
+    (22.0, 2.5),
+    (24.0, 3.0),
+    (27.0, 3.0),
+    (30.0, 3.5),
+    (33.0, 3.5),
+    (36.0, 4.0),
+    (39.0, 4.0),
+    (42.0, 4.5),
+    (45.0, 4.5),
+    (48.0, 5.0),
+];
+
+/// Rigid tapping: in at the feed of the pitch at speed `SST` (G94), out at
+/// `SST1`; without a speed the feed is the pitch itself per revolution
+/// (G95). `SDAC` (M3/M4/M5) is programmed on the final retraction.
+fn cycle84(args: &Arguments, context: &CycleContext) -> Result<String, String> {
+    let planes = Planes::new(args);
+    let (dtb, sdac, mpit, pit, sst, sst1) = (
+        args.number(5),
+        args.number(6),
+        args.number(7),
+        args.number(8),
+        args.number(10),
+        args.number(11),
+    );
+    let pitch = if pit != 0.0 {
+        pit.abs()
+    } else if mpit != 0.0 {
+        METRIC_PITCHES
+            .iter()
+            .find(|(size, _)| *size == mpit.abs())
+            .map(|(_, pitch)| *pitch)
+            .ok_or_else(|| format!("MPIT={} is not a metric thread size (3 to 48)", num(mpit)))?
+    } else {
+        return Err("neither the thread pitch PIT nor the thread size MPIT is given".to_string());
+    };
+    let mut blocks = Blocks::new(context.plane);
+    planes.approach(&mut blocks);
+    if sst > 0.0 {
+        blocks.set_feed(sst * pitch);
+        blocks.depth("G94 G1", planes.depth);
+    } else {
+        blocks.set_feed(pitch);
+        blocks.depth("G95 G1", planes.depth);
+    }
+    blocks.dwell(dtb);
+    if sst > 0.0 {
+        blocks.set_feed(if sst1 > 0.0 { sst1 } else { sst } * pitch);
+    }
+    blocks.depth("G1", planes.safety);
+    if let Some(feed) = context.feed {
+        blocks.set_feed(feed);
+    }
+    let spindle = match sdac as i64 {
+        3..=5 => format!(" M{}", sdac as i64),
+        _ => String::new(),
+    };
+    blocks.depth(&format!("G0{spindle}"), planes.rtp);
+    Ok(blocks.finish())
+}
+
+/// A local coordinate system in the plane: `origin` plus a rotation by
+/// `angle` degrees; `sense` -1 mirrors the local ordinate, which turns a
+/// counter-clockwise outline into a clockwise one.
+struct Placement {
+    origin: (f64, f64),
+    angle: f64,
+    sense: f64,
+}
+
+impl Placement {
+    fn at(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let y = y * self.sense;
+        (self.origin.0 + x * cos - y * sin, self.origin.1 + x * sin + y * cos)
+    }
+
+    fn clockwise(&self) -> bool {
+        self.sense < 0.0
+    }
+}
+
+/// The milling direction `CDIR`: 0 climb (G2 for the inside of a pocket with
+/// the spindle turning clockwise), 1 conventional, 2 G2, 3 G3.
+fn sense(cdir: f64) -> Result<f64, String> {
+    match cdir as i64 {
+        0 | 2 => Ok(-1.0),
+        1 | 3 => Ok(1.0),
+        _ => Err(format!("milling direction CDIR={} is not 0 to 3", num(cdir))),
+    }
+}
+
+/// One lap of a rectangle with half-sizes `half` and rounded corners of
+/// `radius`, starting and ending in the middle of its +x side.
+fn rounded_rectangle(blocks: &mut Blocks, at: &Placement, half: (f64, f64), radius: f64) {
+    let (a, b) = half;
+    let r = radius.min(a).min(b).max(0.0);
+    let cw = at.clockwise();
+    let line = |blocks: &mut Blocks, point: (f64, f64)| {
+        let target = at.at(point);
+        let (da, db) = (target.0 - blocks.position.0, target.1 - blocks.position.1);
+        if da.hypot(db) > 1e-9 {
+            blocks.to("G1", target);
+        }
+    };
+    let corners = [
+        ((a, b - r), (a - r, b), (a - r, b - r)),
+        ((-a + r, b), (-a, b - r), (-a + r, b - r)),
+        ((-a, -b + r), (-a + r, -b), (-a + r, -b + r)),
+        ((a - r, -b), (a, -b + r), (a - r, -b + r)),
+    ];
+    blocks.to("G1", at.at((a, 0.0)));
+    for (start, end, centre) in corners {
+        line(blocks, start);
+        if r > 0.0 {
+            blocks.arc(cw, at.at(end), at.at(centre));
+        }
+    }
+    line(blocks, (a, 0.0));
+}
+
+/// Rectangular pocket. `VARI` units digit: 1 roughing (rings out to the
+/// allowances `FAL`/`FALD`), 2 finishing (the outline at full depth), 0
+/// both.
+fn pocket3(args: &Arguments, context: &CycleContext) -> Result<String, String> {
+    let (rtp, rfp, sdis, dp) = (args.number(0), args.number(1), args.number(2), args.number(3));
+    let (length, width, crad) = (args.number(4), args.number(5), args.number(6));
+    let (pa, po, sta, mid) = (args.number(7), args.number(8), args.number(9), args.number(10));
+    let (fal, fald, ffp1, ffd) = (args.number(11), args.number(12), args.number(13), args.number(14));
+    let (cdir, vari, mida) = (args.number(15), args.number(16), args.number(17));
+    if length <= 0.0 || width <= 0.0 {
+        return Err("the pocket length and width must be positive".to_string());
+    }
+    if crad < 0.0 || 2.0 * crad > length.min(width) {
+        return Err(format!("the corner radius {} does not fit the pocket", num(crad)));
+    }
+    let at = Placement {
+        origin: (pa, po),
+        angle: sta,
+        sense: sense(cdir)?,
+    };
+    let up = if rtp < rfp { -1.0 } else { 1.0 };
+    let safety = rfp + up * sdis.abs();
+    let (half_a, half_b) = (length / 2.0, width / 2.0);
+    let stepover = if mida > 0.0 { mida } else { width / 4.0 };
+    let mode = vari.abs() as i64 % 10;
+
+    let mut blocks = Blocks::new(context.plane);
+    blocks.to("G90 G0", at.at((0.0, 0.0)));
+    blocks.depth("G0", safety);
+    if mode != 2 {
+        // Ring insets from the innermost one out to the allowance.
+        let mut insets = Vec::new();
+        let mut inset = fal.max(0.0);
+        while half_a.min(half_b) - inset > 1e-9 {
+            if insets.len() >= context.iteration_limit {
+                return Err(format!(
+                    "the stepover {} needs more than {} rings",
+                    num(stepover),
+                    context.iteration_limit
+                ));
+            }
+            insets.push(inset);
+            inset += stepover;
+        }
+        for level in levels(rfp, dp + up * fald.max(0.0), mid, context)? {
+            blocks.set_feed(ffd);
+            blocks.depth("G1", level);
+            blocks.set_feed(ffp1);
+            for &inset in insets.iter().rev() {
+                rounded_rectangle(&mut blocks, &at, (half_a - inset, half_b - inset), crad - inset);
+            }
+            blocks.depth("G0", safety);
+            blocks.to("G0", at.at((0.0, 0.0)));
+        }
+    }
+    if mode != 1 {
+        blocks.set_feed(ffd);
+        blocks.depth("G1", dp);
+        blocks.set_feed(ffp1);
+        rounded_rectangle(&mut blocks, &at, (half_a, half_b), crad);
+        blocks.depth("G0", safety);
+    }
+    if ffd > 0.0 || ffp1 > 0.0 {
+        if let Some(feed) = context.feed {
+            blocks.set_feed(feed);
+        }
+    }
+    blocks.depth("G0", rtp);
+    Ok(blocks.finish())
+}
+
+/// One lap around a slot of `length` between its end-arc centres and
+/// half-width `half`, starting and ending at the centre of its inner end.
+fn slot_outline(blocks: &mut Blocks, at: &Placement, length: f64, half: f64) {
+    let cw = at.clockwise();
+    blocks.to("G1", at.at((length, 0.0)));
+    blocks.to("G1", at.at((0.0, 0.0)));
+    if half <= 0.0 {
+        return;
+    }
+    blocks.to("G1", at.at((0.0, -half)));
+    blocks.to("G1", at.at((length, -half)));
+    blocks.arc(cw, at.at((length, half)), at.at((length, 0.0)));
+    blocks.to("G1", at.at((0.0, half)));
+    blocks.arc(cw, at.at((0.0, -half)), at.at((0.0, 0.0)));
+    blocks.to("G1", at.at((0.0, 0.0)));
+}
+
+/// `NUM` slots of length `LENG` and width `WID`, pointing away from the
+/// centre `CPA`/`CPO`: the centre of a slot's inner end lies on the circle of
+/// radius `RAD`, the first at the angle `STA1`, the others `INDA` apart (or
+/// spread over the full circle). `VARI` as for [`pocket3`].
+fn slot1(args: &Arguments, context: &CycleContext) -> Result<String, String> {
+    let planes = Planes::new(args);
+    let (count, length, width) = (args.number(5), args.number(6), args.number(7));
+    let (cpa, cpo, radius, sta1, inda) = (
+        args.number(8),
+        args.number(9),
+        args.number(10),
+        args.number(11),
+        args.number(12),
+    );
+    let (ffd, ffp1, mid, cdir, fal, vari) = (
+        args.number(13),
+        args.number(14),
+        args.number(15),
+        args.number(16),
+        args.number(17),
+        args.number(18),
+    );
+    let fald = args.number(22);
+    if count < 1.0 || count > context.iteration_limit as f64 {
+        return Err(format!("NUM={} is not a slot count", num(count)));
+    }
+    if width <= 0.0 || length < width {
+        return Err("the slot width must be positive and no more than its length".to_string());
+    }
+    let count = count as usize;
+    let step = if inda != 0.0 { inda } else { 360.0 / count as f64 };
+    let sense = sense(cdir)?;
+    let mode = vari.abs() as i64 % 10;
+
+    let mut blocks = Blocks::new(context.plane);
+    for slot in 0..count {
+        let angle = sta1 + step * slot as f64;
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let at = Placement {
+            origin: (cpa + radius * cos, cpo + radius * sin),
+            angle,
+            sense,
+        };
+        blocks.to(if slot == 0 { "G90 G0" } else { "G0" }, at.at((0.0, 0.0)));
+        planes.approach(&mut blocks);
+        if mode != 2 {
+            for level in levels(planes.rfp, planes.depth + planes.up * fald.max(0.0), mid, context)? {
+                blocks.set_feed(ffd);
+                blocks.depth("G1", level);
+                blocks.set_feed(ffp1);
+                slot_outline(&mut blocks, &at, length - width, width / 2.0 - fal.max(0.0));
+            }
+        }
+        if mode != 1 {
+            blocks.set_feed(ffd);
+            blocks.depth("G1", planes.depth);
+            blocks.set_feed(ffp1);
+            slot_outline(&mut blocks, &at, length - width, width / 2.0);
+        }
+        if slot + 1 == count && (ffd > 0.0 || ffp1 > 0.0) {
+            if let Some(feed) = context.feed {
+                blocks.set_feed(feed);
+            }
+        }
+        blocks.depth("G0", planes.rtp);
+    }
+    Ok(blocks.finish())
+}
+
+/// Swivel plane, axis-by-axis mode: the frame translates to `_X0/_Y0/_Z0`,
+/// rotates by `_A`, `_B`, `_C` about the axes `_MODE` names (two bits each,
+/// first axis highest: 1 X, 2 Y, 3 Z; 57 is Z, Y, X), then translates by
+/// `_X1/_Y1/_Z1`. The units digit of `_ST` is 1 to add to the active frame
+/// instead of replacing it.
+fn cycle800(args: &Arguments, _: &CycleContext) -> Result<String, String> {
+    let (st, mode) = (args.number(2), args.number(3) as i64);
+    if mode & 0b1100_0000 != 0 {
+        return Err("only the axis-by-axis swivel mode (_MODE bits 6 and 7 clear) is supported".to_string());
+    }
+    let additive = st.abs() as i64 % 10 == 1;
+    let translation = |first: usize| {
+        AXES.iter()
+            .enumerate()
+            .map(|(i, axis)| format!("{axis}={}", num(args.number(first + i))))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut blocks = Blocks::new(WorkingPlane::G17);
+    blocks.push(format!(
+        "{} {}",
+        if additive { "ATRANS" } else { "TRANS" },
+        translation(4)
+    ));
+    for (slot, angle) in [args.number(7), args.number(8), args.number(9)].into_iter().enumerate() {
+        let axis = (mode >> (4 - 2 * slot)) & 0b11;
+        if angle == 0.0 {
+            continue;
+        }
+        if axis == 0 {
+            return Err(format!("_MODE={mode} names no axis for rotation {}", slot + 1));
+        }
+        blocks.push(format!("AROT {}={}", AXES[axis as usize - 1], num(angle)));
+    }
+    if (10..13).any(|i| args.number(i) != 0.0) {
+        blocks.push(format!("ATRANS {}", translation(10)));
+    }
+    Ok(blocks.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(name: &str, arguments: &[f64], plane: WorkingPlane) -> Result<String, String> {
+        let arguments: Vec<Argument> = arguments.iter().map(|&value| Argument::Number(value)).collect();
+        let context = CycleContext {
+            plane,
+            feed: Some(100.0),
+            iteration_limit: 1000,
+        };
+        builtin(name).expect("built-in cycle").expand(&arguments, &context)
+    }
+
+    #[test]
+    fn drilling_depth_is_relative_when_dpr_is_given() {
+        let text = expand("CYCLE81", &[10.0, 0.0, 2.0, -20.0, 5.0], WorkingPlane::G17).unwrap();
+        assert_eq!(text, "G90 G0 Z=2\nG1 Z=-5\nG0 Z=10\n");
+        // G18: the tool axis is Y; DP is absolute when DPR is 0.
+        let text = expand("CYCLE82", &[10.0, 0.0, 2.0, -20.0, 0.0, 1.5], WorkingPlane::G18).unwrap();
+        assert_eq!(text, "G90 G0 Y=2\nG1 Y=-20\nG4 F=1.5\nG0 Y=10\n");
+    }
+
+    #[test]
+    fn deep_hole_infeeds_degress_and_split_the_rest() {
+        // 20 deep: 8, then 6, then the 6 left split into 3 + 3.
+        let text = expand(
+            "CYCLE83",
+            &[5.0, 0.0, 1.0, -20.0, 0.0, -8.0, 0.0, 2.0, 0.0, 0.0, 1.0, 0.0],
+            WorkingPlane::G17,
+        )
+        .unwrap();
+        let depths: Vec<&str> = text.lines().filter(|l| l.starts_with("G1")).collect();
+        assert_eq!(
+            depths,
+            ["G1 Z=-8", "G1 Z=-7", "G1 Z=-14", "G1 Z=-13", "G1 Z=-17", "G1 Z=-16", "G1 Z=-20"]
+        );
+        // Chip removal: back to the safety plane, then rapid to just above
+        // the depth reached.
+        let text = expand(
+            "CYCLE83",
+            &[5.0, 0.0, 1.0, -10.0, 0.0, -5.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0],
+            WorkingPlane::G17,
+        )
+        .unwrap();
+        assert_eq!(text, "G90 G0 Z=1\nG1 Z=-5\nG0 Z=1\nG0 Z=-4.4\nG1 Z=-10\nG0 Z=5\n");
+    }
+
+    #[test]
+    fn tapping_feed_follows_the_pitch() {
+        let text = expand(
+            "CYCLE84",
+            &[5.0, 0.0, 2.0, -12.0, 0.0, 0.0, 3.0, 8.0, 0.0, 0.0, 400.0, 800.0],
+            WorkingPlane::G17,
+        )
+        .unwrap();
+        assert_eq!(text, "G90 G0 Z=2\nG94 G1 Z=-12 F=500\nG1 Z=2 F=1000\nG0 M3 Z=5 F=100\n");
+        let err = expand(
+            "CYCLE84",
+            &[5.0, 0.0, 2.0, -12.0, 0.0, 0.0, 3.0, 7.0],
+            WorkingPlane::G17,
+        )
+        .unwrap_err();
+        assert!(err.contains("MPIT=7"), "{err}");
+    }
+
+    #[test]
+    fn pocket_rings_run_from_the_centre_out() {
+        // 40 x 20 pocket, corner radius 5, two rings 5 apart, one level.
+        let text = expand(
+            "POCKET3",
+            &[
+                10.0, 0.0, 2.0, -3.0, 40.0, 20.0, 5.0, 50.0, 50.0, 0.0, 0.0, 0.0, 0.0, 300.0, 100.0, 1.0, 1.0, 5.0,
+            ],
+            WorkingPlane::G17,
+        )
+        .unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[..4],
+            ["G90 G0 X=50 Y=50", "G0 Z=2", "G1 Z=-3 F=100", "G1 X=65 Y=50 F=300"]
+        );
+        // The outer ring: counter-clockwise (CDIR=1) around the full outline.
+        assert!(lines.contains(&"G3 X=65 Y=60 I=-5 J=0"), "{text}");
+        assert!(lines.contains(&"G1 X=70 Y=55"), "{text}");
+        assert_eq!(lines.last(), Some(&"G0 Z=10 F=100"));
+    }
+
+    #[test]
+    fn slots_are_spread_over_the_circle() {
+        let text = expand(
+            "SLOT1",
+            &[
+                10.0, 0.0, 2.0, -4.0, 0.0, 2.0, 30.0, 10.0, 0.0, 0.0, 20.0, 0.0, 0.0, 100.0, 200.0, 0.0, 3.0, 0.0, 1.0,
+            ],
+            WorkingPlane::G17,
+        )
+        .unwrap();
+        assert!(
+            text.starts_with("G90 G0 X=20 Y=0\nG90 G0 Z=2\nG1 Z=-4 F=100\nG1 X=40 Y=0 F=200\n"),
+            "{text}"
+        );
+        // The second slot points the other way.
+        assert!(text.contains("G0 X=-20 Y=0\n"), "{text}");
+        assert!(text.contains("G3 X=-40 Y=-5 I=0 J=-5\n"), "{text}");
+    }
+
+    #[test]
+    fn swivel_plane_builds_the_frame() {
+        let arguments = [
+            Argument::Number(0.0),
+            Argument::Text("TC1".to_string()),
+            Argument::Number(0.0),
+            Argument::Number(57.0),
+            Argument::Number(10.0),
+            Argument::Number(0.0),
+            Argument::Number(0.0),
+            Argument::Number(30.0),
+            Argument::Number(0.0),
+            Argument::Number(-15.0),
+        ];
+        let context = CycleContext {
+            plane: WorkingPlane::G17,
+            feed: None,
+            iteration_limit: 10,
+        };
+        let text = builtin("CYCLE800").unwrap().expand(&arguments, &context).unwrap();
+        assert_eq!(text, "TRANS X=10 Y=0 Z=0\nAROT Z=30\nAROT X=-15\n");
+        let err = expand("CYCLE800", &[0.0, 0.0, 0.0, 64.0 + 57.0], WorkingPlane::G17).unwrap_err();
+        assert!(err.starts_with("CYCLE800: only the axis-by-axis"), "{err}");
+    }
+}
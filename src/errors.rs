@@ -230,6 +230,44 @@ Details: Function {name} expects {expected} argument(s), but received {actual}.
         expected: usize,
         actual: usize,
     },
+    #[error(
+        r#"
+Subprogram not found on line {line_no}
+----------------------------------------
+Line: {preview}
+
+Details: No subprogram '{name}' was found (alarm 14011 on a real control).
+Searched: {searched}
+To fix this, add the directory holding {name}.SPF or {name}.MPF to the
+subprogram search path.
+"#
+    )]
+    SubprogramNotFound {
+        line_no: usize,
+        preview: String,
+        name: String,
+        /// The directories searched, comma-separated (or a note that no
+        /// search path is configured).
+        searched: String,
+    },
+    #[error(
+        r#"
+Recursive macro on line {line_no}
+----------------------------------------
+Line: {preview}
+
+Details: Macro '{name}' expands to itself: {chain}.
+To fix this, make sure no macro's replacement text uses the macro, directly
+or through other macros.
+"#
+    )]
+    RecursiveMacro {
+        line_no: usize,
+        preview: String,
+        name: String,
+        /// The expansion path that led back to `name`, e.g. `A -> B -> A`.
+        chain: String,
+    },
 }
 
 /// Structured location of an error, for callers that want the position as data
@@ -259,6 +297,90 @@ impl ParsingError {
         }
     }
 
+    /// Name the generated block `text` next to the calling line in an error
+    /// raised while running it in place of line `line_no` (macro expansion,
+    /// `EXECSTRING`), so both the source and the text that failed are shown.
+    pub(crate) fn in_generated_block(mut self, line_no: usize, source: &str, text: &str) -> Self {
+        if let Some((line, preview)) = self.line_preview_mut() {
+            if line == line_no {
+                preview.push_str(&format!("\nGenerated by {source}: {}", text.trim()));
+            }
+        }
+        self
+    }
+
+    /// Name the subprogram `name` and the block that called it, line
+    /// `line_no` with text `caller`, in an error raised inside the
+    /// subprogram, whose own line alone does not say where it ran. Nested
+    /// calls add one line each, innermost first.
+    pub(crate) fn in_subprogram(mut self, name: &str, line_no: usize, caller: &str) -> Self {
+        if let Some((_, preview)) = self.line_preview_mut() {
+            preview.push_str(&format!(
+                "\nIn subprogram {name}, called on line {line_no}: {}",
+                caller.trim()
+            ));
+        }
+        self
+    }
+
+    /// The line number and the mutable line preview of errors tied to a line.
+    fn line_preview_mut(&mut self) -> Option<(usize, &mut String)> {
+        match self {
+            Self::ParsingContext {
+                line_no: line, preview, ..
+            }
+            | Self::UnexpectedRule {
+                line_no: line, preview, ..
+            }
+            | Self::UnknownVariable {
+                line_no: line, preview, ..
+            }
+            | Self::UndefinedVariable {
+                line_no: line, preview, ..
+            }
+            | Self::TooManyMCommands {
+                line_no: line, preview, ..
+            }
+            | Self::MissingAxisMapping {
+                line_no: line, preview, ..
+            }
+            | Self::InvalidAxisIndex {
+                line_no: line, preview, ..
+            }
+            | Self::UnsupportedStatement {
+                line_no: line, preview, ..
+            }
+            | Self::JumpTargetNotFound {
+                line_no: line, preview, ..
+            }
+            | Self::UnmatchedStructure {
+                line_no: line, preview, ..
+            }
+            | Self::UnknownGCommand {
+                line_no: line, preview, ..
+            }
+            | Self::InvalidFunctionArity {
+                line_no: line, preview, ..
+            }
+            | Self::SubprogramNotFound {
+                line_no: line, preview, ..
+            }
+            | Self::RecursiveMacro {
+                line_no: line, preview, ..
+            }
+            | Self::UnexpectedAxis {
+                line_no: line, preview, ..
+            }
+            | Self::AxisUsedAsVariable {
+                line_no: line, preview, ..
+            }
+            | Self::ReservedNameUsedAsVariable {
+                line_no: line, preview, ..
+            } => Some((*line, preview)),
+            _ => None,
+        }
+    }
+
     /// The error's source location as structured data, or `None` for errors
     /// not tied to a specific line (stream closed, element-count mismatches,
     /// etc.). `line_text`/`context`/`column` are populated when the variant
@@ -296,6 +418,8 @@ impl ParsingError {
             | Self::UnmatchedStructure { line_no, preview, .. }
             | Self::UnknownGCommand { line_no, preview, .. }
             | Self::InvalidFunctionArity { line_no, preview, .. }
+            | Self::SubprogramNotFound { line_no, preview, .. }
+            | Self::RecursiveMacro { line_no, preview, .. }
             // Semantic/validation errors: the offending block is known at raise
             // time, so they anchor to a line (no column) like the others above.
             | Self::UnexpectedAxis { line_no, preview, .. }
@@ -337,13 +461,15 @@ impl ParsingError {
             Self::UnmatchedStructure { .. } => "unmatched_structure",
             Self::UnknownGCommand { .. } => "unknown_g_command",
             Self::InvalidFunctionArity { .. } => "invalid_function_arity",
+            Self::SubprogramNotFound { .. } => "subprogram_not_found",
+            Self::RecursiveMacro { .. } => "recursive_macro",
         }
     }
 }
 
 impl From<ParsingError> for std::io::Error {
     fn from(err: ParsingError) -> std::io::Error {
-        std::io::Error::new(std::io::ErrorKind::Other, err.to_string())
+        std::io::Error::other(err.to_string())
     }
 }
 
@@ -554,6 +680,24 @@ mod tests {
                 },
                 "invalid_function_arity",
             ),
+            (
+                ParsingError::SubprogramNotFound {
+                    line_no: 1,
+                    preview: s(),
+                    name: s(),
+                    searched: s(),
+                },
+                "subprogram_not_found",
+            ),
+            (
+                ParsingError::RecursiveMacro {
+                    line_no: 1,
+                    preview: s(),
+                    name: s(),
+                    chain: s(),
+                },
+                "recursive_macro",
+            ),
         ];
         for (err, expected) in &cases {
             assert_eq!(err.kind(), *expected, "kind mismatch for {err:?}");
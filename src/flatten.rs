@@ -1,5 +1,5 @@
-//! Flatten curved toolpath motions (G2/G3 arcs, ASPLINE/CSPLINE/BSPLINE
-//! splines) into runs of G1 rows, keeping the output row/table format
+//! Flatten curved toolpath motions (G2/G3 arcs, CIP and CT circles,
+//! ASPLINE/CSPLINE/BSPLINE splines, POLY polynomials, INVCW/INVCCW involutes) into runs of G1 rows, keeping the output row/table format
 //! unchanged.
 //!
 //! The flattener sits between the interpreter and the row sink: every
@@ -7,8 +7,9 @@
 //! it along untouched or replaces it with a run of linear rows sampled from
 //! the programmed curve. The emitted rows carry the source row's `line_no`,
 //! so a visualizer still maps every sample back to the block that programmed
-//! it. The interpolation parameters (`I`/`J`/`K`/`CR`) and spline addresses
-//! (`PW`/`SD`/`PL`) are consumed and never appear in flattened output.
+//! it. The interpolation parameters (`I`/`J`/`K`/`CR`, `I1`/`J1`/`K1`), the
+//! spline addresses (`PW`/`SD`/`PL`) and the polynomial coefficients
+//! (`PO_...`) are consumed and never appear in flattened output.
 //!
 //! A single knob controls the point density: `tolerance`, the maximum
 //! chordal deviation (in path units, i.e. mm) between the emitted polyline
@@ -20,52 +21,82 @@
 //!   (CAM rounding) is absorbed by interpolating the radius linearly, and
 //!   any other axes programmed on the arc block (helix axis, extrusion, ...)
 //!   are interpolated linearly over the sweep.
+//! * `CIP` circles run from the start point through the intermediate point
+//!   `I1`/`J1`/`K1` to the end point, in whatever plane these three points
+//!   span (not necessarily the working plane). They are sampled like arcs;
+//!   collinear points do not define a circle and pass through with a
+//!   warning.
+//! * `CT` circles start tangent to the path before them and end on the
+//!   programmed point, in the working plane; an end point straight ahead
+//!   gives a straight line. The flattener tracks the path direction at the
+//!   end of every block: exact for arcs and circles, the last chord for a
+//!   spline, and the move itself for everything else.
+//! * `POLY` blocks carry their geometry axes' polynomials resolved by the
+//!   interpreter (see [`crate::poly`]); they are evaluated over `0..PL`,
+//!   numerators over the common denominator, and flattened like splines.
+//!   Other axes programmed on the block move linearly in the parameter.
+//! * `INVCW`/`INVCCW` involutes of the base circle (centre `I`/`J`/`K`,
+//!   radius `CR`) are sampled in the working plane like splines, over
+//!   spans of at most a quarter turn of the generating line (see
+//!   [`crate::involute`]). An end point within the control's tolerance of
+//!   the involute is reached by blending into the involute through it.
 //! * Splines are flattened by recursive bisection: a parameter span is split
 //!   until the curve, probed at 1/4, 1/2 and 3/4 of the span, stays within
 //!   `tolerance` of the chord. Deviation is measured in the geometric
-//!   X/Y/Z subspace when present (other channels ride along and are sampled
-//!   at the same parameters).
+//!   X/Y/Z subspace of the spline path when present.
 //!
 //! Spline semantics follow SINUMERIK (NC programming manual, 4.7.2):
-//! * `ASPLINE` — Akima spline through the programmed points, chord-length
-//!   parameterized.
-//! * `CSPLINE` — cubic spline through the programmed points (natural end
-//!   conditions), chord-length parameterized.
+//! * `ASPLINE` — Akima spline through the programmed points.
+//! * `CSPLINE` — cubic spline through the programmed points.
+//! * A/C splines are parameterized by chord length, except for the spans
+//!   whose block programs the parameter interval `PL`.
+//! * A/C splines start and end as G groups 19/20 select: `BNAT`/`ENAT` with
+//!   zero curvature, `BTAN`/`ETAN` tangential to the preceding/following
+//!   block (in the spline's X/Y/Z channels), `BAUTO`/`EAUTO` (the default)
+//!   as the first/last points determine: the Akima boundary extrapolation,
+//!   and for CSPLINE an outer segment with the curvature of its inner point.
+//!   The start condition is the one active when the spline is selected, the
+//!   end condition the one active when it is deselected.
 //! * `BSPLINE` — the programmed positions are control points of a clamped
-//!   uniform B-spline; `PW` weights (rational), `SD` degree (default 3).
-//!   A constant `PL` only rescales the parameter and never changes the
-//!   curve shape, so its value is ignored.
+//!   B-spline; `PW` weights (rational), `SD` degree (default 3). The knot
+//!   interval of a span is the `PL` (default 1) of the block that owns it:
+//!   the block of the span's last control point.
+//! * The spline interpolates the axes of its path (`SPLINEPATH`, see
+//!   [`crate::splines`]; by default X, Y and Z). The other axes programmed
+//!   in spline blocks follow linearly over each block, in proportion to the
+//!   path travelled.
 //! * All splines start at the current position (the first point / control
 //!   point) and are modal until deselected by another motion command.
 //!
 //! Helical `TURN=` (additional full turns, manual 3.9.7) is supported.
 //!
+//! Thread blocks (G33/G34/G35) are straight tool moves and pass through
+//! like G1 rows, with their thread columns (see [`crate::threads`]).
+//!
 //! Out of scope (rows pass through unchanged, with a once-per-word
-//! warning): `CIP`/`CT` (the intermediate-point addresses `I1=`/`J1=`/`K1=`
-//! are not captured by the parser), `POLY`, thread cutting (G33/G34/G35)
-//! and involutes (INVCW/INVCCW).
+//! warning): tapping (G331/G332), G335/G336 and OEMIPO1/OEMIPO2.
 //!
 //! Known approximations, checked against the NC programming manual:
-//! * Spline start/end conditions (`BAUTO`/`BNAT`/`BTAN`,
-//!   `EAUTO`/`ENAT`/`ETAN`, manual 4.7.2) are not evaluated: CSPLINE uses
-//!   natural end conditions (= BNAT/ENAT; the control default is
-//!   BAUTO/EAUTO) and ASPLINE uses the standard Akima boundary
-//!   extrapolation. Only the first/last spline segments differ.
-//! * A/C splines are chord-length parameterized; a per-block `PL` parameter
-//!   interval is not applied.
-//! * The spline channels are all axes programmed in the spline blocks
-//!   (`SPLINEPATH` declarations are not parsed; the control default is the
-//!   first three channel axes).
-//! * `G91` incremental mode is not resolved by the interpreter, so arcs and
-//!   splines under G91 are wrong before flattening ever sees them.
-//! * The arc-centre offsets I/J/K are always interpreted as increments
-//!   relative to the start point (the control default); the `I=AC(...)`
-//!   absolute form is not supported by the parser (it fails to parse rather
-//!   than being misread).
+//! * `ETAN` before a curved block ends along the first chord of that block's
+//!   flattened run (within `tolerance` of the curve), not its exact tangent;
+//!   before a `CT` circle or another spline, and at the program end, it
+//!   falls back to `EAUTO`. `BTAN` without a preceding move falls back to
+//!   `BAUTO`.
+//! * Over a B-spline, an axis outside the path follows from block to block
+//!   of the spans' owners; the values of blocks that own no span are passed
+//!   over.
+//!
+//! Positions are tracked from the rows' absolute machine coordinates: the
+//! interpreter has already resolved `G91` and the `AC()`/`IC()` overrides,
+//! and turned an absolute centre `I=AC(...)` into the usual offset relative
+//! to the start point, so I/J/K always arrive as start-relative increments.
 
 use crate::errors::ParsingError;
+use crate::frame::{self, Matrix3};
+use crate::involute::Involute;
 use crate::output::{intern_column, CellMap, Row, FLATTENED_COLUMN};
-use crate::state::emit_warning;
+use crate::poly::{DENOMINATOR_COLUMNS, NUMERATOR_COLUMNS};
+use crate::state::{emit_warning, NON_GEOMETRIC_AXES};
 use crate::types::Value;
 use std::collections::HashMap;
 
@@ -74,14 +105,15 @@ use std::collections::HashMap;
 /// degenerate input.
 const MAX_SUBDIV_DEPTH: u32 = 20;
 
-/// Axis-identifier names that are output value columns but not path
-/// coordinates: never interpolated, never part of a curve.
-const NON_GEOMETRIC_AXES: &[&str] = &["N", "F", "S", "D", "T"];
-
 /// The metric subspace for the deviation measure, when present among the
 /// programmed channels.
 const METRIC_AXES: &[&str] = &["X", "Y", "Z"];
 
+/// Cap on the samples of one arc or circle: a pathological tolerance/radius
+/// combination would otherwise materialize a gigabyte-scale row burst. The
+/// clamp is loud - the emitted polyline is then coarser than requested.
+const MAX_ARC_SEGMENTS: usize = 100_000;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum SplineKind {
     Akima,
@@ -89,10 +121,44 @@ enum SplineKind {
     Cubic,
 }
 
+/// A start or end condition of an interpolating spline (G groups 19/20,
+/// manual 4.7.2).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SplineCondition {
+    /// `BAUTO`/`EAUTO`: determined by the first/last points.
+    Auto,
+    /// `BNAT`/`ENAT`: zero curvature.
+    Natural,
+    /// `BTAN`/`ETAN`: tangential to the preceding/following block.
+    Tangent,
+}
+
+/// The condition at one end of one spline channel, in the chord-length
+/// parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EndCondition {
+    Auto,
+    Natural,
+    /// The first derivative.
+    Slope(f64),
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum Motion {
     Linear,
-    Arc { cw: bool },
+    Arc {
+        cw: bool,
+    },
+    /// `CIP`: circle through an intermediate point.
+    Circle,
+    /// `CT`: circle with a tangential transition.
+    Tangent,
+    /// `POLY`: polynomial interpolation.
+    Poly,
+    /// `INVCW`/`INVCCW`: involute of a base circle.
+    Involute {
+        cw: bool,
+    },
     Spline(SplineKind),
     Other,
 }
@@ -144,9 +210,44 @@ pub struct Flattener {
     spline_start: HashMap<&'static str, f64>,
     /// Current B-spline degree (`SD=`, modal within the spline).
     spline_degree: usize,
-    /// Curve motion words already warned about (CIP, CT, POLY, ...): one
+    /// The active start and end conditions (`BAUTO`/`BNAT`/`BTAN`,
+    /// `EAUTO`/`ENAT`/`ETAN`).
+    spline_conditions: [SplineCondition; 2],
+    /// The start condition when the spline was selected, and the path
+    /// direction there (for `BTAN`).
+    spline_start_condition: SplineCondition,
+    spline_start_tangent: Option<[f64; 3]>,
+    /// The axes the spline interpolates (`SPLINEPATH`, see
+    /// [`crate::splines`]), and the ones of the buffered spline.
+    spline_path: Vec<&'static str>,
+    spline_start_path: Vec<&'static str>,
+    /// Curve motion words already warned about (OEMIPO1, ...): one
     /// warning per word per run, not one per block.
     warned_motions: Vec<String>,
+    /// Geometry part of the programmable frame (rotation, scale and mirror,
+    /// see [`crate::frame`]); arcs are flattened in the frame's working plane
+    /// while it is set.
+    frame: Option<Matrix3>,
+    /// Which of X/Y/Z the frame mirrors.
+    mirrored: [bool; 3],
+    /// The path direction (unit X/Y/Z vector, machine coordinates) at the
+    /// end of the last block that moved: the start tangent of a `CT` circle.
+    tangent: Option<[f64; 3]>,
+}
+
+/// Samples a curve row in the working plane, given the programmed direction
+/// (`true` for clockwise); see [`Flattener::framed_rows`].
+type PlaneSampler = fn(&Flattener, &Row, bool) -> ArcOutcome;
+
+/// What [`Flattener::arc_rows`] made of an arc-mode row.
+enum ArcOutcome {
+    /// The block moves nothing (M codes, feed change, comment): pass through.
+    NotAnArc,
+    /// The arc cannot be resolved; pass through with this reason as warning.
+    Failed(&'static str),
+    /// The sampled run replacing the row, and the path direction at its
+    /// end (X/Y/Z, in the coordinates of the sampled rows).
+    Rows(Vec<Row>, Option<[f64; 3]>),
 }
 
 impl Flattener {
@@ -182,22 +283,37 @@ impl Flattener {
             spline_buffer: Vec::new(),
             spline_start: HashMap::new(),
             spline_degree: 3,
+            spline_conditions: [SplineCondition::Auto; 2],
+            spline_start_condition: SplineCondition::Auto,
+            spline_start_tangent: None,
+            spline_path: METRIC_AXES.iter().map(|&axis| intern_column(axis)).collect(),
+            spline_start_path: Vec::new(),
             warned_motions: Vec::new(),
+            frame: None,
+            mirrored: [false; 3],
+            tangent: None,
         })
     }
 
+    /// Set the frame for the rows that follow: its geometry part (`None`:
+    /// identity) and the mirrored geometry axes. An odd mirror of the working
+    /// plane reverses arcs; the interpreter already outputs the machine
+    /// direction (G2 as G3 and vice versa).
+    pub fn set_frame(&mut self, linear: Option<Matrix3>, mirrored: [bool; 3]) {
+        self.frame = linear;
+        self.mirrored = mirrored;
+    }
+
+    /// Set the axes splines interpolate from the next spline on (`SPLINEPATH`);
+    /// the other axes programmed in spline blocks follow linearly.
+    pub fn set_spline_path(&mut self, axes: &[String]) {
+        self.spline_path = axes.iter().map(|axis| intern_column(&axis.to_uppercase())).collect();
+    }
+
     /// Process one interpreter row, appending the resulting output row(s) to
     /// `out`. Most rows come straight back; arc rows expand into a sampled
     /// run, spline rows are buffered until the spline is deselected.
     pub fn push(&mut self, row: Row, out: &mut Vec<Row>) {
-        // A motion command on this block may deselect an active spline: the
-        // buffered curve is complete and must be emitted first.
-        if let Some(new_motion) = row_motion(&row) {
-            if self.buffering_spline() && new_motion != self.motion {
-                self.flush_spline(out);
-            }
-            self.motion = new_motion;
-        }
         if let Some(Value::Str(plane)) = row.cells.get(intern_column("gg06_plane_select")) {
             self.plane = match plane.as_str() {
                 "G18" => G18,
@@ -205,12 +321,37 @@ impl Flattener {
                 _ => G17,
             };
         }
+        // A motion command on this block may deselect an active spline: the
+        // buffered curve is complete and must be emitted first, ending
+        // towards this block for ETAN.
+        if let Some(new_motion) = row_motion(&row) {
+            if self.buffering_spline() && new_motion != self.motion {
+                let next = self.start_direction(&row, new_motion);
+                self.flush_spline(next, out);
+            }
+            self.motion = new_motion;
+        }
+        for (slot, group) in ["gg19_curve_start_spline", "gg20_curve_end_spline"]
+            .into_iter()
+            .enumerate()
+        {
+            if let Some(Value::Str(word)) = row.cells.get(intern_column(group)) {
+                self.spline_conditions[slot] = match word.as_str() {
+                    "BNAT" | "ENAT" => SplineCondition::Natural,
+                    "BTAN" | "ETAN" => SplineCondition::Tangent,
+                    _ => SplineCondition::Auto,
+                };
+            }
+        }
 
         match self.motion {
             Motion::Spline(_) => {
                 if !self.buffering_spline() {
                     self.spline_start = self.positions.clone();
                     self.spline_degree = 3;
+                    self.spline_start_condition = self.spline_conditions[0];
+                    self.spline_start_tangent = self.tangent;
+                    self.spline_start_path = self.spline_path.clone();
                 }
                 if let Some(Value::Float(sd)) = row.cells.get(intern_column("SD")) {
                     self.spline_degree = (*sd as usize).max(1);
@@ -227,11 +368,26 @@ impl Flattener {
                 }
             }
             Motion::Arc { cw } => {
-                self.flatten_arc(row, cw, out);
+                self.flatten_in_plane(row, cw, Self::arc_rows, out);
+            }
+            Motion::Involute { cw } => {
+                self.flatten_in_plane(row, cw, Self::involute_rows, out);
+            }
+            Motion::Circle => {
+                let outcome = self.circle_rows(&row);
+                self.emit_curve(row, outcome, out);
+            }
+            Motion::Tangent => {
+                let outcome = self.tangent_arc_rows(&row);
+                self.emit_curve(row, outcome, out);
+            }
+            Motion::Poly => {
+                let outcome = self.poly_rows(&row);
+                self.emit_curve(row, outcome, out);
             }
             Motion::Other => {
                 // A curve interpolation the flattener does not implement
-                // (CIP, CT, POLY, G33/G34/G35 threads, INVCW/INVCCW, ...):
+                // (OEMIPO1/OEMIPO2, ...):
                 // the block passes through unchanged. Warn once per word.
                 if let Some(Value::Str(word)) = row.cells.get("gg01_motion") {
                     if !self.warned_motions.iter().any(|w| w == word) {
@@ -255,7 +411,7 @@ impl Flattener {
     /// Flush any pending spline at end of program.
     pub fn finish(&mut self, out: &mut Vec<Row>) {
         if self.buffering_spline() {
-            self.flush_spline(out);
+            self.flush_spline(None, out);
         }
     }
 
@@ -263,12 +419,23 @@ impl Flattener {
         !self.spline_buffer.is_empty()
     }
 
+    /// Note the positions a row moves to, and its direction as the path
+    /// tangent (a curve's exact end tangent replaces it afterwards).
     fn track_positions(&mut self, row: &Row) {
+        let mut step = [0.0; 3];
         for &axis in &self.geometric_axes {
             if let Some(Value::Float(v)) = row.cells.get(axis) {
+                if let (Some(slot), Some(previous)) =
+                    (METRIC_AXES.iter().position(|&a| a == axis), self.positions.get(axis))
+                {
+                    step[slot] = v - previous;
+                }
                 self.positions.insert(axis, *v);
             }
         }
+        if let Some(direction) = unit(step) {
+            self.tangent = Some(direction);
+        }
     }
 
     /// Pass a curve row through unchanged after a warning: the block
@@ -287,7 +454,145 @@ impl Flattener {
     // Arcs
     // ------------------------------------------------------------------
 
-    fn flatten_arc(&mut self, row: Row, cw: bool, out: &mut Vec<Row>) {
+    /// Flatten a curve row that `sample` resolves in the working plane, in
+    /// frame coordinates while a frame is set.
+    fn flatten_in_plane(&mut self, row: Row, cw: bool, sample: PlaneSampler, out: &mut Vec<Row>) {
+        let outcome = match self.frame {
+            Some(linear) => self.framed_rows(&row, cw, &linear, sample),
+            None => sample(self, &row, cw),
+        };
+        self.emit_curve(row, outcome, out);
+    }
+
+    /// Emit the outcome of sampling the curve block `row`.
+    fn emit_curve(&mut self, row: Row, outcome: ArcOutcome, out: &mut Vec<Row>) {
+        match outcome {
+            ArcOutcome::NotAnArc => out.push(row),
+            ArcOutcome::Failed(reason) => self.pass_through_with_warning(row, reason, out),
+            ArcOutcome::Rows(mut emitted, end_tangent) => {
+                self.track_positions(&row);
+                if end_tangent.is_some() {
+                    self.tangent = end_tangent;
+                }
+                // The final position of unprogrammed plane axes is the arc
+                // endpoint (== start for a full circle), already in `positions`.
+                out.append(&mut emitted);
+            }
+        }
+    }
+
+    /// Sample an arc or involute under a rotated, scaled or mirrored frame:
+    /// the machine positions, I/J/K offsets and CR of the row are mapped into
+    /// frame coordinates, where the working plane and the programmed
+    /// direction are the programmed ones, flattened there by `sample`, and
+    /// the samples mapped back.
+    fn framed_rows(&mut self, row: &Row, cw: bool, linear: &Matrix3, sample: PlaneSampler) -> ArcOutcome {
+        let geometry = ["X", "Y", "Z"].map(intern_column);
+        let Some(inverse) = frame::inverse(linear) else {
+            return ArcOutcome::Failed("the frame is singular");
+        };
+        // The row carries the machine direction; undo an odd plane mirror.
+        let [u, v] = self
+            .plane
+            .axes
+            .map(|axis| METRIC_AXES.iter().position(|&a| a == axis).unwrap_or(0));
+        let cw = cw != (self.mirrored[u] != self.mirrored[v]);
+        // Frame-space tolerance: the largest scale factor stretches the
+        // deviation by that much in machine space.
+        let stretch = (0..3)
+            .map(|c| (0..3).map(|r| linear[r][c] * linear[r][c]).sum::<f64>().sqrt())
+            .fold(1e-12, f64::max);
+        // `m · p` for the rows of `m` whose coupled components are all known.
+        let turn = |m: &Matrix3, p: [Option<f64>; 3]| -> [Option<f64>; 3] {
+            std::array::from_fn(|i| {
+                (0..3)
+                    .filter(|&j| m[i][j].abs() >= frame::EPSILON)
+                    .try_fold(0.0, |sum, j| p[j].map(|v| sum + m[i][j] * v))
+            })
+        };
+        let couples = |m: &Matrix3, i: usize, present: &dyn Fn(usize) -> bool| {
+            (0..3).any(|j| m[i][j].abs() >= frame::EPSILON && present(j))
+        };
+
+        let start = geometry.map(|axis| self.positions.get(axis).copied());
+        let end: [Option<f64>; 3] = std::array::from_fn(|i| cell_float(row, geometry[i]).or(start[i]));
+        let frame_start = turn(&inverse, start);
+        let frame_end = turn(&inverse, end);
+
+        let mut frame_row = row.clone();
+        for axis in geometry {
+            frame_row.cells.remove(axis);
+        }
+        for i in 0..3 {
+            let programmed = couples(&inverse, i, &|j| cell_float(row, geometry[j]).is_some());
+            if let (true, Some(value)) = (programmed, frame_end[i]) {
+                frame_row.cells.insert(geometry[i], Value::Float(value));
+            }
+        }
+
+        let offsets = ["I", "J", "K"].map(intern_column);
+        let machine_offsets = offsets.map(|offset| cell_float(row, offset));
+        if machine_offsets.iter().any(Option::is_some) {
+            let frame_offsets = turn(&inverse, machine_offsets.map(|offset| Some(offset.unwrap_or(0.0))));
+            for i in 0..3 {
+                frame_row.cells.remove(offsets[i]);
+                if let (true, Some(value)) = (
+                    couples(&inverse, i, &|j| machine_offsets[j].is_some()),
+                    frame_offsets[i],
+                ) {
+                    frame_row.cells.insert(offsets[i], Value::Float(value));
+                }
+            }
+        }
+        if let Some(radius) = cell_float(row, "CR") {
+            let radius = radius / frame::radius_scale(linear, [u, v]);
+            frame_row.cells.insert(intern_column("CR"), Value::Float(radius));
+        }
+
+        let saved = self.positions.clone();
+        for (i, axis) in geometry.into_iter().enumerate() {
+            match frame_start[i] {
+                Some(value) => self.positions.insert(axis, value),
+                None => self.positions.remove(axis),
+            };
+        }
+        let tolerance = self.tolerance;
+        self.tolerance /= stretch;
+        let outcome = sample(self, &frame_row, cw);
+        self.tolerance = tolerance;
+        self.positions = saved;
+
+        let ArcOutcome::Rows(mut emitted, end_tangent) = outcome else {
+            return outcome;
+        };
+        let mut frame_position = frame_start;
+        let last = emitted.len() - 1;
+        for (k, sample) in emitted.iter_mut().enumerate() {
+            let present: [bool; 3] = std::array::from_fn(|i| sample.cells.get(geometry[i]).is_some());
+            for i in 0..3 {
+                if let Some(value) = cell_float(sample, geometry[i]) {
+                    frame_position[i] = Some(value);
+                }
+                sample.cells.remove(geometry[i]);
+            }
+            let machine = turn(linear, frame_position);
+            for i in 0..3 {
+                // The programmed endpoint is taken verbatim, not round-tripped.
+                let value = match cell_float(row, geometry[i]) {
+                    Some(programmed) if k == last => Some(programmed),
+                    _ => machine[i].filter(|_| couples(linear, i, &|j| present[j])),
+                };
+                if let Some(value) = value {
+                    sample.cells.insert(geometry[i], Value::Float(value));
+                }
+            }
+        }
+        ArcOutcome::Rows(emitted, end_tangent.and_then(|t| unit(frame::apply(linear, t))))
+    }
+
+    /// Sample an arc-mode row in the working plane, without touching the
+    /// tracked positions.
+    fn arc_rows(&self, row: &Row, cw: bool) -> ArcOutcome {
         let plane = self.plane;
         let u_axis = intern_column(plane.axes[0]);
         let v_axis = intern_column(plane.axes[1]);
@@ -296,43 +601,39 @@ impl Flattener {
             .geometric_axes
             .iter()
             .any(|&axis| matches!(row.cells.get(axis), Some(Value::Float(_))))
-            || cell_float(&row, plane.offsets[0]).is_some()
-            || cell_float(&row, plane.offsets[1]).is_some()
-            || cell_float(&row, "CR").is_some();
+            || cell_float(row, plane.offsets[0]).is_some()
+            || cell_float(row, plane.offsets[1]).is_some()
+            || cell_float(row, "CR").is_some();
         if !programs_geometry {
             // A block in modal arc mode that moves nothing (M codes, feed
             // change, comment): not an arc, pass through.
-            out.push(row);
-            return;
+            return ArcOutcome::NotAnArc;
         }
 
         let (Some(su), Some(sv)) = (self.positions.get(u_axis).copied(), self.positions.get(v_axis).copied()) else {
-            self.pass_through_with_warning(row, "arc start position is unknown", out);
-            return;
+            return ArcOutcome::Failed("arc start position is unknown");
         };
 
-        let eu = cell_float(&row, u_axis).unwrap_or(su);
-        let ev = cell_float(&row, v_axis).unwrap_or(sv);
-        let off_u = cell_float(&row, plane.offsets[0]);
-        let off_v = cell_float(&row, plane.offsets[1]);
+        let eu = cell_float(row, u_axis).unwrap_or(su);
+        let ev = cell_float(row, v_axis).unwrap_or(sv);
+        let off_u = cell_float(row, plane.offsets[0]);
+        let off_v = cell_float(row, plane.offsets[1]);
 
         // Centre from I/J/K offsets (relative to the start point, the
         // SINUMERIK default) or from the CR radius form.
         let (cu, cv) = if off_u.is_some() || off_v.is_some() {
             (su + off_u.unwrap_or(0.0), sv + off_v.unwrap_or(0.0))
-        } else if let Some(cr) = cell_float(&row, "CR") {
+        } else if let Some(cr) = cell_float(row, "CR") {
             let (mu, mv) = ((su + eu) / 2.0, (sv + ev) / 2.0);
             let (du, dv) = (eu - su, ev - sv);
             let chord = (du * du + dv * dv).sqrt();
             if chord < 1e-12 {
-                self.pass_through_with_warning(row, "CR arc with coincident start and end point", out);
-                return;
+                return ArcOutcome::Failed("CR arc with coincident start and end point");
             }
             let r = cr.abs();
             let half = chord / 2.0;
             if r < half - 1e-9 {
-                self.pass_through_with_warning(row, "CR radius is smaller than half the chord", out);
-                return;
+                return ArcOutcome::Failed("CR radius is smaller than half the chord");
             }
             let h = (r * r - half * half).max(0.0).sqrt();
             // Unit normal to the chord, counter-clockwise (left of S->E).
@@ -343,15 +644,13 @@ impl Flattener {
             let side = if cw == minor { -1.0 } else { 1.0 };
             (mu + side * h * nu, mv + side * h * nv)
         } else {
-            self.pass_through_with_warning(row, "arc block without I/J/K centre offsets or CR radius", out);
-            return;
+            return ArcOutcome::Failed("arc block without I/J/K centre offsets or CR radius");
         };
 
         let r_start = ((su - cu).powi(2) + (sv - cv).powi(2)).sqrt();
         let r_end = ((eu - cu).powi(2) + (ev - cv).powi(2)).sqrt();
         if r_start < 1e-9 || r_end < 1e-9 {
-            self.pass_through_with_warning(row, "arc with zero radius", out);
-            return;
+            return ArcOutcome::Failed("arc with zero radius");
         }
 
         let a_start = (sv - cv).atan2(su - cu);
@@ -373,41 +672,91 @@ impl Flattener {
         // Helical interpolation with TURN= (NC programming manual 3.9.7):
         // the programmed number of ADDITIONAL full circles on top of the
         // start-to-end sweep.
-        if let Some(turns) = cell_float(&row, "TURN") {
+        if let Some(turns) = cell_float(row, "TURN") {
             let extra = turns.max(0.0).round();
             sweep += if cw { -tau * extra } else { tau * extra };
         }
 
-        // Exact sagitta bound: deviation of a chord spanning angle theta is
-        // r(1 - cos(theta/2)) <= tolerance.
-        let r_max = r_start.max(r_end);
-        let cos_half = (1.0 - self.tolerance / r_max).clamp(-1.0, 1.0);
+        let segments = self.arc_segments(row, r_start.max(r_end), sweep);
+        let linear = self.linear_channels(row, &[u_axis, v_axis]);
+
+        let mut emitted: Vec<Row> = Vec::with_capacity(segments);
+        for k in 1..=segments {
+            let f = k as f64 / segments as f64;
+            let mut cells = CellMap::default();
+            if k == segments {
+                cells.insert(u_axis, Value::Float(eu));
+                cells.insert(v_axis, Value::Float(ev));
+            } else {
+                let angle = a_start + sweep * f;
+                let r = r_start + (r_end - r_start) * f;
+                cells.insert(u_axis, Value::Float(cu + r * angle.cos()));
+                cells.insert(v_axis, Value::Float(cv + r * angle.sin()));
+                // Generated sample, not a programmed position.
+                cells.insert(intern_column(FLATTENED_COLUMN), Value::Float(1.0));
+            }
+            for &(axis, start, end) in &linear {
+                let value = if k == segments { end } else { start + (end - start) * f };
+                cells.insert(axis, Value::Float(value));
+            }
+            emitted.push(Row {
+                line_no: row.line_no,
+                cells,
+                variable_changes: Vec::new(),
+            });
+        }
+
+        let mut geometry: Vec<&'static str> = vec![u_axis, v_axis];
+        geometry.extend(linear.iter().map(|&(axis, _, _)| axis));
+        merge_aux_cells(row, &mut emitted[0], &geometry);
+        emitted[0].variable_changes = row.variable_changes.clone();
+
+        // The end tangent: the derivative over the sweep fraction.
+        let (sin, cos) = (a_start + sweep).sin_cos();
+        let mut velocity = [0.0; 3];
+        for (&axis, component) in [u_axis, v_axis].iter().zip([-sin, cos]) {
+            if let Some(slot) = METRIC_AXES.iter().position(|&a| a == axis) {
+                velocity[slot] = sweep * r_end * component;
+            }
+        }
+        for &(axis, start, end) in &linear {
+            if let Some(slot) = METRIC_AXES.iter().position(|&a| a == axis) {
+                velocity[slot] = end - start;
+            }
+        }
+        ArcOutcome::Rows(emitted, unit(velocity))
+    }
+
+    /// The number of chords for an arc of radius `radius` sweeping `sweep`
+    /// radians, from the exact sagitta bound: the deviation of a chord
+    /// spanning angle theta is r(1 - cos(theta/2)) <= tolerance.
+    fn arc_segments(&self, row: &Row, radius: f64, sweep: f64) -> usize {
+        let cos_half = (1.0 - self.tolerance / radius).clamp(-1.0, 1.0);
         let theta_step = 2.0 * cos_half.acos();
-        // Cap the per-arc sample count: a pathological tolerance/radius combo
-        // would otherwise materialize a gigabyte-scale row burst. The clamp is
-        // loud - the emitted polyline is then coarser than requested.
-        const MAX_ARC_SEGMENTS: usize = 100_000;
         let requested = if theta_step > 1e-12 {
             (sweep.abs() / theta_step).ceil() as usize
         } else {
             1
         };
-        let segments = requested.clamp(1, MAX_ARC_SEGMENTS);
         if requested > MAX_ARC_SEGMENTS {
             emit_warning(format_args!(
                 "Warning [line {}]: arc needs {} segments to meet the tolerance; clamped to {} (deviation will exceed the requested tolerance)",
                 row.line_no, requested, MAX_ARC_SEGMENTS
             ));
         }
+        requested.clamp(1, MAX_ARC_SEGMENTS)
+    }
 
-        // Channels interpolated linearly over the sweep: every other
-        // geometric axis programmed on this block.
-        let mut linear: Vec<(&'static str, f64, f64)> = Vec::new();
+    /// The channels interpolated linearly over an arc's sweep: every
+    /// geometric axis programmed on `row` other than the `curve` axes, with
+    /// its start and end value.
+    fn linear_channels(&self, row: &Row, curve: &[&'static str]) -> Vec<(&'static str, f64, f64)> {
+        let mut linear = Vec::new();
         for &axis in &self.geometric_axes {
-            if axis == u_axis || axis == v_axis {
+            if curve.contains(&axis) {
                 continue;
             }
-            if let Some(end) = cell_float(&row, axis) {
+            if let Some(end) = cell_float(row, axis) {
                 let start = match self.positions.get(axis) {
                     Some(&start) => start,
                     None => {
@@ -425,20 +774,76 @@ impl Flattener {
                 linear.push((axis, start, end));
             }
         }
+        linear
+    }
+
+    /// Sample a `CIP` row: the circle through the start point, the
+    /// intermediate point `I1`/`J1`/`K1` and the end point, in the plane
+    /// they span. All three are machine coordinates, so no frame applies.
+    fn circle_rows(&self, row: &Row) -> ArcOutcome {
+        let geometry = ["X", "Y", "Z"].map(intern_column);
+        let intermediate = ["I1", "J1", "K1"].map(|address| cell_float(row, address));
+        let moves = geometry.iter().any(|&axis| cell_float(row, axis).is_some());
+        if !moves && intermediate.iter().all(Option::is_none) {
+            return ArcOutcome::NotAnArc;
+        }
+        if intermediate.iter().all(Option::is_none) {
+            return ArcOutcome::Failed("CIP block without an intermediate point I1/J1/K1");
+        }
+        let mut start = [0.0; 3];
+        for slot in 0..3 {
+            // An axis the block leaves alone whose position is still unknown
+            // stays in the intermediate point's coordinate.
+            start[slot] = match (self.positions.get(geometry[slot]), cell_float(row, geometry[slot])) {
+                (Some(&value), _) => value,
+                (None, None) => intermediate[slot].unwrap_or(0.0),
+                (None, Some(_)) => return ArcOutcome::Failed("circle start position is unknown"),
+            };
+        }
+        let middle: [f64; 3] = std::array::from_fn(|slot| intermediate[slot].unwrap_or(start[slot]));
+        let end: [f64; 3] = std::array::from_fn(|slot| cell_float(row, geometry[slot]).unwrap_or(start[slot]));
+
+        // Circumcentre of the triangle start-middle-end, and the normal that
+        // orders the three points counter-clockwise.
+        let a = sub(start, end);
+        let b = sub(middle, end);
+        let normal = cross(a, b);
+        let normal_len2 = dot(normal, normal);
+        if normal_len2.sqrt() < 1e-9 * (dot(a, a) + dot(b, b)).max(1e-12) {
+            return ArcOutcome::Failed("CIP start, intermediate and end point lie on a line");
+        }
+        let towards = cross(sub(scale(b, dot(a, a)), scale(a, dot(b, b))), normal);
+        let centre = add(end, scale(towards, 1.0 / (2.0 * normal_len2)));
+        let radius = dot(sub(start, centre), sub(start, centre)).sqrt();
+        let e1 = scale(sub(start, centre), 1.0 / radius);
+        let e2 = cross(scale(normal, 1.0 / normal_len2.sqrt()), e1);
+        let angle = |p: [f64; 3]| dot(sub(p, centre), e2).atan2(dot(sub(p, centre), e1));
+        let sweep = angle(end).rem_euclid(std::f64::consts::TAU);
+
+        let segments = self.arc_segments(row, radius, sweep);
+        // Only the axes the circle moves (or the block programs) are written.
+        let curve: Vec<usize> = (0..3)
+            .filter(|&slot| {
+                self.geometric_axes.contains(&geometry[slot])
+                    && (cell_float(row, geometry[slot]).is_some() || e1[slot].abs() > 1e-12 || e2[slot].abs() > 1e-12)
+            })
+            .collect();
+        let linear = self.linear_channels(row, &geometry);
 
         let mut emitted: Vec<Row> = Vec::with_capacity(segments);
         for k in 1..=segments {
             let f = k as f64 / segments as f64;
             let mut cells = CellMap::default();
             if k == segments {
-                cells.insert(u_axis, Value::Float(eu));
-                cells.insert(v_axis, Value::Float(ev));
+                for &slot in &curve {
+                    cells.insert(geometry[slot], Value::Float(end[slot]));
+                }
             } else {
-                let angle = a_start + sweep * f;
-                let r = r_start + (r_end - r_start) * f;
-                cells.insert(u_axis, Value::Float(cu + r * angle.cos()));
-                cells.insert(v_axis, Value::Float(cv + r * angle.sin()));
-                // Generated sample, not a programmed position.
+                let (sin, cos) = (sweep * f).sin_cos();
+                for &slot in &curve {
+                    let value = centre[slot] + radius * (cos * e1[slot] + sin * e2[slot]);
+                    cells.insert(geometry[slot], Value::Float(value));
+                }
                 cells.insert(intern_column(FLATTENED_COLUMN), Value::Float(1.0));
             }
             for &(axis, start, end) in &linear {
@@ -452,22 +857,384 @@ impl Flattener {
             });
         }
 
-        let mut geometry: Vec<&'static str> = vec![u_axis, v_axis];
-        geometry.extend(linear.iter().map(|&(axis, _, _)| axis));
-        merge_aux_cells(&row, &mut emitted[0], &geometry);
+        let mut channels: Vec<&'static str> = geometry.to_vec();
+        channels.extend(linear.iter().map(|&(axis, _, _)| axis));
+        merge_aux_cells(row, &mut emitted[0], &channels);
         emitted[0].variable_changes = row.variable_changes.clone();
 
-        self.track_positions(&row);
-        // The final position of unprogrammed plane axes is the arc endpoint
-        // (== start for a full circle), already in `positions`.
-        out.append(&mut emitted);
+        let (sin, cos) = sweep.sin_cos();
+        ArcOutcome::Rows(emitted, unit(sub(scale(e2, cos), scale(e1, sin))))
+    }
+
+    /// Sample a `CT` row: the arc in the working plane that leaves the start
+    /// point along the tracked path direction and ends on the programmed
+    /// point. It is handed to the G2/G3 sampling as an I/J/K arc, in frame
+    /// coordinates while a frame is set.
+    fn tangent_arc_rows(&mut self, row: &Row) -> ArcOutcome {
+        let geometry = ["X", "Y", "Z"].map(intern_column);
+        if geometry.iter().all(|&axis| cell_float(row, axis).is_none()) {
+            return ArcOutcome::NotAnArc;
+        }
+        let Some(tangent) = self.tangent else {
+            return ArcOutcome::Failed("CT without a path direction to continue");
+        };
+        let start = geometry.map(|axis| self.positions.get(axis).copied().unwrap_or(0.0));
+        let chord: [f64; 3] = std::array::from_fn(|i| cell_float(row, geometry[i]).map_or(0.0, |end| end - start[i]));
+        // Frame coordinates, where the working plane is the programmed one.
+        let inverse = match self.frame {
+            Some(linear) => match frame::inverse(&linear) {
+                Some(inverse) => Some(inverse),
+                None => return ArcOutcome::Failed("the frame is singular"),
+            },
+            None => None,
+        };
+        let to_frame = |v: [f64; 3]| inverse.as_ref().map_or(v, |inverse| frame::apply(inverse, v));
+        let (chord, tangent) = (to_frame(chord), to_frame(tangent));
+
+        let [u, v] = self
+            .plane
+            .axes
+            .map(|axis| METRIC_AXES.iter().position(|&a| a == axis).unwrap_or(0));
+        let (du, dv) = (chord[u], chord[v]);
+        let chord2 = du * du + dv * dv;
+        if chord2 < 1e-18 {
+            return ArcOutcome::Failed("CT with coincident start and end point");
+        }
+        let tangent_len = tangent[u].hypot(tangent[v]);
+        if tangent_len < 1e-9 {
+            return ArcOutcome::Failed("the path direction before CT is normal to the working plane");
+        }
+        let (tu, tv) = (tangent[u] / tangent_len, tangent[v] / tangent_len);
+        // The centre lies on the start point's normal, at the signed distance
+        // that makes it equidistant from start and end point: left of the
+        // tangent (counter-clockwise) for a positive distance.
+        let (nu, nv) = (-tv, tu);
+        let across = du * nu + dv * nv;
+        if across.abs() < 1e-9 * chord2.sqrt() {
+            if du * tu + dv * tv < 0.0 {
+                return ArcOutcome::Failed("the CT end point lies behind the start point on its tangent");
+            }
+            // Straight ahead: the tangent circle degenerates to a line.
+            let mut line = row.clone();
+            line.cells
+                .insert(intern_column("gg01_motion"), Value::Str("G1".to_string()));
+            return ArcOutcome::Rows(vec![line], None);
+        }
+        let distance = chord2 / (2.0 * across);
+        let mut centre = [0.0; 3];
+        (centre[u], centre[v]) = (distance * nu, distance * nv);
+        let mut arc_row = row.clone();
+        let cw = distance < 0.0;
+        match self.frame {
+            // The framed sampling takes machine offsets, and undoes an odd
+            // plane mirror of the machine direction; this direction is
+            // already the frame's.
+            Some(linear) => {
+                for (offset, value) in ["I", "J", "K"].into_iter().zip(frame::apply(&linear, centre)) {
+                    arc_row.cells.insert(intern_column(offset), Value::Float(value));
+                }
+                let odd_mirror = self.mirrored[u] != self.mirrored[v];
+                self.framed_rows(&arc_row, cw != odd_mirror, &linear, Self::arc_rows)
+            }
+            None => {
+                for slot in [u, v] {
+                    arc_row
+                        .cells
+                        .insert(intern_column(["I", "J", "K"][slot]), Value::Float(centre[slot]));
+                }
+                self.arc_rows(&arc_row, cw)
+            }
+        }
+    }
+
+    /// Sample an involute row in the working plane (see [`crate::involute`]):
+    /// the unwound length runs from the start to the end point, while the
+    /// phase blends from the involute through the start point to the one
+    /// through the end point (the interpreter keeps them within
+    /// [`crate::involute::END_POINT_TOLERANCE`] of each other).
+    fn involute_rows(&self, row: &Row, cw: bool) -> ArcOutcome {
+        let plane = self.plane;
+        let u_axis = intern_column(plane.axes[0]);
+        let v_axis = intern_column(plane.axes[1]);
+        let offsets = plane.offsets.map(|offset| cell_float(row, offset));
+        let programs_geometry = self.geometric_axes.iter().any(|&axis| cell_float(row, axis).is_some())
+            || offsets.iter().any(Option::is_some)
+            || cell_float(row, "CR").is_some();
+        if !programs_geometry {
+            return ArcOutcome::NotAnArc;
+        }
+        let Some(radius) = cell_float(row, "CR").filter(|radius| *radius > 0.0) else {
+            return ArcOutcome::Failed("involute block without a positive base circle radius CR");
+        };
+        if offsets.iter().all(Option::is_none) {
+            return ArcOutcome::Failed("involute block without the base circle centre I/J/K");
+        }
+        let (Some(su), Some(sv)) = (self.positions.get(u_axis).copied(), self.positions.get(v_axis).copied()) else {
+            return ArcOutcome::Failed("involute start position is unknown");
+        };
+        let start = [su, sv];
+        let end = [
+            cell_float(row, u_axis).unwrap_or(su),
+            cell_float(row, v_axis).unwrap_or(sv),
+        ];
+        let centre = [su + offsets[0].unwrap_or(0.0), sv + offsets[1].unwrap_or(0.0)];
+        let probe = Involute::new(centre, radius, cw, true);
+        let (Some(start_unwound), Some(end_unwound)) = (probe.unwound(start), probe.unwound(end)) else {
+            return ArcOutcome::Failed("involute start or end point lies inside the base circle");
+        };
+        let linear = self.linear_channels(row, &[u_axis, v_axis]);
+        let unwinding = end_unwound - start_unwound;
+        if unwinding.abs() < 1e-12 {
+            // No way along the involute: at most the other axes move.
+            let mut line = row.clone();
+            line.cells
+                .insert(intern_column("gg01_motion"), Value::Str("G1".to_string()));
+            return ArcOutcome::Rows(vec![line], None);
+        }
+
+        let involute = Involute::new(centre, radius, cw, unwinding > 0.0);
+        let phase = involute.phase(start);
+        let pi = std::f64::consts::PI;
+        let drift = (involute.phase(end) - phase + pi).rem_euclid(2.0 * pi) - pi;
+        let eval = |f: f64| -> Vec<f64> {
+            let point = involute.point(phase + drift * f, start_unwound + unwinding * f);
+            let mut values = point.to_vec();
+            values.extend(linear.iter().map(|&(_, start, end)| start + (end - start) * f));
+            values
+        };
+        let channels: Vec<&'static str> = [u_axis, v_axis]
+            .into_iter()
+            .chain(linear.iter().map(|&(axis, _, _)| axis))
+            .collect();
+        let metric: Vec<usize> = (0..channels.len())
+            .filter(|&i| i < 2 || METRIC_AXES.contains(&channels[i]))
+            .collect();
+        // Spans of at most a quarter turn of the generating line, so the
+        // probes of the subdivision cannot skip over a whole winding.
+        let spans = (unwinding.abs() / std::f64::consts::FRAC_PI_2).ceil().max(1.0) as usize;
+        let mut samples: Vec<Vec<f64>> = Vec::new();
+        let mut previous = eval(0.0);
+        for k in 1..=spans {
+            let (t0, t1) = ((k - 1) as f64 / spans as f64, k as f64 / spans as f64);
+            let next = eval(t1);
+            adaptive_flatten(
+                &eval,
+                t0,
+                &previous,
+                t1,
+                &next,
+                self.tolerance,
+                &metric,
+                0,
+                &mut samples,
+            );
+            previous = next;
+        }
+        if let Some(last) = samples.last_mut() {
+            last[..2].copy_from_slice(&end);
+        }
+        let emitted = sampled_rows(row, &channels, &samples);
+
+        // The end tangent: along the generating line, in the direction of
+        // travel (the limit direction when the path ends on the base circle).
+        let direction = involute.direction(phase + drift, end_unwound);
+        let speed = radius * end_unwound.max(f64::EPSILON) * unwinding;
+        let mut velocity = [0.0; 3];
+        for (&axis, component) in [u_axis, v_axis].iter().zip(direction) {
+            if let Some(slot) = METRIC_AXES.iter().position(|&a| a == axis) {
+                velocity[slot] = speed * component;
+            }
+        }
+        for &(axis, start, end) in &linear {
+            if let Some(slot) = METRIC_AXES.iter().position(|&a| a == axis) {
+                velocity[slot] = end - start;
+            }
+        }
+        ArcOutcome::Rows(emitted, unit(velocity))
+    }
+
+    /// Sample a `POLY` row: the geometry axes with polynomial coefficients
+    /// follow them (over the common denominator), every other axis the
+    /// block programs moves linearly in the parameter.
+    fn poly_rows(&self, row: &Row) -> ArcOutcome {
+        let length = cell_float(row, "PL").unwrap_or(1.0);
+        let denominator: [f64; 6] = std::array::from_fn(|n| {
+            if n == 0 {
+                1.0
+            } else {
+                cell_float(row, DENOMINATOR_COLUMNS[n - 1]).unwrap_or(0.0)
+            }
+        });
+        let mut curves: Vec<(&'static str, [f64; 6])> = Vec::new();
+        for (slot, columns) in NUMERATOR_COLUMNS.iter().enumerate() {
+            let axis = intern_column(METRIC_AXES[slot]);
+            if cell_float(row, columns[0]).is_none() || !self.geometric_axes.contains(&axis) {
+                continue;
+            }
+            let Some(&start) = self.positions.get(axis) else {
+                return ArcOutcome::Failed("polynomial start position is unknown");
+            };
+            let numerator: [f64; 6] = std::array::from_fn(|n| {
+                if n == 0 {
+                    start
+                } else {
+                    cell_float(row, columns[n - 1]).unwrap_or(0.0)
+                }
+            });
+            curves.push((axis, numerator));
+        }
+        if curves.is_empty() {
+            // No polynomial: the block is a straight line.
+            if self.geometric_axes.iter().all(|&axis| cell_float(row, axis).is_none()) {
+                return ArcOutcome::NotAnArc;
+            }
+            let mut line = row.clone();
+            line.cells
+                .insert(intern_column("gg01_motion"), Value::Str("G1".to_string()));
+            return ArcOutcome::Rows(vec![line], None);
+        }
+        let curve_axes: Vec<&'static str> = curves.iter().map(|&(axis, _)| axis).collect();
+        let linear = self.linear_channels(row, &curve_axes);
+        let channels: Vec<&'static str> = curve_axes
+            .iter()
+            .copied()
+            .chain(linear.iter().map(|&(axis, _, _)| axis))
+            .collect();
+
+        let g = |p: f64| horner(&denominator, p);
+        let eval = |p: f64| -> Vec<f64> {
+            let mut values: Vec<f64> = curves
+                .iter()
+                .map(|(_, numerator)| horner(numerator, p) / g(p))
+                .collect();
+            values.extend(linear.iter().map(|&(_, start, end)| start + (end - start) * p / length));
+            values
+        };
+        let metric: Vec<usize> = {
+            let xyz: Vec<usize> = (0..channels.len())
+                .filter(|&i| METRIC_AXES.contains(&channels[i]))
+                .collect();
+            if xyz.is_empty() {
+                (0..channels.len()).collect()
+            } else {
+                xyz
+            }
+        };
+        let mut samples: Vec<Vec<f64>> = Vec::new();
+        let (first, end) = (eval(0.0), eval(length));
+        adaptive_flatten(
+            &eval,
+            0.0,
+            &first,
+            length,
+            &end,
+            self.tolerance,
+            &metric,
+            0,
+            &mut samples,
+        );
+
+        let emitted = sampled_rows(row, &channels, &samples);
+
+        // The end tangent: (f'g - fg') / g^2 at p = PL, without the positive
+        // factor 1 / g^2.
+        let slope = |coefficients: &[f64; 6]| {
+            (1..6)
+                .map(|n| n as f64 * coefficients[n] * length.powi(n as i32 - 1))
+                .sum::<f64>()
+        };
+        let (g_end, g_slope) = (g(length), slope(&denominator));
+        let mut velocity = [0.0; 3];
+        for (axis, numerator) in &curves {
+            if let Some(slot) = METRIC_AXES.iter().position(|a| a == axis) {
+                velocity[slot] = slope(numerator) * g_end - horner(numerator, length) * g_slope;
+            }
+        }
+        for &(axis, start, end) in &linear {
+            if let Some(slot) = METRIC_AXES.iter().position(|&a| a == axis) {
+                velocity[slot] = (end - start) / length * g_end * g_end;
+            }
+        }
+        ArcOutcome::Rows(emitted, unit(velocity))
     }
 
     // ------------------------------------------------------------------
     // Splines
     // ------------------------------------------------------------------
 
-    fn flush_spline(&mut self, out: &mut Vec<Row>) {
+    /// The direction (unit X/Y/Z vector) in which the block `row`, which
+    /// selects `motion` after a spline, leaves the spline's end point: exact
+    /// for a straight move, the first chord of a flattened curve. `None` for
+    /// blocks that do not move or whose start depends on the spline (`CT`,
+    /// another spline).
+    fn start_direction(&mut self, row: &Row, motion: Motion) -> Option<[f64; 3]> {
+        let start: [Option<f64>; 3] = std::array::from_fn(|i| self.positions.get(METRIC_AXES[i]).copied());
+        let outcome = match motion {
+            Motion::Linear => {
+                let step = std::array::from_fn(|i| match (cell_float(row, METRIC_AXES[i]), start[i]) {
+                    (Some(end), Some(start)) => end - start,
+                    _ => 0.0,
+                });
+                return unit(step);
+            }
+            Motion::Arc { cw } | Motion::Involute { cw } => {
+                let sample: PlaneSampler = match motion {
+                    Motion::Arc { .. } => Self::arc_rows,
+                    _ => Self::involute_rows,
+                };
+                match self.frame {
+                    Some(linear) => self.framed_rows(row, cw, &linear, sample),
+                    None => sample(self, row, cw),
+                }
+            }
+            Motion::Circle => self.circle_rows(row),
+            Motion::Poly => self.poly_rows(row),
+            Motion::Tangent | Motion::Spline(_) | Motion::Other => return None,
+        };
+        let ArcOutcome::Rows(emitted, _) = outcome else {
+            return None;
+        };
+        let first = emitted.first()?;
+        let step = std::array::from_fn(|i| match (cell_float(first, METRIC_AXES[i]), start[i]) {
+            (Some(end), Some(start)) => end - start,
+            _ => 0.0,
+        });
+        unit(step)
+    }
+
+    /// The per-channel start and end conditions of an interpolating spline
+    /// over `channels`, ending towards `next` (see [`Self::start_direction`]).
+    /// A tangent is the unit direction within the spline's X/Y/Z channels;
+    /// `BTAN`/`ETAN` without a direction there, and the other channels, fall
+    /// back to `BAUTO`/`EAUTO`.
+    fn spline_ends(&self, channels: &[&'static str], next: Option<[f64; 3]>) -> Vec<[EndCondition; 2]> {
+        let ends = [
+            (self.spline_start_condition, self.spline_start_tangent),
+            (self.spline_conditions[1], next),
+        ]
+        .map(|(condition, direction)| {
+            let slopes: Vec<Option<f64>> = channels
+                .iter()
+                .map(|&c| {
+                    let slot = METRIC_AXES.iter().position(|&a| a == c)?;
+                    direction.map(|d| d[slot])
+                })
+                .collect();
+            let length = slopes.iter().flatten().map(|v| v * v).sum::<f64>().sqrt();
+            let per_channel: Vec<EndCondition> = slopes
+                .iter()
+                .map(|slope| match (condition, slope) {
+                    (SplineCondition::Natural, _) => EndCondition::Natural,
+                    (SplineCondition::Tangent, Some(slope)) if length > 1e-9 => EndCondition::Slope(slope / length),
+                    _ => EndCondition::Auto,
+                })
+                .collect();
+            per_channel
+        });
+        (0..channels.len()).map(|ci| [ends[0][ci], ends[1][ci]]).collect()
+    }
+
+    fn flush_spline(&mut self, next: Option<[f64; 3]>, out: &mut Vec<Row>) {
         let Motion::Spline(kind) = self.motion else {
             self.spline_buffer.clear();
             return;
@@ -475,8 +1242,9 @@ impl Flattener {
         let items = std::mem::take(&mut self.spline_buffer);
         let start = std::mem::take(&mut self.spline_start);
 
-        // The spline channels: every geometric axis programmed on any
-        // point-bearing block of the spline.
+        // The channels: every geometric axis programmed on any point-bearing
+        // block of the spline, those of the spline path first; the others
+        // follow linearly.
         let mut channels: Vec<&'static str> = Vec::new();
         for item in &items {
             if let SplineItem::Point(row) = item {
@@ -487,6 +1255,9 @@ impl Flattener {
                 }
             }
         }
+        let path = std::mem::take(&mut self.spline_start_path);
+        channels.sort_by_key(|axis| !path.contains(axis));
+        let spline_count = channels.iter().filter(|axis| path.contains(axis)).count();
 
         // Build the point list: the start position, then one point per
         // point-bearing block (unprogrammed channels hold their previous
@@ -494,6 +1265,7 @@ impl Flattener {
         // programmed point i (start point: None).
         let mut points: Vec<Vec<f64>> = Vec::with_capacity(items.len() + 1);
         let mut weights: Vec<f64> = Vec::with_capacity(items.len() + 1);
+        let mut intervals: Vec<Option<f64>> = Vec::with_capacity(items.len() + 1);
         let mut point_rows: Vec<Option<usize>> = Vec::with_capacity(items.len() + 1);
         let mut current: Vec<f64> = channels
             .iter()
@@ -513,6 +1285,7 @@ impl Flattener {
             .collect();
         points.push(current.clone());
         weights.push(1.0);
+        intervals.push(None);
         point_rows.push(None);
         for (index, item) in items.iter().enumerate() {
             if let SplineItem::Point(row) = item {
@@ -523,20 +1296,22 @@ impl Flattener {
                 }
                 points.push(current.clone());
                 weights.push(cell_float(row, "PW").unwrap_or(1.0).max(1e-4));
+                intervals.push(cell_float(row, "PL"));
                 point_rows.push(Some(index));
             }
         }
 
-        // Indices (into `channels`) of the deviation-metric subspace.
+        // Indices (into `channels`) of the deviation-metric subspace of the
+        // spline path.
         let metric: Vec<usize> = {
-            let xyz: Vec<usize> = channels
+            let xyz: Vec<usize> = channels[..spline_count]
                 .iter()
                 .enumerate()
                 .filter(|(_, c)| METRIC_AXES.contains(&**c))
                 .map(|(i, _)| i)
                 .collect();
             if xyz.is_empty() {
-                (0..channels.len()).collect()
+                (0..spline_count).collect()
             } else {
                 xyz
             }
@@ -544,16 +1319,39 @@ impl Flattener {
 
         // Sample the curve: per source point, the run of samples that ends
         // at (or is attributed to) that point.
-        let runs: Option<Vec<Vec<Vec<f64>>>> = if points.len() < 2 {
+        let path_points: Vec<Vec<f64>> = points.iter().map(|point| point[..spline_count].to_vec()).collect();
+        let runs: Option<Vec<Vec<Vec<f64>>>> = if points.len() < 2 || spline_count == 0 {
             None
         } else {
             match kind {
                 SplineKind::Akima | SplineKind::Cubic => {
-                    interpolating_spline_runs(&points, &metric, self.tolerance, kind)
+                    let ends = self.spline_ends(&channels[..spline_count], next);
+                    interpolating_spline_runs(&path_points, &metric, &intervals, &ends, self.tolerance, kind)
+                }
+                SplineKind::BSpline => {
+                    let intervals: Vec<f64> = intervals.iter().map(|interval| interval.unwrap_or(1.0)).collect();
+                    let degree = self.spline_degree;
+                    bspline_runs(&path_points, &weights, &intervals, degree, &metric, self.tolerance)
                 }
-                SplineKind::BSpline => bspline_runs(&points, &weights, self.spline_degree, &metric, self.tolerance),
             }
         };
+        let runs = runs.map(|runs| follow_linearly(runs, &points, spline_count, &metric));
+        let sampled = runs.is_some();
+
+        // The path direction at the spline end: its last chord.
+        let end_tangent = runs.as_ref().and_then(|runs| {
+            let slots: Vec<Option<usize>> = METRIC_AXES
+                .iter()
+                .map(|&axis| channels.iter().position(|&c| c == axis))
+                .collect();
+            let xyz = |p: &Vec<f64>| -> [f64; 3] { std::array::from_fn(|i| slots[i].map_or(0.0, |ci| p[ci])) };
+            let samples: Vec<[f64; 3]> = std::iter::once(&points[0])
+                .chain(runs.iter().flatten())
+                .map(xyz)
+                .collect();
+            let end = *samples.last()?;
+            samples.iter().rev().find_map(|&p| unit(sub(end, p)))
+        });
 
         // Emit: walk the buffered blocks in order; each point-bearing block
         // is replaced by its sampled run, everything else passes through in
@@ -586,10 +1384,11 @@ impl Flattener {
                         // a plain move to its programmed point. A B-spline
                         // control point is not on the curve, so its block
                         // keeps only its auxiliary cells (coordinates come
-                        // from the samples attributed to later blocks).
+                        // from the samples attributed to later blocks), or
+                        // its linear axes when there is no curve to sample.
                         let mut cells = CellMap::default();
-                        if kind != SplineKind::BSpline {
-                            for (ci, &c) in channels.iter().enumerate() {
+                        for (ci, &c) in channels.iter().enumerate() {
+                            if kind != SplineKind::BSpline || (!sampled && ci >= spline_count) {
                                 if let Some(Value::Float(_)) = row.cells.get(c) {
                                     cells.insert(c, Value::Float(points_value(&points, &point_rows, index, ci)));
                                 }
@@ -640,9 +1439,62 @@ impl Flattener {
                 }
             }
         }
+        if end_tangent.is_some() {
+            self.tangent = end_tangent;
+        }
     }
 }
 
+/// Extend the samples of the spline `runs` (over the first `spline_count`
+/// channels of `points`) with the channels that follow linearly: over each
+/// run, from where the last run left them to the run's point, in proportion
+/// to the distance travelled in the `metric` subspace.
+fn follow_linearly(
+    runs: Vec<Vec<Vec<f64>>>,
+    points: &[Vec<f64>],
+    spline_count: usize,
+    metric: &[usize],
+) -> Vec<Vec<Vec<f64>>> {
+    let mut from = points[0][spline_count..].to_vec();
+    let mut previous = points[0][..spline_count].to_vec();
+    let mut followed = Vec::with_capacity(runs.len());
+    for (i, run) in runs.into_iter().enumerate() {
+        if run.is_empty() {
+            followed.push(run);
+            continue;
+        }
+        let target = &points[i + 1][spline_count..];
+        let mut travelled = Vec::with_capacity(run.len());
+        let mut total = 0.0;
+        for sample in &run {
+            total += metric
+                .iter()
+                .map(|&c| (sample[c] - previous[c]).powi(2))
+                .sum::<f64>()
+                .sqrt();
+            travelled.push(total);
+            previous = sample.clone();
+        }
+        let count = run.len();
+        let run = run
+            .into_iter()
+            .enumerate()
+            .map(|(k, mut sample)| {
+                let fraction = if total > 1e-12 {
+                    travelled[k] / total
+                } else {
+                    (k + 1) as f64 / count as f64
+                };
+                sample.extend(from.iter().zip(target).map(|(a, b)| a + (b - a) * fraction));
+                sample
+            })
+            .collect();
+        from = target.to_vec();
+        followed.push(run);
+    }
+    followed
+}
+
 /// The exact coordinates of the point programmed by buffer item `item_index`.
 fn points_value(points: &[Vec<f64>], point_rows: &[Option<usize>], item_index: usize, channel: usize) -> f64 {
     for (pi, source) in point_rows.iter().enumerate() {
@@ -656,9 +1508,15 @@ fn points_value(points: &[Vec<f64>], point_rows: &[Option<usize>], item_index: u
 fn row_motion(row: &Row) -> Option<Motion> {
     match row.cells.get(intern_column("gg01_motion")) {
         Some(Value::Str(m)) => Some(match m.as_str() {
-            "G0" | "G1" => Motion::Linear,
+            // A thread is cut on a straight tool move (see crate::threads).
+            "G0" | "G1" | "G33" | "G34" | "G35" => Motion::Linear,
             "G2" => Motion::Arc { cw: true },
             "G3" => Motion::Arc { cw: false },
+            "CIP" => Motion::Circle,
+            "CT" => Motion::Tangent,
+            "POLY" => Motion::Poly,
+            "INVCW" => Motion::Involute { cw: true },
+            "INVCCW" => Motion::Involute { cw: false },
             "ASPLINE" => Motion::Spline(SplineKind::Akima),
             "BSPLINE" => Motion::Spline(SplineKind::BSpline),
             "CSPLINE" => Motion::Spline(SplineKind::Cubic),
@@ -668,6 +1526,41 @@ fn row_motion(row: &Row) -> Option<Motion> {
     }
 }
 
+/// The polynomial with `coefficients` (constant term first) at `p`.
+fn horner(coefficients: &[f64; 6], p: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |sum, &c| sum * p + c)
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|i| a[i] - b[i])
+}
+
+fn add(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|i| a[i] + b[i])
+}
+
+fn scale(a: [f64; 3], factor: f64) -> [f64; 3] {
+    a.map(|v| v * factor)
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    (0..3).map(|i| a[i] * b[i]).sum()
+}
+
+/// `a` scaled to unit length, `None` for a (near-)zero vector.
+fn unit(a: [f64; 3]) -> Option<[f64; 3]> {
+    let length = dot(a, a).sqrt();
+    (length > 1e-12).then(|| scale(a, 1.0 / length))
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
 fn cell_float(row: &Row, key: &str) -> Option<f64> {
     match row.cells.get(key) {
         Some(Value::Float(v)) => Some(*v),
@@ -675,6 +1568,36 @@ fn cell_float(row: &Row, key: &str) -> Option<f64> {
     }
 }
 
+/// The run of rows for the `samples` (one value per channel, the start
+/// point excluded) of the curve block `row`. The last row takes the
+/// programmed end point verbatim; the first carries the block's other cells.
+fn sampled_rows(row: &Row, channels: &[&'static str], samples: &[Vec<f64>]) -> Vec<Row> {
+    let count = samples.len();
+    let mut emitted: Vec<Row> = Vec::with_capacity(count);
+    for (k, sample) in samples.iter().enumerate() {
+        let mut cells = CellMap::default();
+        for (i, &axis) in channels.iter().enumerate() {
+            // The programmed end point is taken verbatim.
+            let value = match cell_float(row, axis) {
+                Some(programmed) if k + 1 == count => programmed,
+                _ => sample[i],
+            };
+            cells.insert(axis, Value::Float(value));
+        }
+        if k + 1 < count {
+            cells.insert(intern_column(FLATTENED_COLUMN), Value::Float(1.0));
+        }
+        emitted.push(Row {
+            line_no: row.line_no,
+            cells,
+            variable_changes: Vec::new(),
+        });
+    }
+    merge_aux_cells(row, &mut emitted[0], channels);
+    emitted[0].variable_changes = row.variable_changes.clone();
+    emitted
+}
+
 /// Copy every cell of `source` that is neither a flattened geometry channel
 /// nor a consumed interpolation address onto `target` (the first row of the
 /// flattened run), rewriting the motion command to G1. Feed, spindle, block
@@ -748,35 +1671,45 @@ fn adaptive_flatten(
     }
 }
 
-/// Chord-length parameter values for a point sequence, measured in the
-/// metric subspace. Duplicate points get a zero-length span.
-fn chord_parameters(points: &[Vec<f64>], metric: &[usize]) -> Vec<f64> {
+/// Parameter values for a point sequence: each span is the `PL` interval
+/// programmed for its end point (`intervals[i]` for the span ending at point
+/// i), by default its chord length in the metric subspace. Duplicate points
+/// without `PL` get a zero-length span.
+fn chord_parameters(points: &[Vec<f64>], metric: &[usize], intervals: &[Option<f64>]) -> Vec<f64> {
     let mut ts = Vec::with_capacity(points.len());
     let mut t = 0.0;
     ts.push(0.0);
-    for pair in points.windows(2) {
-        let mut d2 = 0.0;
-        for &i in metric {
-            let d = pair[1][i] - pair[0][i];
-            d2 += d * d;
-        }
-        t += d2.sqrt();
+    for (pair, interval) in points.windows(2).zip(&intervals[1..]) {
+        t += interval.unwrap_or_else(|| chord(&pair[0], &pair[1], metric));
         ts.push(t);
     }
     ts
 }
 
-/// Flatten an interpolating spline (Akima or natural cubic) through
-/// `points`. Returns one run of samples per segment `points[i] ->
-/// points[i+1]` (each run ends exactly at `points[i+1]`), or `None` when the
-/// path is degenerate (all points coincide).
+/// The distance between `a` and `b` in the `metric` subspace.
+fn chord(a: &[f64], b: &[f64], metric: &[usize]) -> f64 {
+    metric
+        .iter()
+        .map(|&i| (b[i] - a[i]) * (b[i] - a[i]))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Flatten an interpolating spline (Akima or cubic) through `points`, over
+/// the parameter `intervals` (see [`chord_parameters`]), with the start and
+/// end condition `ends` per channel. Returns one run of
+/// samples per segment `points[i] -> points[i+1]` (each run ends exactly at
+/// `points[i+1]`), or `None` when the path is degenerate (all points
+/// coincide).
 fn interpolating_spline_runs(
     points: &[Vec<f64>],
     metric: &[usize],
+    intervals: &[Option<f64>],
+    ends: &[[EndCondition; 2]],
     tol: f64,
     kind: SplineKind,
 ) -> Option<Vec<Vec<Vec<f64>>>> {
-    let ts = chord_parameters(points, metric);
+    let ts = chord_parameters(points, metric, intervals);
     if *ts.last().unwrap() <= 0.0 {
         return None;
     }
@@ -795,13 +1728,23 @@ fn interpolating_spline_runs(
     let channels = points[0].len();
     let fit_points: Vec<Vec<f64>> = kept.iter().map(|&i| points[i].clone()).collect();
 
+    // A tangent end slope is a direction per unit of path length: the path
+    // speed of the end span turns it into one per unit of parameter.
+    let last = fit_ts.len() - 1;
+    let speeds = [(0, 1), (last - 1, last)]
+        .map(|(a, b)| chord(&fit_points[a], &fit_points[b], metric) / (fit_ts[b] - fit_ts[a]));
+
     // Per-channel Hermite slopes at the kept points.
     let slopes: Vec<Vec<f64>> = (0..channels)
         .map(|c| {
             let ys: Vec<f64> = fit_points.iter().map(|p| p[c]).collect();
+            let ends = [0, 1].map(|end| match ends[c][end] {
+                EndCondition::Slope(slope) => EndCondition::Slope(slope * speeds[end]),
+                condition => condition,
+            });
             match kind {
-                SplineKind::Akima => akima_slopes(&fit_ts, &ys),
-                _ => natural_cubic_slopes(&fit_ts, &ys),
+                SplineKind::Akima => akima_slopes(&fit_ts, &ys, ends),
+                _ => cubic_slopes(&fit_ts, &ys, ends),
             }
         })
         .collect();
@@ -859,14 +1802,39 @@ fn interpolating_spline_runs(
     Some(runs)
 }
 
-/// Akima slope estimates on a (strictly increasing) parameterization, with
-/// the standard two-point extrapolation at the boundaries.
-fn akima_slopes(ts: &[f64], ys: &[f64]) -> Vec<f64> {
+/// Akima slope estimates on a (strictly increasing) parameterization. The
+/// automatic end condition is the standard two-point extrapolation; a given
+/// slope or zero curvature replaces the end slope.
+fn akima_slopes(ts: &[f64], ys: &[f64], ends: [EndCondition; 2]) -> Vec<f64> {
     let n = ts.len();
-    if n == 2 {
+    let mut slopes = if n == 2 {
         let m = (ys[1] - ys[0]) / (ts[1] - ts[0]);
-        return vec![m, m];
+        vec![m, m]
+    } else {
+        akima_interior_slopes(ts, ys)
+    };
+    // Given slopes first: a natural end depends on its neighbour's slope.
+    for (end, i) in [(ends[0], 0), (ends[1], n - 1)] {
+        if let EndCondition::Slope(slope) = end {
+            slopes[i] = slope;
+        }
+    }
+    // Zero second derivative of the Hermite segment at its outer end.
+    if ends[0] == EndCondition::Natural {
+        let chord = (ys[1] - ys[0]) / (ts[1] - ts[0]);
+        slopes[0] = 0.5 * (3.0 * chord - slopes[1]);
+    }
+    if ends[1] == EndCondition::Natural {
+        let chord = (ys[n - 1] - ys[n - 2]) / (ts[n - 1] - ts[n - 2]);
+        slopes[n - 1] = 0.5 * (3.0 * chord - slopes[n - 2]);
     }
+    slopes
+}
+
+/// Akima slopes of at least three points, with the standard two-point
+/// extrapolation at the boundaries.
+fn akima_interior_slopes(ts: &[f64], ys: &[f64]) -> Vec<f64> {
+    let n = ts.len();
     // Segment slopes with two virtual segments extrapolated on each side.
     let mut m = Vec::with_capacity(n + 3);
     m.push(0.0); // placeholder m[-2]
@@ -895,16 +1863,23 @@ fn akima_slopes(ts: &[f64], ys: &[f64]) -> Vec<f64> {
         .collect()
 }
 
-/// Endpoint slopes of the natural cubic spline through `(ts, ys)`: solve the
-/// tridiagonal system for the second derivatives (natural end conditions),
-/// then convert to first derivatives at the knots.
-fn natural_cubic_slopes(ts: &[f64], ys: &[f64]) -> Vec<f64> {
+/// Knot slopes of the cubic spline through `(ts, ys)`: solve the
+/// tridiagonal system for the second derivatives `sigma`, then convert to
+/// first derivatives at the knots. The end rows encode the end conditions:
+/// zero curvature (natural), a given slope (clamped), or automatic, where
+/// the outer segment keeps the curvature of its inner knot (a parabolic
+/// runout, determined by the first/last three points).
+fn cubic_slopes(ts: &[f64], ys: &[f64], ends: [EndCondition; 2]) -> Vec<f64> {
     let n = ts.len();
-    if n == 2 {
+    // Two points fix no curvature: automatic ends are natural there.
+    let ends = ends.map(|end| match end {
+        EndCondition::Auto if n == 2 => EndCondition::Natural,
+        end => end,
+    });
+    if n == 2 && ends == [EndCondition::Natural; 2] {
         let m = (ys[1] - ys[0]) / (ts[1] - ts[0]);
         return vec![m, m];
     }
-    // Thomas algorithm on the interior equations; sigma[0] = sigma[n-1] = 0.
     let mut sub = vec![0.0; n];
     let mut diag = vec![1.0; n];
     let mut sup = vec![0.0; n];
@@ -917,13 +1892,35 @@ fn natural_cubic_slopes(ts: &[f64], ys: &[f64]) -> Vec<f64> {
         sup[i] = h1;
         rhs[i] = 6.0 * ((ys[i + 1] - ys[i]) / h1 - (ys[i] - ys[i - 1]) / h0);
     }
+    let h = ts[1] - ts[0];
+    match ends[0] {
+        EndCondition::Natural => {}
+        EndCondition::Auto => sup[0] = -1.0,
+        EndCondition::Slope(slope) => {
+            diag[0] = 2.0 * h;
+            sup[0] = h;
+            rhs[0] = 6.0 * ((ys[1] - ys[0]) / h - slope);
+        }
+    }
+    let h = ts[n - 1] - ts[n - 2];
+    match ends[1] {
+        EndCondition::Natural => {}
+        EndCondition::Auto => sub[n - 1] = -1.0,
+        EndCondition::Slope(slope) => {
+            sub[n - 1] = h;
+            diag[n - 1] = 2.0 * h;
+            rhs[n - 1] = 6.0 * (slope - (ys[n - 1] - ys[n - 2]) / h);
+        }
+    }
+    // Thomas algorithm.
     for i in 1..n {
         let w = sub[i] / diag[i - 1];
         diag[i] -= w * sup[i - 1];
         rhs[i] -= w * rhs[i - 1];
     }
     let mut sigma = vec![0.0; n];
-    for i in (1..n - 1).rev() {
+    sigma[n - 1] = rhs[n - 1] / diag[n - 1];
+    for i in (0..n - 1).rev() {
         sigma[i] = (rhs[i] - sup[i] * sigma[i + 1]) / diag[i];
     }
     (0..n)
@@ -939,14 +1936,17 @@ fn natural_cubic_slopes(ts: &[f64], ys: &[f64]) -> Vec<f64> {
         .collect()
 }
 
-/// Flatten a clamped uniform (rational) B-spline whose control points are
-/// `points` with weights `weights`. Returns one run of samples per control
-/// point after the first (span j is attributed to control point
+/// Flatten a clamped (rational) B-spline whose control points are `points`
+/// with weights `weights`. Returns one run of samples per control point
+/// after the first (span j is attributed to control point
 /// `min(j + degree, n - 1)`, the last point whose block influences it), so
-/// every source block owns a contiguous stretch of the curve.
+/// every source block owns a contiguous stretch of the curve. The knot
+/// interval of a span is the `intervals` entry of that control point (`PL`,
+/// default 1).
 fn bspline_runs(
     points: &[Vec<f64>],
     weights: &[f64],
+    intervals: &[f64],
     degree: usize,
     metric: &[usize],
     tol: f64,
@@ -955,14 +1955,20 @@ fn bspline_runs(
     let p = degree.min(n - 1).max(1);
     let channels = points[0].len();
 
-    // Clamped uniform knot vector on [0, n - p].
+    // Clamped knot vector: span j runs between knots p + j and p + j + 1.
     let spans = n - p;
     let mut knots = Vec::with_capacity(n + p + 1);
     knots.extend(std::iter::repeat_n(0.0, p + 1));
-    for i in 1..spans {
-        knots.push(i as f64);
+    let mut end = 0.0;
+    for j in 0..spans {
+        end += intervals[j + p];
+        if j + 1 < spans {
+            knots.push(end);
+        }
     }
-    knots.extend(std::iter::repeat_n(spans as f64, p + 1));
+    knots.extend(std::iter::repeat_n(end, p + 1));
+
+    let span_ends = knots[p + 1..=n].to_vec();
 
     // Homogeneous control points (channel * w, ..., w).
     let ctrl: Vec<Vec<f64>> = points
@@ -976,9 +1982,10 @@ fn bspline_runs(
         .collect();
 
     let eval = move |t: f64| -> Vec<f64> {
-        let t = t.clamp(0.0, spans as f64);
-        // Knot span index k with knots[k] <= t < knots[k+1].
-        let k = if t >= spans as f64 { n - 1 } else { p + t as usize };
+        let t = t.clamp(0.0, end);
+        // Knot span index k with knots[k] <= t < knots[k+1] (the last span
+        // at the end).
+        let k = p + knots[p..n].partition_point(|&knot| knot <= t) - 1;
         // de Boor on the homogeneous coordinates.
         let mut d: Vec<Vec<f64>> = (0..=p).map(|j| ctrl[j + k - p].clone()).collect();
         for r in 1..=p {
@@ -1008,8 +2015,7 @@ fn bspline_runs(
     let mut runs: Vec<Vec<Vec<f64>>> = vec![Vec::new(); n - 1];
     let mut prev_t = 0.0;
     let mut prev_p = eval(0.0);
-    for j in 0..spans {
-        let t1 = (j + 1) as f64;
+    for (j, &t1) in span_ends.iter().enumerate().take(spans) {
         let p1 = eval(t1);
         let owner = (j + p).min(n - 1) - 1;
         adaptive_flatten(&eval, prev_t, &prev_p, t1, &p1, tol, metric, 0, &mut runs[owner]);
@@ -1435,29 +2441,208 @@ mod tests {
         assert!(out.iter().all(|r| r.cells.get("TURN").is_none()));
     }
 
-    /// CIP and other unimplemented curve interpolations pass through
-    /// unchanged (with a once-per-word warning).
+    /// A CIP circle runs from the start through the intermediate point to
+    /// the end point; the intermediate point is consumed.
     #[test]
-    fn cip_passes_through_unchanged() {
+    fn cip_circle_through_intermediate_point() {
         let mut fl = flattener(0.01);
         let rows = vec![
-            row(1, &[("gg01_motion", s("G1")), ("X", f(0.0)), ("Y", f(0.0))]),
+            row(
+                1,
+                &[("gg01_motion", s("G1")), ("X", f(0.0)), ("Y", f(0.0)), ("Z", f(0.0))],
+            ),
             row(
                 2,
                 &[
                     ("gg01_motion", s("CIP")),
                     ("X", f(20.0)),
                     ("Y", f(0.0)),
-                    ("I", f(10.0)),
-                    ("J", f(5.0)),
+                    ("I1", f(10.0)),
+                    ("J1", f(0.0)),
+                    ("K1", f(10.0)),
                 ],
             ),
-            row(3, &[("gg01_motion", s("G1")), ("X", f(30.0)), ("Y", f(0.0))]),
+            row(
+                3,
+                &[
+                    ("gg01_motion", s("CIP")),
+                    ("X", f(30.0)),
+                    ("I1", f(25.0)),
+                    ("J1", f(0.0)),
+                ],
+            ),
+        ];
+        let out = run(&mut fl, rows);
+        let circle: Vec<&Row> = out.iter().filter(|r| r.line_no == 2).collect();
+        assert!(circle.len() > 10);
+        // A half circle of radius 10 about (10, 0, 0) in the XZ plane,
+        // bulging towards +Z.
+        let mut z = 0.0;
+        for sample in &circle {
+            let x = cell_float(sample, "X").unwrap();
+            z = cell_float(sample, "Z").unwrap_or(z);
+            assert!(((x - 10.0).hypot(z) - 10.0).abs() < 1e-9);
+            assert!(z >= -1e-9);
+            assert!(cell_float(sample, "Y").unwrap().abs() < 1e-9, "Y does not move");
+            assert!(sample.cells.get("I1").is_none());
+        }
+        assert!(matches!(circle[0].cells.get("gg01_motion"), Some(Value::Str(m)) if m == "G1"));
+        let end = circle.last().unwrap();
+        assert_eq!((cell_float(end, "X"), cell_float(end, "Z")), (Some(20.0), Some(0.0)));
+        // Collinear points do not define a circle.
+        let last = out.last().unwrap();
+        assert_eq!(last.line_no, 3);
+        assert!(matches!(last.cells.get("gg01_motion"), Some(Value::Str(m)) if m == "CIP"));
+    }
+
+    /// CT continues the path tangentially: after a line along +X, the arc
+    /// to (20, 10) is the quarter circle about (10, 10); the next CT
+    /// continues along +Y, where a point straight ahead is a line.
+    #[test]
+    fn ct_arc_continues_the_path_direction() {
+        let mut fl = flattener(0.01);
+        let rows = vec![
+            row(1, &[("gg01_motion", s("G1")), ("X", f(0.0)), ("Y", f(0.0))]),
+            row(2, &[("X", f(10.0))]),
+            row(3, &[("gg01_motion", s("CT")), ("X", f(20.0)), ("Y", f(10.0))]),
+            row(4, &[("Y", f(30.0))]),
+            row(5, &[("X", f(0.0))]),
         ];
         let out = run(&mut fl, rows);
-        assert_eq!(out.len(), 3);
-        assert!(matches!(out[1].cells.get("gg01_motion"), Some(Value::Str(m)) if m == "CIP"));
-        assert!(out[1].cells.get("I").is_some(), "CIP block must keep its parameters");
+        let quarter: Vec<&Row> = out.iter().filter(|r| r.line_no == 3).collect();
+        assert!(quarter.len() > 10);
+        for sample in &quarter {
+            let (x, y) = xy(sample);
+            assert!(((x - 10.0).hypot(y - 10.0) - 10.0).abs() < 1e-9);
+            assert!(x >= 10.0 && y <= 10.0, "counter-clockwise below the centre");
+        }
+        assert_eq!(xy(quarter.last().unwrap()), (20.0, 10.0));
+        let straight: Vec<&Row> = out.iter().filter(|r| r.line_no == 4).collect();
+        assert_eq!(straight.len(), 1);
+        assert!(matches!(straight[0].cells.get("gg01_motion"), Some(Value::Str(m)) if m == "G1"));
+        // After the line along +Y, turning back to X0 is a counter-clockwise
+        // half turn about (10, 30).
+        let half: Vec<&Row> = out.iter().filter(|r| r.line_no == 5).collect();
+        for sample in &half {
+            let (x, y) = xy(sample);
+            assert!(((x - 10.0).hypot(y - 30.0) - 10.0).abs() < 1e-9);
+            assert!(y >= 30.0 - 1e-9);
+        }
+    }
+
+    /// The direction at the end of an arc is its exact tangent, and a CT
+    /// with nothing before it cannot be resolved.
+    #[test]
+    fn ct_arc_after_arc_and_without_direction() {
+        let mut fl = flattener(0.01);
+        let rows = vec![
+            row(1, &[("gg01_motion", s("G1")), ("X", f(0.0)), ("Y", f(0.0))]),
+            // Quarter circle about (0, 10) ending at (10, 10), heading +Y.
+            row(
+                2,
+                &[("gg01_motion", s("G3")), ("X", f(10.0)), ("Y", f(10.0)), ("J", f(10.0))],
+            ),
+            row(3, &[("gg01_motion", s("CT")), ("X", f(10.0)), ("Y", f(30.0))]),
+        ];
+        let out = run(&mut fl, rows);
+        let ahead: Vec<&Row> = out.iter().filter(|r| r.line_no == 3).collect();
+        assert_eq!(ahead.len(), 1, "straight ahead of the arc's end tangent");
+
+        let mut fl = flattener(0.01);
+        let out = run(
+            &mut fl,
+            vec![row(1, &[("gg01_motion", s("CT")), ("X", f(10.0)), ("Y", f(10.0))])],
+        );
+        assert_eq!(out.len(), 1);
+        assert!(matches!(out[0].cells.get("gg01_motion"), Some(Value::Str(m)) if m == "CT"));
+    }
+
+    /// An involute of the base circle r = 10 about the origin, unwound a
+    /// quarter turn from (10, 0): every sample lies on it, and the path
+    /// leaves it along the generating line.
+    #[test]
+    fn involute_unwinds_from_the_base_circle() {
+        let on_involute = |x: f64, y: f64| {
+            let unwound = (x.hypot(y).powi(2) / 100.0 - 1.0).max(0.0).sqrt();
+            y.atan2(x) + unwound.atan() - unwound
+        };
+        let mut fl = flattener(0.01);
+        let rows = vec![
+            row(1, &[("gg01_motion", s("G1")), ("X", f(10.0)), ("Y", f(0.0))]),
+            row(
+                2,
+                &[
+                    ("gg01_motion", s("INVCCW")),
+                    ("X", f(5.0 * std::f64::consts::PI)),
+                    ("Y", f(10.0)),
+                    ("I", f(-10.0)),
+                    ("CR", f(10.0)),
+                ],
+            ),
+            // Straight ahead of the end tangent (+Y).
+            row(3, &[("gg01_motion", s("CT")), ("Y", f(30.0))]),
+            // An end point at the centre of the base circle cannot be reached.
+            row(
+                4,
+                &[
+                    ("gg01_motion", s("INVCW")),
+                    ("X", f(0.0)),
+                    ("Y", f(0.0)),
+                    ("I", f(-5.0 * std::f64::consts::PI)),
+                    ("J", f(-30.0)),
+                    ("CR", f(10.0)),
+                ],
+            ),
+        ];
+        let out = run(&mut fl, rows);
+        let unwound: Vec<&Row> = out.iter().filter(|r| r.line_no == 2).collect();
+        assert!(unwound.len() > 3);
+        for sample in &unwound {
+            let (x, y) = xy(sample);
+            assert!(on_involute(x, y).abs() < 1e-9, "({x}, {y})");
+            assert!(sample.cells.iter().all(|(key, _)| !["I", "J", "CR"].contains(key)));
+        }
+        assert_eq!(xy(unwound.last().unwrap()), (5.0 * std::f64::consts::PI, 10.0));
+        assert_eq!(out.iter().filter(|r| r.line_no == 3).count(), 1);
+        let back: Vec<&Row> = out.iter().filter(|r| r.line_no == 4).collect();
+        assert_eq!(back.len(), 1);
+        assert!(matches!(back[0].cells.get("gg01_motion"), Some(Value::Str(m)) if m == "INVCW"));
+    }
+
+    /// A rational POLY block is exact for conics: the quarter circle
+    /// x = (10 - 10p^2) / (1 + p^2), y = 20p / (1 + p^2).
+    #[test]
+    fn poly_quarter_circle_over_denominator() {
+        let mut fl = flattener(0.01);
+        let rows = vec![
+            row(1, &[("gg01_motion", s("G1")), ("X", f(10.0)), ("Y", f(0.0))]),
+            row(
+                2,
+                &[
+                    ("gg01_motion", s("POLY")),
+                    ("X", f(0.0)),
+                    ("Y", f(10.0)),
+                    ("PO_X_1", f(0.0)),
+                    ("PO_X_2", f(-10.0)),
+                    ("PO_Y_1", f(20.0)),
+                    ("PO_2", f(1.0)),
+                ],
+            ),
+            row(3, &[("X", f(5.0))]),
+        ];
+        let out = run(&mut fl, rows);
+        let quarter: Vec<&Row> = out.iter().filter(|r| r.line_no == 2).collect();
+        assert!(quarter.len() > 10);
+        for sample in &quarter {
+            let (x, y) = xy(sample);
+            assert!((x.hypot(y) - 10.0).abs() < 1e-9);
+            assert!(sample.cells.iter().all(|(key, _)| !key.starts_with("PO_")));
+        }
+        assert_eq!(xy(quarter.last().unwrap()), (0.0, 10.0));
+        // A POLY block without coefficients is a straight line.
+        let line: Vec<&Row> = out.iter().filter(|r| r.line_no == 3).collect();
+        assert_eq!(line.len(), 1);
+        assert!(matches!(line[0].cells.get("gg01_motion"), Some(Value::Str(m)) if m == "G1"));
     }
 
     #[test]
@@ -1608,6 +2793,86 @@ mod tests {
         assert_eq!(xy(out.last().unwrap()), (50.0, 0.0));
     }
 
+    /// The automatic cubic end keeps the inner curvature, so a parabola is
+    /// reproduced exactly; given end slopes reproduce a cubic.
+    #[test]
+    fn cubic_end_conditions() {
+        let ts = [0.0, 1.0, 2.0, 3.0];
+        let close = |a: &[f64], b: &[f64]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-9);
+        let parabola: Vec<f64> = ts.iter().map(|t| t * t).collect();
+        let slopes = cubic_slopes(&ts, &parabola, [EndCondition::Auto; 2]);
+        assert!(close(&slopes, &[0.0, 2.0, 4.0, 6.0]), "{slopes:?}");
+        let cubic: Vec<f64> = ts.iter().map(|t| t * t * t).collect();
+        let slopes = cubic_slopes(&ts, &cubic, [EndCondition::Slope(0.0), EndCondition::Slope(27.0)]);
+        assert!(close(&slopes, &[0.0, 3.0, 12.0, 27.0]), "{slopes:?}");
+        // Natural: zero curvature at the ends, so not the parabola.
+        let slopes = cubic_slopes(&ts, &parabola, [EndCondition::Natural; 2]);
+        assert!((slopes[0] - 0.0).abs() > 0.1, "{slopes:?}");
+        // Two points: a straight line, or the cubic with the given slope.
+        assert_eq!(
+            cubic_slopes(&ts[..2], &[0.0, 1.0], [EndCondition::Auto; 2]),
+            vec![1.0, 1.0]
+        );
+        let slopes = cubic_slopes(&ts[..2], &[0.0, 1.0], [EndCondition::Slope(0.0), EndCondition::Natural]);
+        assert!(close(&slopes, &[0.0, 1.5]), "{slopes:?}");
+        // Akima: the natural end slope zeroes the Hermite segment's curvature.
+        let slopes = akima_slopes(&ts[..2], &[0.0, 1.0], [EndCondition::Natural, EndCondition::Slope(0.0)]);
+        assert!(close(&slopes, &[1.5, 0.0]), "{slopes:?}");
+    }
+
+    /// BTAN and ETAN make the spline leave the preceding block's direction
+    /// and enter the following block's; BNAT/ENAT and BAUTO/EAUTO do not.
+    #[test]
+    fn spline_tangential_start_and_end() {
+        for kind in ["ASPLINE", "CSPLINE"] {
+            let program = |start: &str, end: &str| {
+                vec![
+                    row(1, &[("gg01_motion", s("G1")), ("X", f(-10.0)), ("Y", f(0.0))]),
+                    row(2, &[("X", f(0.0))]),
+                    row(
+                        3,
+                        &[
+                            ("gg01_motion", s(kind)),
+                            ("gg19_curve_start_spline", s(start)),
+                            ("gg20_curve_end_spline", s(end)),
+                            ("X", f(10.0)),
+                            ("Y", f(10.0)),
+                        ],
+                    ),
+                    row(4, &[("X", f(20.0)), ("Y", f(0.0))]),
+                    row(5, &[("gg01_motion", s("G1")), ("Y", f(-10.0))]),
+                ]
+            };
+            // The directions of the first and last spline chords.
+            let chords = |out: &[Row]| {
+                let points: Vec<(f64, f64)> = std::iter::once((0.0, 0.0))
+                    .chain(out[2..out.len() - 1].iter().map(xy))
+                    .collect();
+                let direction = |a: (f64, f64), b: (f64, f64)| (b.1 - a.1).atan2(b.0 - a.0);
+                let last = points.len() - 1;
+                (
+                    direction(points[0], points[1]),
+                    direction(points[last - 1], points[last]),
+                )
+            };
+
+            let tangential = run(&mut flattener(0.001), program("BTAN", "ETAN"));
+            let (first, last) = chords(&tangential);
+            assert!(first.abs() < 0.05, "{kind}: leaves along +X, got {first}");
+            assert!(
+                (last + std::f64::consts::FRAC_PI_2).abs() < 0.05,
+                "{kind}: enters -Y, got {last}"
+            );
+            assert_eq!(xy(&tangential[tangential.len() - 2]), (20.0, 0.0));
+
+            for (start, end) in [("BNAT", "ENAT"), ("BAUTO", "EAUTO")] {
+                let (first, last) = chords(&run(&mut flattener(0.001), program(start, end)));
+                assert!(first > 0.3, "{kind} {start}: {first}");
+                assert!(last > -1.3, "{kind} {end}: {last}");
+            }
+        }
+    }
+
     /// Collinear control points make the B-spline a straight line: the
     /// flattener must not densify it.
     #[test]
@@ -1747,6 +3012,109 @@ mod tests {
         assert!(es.windows(2).all(|w| w[1] >= w[0] - 1e-6));
     }
 
+    /// Axes outside the spline path follow linearly: in proportion to the
+    /// path travelled over each block.
+    #[test]
+    fn spline_path_axes_and_linear_followers() {
+        for path in [None, Some(["X", "Y"])] {
+            let mut fl = flattener(0.01);
+            if let Some(path) = path {
+                fl.set_spline_path(&path.map(String::from));
+            }
+            let mut rows = vec![
+                row(
+                    1,
+                    &[("gg01_motion", s("G1")), ("X", f(0.0)), ("Y", f(0.0)), ("Z", f(0.0))],
+                ),
+                row(
+                    2,
+                    &[
+                        ("gg01_motion", s("CSPLINE")),
+                        ("X", f(10.0)),
+                        ("Y", f(10.0)),
+                        ("Z", f(1.0)),
+                    ],
+                ),
+            ];
+            rows.push(row(3, &[("X", f(20.0)), ("Y", f(0.0)), ("Z", f(3.0))]));
+            rows.push(row(4, &[("X", f(30.0)), ("Y", f(10.0)), ("Z", f(4.0))]));
+            let out = run(&mut fl, rows);
+            let mut linear = true;
+            let (mut start, mut block_start) = ((0.0, 0.0, 0.0), 0);
+            for k in 1..out.len() {
+                if cell_float(&out[k], "flattened").is_some() {
+                    continue;
+                }
+                // out[block_start + 1..=k] is one block's run.
+                let end = (xy(&out[k]).0, xy(&out[k]).1, cell_float(&out[k], "Z").unwrap());
+                let mut previous = (start.0, start.1);
+                let mut travelled = vec![0.0];
+                for r in &out[block_start + 1..=k] {
+                    let (x, y) = xy(r);
+                    travelled.push(travelled.last().unwrap() + (x - previous.0).hypot(y - previous.1));
+                    previous = (x, y);
+                }
+                let total = *travelled.last().unwrap();
+                for (r, d) in out[block_start + 1..=k].iter().zip(&travelled[1..]) {
+                    let z = cell_float(r, "Z").unwrap();
+                    linear &= (z - (start.2 + (end.2 - start.2) * d / total)).abs() < 1e-9;
+                }
+                (start, block_start) = (end, k);
+            }
+            assert_eq!(linear, path.is_some(), "{path:?}: Z is a spline axis by default");
+            assert_eq!(cell_float(out.last().unwrap(), "Z"), Some(4.0));
+        }
+    }
+
+    /// `PL` gives the parameter interval of a span: a cubic through unevenly
+    /// spaced points on a uniform parameter swings back, where the chord
+    /// length parameter follows the line.
+    #[test]
+    fn spline_parameter_intervals() {
+        let line = |pl: Option<f64>| {
+            let with_pl = |cells: &[(&'static str, Value)]| {
+                let mut cells = cells.to_vec();
+                cells.extend(pl.map(|pl| ("PL", f(pl))));
+                cells
+            };
+            let rows = vec![
+                row(1, &[("gg01_motion", s("G1")), ("X", f(0.0)), ("Y", f(0.0))]),
+                row(2, &with_pl(&[("gg01_motion", s("CSPLINE")), ("X", f(1.0))])),
+                row(3, &with_pl(&[("X", f(10.0))])),
+            ];
+            let out = run(&mut flattener(0.001), rows);
+            out.iter()
+                .filter_map(|r| cell_float(r, "X"))
+                .fold(f64::INFINITY, f64::min)
+        };
+        assert_eq!(line(None), 0.0);
+        // X(t) = 4t^2 - 3t, least at t = 3/8.
+        assert!((line(Some(1.0)) + 0.5625).abs() < 1e-3, "{}", line(Some(1.0)));
+
+        // A quadratic B-spline's knot at the end of span 0 lies on the control
+        // leg P1 P2, split in the ratio of the knot intervals.
+        let knot = |pl: [f64; 2]| {
+            let rows = vec![
+                row(1, &[("gg01_motion", s("G1")), ("X", f(0.0)), ("Y", f(0.0))]),
+                row(
+                    2,
+                    &[
+                        ("gg01_motion", s("BSPLINE")),
+                        ("SD", f(2.0)),
+                        ("X", f(10.0)),
+                        ("Y", f(10.0)),
+                    ],
+                ),
+                row(3, &[("X", f(20.0)), ("Y", f(10.0)), ("PL", f(pl[0]))]),
+                row(4, &[("X", f(30.0)), ("Y", f(0.0)), ("PL", f(pl[1]))]),
+            ];
+            let out = run(&mut flattener(0.01), rows);
+            xy(out.iter().rev().find(|r| r.line_no == 3).unwrap())
+        };
+        assert_eq!(knot([1.0, 1.0]), (15.0, 10.0));
+        assert_eq!(knot([1.0, 3.0]), (12.5, 10.0));
+    }
+
     /// Aux rows (M codes, comments) inside a spline stay in program order.
     #[test]
     fn non_point_rows_inside_spline_kept_in_order() {
@@ -1810,7 +3178,7 @@ mod tests {
             rows.push(row(3 + i, &[("X", f(*x)), ("Y", f(*y))]));
         }
         let out = run(&mut fl, rows);
-        let unmarked: Vec<(f64, f64)> = out[1..].iter().filter(|r| !marked(r)).map(|r| xy(r)).collect();
+        let unmarked: Vec<(f64, f64)> = out[1..].iter().filter(|r| !marked(r)).map(xy).collect();
         assert_eq!(
             unmarked,
             pts.to_vec(),
@@ -1831,7 +3199,7 @@ mod tests {
         let unmarked_coord_rows: Vec<(f64, f64)> = out[1..]
             .iter()
             .filter(|r| !marked(r) && r.cells.get("X").is_some())
-            .map(|r| xy(r))
+            .map(xy)
             .collect();
         assert_eq!(unmarked_coord_rows, vec![(30.0, 0.0)]);
     }
@@ -0,0 +1,244 @@
+//! Geometry of the programmable frame (NC programming manual 3.12): 3x3
+//! matrices over the geometry axes X/Y/Z, mapping a direction given in the
+//! rotated (and scaled/mirrored) workpiece coordinate system to machine
+//! (parent) coordinates.
+//!
+//! * `ROT`/`AROT` with axis angles use the control's default RPY convention
+//!   (MD10600 `$MN_FRAME_ANGLE_INPUT_MODE` = 1): rotate about Z, then the new
+//!   Y, then the new X, i.e. `R = Rz(Z) · Ry(Y) · Rx(X)`.
+//! * `RPL=` rotates in the active working plane, about its applicate.
+//! * `ROTS`/`AROTS`/`CROTS` give the orientation of the working plane by two
+//!   solid angles (manual 3.12.4.2); see [`solid_angles`].
+//!
+//! Positive angles turn counter-clockwise looking down the rotation axis
+//! (right-hand rule), for every plane.
+
+/// A 3x3 matrix over the geometry axes X, Y, Z (row-major).
+pub(crate) type Matrix3 = [[f64; 3]; 3];
+
+pub(crate) const IDENTITY: Matrix3 = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// Below this a matrix coefficient counts as zero: an axis whose coefficient
+/// vanishes is not coupled to the other by the rotation.
+pub(crate) const EPSILON: f64 = 1e-12;
+
+/// The working plane selected by G17/G18/G19 (G-group 6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorkingPlane {
+    #[default]
+    G17,
+    G18,
+    G19,
+}
+
+impl WorkingPlane {
+    /// The plane for a G-group 6 word, `None` for anything else.
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code.to_ascii_uppercase().as_str() {
+            "G17" => Some(WorkingPlane::G17),
+            "G18" => Some(WorkingPlane::G18),
+            "G19" => Some(WorkingPlane::G19),
+            _ => None,
+        }
+    }
+
+    /// Geometry-axis indices (X=0, Y=1, Z=2) of the abscissa, ordinate and
+    /// applicate: a cyclic (right-handed) permutation of X, Y, Z.
+    pub fn axes(self) -> [usize; 3] {
+        match self {
+            WorkingPlane::G17 => [0, 1, 2],
+            WorkingPlane::G18 => [2, 0, 1],
+            WorkingPlane::G19 => [1, 2, 0],
+        }
+    }
+}
+
+pub(crate) fn multiply(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    let mut m = [[0.0; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+/// The inverse matrix, `None` if `m` is singular.
+pub(crate) fn inverse(m: &Matrix3) -> Option<Matrix3> {
+    let cofactor = |r: usize, c: usize| {
+        let (r1, r2) = ((r + 1) % 3, (r + 2) % 3);
+        let (c1, c2) = ((c + 1) % 3, (c + 2) % 3);
+        m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]
+    };
+    let det: f64 = (0..3).map(|c| m[0][c] * cofactor(0, c)).sum();
+    if det.abs() < EPSILON {
+        return None;
+    }
+    let mut inv = [[0.0; 3]; 3];
+    for (i, row) in inv.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            // Adjugate: transposed cofactors.
+            *cell = cofactor(j, i) / det;
+        }
+    }
+    Some(inv)
+}
+
+pub(crate) fn apply(m: &Matrix3, v: [f64; 3]) -> [f64; 3] {
+    let mut out = [0.0; 3];
+    for (i, cell) in out.iter_mut().enumerate() {
+        *cell = (0..3).map(|j| m[i][j] * v[j]).sum();
+    }
+    out
+}
+
+/// The factor `m` scales a radius (`CR`) in the plane of the X/Y/Z slots
+/// `plane` by: the square root of the plane's area scale, which is exact for
+/// rotations, mirrors and scales that are uniform in that plane.
+pub(crate) fn radius_scale(m: &Matrix3, plane: [usize; 2]) -> f64 {
+    let [u, v] = plane.map(|slot| [m[0][slot], m[1][slot], m[2][slot]]);
+    let normal = [
+        u[1] * v[2] - u[2] * v[1],
+        u[2] * v[0] - u[0] * v[2],
+        u[0] * v[1] - u[1] * v[0],
+    ];
+    normal.iter().map(|c| c * c).sum::<f64>().sqrt().sqrt()
+}
+
+pub(crate) fn is_identity(m: &Matrix3) -> bool {
+    (0..3).all(|i| (0..3).all(|j| (m[i][j] - IDENTITY[i][j]).abs() < EPSILON))
+}
+
+/// Rotation by `degrees` about geometry axis `axis` (0 = X, 1 = Y, 2 = Z).
+pub(crate) fn about_axis(axis: usize, degrees: f64) -> Matrix3 {
+    let (s, c) = degrees.to_radians().sin_cos();
+    // The two axes turned by the rotation, in right-handed order.
+    let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+    let mut m = IDENTITY;
+    m[a][a] = c;
+    m[a][b] = -s;
+    m[b][a] = s;
+    m[b][b] = c;
+    m
+}
+
+/// `ROT X=.. Y=.. Z=..` in the RPY convention: `Rz(z) · Ry(y) · Rx(x)`.
+pub(crate) fn rpy(angles: [f64; 3]) -> Matrix3 {
+    multiply(
+        &about_axis(2, angles[2]),
+        &multiply(&about_axis(1, angles[1]), &about_axis(0, angles[0])),
+    )
+}
+
+/// `ROTS` solid angles (manual 3.12.4.2): the rotated working plane is given
+/// by `abscissa_angle` (programmed on the abscissa address, e.g. X for G17)
+/// and `ordinate_angle` (on the ordinate address, e.g. Y). The new abscissa
+/// lies in the old abscissa/applicate plane, turned by `ordinate_angle` about
+/// the ordinate; the projection of the new ordinate onto the old
+/// ordinate/applicate plane is turned by `abscissa_angle` about the abscissa.
+/// With one angle zero this is the plain rotation about the other axis.
+pub(crate) fn solid_angles(plane: WorkingPlane, abscissa_angle: f64, ordinate_angle: f64) -> Matrix3 {
+    let (sa, ca) = abscissa_angle.to_radians().sin_cos();
+    let (sb, cb) = ordinate_angle.to_radians().sin_cos();
+    // Columns of the rotation in (abscissa, ordinate, applicate) coordinates.
+    let x = [cb, 0.0, -sb];
+    let y = {
+        let v = [sa * sb, ca * cb, sa * cb];
+        let norm = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        if norm < EPSILON {
+            // New abscissa along the applicate with no ordinate tilt: the
+            // ordinate is unchanged.
+            [0.0, 1.0, 0.0]
+        } else {
+            [v[0] / norm, v[1] / norm, v[2] / norm]
+        }
+    };
+    let z = [
+        x[1] * y[2] - x[2] * y[1],
+        x[2] * y[0] - x[0] * y[2],
+        x[0] * y[1] - x[1] * y[0],
+    ];
+    // Plane coordinates to X/Y/Z: the permutation is cyclic, so the result is
+    // still a proper rotation.
+    let idx = plane.axes();
+    let mut m = [[0.0; 3]; 3];
+    for (c, column) in [x, y, z].iter().enumerate() {
+        for (r, &value) in column.iter().enumerate() {
+            m[idx[r]][idx[c]] = value;
+        }
+    }
+    m
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transpose(m: &Matrix3) -> Matrix3 {
+        std::array::from_fn(|i| std::array::from_fn(|j| m[j][i]))
+    }
+
+    fn assert_close(a: [f64; 3], b: [f64; 3]) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 1e-12, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn rotation_about_z_turns_x_towards_y() {
+        let m = about_axis(2, 90.0);
+        assert_close(apply(&m, [1.0, 0.0, 0.0]), [0.0, 1.0, 0.0]);
+        // Right-hand rule for every axis: X -> Y -> Z -> X.
+        assert_close(apply(&about_axis(0, 90.0), [0.0, 1.0, 0.0]), [0.0, 0.0, 1.0]);
+        assert_close(apply(&about_axis(1, 90.0), [0.0, 0.0, 1.0]), [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn rpy_rotates_about_z_first_then_the_new_axes() {
+        // Z=90 turns X onto Y; 90 about the new Y (the old -X) then tips the
+        // new X down onto the old -Z.
+        let m = rpy([0.0, 90.0, 90.0]);
+        assert_close(apply(&m, [1.0, 0.0, 0.0]), [0.0, 0.0, -1.0]);
+        assert_close(apply(&m, [0.0, 1.0, 0.0]), [-1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn solid_angles_reduce_to_single_rotations() {
+        for plane in [WorkingPlane::G17, WorkingPlane::G18, WorkingPlane::G19] {
+            let [a, o, _] = plane.axes();
+            let single_a = solid_angles(plane, 30.0, 0.0);
+            let single_o = solid_angles(plane, 0.0, 30.0);
+            for (got, want) in [(single_a, about_axis(a, 30.0)), (single_o, about_axis(o, 30.0))] {
+                for i in 0..3 {
+                    assert_close(got[i], want[i]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_a_scaled_mirrored_rotation() {
+        let mut m = rpy([10.0, 20.0, 30.0]);
+        for row in m.iter_mut() {
+            row[0] *= 2.0;
+            row[1] *= -1.0;
+        }
+        let inv = inverse(&m).expect("invertible");
+        assert!(is_identity(&multiply(&m, &inv)));
+        assert!(inverse(&[[1.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 1.0]]).is_none());
+    }
+
+    #[test]
+    fn solid_angles_keep_the_projection_angles() {
+        let m = solid_angles(WorkingPlane::G17, 20.0, 35.0);
+        // Orthonormal.
+        assert!(is_identity(&multiply(&transpose(&m), &m)));
+        let x = apply(&m, [1.0, 0.0, 0.0]);
+        let y = apply(&m, [0.0, 1.0, 0.0]);
+        // New X in the old Z/X plane, 35 degrees below X.
+        assert!(x[1].abs() < 1e-12);
+        assert!(((-x[2]).atan2(x[0]).to_degrees() - 35.0).abs() < 1e-9);
+        // New Y projected onto the old Y/Z plane, 20 degrees above Y.
+        assert!((y[2].atan2(y[1]).to_degrees() - 20.0).abs() < 1e-9);
+    }
+}
@@ -1,9 +1,16 @@
 use crate::errors::ParsingError;
-use crate::state::{ColKind, State};
+use crate::frame::{self, Matrix3, WorkingPlane};
+use crate::state::{
+    settable_frame_number, suppresses_frames, ColKind, DiameterMode, Dimensioning, FxMap, State, TRANSVERSE_AXIS,
+};
+use crate::subprogram::{Argument, ModalCall, PendingCall, Subprogram};
+use crate::types::NCParser;
 use crate::types::Pair;
 use crate::types::Rule;
 use crate::types::Value;
+use pest::Parser;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 type Output = crate::output::OutputRows;
 
@@ -24,6 +31,31 @@ pub enum BlockFlow {
     /// marker is not necessarily the last block (manual 4.1.5.2), so it must
     /// terminate interpretation instead of falling through to later blocks.
     EndProgram,
+    /// REPEAT/REPEATB: run a labelled section of the current scope, then
+    /// return to the block after the repetition (see [`Sections`]).
+    Repeat(RepeatRequest),
+}
+
+/// A program section repetition (manual 4.1.5.4), resolved against the
+/// jump targets of the scope that executes it.
+#[derive(Debug, Clone)]
+pub struct RepeatRequest {
+    /// The section's first block.
+    start: JumpRequest,
+    end: SectionEnd,
+    /// How often the section runs (P=, default 1).
+    count: usize,
+}
+
+#[derive(Debug, Clone)]
+enum SectionEnd {
+    /// REPEATB: the start block alone.
+    StartBlock,
+    /// REPEAT <label>: the block before the REPEAT.
+    BeforeRepeat,
+    /// REPEAT <start> <end> / REPEAT <start> ENDLABEL: the first block at or
+    /// after the start carrying this label.
+    Label(JumpRequest),
 }
 
 #[derive(Debug, Clone)]
@@ -50,7 +82,9 @@ impl JumpRequest {
                 JumpDirection::Backward => {
                     "GOTOB only searches toward the beginning of the program; use GOTOF or GOTO for a destination that lies after this block"
                 }
-                JumpDirection::BothForwardFirst => "a jump cannot enter an IF or loop body from outside",
+                JumpDirection::BothForwardFirst | JumpDirection::BothBackwardFirst => {
+                    "a jump cannot enter an IF or loop body from outside"
+                }
             };
             format!(
                 "\n'{}' is defined, but not where this jump searches: {}.",
@@ -81,7 +115,7 @@ fn suggest_jump_target(key: &str, seen: &std::collections::HashSet<String>) -> O
         // Distance 0 (the target exists) is handled by the caller with a
         // direction/scope hint; suggesting the same name would be absurd.
         let distance = edit_distance(key, candidate);
-        if (1..=2).contains(&distance) && best.map_or(true, |(d, _)| distance < d) {
+        if (1..=2).contains(&distance) && best.is_none_or(|(d, _)| distance < d) {
             best = Some((distance, candidate));
         }
     }
@@ -117,6 +151,9 @@ enum JumpDirection {
     Forward,
     /// GOTO / GOTOC: first toward the end, then toward the beginning.
     BothForwardFirst,
+    /// REPEATB and the start of a two-label REPEAT: first toward the
+    /// beginning, then toward the end.
+    BothBackwardFirst,
 }
 
 impl JumpDirection {
@@ -125,6 +162,7 @@ impl JumpDirection {
             JumpDirection::Backward => "toward the beginning of the program (GOTOB)",
             JumpDirection::Forward => "toward the end of the program (GOTOF)",
             JumpDirection::BothForwardFirst => "in both directions (GOTO/GOTOC)",
+            JumpDirection::BothBackwardFirst => "in both directions, toward the beginning first (REPEAT/REPEATB)",
         }
     }
 }
@@ -198,6 +236,114 @@ pub(crate) fn resolve_jump(
         JumpDirection::Forward => forward(),
         JumpDirection::Backward => backward(),
         JumpDirection::BothForwardFirst => forward().or_else(backward),
+        JumpDirection::BothBackwardFirst => backward().or_else(forward),
+    }
+}
+
+/// One section repetition in progress: blocks `start..=end` of the scope,
+/// entered from the REPEAT block `caller`.
+#[derive(Debug, Clone)]
+struct Section {
+    start: usize,
+    end: usize,
+    caller: usize,
+    remaining: usize,
+}
+
+/// The program section repetitions active in one scope, innermost last. Each
+/// scope runner keeps one beside its block index: [`Sections::enter`] on a
+/// `BlockFlow::Repeat`, [`Sections::advance`] instead of `index += 1`, and
+/// [`Sections::jumped`] after resolving a jump.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Sections(Vec<Section>);
+
+impl Sections {
+    /// Resolve the section the REPEAT block `current` names and return the
+    /// block to continue at: the section's first block.
+    pub(crate) fn enter(
+        &mut self,
+        targets: &HashMap<String, Vec<usize>>,
+        current: usize,
+        request: RepeatRequest,
+        state: &State,
+    ) -> Result<usize, ParsingError> {
+        let RepeatRequest { start, end, count } = request;
+        let Some(first) = resolve_jump(targets, current, &start) else {
+            return Err(start.into_not_found_error(state));
+        };
+        let last = match end {
+            SectionEnd::StartBlock => first,
+            SectionEnd::BeforeRepeat => current.saturating_sub(1),
+            SectionEnd::Label(end) => match targets.get(&end.key).and_then(|p| p.iter().find(|&&p| p >= first)) {
+                Some(&last) => last,
+                None => return Err(end.into_not_found_error(state)),
+            },
+        };
+        let error = |message: String| {
+            Err(ParsingError::with_context(
+                start.line_no,
+                start.preview.clone(),
+                "program section repetition".to_string(),
+                message,
+            ))
+        };
+        if (first..=last).contains(&current) {
+            return error(format!(
+                "the section starting at '{}' contains the repeating block itself",
+                start.display
+            ));
+        }
+        if first > last {
+            return error(format!("the section starting at '{}' is empty", start.display));
+        }
+        if self.0.len() >= crate::subprogram::MAX_CALL_DEPTH {
+            return error(format!(
+                "more than {} nested section repetitions",
+                crate::subprogram::MAX_CALL_DEPTH
+            ));
+        }
+        if count > state.iteration_limit {
+            return Err(ParsingError::LoopLimit {
+                limit: state.iteration_limit.to_string(),
+            });
+        }
+        self.0.push(Section {
+            start: first,
+            end: last,
+            caller: current,
+            remaining: count,
+        });
+        Ok(first)
+    }
+
+    /// The block to run after block `current` completed: the next one, or
+    /// the section start again, or - once a section has run its last time -
+    /// the block after its REPEAT.
+    pub(crate) fn advance(&mut self, mut current: usize) -> usize {
+        while let Some(section) = self.0.last_mut() {
+            if section.end != current {
+                break;
+            }
+            section.remaining -= 1;
+            if section.remaining > 0 {
+                return section.start;
+            }
+            // The REPEAT block may itself end an enclosing section.
+            current = section.caller;
+            self.0.pop();
+        }
+        current + 1
+    }
+
+    /// A jump to `destination` leaves every section not containing it.
+    pub(crate) fn jumped(&mut self, destination: usize) {
+        while self
+            .0
+            .last()
+            .is_some_and(|section| !(section.start..=section.end).contains(&destination))
+        {
+            self.0.pop();
+        }
     }
 }
 
@@ -703,6 +849,16 @@ fn interpret_g_command(g_command: Pair<Rule>, state: &State) -> Result<(&'static
         }
     }
 }
+/// The output value of a G command: the word itself, except that a motion
+/// word goes through [`State::output_motion`] (mirrored G2/G3).
+pub(crate) fn g_command_output(group: &'static str, word: String, state: &mut State) -> String {
+    state.record_modal_word(group, &word);
+    if group == "gg01_motion" {
+        state.output_motion(word)
+    } else {
+        word
+    }
+}
 fn interpret_m_command(m_command: Pair<Rule>) -> (String, String) {
     // Log the interpretd M command for debugging
     //println!("Parsed M command: {:?}", m_command);
@@ -718,7 +874,7 @@ fn interpret_m_command(m_command: Pair<Rule>) -> (String, String) {
 fn interpret_tool_selection(
     tool_selection: Pair<Rule>,
     output: &mut Output,
-    _state: &mut State,
+    state: &mut State,
 ) -> Result<(), ParsingError> {
     // Get the last HashMap from the output vector to insert the tool selection.
     let last = output.last_mut().expect("Output vector should not be empty");
@@ -751,8 +907,9 @@ fn interpret_tool_selection(
         }
     }
 
-    // Insert the tool name into the output.
-    last.insert("T", Value::Str(tool_name));
+    // Insert the tool name into the output; M6 loads the tool.
+    last.insert("T", Value::Str(tool_name.clone()));
+    state.tools.select_name(tool_name);
 
     Ok(())
 }
@@ -788,11 +945,11 @@ fn interpret_assignment(element: Pair<Rule>, state: &mut State) -> Result<(Strin
 
     let variable_pair = inner_pairs
         .next()
-        .ok_or_else(|| ParsingError::InvalidElementCount { expected: 2, actual: 0 })?;
+        .ok_or(ParsingError::InvalidElementCount { expected: 2, actual: 0 })?;
 
     let expression_pair = inner_pairs
         .next()
-        .ok_or_else(|| ParsingError::InvalidElementCount { expected: 2, actual: 1 })?;
+        .ok_or(ParsingError::InvalidElementCount { expected: 2, actual: 1 })?;
 
     // A string-valued right-hand side: a quoted literal, a `<<` concatenation,
     // or an expression that yields a string (a STRING variable read or a
@@ -804,6 +961,12 @@ fn interpret_assignment(element: Pair<Rule>, state: &mut State) -> Result<(Strin
         // STRING[<index>] = <one char>: write a single character into a string
         // variable (manual 4.1.4.8), as opposed to reassigning the whole string.
         if variable_pair.as_rule() == Rule::variable_array {
+            let name = variable_pair.clone().into_inner().next();
+            if name.is_some_and(|name| {
+                name.as_rule() == Rule::nc_variable && name.as_str().eq_ignore_ascii_case("$TC_TP2")
+            }) {
+                return interpret_tool_name(variable_pair, text, state);
+            }
             return interpret_string_char_write(variable_pair, text, state);
         }
         let key = normalize_reserved_case(interpret_variable(variable_pair.clone(), state)?, state);
@@ -829,7 +992,7 @@ fn interpret_assignment(element: Pair<Rule>, state: &mut State) -> Result<(Strin
 
     // All axis values are now stored as LOCAL coordinates.
     // Translation is applied at output time, not storage time.
-    let (key, local_value) = match (variable_pair.as_rule(), expression_pair.as_rule()) {
+    let (key, value, dimensioning) = match (variable_pair.as_rule(), expression_pair.as_rule()) {
         (Rule::variable_single_char, Rule::value) => {
             // Uppercase: the rule matches case-insensitively (x100 == X100).
             let key = variable_pair.as_str().to_uppercase();
@@ -841,19 +1004,49 @@ fn interpret_assignment(element: Pair<Rule>, state: &mut State) -> Result<(Strin
                     state,
                 )
             })?;
-            (key, value)
+            (key, value, Dimensioning::Modal)
         }
-        (Rule::variable, Rule::axis_increment) => {
+        (Rule::variable, Rule::axis_increment | Rule::axis_absolute | Rule::axis_diameter) => {
             let key = normalize_reserved_case(interpret_variable(variable_pair.clone(), state)?, state);
-            let value = interpret_axis_increment(expression_pair, state, key.clone())?;
-            (key, value)
+            if expression_pair.as_rule() == Rule::axis_diameter && !key.eq_ignore_ascii_case(TRANSVERSE_AXIS) {
+                return Err(annotate_error(
+                    &expression_pair,
+                    "assignment",
+                    format!(
+                        "DAC()/DIC()/RAC()/RIC() are only allowed on the transverse axis {TRANSVERSE_AXIS}, not on '{key}'"
+                    ),
+                    state,
+                ));
+            }
+            if expression_pair.as_rule() == Rule::axis_absolute
+                && !state.is_axis(&key)
+                && !matches!(key.as_str(), "I" | "J" | "K" | "I1" | "J1" | "K1" | "AP")
+            {
+                return Err(annotate_error(
+                    &expression_pair,
+                    "assignment",
+                    format!(
+                        "AC() is only allowed on an axis, an arc centre (I/J/K), an intermediate point \
+                         (I1/J1/K1) or the polar angle AP, not on '{key}'"
+                    ),
+                    state,
+                ));
+            }
+            let (value, dimensioning) = interpret_dimensioned_value(expression_pair, state)?;
+            (key, value, dimensioning)
         }
         (Rule::variable, Rule::expression) => {
             let key = normalize_reserved_case(interpret_variable(variable_pair.clone(), state)?, state);
             let value = evaluate_expression(expression_pair, state)?;
-            (key, value)
+            (key, value, Dimensioning::Modal)
         }
         (Rule::variable_array, Rule::expression) => {
+            if is_settable_frame_variable(&variable_pair) {
+                return interpret_settable_frame(variable_pair, expression_pair, state);
+            }
+            if let Some(variable) = tool_offset_variable(&variable_pair) {
+                return interpret_tool_offset(variable, variable_pair, expression_pair, state);
+            }
             if let Some(name) = actual_position_sysvar_name(&variable_pair) {
                 let (line_no, preview) = get_error_context(&variable_pair, state);
                 return Err(ParsingError::with_context(
@@ -865,7 +1058,7 @@ fn interpret_assignment(element: Pair<Rule>, state: &mut State) -> Result<(Strin
             }
             let keys = interpret_variable_array(variable_pair, state)?;
             let value = evaluate_expression(expression_pair, state)?;
-            (keys[keys.len() - 1].clone(), value)
+            (keys[keys.len() - 1].clone(), value, Dimensioning::Modal)
         }
         _ => {
             return Err(ParsingError::UnexpectedRule {
@@ -881,6 +1074,20 @@ fn interpret_assignment(element: Pair<Rule>, state: &mut State) -> Result<(Strin
         }
     };
 
+    // Lengths programmed in inch or metric come in the output unit (see
+    // crate::units). Polar angle/radius, the axes of a pole definition, the
+    // opening angle of an arc, the tool number and the spindle words
+    // LIMS/SPOS move nothing until the block ends (see crate::polar,
+    // crate::arcs, crate::tools and crate::spindle).
+    let value = crate::units::geometry(&key, value, state);
+    if crate::polar::take_word(&key, value, dimensioning, state)
+        || crate::arcs::take_word(&key, value, dimensioning, state)
+        || crate::tools::take_word(&key, value, state)
+        || crate::spindle::take_word(&key, value, state)
+    {
+        return Ok((key, None));
+    }
+    let local_value = state.resolve_programmed(&key, value, dimensioning);
     if state.is_axis(&key) {
         state.update_axis(&key, local_value)?;
     } else if state.is_block_address(&key) {
@@ -1431,46 +1638,46 @@ fn eval_index_arg(arg: &Pair<Rule>, state: &mut State, line_no: usize, preview:
     Ok(n as usize)
 }
 
-fn interpret_axis_increment(pair: Pair<Rule>, state: &mut State, key: String) -> Result<f64, ParsingError> {
-    // axis_increment = { "IC" ~ "(" ~ expression ~ ")" }
-    // Returns the new LOCAL coordinate. Since axes now store local coordinates,
-    // we simply add the increment to the current local value.
-    // Note: when the frame changed since the previous move, the machine-space
-    // delta is increment + frame change. This matches the control's factory
-    // default SD42440 $SC_FRAME_OFFSET_INCR_PROG = TRUE ("changes to work
-    // offsets are traversed after a frame change"); machines configured with
-    // FALSE traverse the pure increment instead, which is not modeled here.
+/// Evaluate an `IC(...)` / `AC(...)` / `DAC(...)` (`DIC`, `RAC`, `RIC`)
+/// wrapper: the inner value and the dimensioning it forces. The caller resolves it against the current
+/// position (`State::resolve_programmed`).
+/// Note: when the frame changed since the previous move, an IC() machine-space
+/// delta is increment + frame change. This matches the control's factory
+/// default SD42440 $SC_FRAME_OFFSET_INCR_PROG = TRUE ("changes to work
+/// offsets are traversed after a frame change"); machines configured with
+/// FALSE traverse the pure increment instead, which is not modeled here.
+fn interpret_dimensioned_value(pair: Pair<Rule>, state: &mut State) -> Result<(f64, Dimensioning), ParsingError> {
     let pair_clone = pair.clone();
-    let inner_pair = pair
-        .into_inner()
+    let mut inner = pair.into_inner();
+    let dimensioning = match pair_clone.as_rule() {
+        Rule::axis_absolute => Dimensioning::Absolute,
+        Rule::axis_diameter => {
+            let form = inner.next().expect("axis_diameter starts with its form").as_str();
+            let incremental = form.eq_ignore_ascii_case("DIC") || form.eq_ignore_ascii_case("RIC");
+            if form.to_ascii_uppercase().starts_with('D') {
+                Dimensioning::Diameter { incremental }
+            } else {
+                Dimensioning::Radius { incremental }
+            }
+        }
+        _ => Dimensioning::Incremental,
+    };
+    let inner_pair = inner
         .next()
-        .expect("Expected an expression inside axis_increment, found none");
+        .expect("Expected an expression inside IC()/AC(), found none");
     if inner_pair.as_rule() != Rule::expression {
         return Err(ParsingError::UnexpectedRule {
             rule: inner_pair.as_rule(),
-            context: "interpret_axis_increment::axis_increment".to_string(),
+            context: "interpret_dimensioned_value".to_string(),
             line_no: pair_clone.line_col().0,
             preview: state.get_line(pair_clone.line_col().0).unwrap_or("").to_string(),
             message: format!(
-                "Unexpected rule in interpret_axis_increment: {:?}",
+                "Unexpected rule in interpret_dimensioned_value: {:?}",
                 inner_pair.as_rule()
             ),
         });
     }
-    let increment = evaluate_expression(inner_pair, state)?;
-    match state.get_axis_local(&key) {
-        Some(local_coord) => {
-            // Add increment to current local coordinate
-            Ok(local_coord + increment)
-        }
-        None => {
-            crate::state::emit_warning(format_args!(
-                "Warning: axis '{}' is incremented with IC() before any absolute position was set; assuming it starts at 0 (a real control would start from the actual axis position).",
-                key
-            ));
-            Ok(increment)
-        }
-    }
+    Ok((evaluate_expression(inner_pair, state)?, dimensioning))
 }
 /// Returns each target key with the value assigned to it, or `None` for
 /// keys a gap in the value array left untouched.
@@ -1636,7 +1843,8 @@ fn read_actual_position_sysvar(pair: &Pair<Rule>, state: &mut State) -> Result<O
         state.get_axis_local(&axis)
     } else {
         state.get_axis_machine(&axis)
-    };
+    }
+    .map(|value| crate::units::data_read(&axis, value, state));
     match value {
         Some(v) => Ok(Some(v)),
         None => {
@@ -1664,6 +1872,144 @@ fn read_actual_position_sysvar(pair: &Pair<Rule>, state: &mut State) -> Result<O
     }
 }
 
+/// True if `pair` is a `variable_array` on the settable-frame system variable
+/// `$P_UIFR` (structural check, like [`actual_position_sysvar_name`]).
+fn is_settable_frame_variable(pair: &Pair<Rule>) -> bool {
+    pair.clone()
+        .into_inner()
+        .next()
+        .is_some_and(|name| name.as_rule() == Rule::nc_variable && name.as_str().eq_ignore_ascii_case("$P_UIFR"))
+}
+
+/// `$P_UIFR[<n>] = CTRANS(<axis>, <offset>, ...)`: write settable frame `n`,
+/// the work offset G54..G57 (n = 1..4) or G505..G599 (n = 5..99) select.
+/// Only the translation (`CTRANS`) form is modeled. Typically programmed in
+/// the initial-state file.
+fn interpret_settable_frame(
+    variable_pair: Pair<Rule>,
+    expression_pair: Pair<Rule>,
+    state: &mut State,
+) -> Result<(String, Option<f64>), ParsingError> {
+    let (line_no, preview) = get_error_context(&variable_pair, state);
+    let err =
+        |message: String| ParsingError::with_context(line_no, preview.clone(), "settable frame".to_string(), message);
+    let indices_pair = variable_pair.into_inner().nth(1).expect("Expected indices");
+    let number = match interpret_indices(indices_pair, state)?.as_slice() {
+        [n] if n.fract() == 0.0 && (1.0..=99.0).contains(n) => *n as usize,
+        _ => {
+            return Err(err(
+                "$P_UIFR takes one settable frame number 1..99 (G54..G57 are 1..4, G505..G599 are 5..99)".to_string(),
+            ))
+        }
+    };
+
+    // The right-hand side must be a lone CTRANS(...) call.
+    let mut terms = expression_pair.into_inner();
+    let call = match (terms.next(), terms.next()) {
+        (Some(primary), None) if primary.as_rule() == Rule::primary => primary.into_inner().next(),
+        _ => None,
+    }
+    .filter(|call| call.as_rule() == Rule::arith_fun);
+    let mut call = call.map(Pair::into_inner);
+    let name = call.as_mut().and_then(Iterator::next);
+    if !name.is_some_and(|name| name.as_str().eq_ignore_ascii_case("CTRANS")) {
+        return Err(err(
+            "only the translation form $P_UIFR[<n>] = CTRANS(<axis>, <offset>, ...) is supported".to_string(),
+        ));
+    }
+    let args: Vec<Pair<Rule>> = call
+        .and_then(|mut call| call.next())
+        .map(|args| args.into_inner().collect())
+        .unwrap_or_default();
+    if !args.len().is_multiple_of(2) {
+        return Err(err("CTRANS takes pairs of an axis and its offset".to_string()));
+    }
+    let mut offsets = FxMap::default();
+    for pair in args.chunks(2) {
+        let axis = pair[0].as_str().trim().to_uppercase();
+        if !state.is_axis(&axis) {
+            return Err(err(format!(
+                "CTRANS: '{axis}' is not an axis (axes: {})",
+                state.axis_identifiers.join(", ")
+            )));
+        }
+        let offset = crate::units::data(&axis, evaluate_expression(pair[1].clone(), state)?, state);
+        offsets.insert(axis, offset);
+    }
+    state.set_settable_frame(number, offsets);
+    Ok((format!("$P_UIFR[{number}]"), None))
+}
+
+/// The tool offset parameter a `variable_array` sets, the index into
+/// [`crate::tools::OFFSET_VARIABLES`] (`$TC_DP3`..`$TC_DP6`; structural
+/// check, like [`is_settable_frame_variable`]).
+fn tool_offset_variable(pair: &Pair<Rule>) -> Option<usize> {
+    let name = pair.clone().into_inner().next()?;
+    if name.as_rule() != Rule::nc_variable {
+        return None;
+    }
+    crate::tools::OFFSET_VARIABLES
+        .iter()
+        .position(|variable| name.as_str().eq_ignore_ascii_case(variable))
+}
+
+/// `$TC_DP3[<t>, <d>] = <value>` .. `$TC_DP6[...]`: the lengths 1..3 and the
+/// radius of tool offset `D<d>` of tool `<t>` (see [`crate::tools`]).
+/// Typically programmed in the initial-state file; the value also stays
+/// readable as a variable.
+fn interpret_tool_offset(
+    variable: usize,
+    variable_pair: Pair<Rule>,
+    expression_pair: Pair<Rule>,
+    state: &mut State,
+) -> Result<(String, Option<f64>), ParsingError> {
+    let (line_no, preview) = get_error_context(&variable_pair, state);
+    let err =
+        |message: String| ParsingError::with_context(line_no, preview.clone(), "tool offset".to_string(), message);
+    let name = crate::tools::OFFSET_VARIABLES[variable];
+    let indices_pair = variable_pair.into_inner().nth(1).expect("Expected indices");
+    let (tool, number) = match interpret_indices(indices_pair, state)?.as_slice() {
+        [t, d] if t.fract() == 0.0 && d.fract() == 0.0 && *t >= 1.0 && *d >= 1.0 => (*t as usize, *d as usize),
+        _ => {
+            return Err(err(format!(
+                "{name} takes a tool number and a D number, both from 1: {name}[<t>, <d>]"
+            )))
+        }
+    };
+    let value = evaluate_expression(expression_pair, state)?;
+    if !value.is_finite() {
+        return Err(err(format!("{name}[{tool},{number}]: {value} is not a number")));
+    }
+    state.tools.set_offset(variable, tool, number, state.units.data(value));
+    let key = format!("{name}[{tool},{number}]");
+    state.symbol_table.insert(key.clone(), value);
+    Ok((key, Some(value)))
+}
+
+/// `$TC_TP2[<t>] = "<name>"`: the name `T="<name>"` selects tool `<t>` by
+/// (see [`crate::tools`]).
+fn interpret_tool_name(
+    variable_pair: Pair<Rule>,
+    name: String,
+    state: &mut State,
+) -> Result<(String, Option<f64>), ParsingError> {
+    let (line_no, preview) = get_error_context(&variable_pair, state);
+    let indices_pair = variable_pair.into_inner().nth(1).expect("Expected indices");
+    let tool = match interpret_indices(indices_pair, state)?.as_slice() {
+        [t] if t.fract() == 0.0 && *t >= 1.0 => *t as usize,
+        _ => {
+            return Err(ParsingError::with_context(
+                line_no,
+                preview,
+                "tool name".to_string(),
+                "$TC_TP2 takes one tool number from 1: $TC_TP2[<t>]=\"<name>\"".to_string(),
+            ))
+        }
+    };
+    state.tools.set_name(tool, name);
+    Ok((format!("$TC_TP2[{tool}]"), None))
+}
+
 fn interpret_indices(pair: Pair<Rule>, state: &mut State) -> Result<Vec<f64>, ParsingError> {
     let mut indices = Vec::new();
     // Get error context before consuming pair
@@ -1741,6 +2087,20 @@ fn definition_target_name(assignment: &Pair<Rule>, state: &State) -> Option<Stri
     };
     Some(normalize_reserved_case(name, state))
 }
+/// `DEFINE name AS text`: record the macro (see [`crate::macros`]).
+fn interpret_define(element: Pair<Rule>, state: &mut State) -> Result<(), ParsingError> {
+    let line_no = element.line_col().0;
+    let mut inner = element.into_inner();
+    let name = inner.find(|pair| pair.as_rule() == Rule::identifier);
+    let text = inner.find(|pair| pair.as_rule() == Rule::macro_text);
+    match (name, text) {
+        (Some(name), Some(text)) => crate::macros::define(name.as_str(), text.as_str(), line_no, state),
+        _ => Err(ParsingError::ParseError {
+            message: format!("Malformed DEFINE statement on line {line_no}"),
+        }),
+    }
+}
+
 fn interpret_definition(element: Pair<Rule>, output: &mut Output, state: &mut State) -> Result<(), ParsingError> {
     let pairs = element.into_inner();
     // Whether this DEF declares STRING[n] variables (the data_type pair
@@ -1761,6 +2121,7 @@ fn interpret_definition(element: Pair<Rule>, output: &mut Output, state: &mut St
                     } else if state.is_block_address(&name) {
                         return Err(ParsingError::ReservedNameUsedAsVariable { name, line_no, preview });
                     }
+                    state.declare_local(&name);
                 }
                 let res = interpret_assignment(pair, state)?;
                 if state.is_axis(res.0.as_str()) {
@@ -1804,6 +2165,9 @@ fn interpret_definition(element: Pair<Rule>, output: &mut Output, state: &mut St
                 }
             }
             Rule::assignment_multi => {
+                if let Some(name) = definition_target_name(&pair, state) {
+                    state.declare_local(&name);
+                }
                 for (key, value) in interpret_assignment_multi(pair, state)? {
                     if let Some(value) = value {
                         output.record_variable_change(&key, value);
@@ -1826,6 +2190,7 @@ fn interpret_definition(element: Pair<Rule>, output: &mut Output, state: &mut St
                         preview,
                     });
                 }
+                state.declare_local(&key);
                 if is_string {
                     state.string_table.insert(key.clone(), String::new());
                 } else {
@@ -1834,6 +2199,9 @@ fn interpret_definition(element: Pair<Rule>, output: &mut Output, state: &mut St
                 }
             }
             Rule::variable_array => {
+                if let Some(name) = pair.clone().into_inner().next() {
+                    state.declare_local(&interpret_identifier(name)?);
+                }
                 let keys = interpret_variable_array(pair, state)?;
                 for key in keys {
                     state.symbol_table.insert(key.clone(), 0.0);
@@ -1908,7 +2276,7 @@ fn interpret_statement_if(
     // Match the condition
     let condition = pairs
         .next()
-        .ok_or_else(|| ParsingError::InvalidElementCount { expected: 1, actual: 0 })?;
+        .ok_or(ParsingError::InvalidElementCount { expected: 1, actual: 0 })?;
     if condition.as_rule() != Rule::condition {
         return Err(ParsingError::UnexpectedRule {
             rule: condition.as_rule(),
@@ -1930,7 +2298,7 @@ fn interpret_statement_if(
     // Match the true block
     let true_block = pairs
         .next()
-        .ok_or_else(|| ParsingError::InvalidElementCount { expected: 1, actual: 0 })?;
+        .ok_or(ParsingError::InvalidElementCount { expected: 1, actual: 0 })?;
     if true_block.as_rule() != Rule::blocks {
         return Err(ParsingError::UnexpectedRule {
             rule: true_block.as_rule(),
@@ -2200,6 +2568,70 @@ fn interpret_goto(pair: Pair<Rule>, state: &State) -> Result<BlockFlow, ParsingE
     }))
 }
 
+/// REPEAT/REPEATB: a request to repeat a labelled section, resolved by the
+/// scope runner. The count is evaluated once, here.
+fn interpret_repeat_section(pair: Pair<Rule>, state: &mut State) -> Result<BlockFlow, ParsingError> {
+    let (line_no, preview) = get_error_context(&pair, state);
+    let request = |target: &Pair<Rule>, direction: JumpDirection| {
+        let display = target.as_str().trim().to_string();
+        JumpRequest {
+            key: canonical_jump_target(&display),
+            display,
+            direction,
+            line_no,
+            preview: preview.clone(),
+        }
+    };
+    let mut start = None;
+    let mut end = SectionEnd::StartBlock;
+    let mut count = 1;
+    for item in pair.clone().into_inner() {
+        match item.as_rule() {
+            Rule::repeat_kw => end = SectionEnd::BeforeRepeat,
+            Rule::goto_target if start.is_none() => start = Some(item),
+            Rule::goto_target => end = SectionEnd::Label(request(&item, JumpDirection::Forward)),
+            Rule::endlabel_kw => {
+                end = SectionEnd::Label(JumpRequest {
+                    key: "LABEL:ENDLABEL".to_string(),
+                    display: "ENDLABEL".to_string(),
+                    direction: JumpDirection::Forward,
+                    line_no,
+                    preview: preview.clone(),
+                })
+            }
+            Rule::repeat_count => {
+                let expression = item
+                    .into_inner()
+                    .find(|p| p.as_rule() == Rule::expression)
+                    .expect("repeat_count contains an expression");
+                let value = evaluate_expression(expression, state)?;
+                if value < 1.0 || value.fract() != 0.0 {
+                    return Err(ParsingError::with_context(
+                        line_no,
+                        preview.clone(),
+                        "program section repetition".to_string(),
+                        format!("P={value} is not a repetition count (a whole number of at least 1)"),
+                    ));
+                }
+                count = value as usize;
+            }
+            _ => {}
+        }
+    }
+    let start = start.expect("repeat_section contains a goto_target");
+    // REPEAT <label> searches toward the beginning only: the section ends
+    // just before the REPEAT. The other forms search both ways.
+    let direction = match end {
+        SectionEnd::BeforeRepeat => JumpDirection::Backward,
+        _ => JumpDirection::BothBackwardFirst,
+    };
+    Ok(BlockFlow::Repeat(RepeatRequest {
+        start: request(&start, direction),
+        end,
+        count,
+    }))
+}
+
 /// IF <condition> GOTO... <target>: single-block conditional jump.
 fn interpret_if_goto(pair: Pair<Rule>, state: &mut State) -> Result<BlockFlow, ParsingError> {
     let mut pairs = pair.into_inner();
@@ -2270,6 +2702,7 @@ fn interpret_control(element: Pair<Rule>, output: &mut Output, state: &mut State
             Rule::goto_statement => interpret_goto(pair, state)?,
             Rule::if_goto_statement => interpret_if_goto(pair, state)?,
             Rule::case_statement => interpret_case(pair, state)?,
+            Rule::repeat_section => interpret_repeat_section(pair, state)?,
             Rule::gotos_statement => {
                 // GOTOS repeats the program only when the PLC requests it via
                 // <Chan>.basic.out.enableGoToStart; without that request the
@@ -2355,6 +2788,143 @@ pub(crate) fn looks_like_axis_word_typo(name: &str) -> bool {
         && chars[2..].iter().any(|c| c.is_ascii_alphabetic())
 }
 
+/// Schedule a call to `program` for the end of the block, with its
+/// arguments bound by [`bind_call`].
+pub(crate) fn schedule_subprogram_call(
+    program: Rc<Subprogram>,
+    arguments: Option<Pair<Rule>>,
+    line_no: usize,
+    state: &mut State,
+) -> Result<(), ParsingError> {
+    if state.pending_call.is_some() {
+        return Err(ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or("").to_string(),
+            "subprogram call".to_string(),
+            format!("'{}' is the second subprogram call in this block", program.name),
+        ));
+    }
+    state.pending_call = Some(bind_call(program, arguments, line_no, state)?);
+    Ok(())
+}
+
+/// Bind the arguments of a call to `program` (the `function_arguments` of
+/// `NAME(...)`, if any) to its formal parameters. An omitted argument
+/// (`NAME(1,,3)`) passes 0, or "" to a STRING parameter; a VAR parameter must
+/// be passed a variable.
+fn bind_call(
+    program: Rc<Subprogram>,
+    arguments: Option<Pair<Rule>>,
+    line_no: usize,
+    state: &mut State,
+) -> Result<PendingCall, ParsingError> {
+    let preview = |state: &State| state.get_line(line_no).unwrap_or("").to_string();
+    // The arguments by position: the commas between the argument spans count
+    // the omitted ones.
+    let mut slots: Vec<Option<Pair<Rule>>> = Vec::new();
+    if let Some(arguments) = arguments {
+        let text = arguments.as_str();
+        let base = arguments.as_span().start();
+        let mut cursor = base;
+        for argument in arguments.into_inner() {
+            let span = argument.as_span();
+            let commas = text[cursor - base..span.start() - base].matches(',').count();
+            let omitted = if slots.is_empty() { commas } else { commas - 1 };
+            slots.extend((0..omitted).map(|_| None));
+            slots.push(Some(argument));
+            cursor = span.end();
+        }
+    }
+    if slots.len() > program.parameters.len() {
+        return Err(ParsingError::InvalidFunctionArity {
+            line_no,
+            preview: preview(state),
+            name: program.name.clone(),
+            expected: program.parameters.len(),
+            actual: slots.len(),
+        });
+    }
+    slots.resize_with(program.parameters.len(), || None);
+
+    let mut values = Vec::with_capacity(slots.len());
+    let mut references = Vec::new();
+    for (index, (parameter, argument)) in program.parameters.iter().zip(slots).enumerate() {
+        if parameter.by_reference {
+            let key = match argument.as_ref().and_then(variable_argument) {
+                Some(variable) => normalize_reserved_case(interpret_variable(variable, state)?, state),
+                None => String::new(),
+            };
+            if key.is_empty() || state.is_axis(&key) || state.is_block_address(&key) {
+                return Err(ParsingError::with_context(
+                    line_no,
+                    preview(state),
+                    "subprogram call".to_string(),
+                    format!(
+                        "the VAR parameter '{}' of {} must be passed a variable",
+                        parameter.name, program.name
+                    ),
+                ));
+            }
+            references.push((index, key));
+        }
+        values.push(match argument {
+            None if parameter.is_string => Argument::Text(String::new()),
+            None => Argument::Number(0.0),
+            Some(argument) if parameter.is_string => Argument::Text(evaluate_string(argument, state)?),
+            Some(argument) if argument.as_rule() == Rule::string_value => {
+                return Err(ParsingError::with_context(
+                    line_no,
+                    preview(state),
+                    "subprogram call".to_string(),
+                    format!(
+                        "a string is passed to the numeric parameter '{}' of {}",
+                        parameter.name, program.name
+                    ),
+                ));
+            }
+            Some(argument) => Argument::Number(evaluate_expression(argument, state)?),
+        });
+    }
+    Ok(PendingCall {
+        program,
+        arguments: values,
+        references,
+        line_no,
+        passes: 1,
+    })
+}
+
+/// The spline group and the axis names of `SPLINEPATH(group, axis, ...)`.
+fn spline_path_arguments(
+    arguments: Option<Pair<Rule>>,
+    line_no: usize,
+    state: &mut State,
+) -> Result<(f64, Vec<String>), ParsingError> {
+    let mut arguments = arguments.into_iter().flat_map(|arguments| arguments.into_inner());
+    let Some(group) = arguments.next() else {
+        return Err(ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or("").to_string(),
+            "SPLINEPATH".to_string(),
+            "SPLINEPATH needs the spline group and its axes".to_string(),
+        ));
+    };
+    let group = evaluate_expression(group, state)?;
+    Ok((group, arguments.map(|axis| axis.as_str().trim().to_string()).collect()))
+}
+
+/// The variable an argument expression consists of, if it is nothing else.
+fn variable_argument<'i>(argument: &Pair<'i, Rule>) -> Option<Pair<'i, Rule>> {
+    let mut terms = argument.clone().into_inner();
+    let primary = terms
+        .next()
+        .filter(|p| p.as_rule() == Rule::primary && terms.next().is_none())?;
+    let mut inner = primary.into_inner();
+    inner
+        .next()
+        .filter(|p| p.as_rule() == Rule::variable && inner.next().is_none())
+}
+
 fn interpret_statement(element: Pair<Rule>, output: &mut Output, state: &mut State) -> Result<BlockFlow, ParsingError> {
     // Grammar:
     // statement           =  {
@@ -2373,6 +2943,7 @@ fn interpret_statement(element: Pair<Rule>, output: &mut Output, state: &mut Sta
         match statement.as_rule() {
             Rule::non_returning_function_call => {
                 let (line_no, preview) = get_error_context(&statement, state);
+                let arguments = statement.clone().into_inner().nth(1);
                 let (key, value) = interpret_non_returning_function_call(statement);
                 let name = value.split('(').next().unwrap_or("").trim();
                 if !value.contains('(') {
@@ -2400,30 +2971,123 @@ fn interpret_statement(element: Pair<Rule>, output: &mut Output, state: &mut Sta
                         // `value` may carry trailing whitespace from the
                         // backtracked optional argument list; store the
                         // trimmed word uppercased like the g_command path.
-                        last.insert(group, Value::Str(name.to_uppercase()));
+                        last.insert(group, Value::Str(g_command_output(group, name.to_uppercase(), state)));
+                        continue;
+                    }
+                    // RET ends a subprogram like M17 (without its output).
+                    if name.eq_ignore_ascii_case("RET") {
+                        flow = BlockFlow::EndProgram;
                         continue;
                     }
+                    if crate::subprogram::take_pass_count(name, line_no, state)? {
+                        continue;
+                    }
+                }
+                if name.eq_ignore_ascii_case("SPLINEPATH") && value.contains('(') {
+                    let (group, axes) = spline_path_arguments(arguments, line_no, state)?;
+                    crate::splines::declare_path(group, axes, line_no, output, state)?;
+                    continue;
+                }
+                match crate::subprogram::find(name, line_no, state)? {
+                    Some(program) => schedule_subprogram_call(program, arguments, line_no, state)?,
                     // A parenless word like Y2O or X10Y20 is far more likely
                     // a mistyped axis word than a subprogram call; the
                     // toolpath would silently lose a move. Warn, but keep the
                     // call semantics: only the user can tell.
-                    if looks_like_axis_word_typo(name) {
+                    None if !value.contains('(') && looks_like_axis_word_typo(name) => {
                         crate::state::emit_warning(format_args!(
                             "Warning: '{}' (line {}) is interpreted as a subprogram call; did you mean one or more axis words (e.g. Y20)?",
                             name, line_no
                         ));
                     }
+                    None => {}
+                }
+                // The span may end in the whitespace skipped while trying
+                // the optional argument list.
+                last.insert(key, Value::Str(value.trim_end().to_string()));
+            }
+            Rule::call_statement => {
+                let (line_no, _) = get_error_context(&statement, state);
+                let target = statement
+                    .into_inner()
+                    .find(|pair| pair.as_rule() != Rule::call_kw)
+                    .expect("CALL has a program name");
+                let name = evaluate_string(target, state)?;
+                let name = name.trim();
+                match crate::subprogram::find(name, line_no, state)? {
+                    Some(program) => schedule_subprogram_call(program, None, line_no, state)?,
+                    None => return Err(crate::subprogram::not_found(name, line_no, state)),
+                }
+            }
+            Rule::execstring_statement => {
+                let (line_no, preview) = get_error_context(&statement, state);
+                let mut inner = statement.into_inner();
+                let keyword = inner.next().expect("EXECSTRING has a keyword");
+                if keyword.as_str().eq_ignore_ascii_case("EXECTAB") {
+                    return Err(ParsingError::UnsupportedStatement {
+                        line_no,
+                        preview,
+                        statement: "EXECTAB".to_string(),
+                        hint: "Motion tables are not modelled; use EXECSTRING with the block text.".to_string(),
+                    });
+                }
+                let text = inner.next().expect("EXECSTRING has a block text");
+                let text = evaluate_string(text, state)?;
+                // A string that executes itself would never return.
+                if state.exec_depth >= crate::subprogram::MAX_CALL_DEPTH {
+                    return Err(ParsingError::with_context(
+                        line_no,
+                        preview,
+                        EXECSTRING.to_string(),
+                        format!(
+                            "EXECSTRING blocks nest deeper than {} levels",
+                            crate::subprogram::MAX_CALL_DEPTH
+                        ),
+                    ));
+                }
+                state.exec_depth += 1;
+                let result = interpret_generated_block(&text, line_no, EXECSTRING, output, state);
+                state.exec_depth -= 1;
+                // The generated block opened its own row; the rest of this
+                // block's words land in it.
+                match result? {
+                    BlockFlow::Continue => {}
+                    other => flow = other,
+                }
+            }
+            Rule::mcall_statement => {
+                let (line_no, _) = get_error_context(&statement, state);
+                let text = statement.as_str().trim().to_string();
+                // A new MCALL replaces the modal call; a bare one only
+                // deselects it.
+                state.modal_call = None;
+                let Some(call) = statement
+                    .into_inner()
+                    .find(|pair| pair.as_rule() == Rule::non_returning_function_call)
+                else {
+                    continue;
+                };
+                let name = call.as_str().split('(').next().unwrap_or("").trim().to_string();
+                let arguments = call.into_inner().nth(1);
+                match crate::subprogram::find(&name, line_no, state)? {
+                    Some(program) => {
+                        let call = bind_call(program, arguments, line_no, state)?;
+                        state.modal_call = Some(ModalCall::new(call));
+                    }
+                    // Like an unresolved plain call: kept for the record.
+                    None => {
+                        last.insert("non_returning_function_call", Value::Str(text));
+                    }
                 }
-                last.insert(key, Value::Str(value));
             }
             Rule::g_command => {
                 let (key, value) = interpret_g_command(statement, state)?;
-                last.insert(key, Value::Str(value));
+                last.insert(key, Value::Str(g_command_output(key, value, state)));
             }
             Rule::g_command_numbered => {
                 let inner = statement.into_inner().next().expect("Error parsing g_command_numbered");
                 let (key, value) = interpret_g_command(inner, state)?;
-                last.insert(key, Value::Str(value));
+                last.insert(key, Value::Str(g_command_output(key, value, state)));
             }
             Rule::m_command => {
                 let (line_no, preview) = get_error_context(&statement, state);
@@ -2438,29 +3102,39 @@ fn interpret_statement(element: Pair<Rule>, output: &mut Output, state: &mut Sta
             // axis_word is the hoisted fast-path form of assignment's first
             // alternative; both carry (variable_single_char, value) inners.
             Rule::assignment | Rule::axis_word => {
-                let line_no = statement.line_col().0;
                 let (key, local_value) = interpret_assignment(statement, state)?;
-                // A string assignment is fully handled in the state (string
-                // table); nothing lands in the output row.
+                // A string or settable-frame assignment is fully handled in
+                // the state; nothing lands in the output row.
                 let Some(local_value) = local_value else { continue };
                 match state.resolve_output_key(&key) {
                     Some((ColKind::Axis, skey)) => {
                         // State keeps local coordinates; the output row gets the machine
-                        // coordinate under the translation active here. `update_axis` just
-                        // stored this axis' local == `local_value`, so machine =
+                        // coordinates under the frame active here. `update_axis` just
+                        // stored this axis' local == `local_value`, so unrotated this is
                         // local_value + translation — one lookup instead of
                         // `get_axis_machine`'s two (it would re-read the axis we just set).
-                        let machine_value = local_value + state.get_translation(skey);
-                        last.insert(skey, Value::Float(machine_value));
+                        state.machine_outputs(skey, local_value, |axis, machine_value| {
+                            last.insert(axis, Value::Float(machine_value));
+                        });
                     }
                     Some((ColKind::Block, skey)) => {
                         last.insert(skey, Value::Float(local_value));
                     }
-                    None => {
-                        state.warn_unsupported_address(&key, line_no);
-                        output.record_variable_change(&key, local_value);
+                    None => output.record_variable_change(&key, local_value),
+                }
+            }
+            Rule::poly_coefficients => {
+                let (line_no, _) = get_error_context(&statement, state);
+                let mut axis = None;
+                let mut values = Vec::new();
+                for pair in statement.into_inner() {
+                    match pair.as_rule() {
+                        Rule::po_axis => axis = Some(pair.as_str().to_uppercase()),
+                        Rule::expression => values.push(evaluate_expression(pair, state)?),
+                        _ => {}
                     }
                 }
+                crate::poly::take_coefficients(axis.as_deref(), &values, line_no, last, state)?;
             }
             Rule::tool_selection => interpret_tool_selection(statement, output, state)?,
             _ => Err(ParsingError::UnexpectedRule {
@@ -2476,17 +3150,52 @@ fn interpret_statement(element: Pair<Rule>, output: &mut Output, state: &mut Sta
 }
 /// Evaluate the assignments of a frame instruction without moving any axis:
 /// the axis state is saved and restored around parsing, and each assignment
-/// must target a valid axis.
-fn frame_assignments(pairs: Vec<Pair<Rule>>, state: &mut State) -> Result<Vec<(String, f64)>, ParsingError> {
-    let mut result = Vec::with_capacity(pairs.len());
+/// must target a valid axis or one of the non-axis frame `addresses` (`RPL`).
+fn frame_assignments(
+    pairs: Vec<Pair<Rule>>,
+    addresses: &[&str],
+    state: &mut State,
+) -> Result<Vec<(String, f64)>, ParsingError> {
     // Save the axis state once for the whole instruction; interpret_assignment
     // mutates it as a side effect and frame instructions must not move axes.
     let saved_axes = state.axes.clone();
+    let saved_moves = state.block_moves();
+    // Frame components are absolute values, never G91 increments, and
+    // angles and factors besides the offsets (which the caller converts to
+    // the output unit).
+    let saved_incremental = std::mem::replace(&mut state.incremental, false);
+    let basic_system = crate::units::Units::new(state.units.output);
+    let saved_units = std::mem::replace(&mut state.units, basic_system);
+    // A non-axis address lands in the symbol table like a user variable.
+    let saved_addresses: Vec<(&str, Option<f64>)> = addresses
+        .iter()
+        .map(|&address| (address, state.symbol_table.get(address).copied()))
+        .collect();
+    let result = frame_assignment_values(pairs, addresses, state);
+    // Undo the axis-position side effects of interpret_assignment
+    state.axes = saved_axes;
+    state.restore_block_moves(saved_moves);
+    state.incremental = saved_incremental;
+    state.units = saved_units;
+    for (address, value) in saved_addresses {
+        match value {
+            Some(value) => state.symbol_table.insert(address.to_string(), value),
+            None => state.symbol_table.remove(address),
+        };
+    }
+    result
+}
+
+fn frame_assignment_values(
+    pairs: Vec<Pair<Rule>>,
+    addresses: &[&str],
+    state: &mut State,
+) -> Result<Vec<(String, f64)>, ParsingError> {
+    let mut result = Vec::with_capacity(pairs.len());
     for pair in pairs {
         let (pair_line_no, pair_preview) = get_error_context(&pair, state);
         let (key, value) = interpret_assignment(pair, state)?;
         let Some(value) = value else {
-            state.axes = saved_axes;
             return Err(ParsingError::with_context(
                 pair_line_no,
                 pair_preview,
@@ -2494,8 +3203,7 @@ fn frame_assignments(pairs: Vec<Pair<Rule>>, state: &mut State) -> Result<Vec<(S
                 format!("frame instruction cannot assign a string to '{key}'"),
             ));
         };
-        if !state.is_axis(&key) {
-            state.axes = saved_axes;
+        if !state.is_axis(&key) && !addresses.contains(&key.as_str()) {
             return Err(ParsingError::UnexpectedAxis {
                 axis: key,
                 axes: state.axis_identifiers.join(", "),
@@ -2505,66 +3213,147 @@ fn frame_assignments(pairs: Vec<Pair<Rule>>, state: &mut State) -> Result<Vec<(S
         }
         result.push((key, value));
     }
-    // Undo the axis-position side effects of interpret_assignment
-    state.axes = saved_axes;
     Ok(result)
 }
 
-fn interpret_frame_op(element: Pair<Rule>, state: &mut State) -> Result<(), ParsingError> {
+/// The rotation programmed by `ROT`/`AROT` (RPY axis angles or `RPL=`) or by
+/// `ROTS`/`AROTS`/`CROTS` (solid angles in the working plane).
+fn frame_rotation(
+    op: &str,
+    components: &[(String, f64)],
+    state: &State,
+    line_no: usize,
+    preview: &str,
+) -> Result<Matrix3, ParsingError> {
+    let error = |message: String| {
+        ParsingError::with_context(line_no, preview.to_string(), "frame instruction".to_string(), message)
+    };
+    let mut angles = [0.0; 3];
+    let mut plane_angle = None;
+    for (key, value) in components {
+        match key.as_str() {
+            "RPL" => plane_angle = Some(*value),
+            "X" => angles[0] = *value,
+            "Y" => angles[1] = *value,
+            "Z" => angles[2] = *value,
+            _ => {
+                return Err(error(format!(
+                    "{op} can only rotate about the geometry axes X, Y and Z, not '{key}'"
+                )))
+            }
+        }
+    }
+    let [abscissa, ordinate, applicate] = state.plane.axes();
+    if op.ends_with("ROTS") {
+        if plane_angle.is_some() || angles[applicate] != 0.0 {
+            return Err(error(format!(
+                "{op} takes the two solid angles of the working plane axes ({}, {})",
+                ["X", "Y", "Z"][abscissa],
+                ["X", "Y", "Z"][ordinate]
+            )));
+        }
+        return Ok(frame::solid_angles(state.plane, angles[abscissa], angles[ordinate]));
+    }
+    match plane_angle {
+        Some(_) if components.len() > 1 => Err(error(format!("{op} RPL= cannot be combined with axis angles"))),
+        Some(angle) => Ok(frame::about_axis(applicate, angle)),
+        None => Ok(frame::rpy(angles)),
+    }
+}
+
+fn interpret_frame_op(element: Pair<Rule>, output: &mut Output, state: &mut State) -> Result<(), ParsingError> {
     let (line_no, preview) = get_error_context(&element, state);
     let mut pairs = element.into_inner();
     let kw = pairs.next().expect("frame_op must start with a frame keyword");
     let op = kw.as_str().to_uppercase();
     let assignments: Vec<Pair<Rule>> = pairs.collect();
-
+    let arcs_reversed = state.arcs_reversed();
+
+    // The absolute forms (TRANS, ROT, ROTS, CROTS, SCALE, MIRROR) are
+    // substituting frame instructions: they delete ALL previously programmed
+    // frame components, including offsets on axes not mentioned in the block
+    // (manual 3.12.2.1/3.12.2.2, and the Notice "Absolute frame instructions
+    // delete all programmed frames"); bare, they are just the reset. The
+    // additive forms build on the current frame, and a bare one adds
+    // nothing. (CROTS is absolute despite the leading C.)
+    let additive = op.starts_with('A');
     match op.as_str() {
-        "TRANS" => {
-            // TRANS is a substituting frame instruction: it deletes ALL
-            // previously programmed frame components, including offsets on
-            // axes not mentioned in this block (manual 3.12.2.2, and the
-            // Notice "Absolute frame instructions delete all programmed
-            // frames"). Bare TRANS is therefore just the reset.
-            state.reset_translations();
-            for (key, value) in frame_assignments(assignments, state)? {
-                state.update_translation(&key, value, line_no, &preview)?;
-            }
-            Ok(())
-        }
-        "ATRANS" => {
-            for (key, value) in frame_assignments(assignments, state)? {
-                let current_translation = state.get_translation(&key);
-                state.update_translation(&key, current_translation + value, line_no, &preview)?;
-            }
-            Ok(())
-        }
-        // Rotation, scaling and mirroring change the geometry in ways this
-        // interpreter does not model, so anything with parameters must fail
-        // loudly instead of producing wrong coordinates. The bare forms are
-        // frame resets: a bare ABSOLUTE instruction (ROT/ROTS/CROTS/SCALE/
-        // MIRROR) deletes the whole programmable frame including the
-        // translation (manual 3.12.2.1), while a bare ADDITIVE instruction
-        // adds nothing and is a no-op.
-        _ => {
-            if assignments.is_empty() {
-                if !op.starts_with('A') {
-                    // Bare absolute frame instruction: delete the programmable
-                    // frame. (CROTS is absolute despite the leading C.)
-                    state.reset_translations();
+        "TRANS" | "ATRANS" => {
+            let mut offsets = frame_assignments(assignments, &[], state)?;
+            for (key, value) in &mut offsets {
+                *value = crate::units::geometry(key, *value, state);
+            }
+            if additive {
+                state.add_translation(&offsets, line_no, &preview)?;
+            } else {
+                state.reset_frame();
+                for (key, value) in offsets {
+                    state.update_translation(&key, value, line_no, &preview)?;
+                }
+            }
+        }
+        // Rotations: the additive forms turn the current frame further about
+        // its own (already rotated) axes. CROTS refers to the settable frame,
+        // which is never rotated here, so it equals ROTS.
+        "ROT" | "ROTS" | "CROTS" | "AROT" | "AROTS" => {
+            let components = frame_assignments(assignments, &["RPL"], state)?;
+            if components.is_empty() {
+                if !additive {
+                    state.reset_frame();
                 }
-                Ok(())
             } else {
-                Err(ParsingError::UnsupportedStatement {
+                let rotation = frame_rotation(&op, &components, state, line_no, &preview)?;
+                if additive {
+                    let current = state.frame_rotation().unwrap_or(frame::IDENTITY);
+                    state.set_frame_rotation(frame::multiply(&current, &rotation));
+                } else {
+                    state.reset_frame();
+                    state.set_frame_rotation(rotation);
+                }
+            }
+        }
+        // Scale factors multiply (ASCALE on top of the active factors);
+        // a factor of 0 would collapse the axis and is rejected.
+        "SCALE" | "ASCALE" => {
+            let factors = frame_assignments(assignments, &[], state)?;
+            if let Some((axis, _)) = factors.iter().find(|(_, factor)| *factor == 0.0) {
+                return Err(ParsingError::with_context(
                     line_no,
                     preview,
-                    statement: format!("The frame instruction {}", op),
-                    hint: "Rotation, scaling and mirroring frames are not modeled; interpreting \
-                           this program would produce wrong coordinates. Only TRANS and ATRANS \
-                           are supported."
-                        .to_string(),
-                })
+                    "frame instruction".to_string(),
+                    format!("{op} factor for axis '{axis}' must not be 0"),
+                ));
+            }
+            if !additive {
+                state.reset_frame();
+            }
+            for (axis, factor) in factors {
+                state.multiply_scale(&axis, factor);
+            }
+        }
+        // Mirroring reverses the sign of each named axis; the programmed
+        // value is irrelevant (`MIRROR X0`). AMIRROR on an already mirrored
+        // axis cancels the mirror.
+        _ => {
+            let axes = frame_assignments(assignments, &[], state)?;
+            if !additive {
+                state.reset_frame();
+            }
+            for (axis, _) in axes {
+                state.toggle_mirror(&axis);
             }
         }
     }
+    // An odd mirror of the working plane reverses G2/G3: restate the modal
+    // direction so the output keeps telling the machine direction.
+    if let Some(word) = state.restated_arc_motion(arcs_reversed) {
+        let last = output.last_mut().expect("Output vector should not be empty");
+        last.insert("gg01_motion", Value::Str(word.to_string()));
+    }
+    // The flattener follows the frame into the rotated, scaled and mirrored
+    // working plane.
+    output.set_frame(state.frame_linear(), state.mirrored_geometry());
+    Ok(())
 }
 fn interpret_block_number(element: Pair<Rule>, output: &mut Output) {
     let mut pairs = element.into_inner();
@@ -2593,6 +3382,107 @@ fn annotate_error(pair: &Pair<Rule>, context: &str, message: String, state: &Sta
     ParsingError::with_context(line_no, preview, context.to_string(), message)
 }
 
+/// Settle the per-block state before any word of `block` is evaluated.
+/// G90/G91 act on the whole block they are programmed in, so `X10 G91`
+/// moves incrementally just like `G91 X10`; likewise the plane, the work
+/// offset (`G54 X10` moves to X10 in the new work coordinates), the unit
+/// (G70/G71, G700/G710) and the frame suppression of G53/G153/SUPA.
+fn begin_block(block: &Pair<Rule>, state: &mut State) {
+    state.begin_block();
+    for item in block.clone().into_inner() {
+        if item.as_rule() != Rule::statement {
+            continue;
+        }
+        for statement in item.into_inner() {
+            // SUPA and DIAMON/DIAM90/DIAMOF are keyword-shaped: they parse as
+            // bare words.
+            if matches!(
+                statement.as_rule(),
+                Rule::g_command_numbered | Rule::non_returning_function_call
+            ) {
+                select_block_mode(statement.as_str().trim(), state);
+            }
+        }
+    }
+}
+
+/// Apply the G function `code` ahead of its block if it acts on the whole
+/// block (see [`begin_block`]); any other word is left alone. The line driver
+/// runs its decoded G words through here as well.
+pub(crate) fn select_block_mode(code: &str, state: &mut State) {
+    if code.eq_ignore_ascii_case("G91") {
+        state.incremental = true;
+    } else if code.eq_ignore_ascii_case("G90") {
+        state.incremental = false;
+    } else if let Some(plane) = WorkingPlane::from_code(code) {
+        state.plane = plane;
+    } else if settable_frame_number(code).is_some() {
+        state.select_work_offset(code);
+    } else if suppresses_frames(code) {
+        state.frame_suppressed = true;
+    } else if let Some(mode) = DiameterMode::from_code(code) {
+        state.diameter = mode;
+    } else {
+        state.units.select(code);
+        state.polar.select_pole(code);
+    }
+}
+
+/// Run the end-of-block hooks of block `line_no` once all its words ran, then
+/// the subprogram it calls (or the modal call): the one place every path
+/// (block walker, VM, line driver) finishes a block.
+pub(crate) fn finish_block(line_no: usize, output: &mut Output, state: &mut State) -> Result<(), ParsingError> {
+    crate::units::finish_block(output, state)?;
+    crate::tools::finish_block(line_no, output, state)?;
+    crate::polar::finish_block(line_no, output, state)?;
+    crate::arcs::finish_block(line_no, output, state)?;
+    crate::poly::finish_block(line_no, output, state)?;
+    // The thread feed follows the speed the spindle reaches in the block.
+    crate::spindle::finish_block(line_no, output, state)?;
+    crate::threads::finish_block(line_no, output, state)?;
+    crate::splines::finish_block(line_no, output, state)?;
+    crate::tool_radius::finish_block(line_no, output, state)?;
+    // A subprogram called in this block runs after its other words.
+    crate::subprogram::run_pending_call(line_no, output, state)
+}
+
+/// Context of blocks produced by expanding macros.
+pub(crate) const MACRO_EXPANSION: &str = "macro expansion";
+/// Context of blocks produced by `EXECSTRING`.
+pub(crate) const EXECSTRING: &str = "EXECSTRING";
+
+/// Parse `text`, a block generated on line `line_no` by `source` (macro
+/// expansion, `EXECSTRING`), and interpret it in that line's place. Errors
+/// report the calling line together with the generated text.
+pub(crate) fn interpret_generated_block(
+    text: &str,
+    line_no: usize,
+    source: &str,
+    output: &mut Output,
+    state: &mut State,
+) -> Result<BlockFlow, ParsingError> {
+    // Padded to the calling line, like the stage-1 per-line parses, so the
+    // block reports that line's number.
+    let mut padded = "\n".repeat(line_no.saturating_sub(1));
+    padded.push_str(text);
+    let parsed = NCParser::parse(Rule::line_entry, &padded).map_err(|e| ParsingError::ParsingContext {
+        line_no,
+        column: None,
+        preview: state.get_line(line_no).unwrap_or(text).to_string(),
+        context: source.to_string(),
+        message: format!(
+            "the generated block '{}' does not parse: {}",
+            text.trim(),
+            crate::interpreter::describe_parse_error(&e)
+        ),
+    })?;
+    let Some(block) = parsed.flatten().find(|pair| pair.as_rule() == Rule::block) else {
+        // An empty string (or a bare comment) is an empty block.
+        return Ok(BlockFlow::Continue);
+    };
+    interpret_block(block, output, state).map_err(|e| e.in_generated_block(line_no, source, text))
+}
+
 pub(crate) fn interpret_block(
     element: Pair<Rule>,
     output: &mut Output,
@@ -2600,24 +3490,32 @@ pub(crate) fn interpret_block(
 ) -> Result<BlockFlow, ParsingError> {
     match element.as_rule() {
         Rule::block => {
+            let line_no = element.line_col().0;
+            if let Some(expanded) = crate::macros::expand_block(element.as_str(), line_no, state)? {
+                return interpret_generated_block(&expanded, line_no, MACRO_EXPANSION, output, state);
+            }
             // Start this block's output row, flushing the previous one.
-            output.start_row(element.line_col().0)?;
+            output.start_row(line_no)?;
+            begin_block(&element, state);
 
             let mut flow = BlockFlow::Continue;
             for item in element.into_inner() {
                 match item.as_rule() {
-                    Rule::statement => {
-                        if let BlockFlow::EndProgram = interpret_statement(item, output, state)? {
-                            flow = BlockFlow::EndProgram;
-                        }
-                    }
+                    Rule::statement => match interpret_statement(item, output, state)? {
+                        BlockFlow::Continue => {}
+                        other => flow = other,
+                    },
                     Rule::block_number => interpret_block_number(item, output),
                     // Jump labels are collected by scan_jump_targets before
                     // execution; at execution time they are inert.
                     Rule::label_def => {}
                     Rule::control => flow = interpret_control(item, output, state)?,
                     Rule::definition => interpret_definition(item, output, state)?,
-                    Rule::frame_op => interpret_frame_op(item, state)?,
+                    Rule::define_statement => interpret_define(item, state)?,
+                    // A PROC statement only matters when the subprogram is
+                    // called (see crate::subprogram); EXTERN is informational.
+                    Rule::proc_definition | Rule::extern_declaration => {}
+                    Rule::frame_op => interpret_frame_op(item, output, state)?,
                     Rule::comment => {
                         let last = output.last_mut().expect("Output vector should not be empty");
                         last.insert("comment", Value::Str(item.as_str().to_string()));
@@ -2632,16 +3530,15 @@ pub(crate) fn interpret_block(
                     }
                 }
             }
+            finish_block(line_no, output, state)?;
             Ok(flow)
         }
-        _ => {
-            return Err(annotate_error(
-                &element,
-                "blocks interpretation",
-                format!("Expected blocks, found {:?}", element.as_rule()),
-                state,
-            ));
-        }
+        _ => Err(annotate_error(
+            &element,
+            "blocks interpretation",
+            format!("Expected blocks, found {:?}", element.as_rule()),
+            state,
+        )),
     }
 }
 
@@ -2675,10 +3572,12 @@ fn run_blocks(
 ) -> Result<BlockFlow, ParsingError> {
     let mut index = 0;
     let mut jumps_taken = 0;
+    let mut sections = Sections::default();
     while index < block_pairs.len() {
         match interpret_block(block_pairs[index].clone(), output, state)? {
-            BlockFlow::Continue => index += 1,
+            BlockFlow::Continue => index = sections.advance(index),
             BlockFlow::EndProgram => return Ok(BlockFlow::EndProgram),
+            BlockFlow::Repeat(request) => index = sections.enter(targets, index, request, state)?,
             BlockFlow::Jump(request) => match resolve_jump(targets, index, &request) {
                 Some(destination) => {
                     // Only backward jumps can form cycles; bound them like the
@@ -2692,6 +3591,7 @@ fn run_blocks(
                             });
                         }
                     }
+                    sections.jumped(destination);
                     index = destination;
                 }
                 None => return Ok(BlockFlow::Jump(request)),
@@ -4,14 +4,19 @@ use crate::interpret_rules::{interpret_blocks, BlockFlow};
 use crate::output::{OutputRows, Row, Table};
 use crate::state::{self, State};
 use crate::types::{NCParser, Rule};
+use crate::units::Unit;
+use pest::iterators::Pair;
 use pest::Parser;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 const DEFAULT_AXIS_IDENTIFIERS: &[&str] = &[
     "N", "X", "Y", "Z", "A", "B", "C", "D", "E", "F", "S", "U", "V", "RA1", "RA2", "RA3", "RA4", "RA5", "RA6",
 ];
 
 /// Main function: interpret the input program into a sanitized output table.
+#[allow(clippy::too_many_arguments)]
+#[allow(dead_code)] // the bin calls nc_to_table_with_options
 pub fn nc_to_table(
     input: &str,
     initial_state: Option<&str>,
@@ -23,12 +28,86 @@ pub fn nc_to_table(
     allow_undefined_variables: bool,
     flatten_tolerance: Option<f64>,
 ) -> Result<(Table, state::State), ParsingError> {
+    nc_to_table_with_options(
+        input,
+        initial_state,
+        axis_identifiers,
+        extra_axes,
+        iteration_limit,
+        disable_forward_fill,
+        axis_index_map,
+        allow_undefined_variables,
+        flatten_tolerance,
+        InterpreterOptions::default(),
+    )
+}
+
+/// The interpreter options beyond the original entry points' arguments.
+/// The default is what those entry points do; set options builder-style,
+/// e.g. `InterpreterOptions::default().with_unit(Unit::Inch)`, so new ones
+/// do not change any signature.
+#[derive(Debug, Clone, Default)]
+pub struct InterpreterOptions {
+    /// Directories searched for called subprograms, in order: `NAME` loads
+    /// `NAME.SPF` (or `NAME.MPF`) from the first that has it (see
+    /// [`crate::subprogram`]).
+    pub subprogram_dirs: Vec<PathBuf>,
+    /// Cycle names mapped to their NC source, taking precedence over the
+    /// search path and the built-in cycles.
+    pub cycles: HashMap<String, String>,
+    /// Output the programmed contour next to the compensated tool path from
+    /// the first row on (see [`crate::tool_radius`]).
+    pub keep_contour: bool,
+    /// The unit of the output lengths.
+    pub unit: Unit,
+}
+
+impl InterpreterOptions {
+    pub fn with_subprogram_dirs(mut self, subprogram_dirs: Vec<PathBuf>) -> Self {
+        self.subprogram_dirs = subprogram_dirs;
+        self
+    }
+
+    #[allow(dead_code)] // used by the python-feature bindings, not the bin
+    pub fn with_cycles(mut self, cycles: HashMap<String, String>) -> Self {
+        self.cycles = cycles;
+        self
+    }
+
+    pub fn with_keep_contour(mut self, keep_contour: bool) -> Self {
+        self.keep_contour = keep_contour;
+        self
+    }
+
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+}
+
+/// As [`nc_to_table`], with [`InterpreterOptions`]. Separate function so
+/// `nc_to_table`'s signature stays stable for existing Rust callers.
+#[allow(clippy::too_many_arguments)]
+pub fn nc_to_table_with_options(
+    input: &str,
+    initial_state: Option<&str>,
+    axis_identifiers: Option<Vec<String>>,
+    extra_axes: Option<Vec<String>>,
+    iteration_limit: usize,
+    disable_forward_fill: bool,
+    axis_index_map: Option<HashMap<String, usize>>,
+    allow_undefined_variables: bool,
+    flatten_tolerance: Option<f64>,
+    options: InterpreterOptions,
+) -> Result<(Table, state::State), ParsingError> {
+    let keep_contour = options.keep_contour;
     let mut state = build_state(
         axis_identifiers,
         extra_axes,
         iteration_limit,
         axis_index_map,
         allow_undefined_variables,
+        options,
     );
     if let Some(initial_state) = initial_state {
         // Propagate the error instead of exiting: this is library code, and
@@ -41,6 +120,7 @@ pub fn nc_to_table(
     // Now interpret the main input using the axis_index_map from state
     let mut output = OutputRows::collect();
     install_flattener(&mut output, &state, flatten_tolerance)?;
+    install_compensator(&mut output, &mut state, keep_contour);
     interpret_file(input, &mut state, &mut output)?;
     let rows = output.finish()?;
 
@@ -54,6 +134,7 @@ fn build_state(
     iteration_limit: usize,
     axis_index_map: Option<HashMap<String, usize>>,
     allow_undefined_variables: bool,
+    options: InterpreterOptions,
 ) -> State {
     // Use the override if provided, otherwise use the default identifiers
     let mut axis_identifiers: Vec<String> =
@@ -61,12 +142,18 @@ fn build_state(
     if let Some(extra_axes) = extra_axes {
         axis_identifiers.extend(extra_axes);
     }
-    state::State::new(
+    let mut state = state::State::new(
         axis_identifiers,
         iteration_limit,
         axis_index_map,
         allow_undefined_variables,
-    )
+    );
+    state.set_output_unit(options.unit);
+    state.set_subprogram_dirs(options.subprogram_dirs);
+    for (name, source) in options.cycles {
+        state.register_cycle(&name, source);
+    }
+    state
 }
 
 /// Install the curve flattener on the output when a tolerance was given
@@ -87,11 +174,28 @@ fn install_flattener(
                 flattener.seed_position(axis, machine_value);
             }
         }
+        flattener.set_frame(state.frame_linear(), state.mirrored_geometry());
+        if let Some(path) = &state.spline_path {
+            flattener.set_spline_path(path);
+        }
         output.set_flattener(flattener);
     }
     Ok(())
 }
 
+/// Start tool radius compensation afresh on the output (the initial-state
+/// file's rows were discarded), installing the compensation stage right away
+/// with `keep_contour` so that every row carries the programmed contour (see
+/// [`crate::tool_radius`]); otherwise the first G41/G42 installs it.
+fn install_compensator(output: &mut OutputRows, state: &mut State, keep_contour: bool) {
+    state.tool_radius.restart();
+    if keep_contour {
+        let contour = crate::arcs::GEOMETRY_AXES.map(|axis| state.get_axis_machine(axis));
+        let motion = state.modal_word("gg01_motion").map(str::to_string);
+        output.set_compensator(crate::tool_radius::Compensator::new(true, contour, motion));
+    }
+}
+
 /// Streaming twin of `nc_to_table`: interpret the program pushing each
 /// finished `Row` (which carries its own `line_no`) into `sender`, returning
 /// the final state. The Python-facing `nc_to_rows` splits each `Row` into the
@@ -114,12 +218,43 @@ pub fn nc_to_row_stream(
     flatten_tolerance: Option<f64>,
     sender: std::sync::mpsc::SyncSender<Row>,
 ) -> Result<state::State, ParsingError> {
+    nc_to_row_stream_with_options(
+        input,
+        initial_state,
+        axis_identifiers,
+        extra_axes,
+        iteration_limit,
+        axis_index_map,
+        allow_undefined_variables,
+        flatten_tolerance,
+        InterpreterOptions::default(),
+        sender,
+    )
+}
+
+/// As [`nc_to_row_stream`], with [`InterpreterOptions`]. Separate function
+/// so `nc_to_row_stream`'s signature stays stable for existing Rust callers.
+#[allow(clippy::too_many_arguments)]
+pub fn nc_to_row_stream_with_options(
+    input: &str,
+    initial_state: Option<&str>,
+    axis_identifiers: Option<Vec<String>>,
+    extra_axes: Option<Vec<String>>,
+    iteration_limit: usize,
+    axis_index_map: Option<HashMap<String, usize>>,
+    allow_undefined_variables: bool,
+    flatten_tolerance: Option<f64>,
+    options: InterpreterOptions,
+    sender: std::sync::mpsc::SyncSender<Row>,
+) -> Result<state::State, ParsingError> {
+    let keep_contour = options.keep_contour;
     let mut state = build_state(
         axis_identifiers,
         extra_axes,
         iteration_limit,
         axis_index_map,
         allow_undefined_variables,
+        options,
     );
     if let Some(initial_state) = initial_state {
         let mut discard = OutputRows::collect();
@@ -127,6 +262,7 @@ pub fn nc_to_row_stream(
     }
     let mut output = OutputRows::stream(sender);
     install_flattener(&mut output, &state, flatten_tolerance)?;
+    install_compensator(&mut output, &mut state, keep_contour);
     interpret_file(input, &mut state, &mut output)?;
     output.finish()?;
     Ok(state)
@@ -143,7 +279,6 @@ pub fn nc_to_row_stream(
 /// `nc_to_batches` iterator.
 #[allow(clippy::too_many_arguments)]
 #[allow(dead_code)] // used by the python-feature bindings, not the bin
-#[allow(clippy::too_many_arguments)]
 pub fn nc_to_batch_stream(
     input: &str,
     initial_state: Option<&str>,
@@ -198,12 +333,54 @@ pub fn nc_to_batch_stream_with_line_numbers(
     include_variables: bool,
     events_sender: std::sync::mpsc::Sender<crate::output::VariableEvents>,
 ) -> Result<state::State, ParsingError> {
+    nc_to_batch_stream_with_options(
+        input,
+        initial_state,
+        axis_identifiers,
+        extra_axes,
+        iteration_limit,
+        disable_forward_fill,
+        axis_index_map,
+        allow_undefined_variables,
+        flatten_tolerance,
+        batch_size,
+        emit_line_no,
+        InterpreterOptions::default(),
+        sender,
+        include_variables,
+        events_sender,
+    )
+}
+
+/// As [`nc_to_batch_stream_with_line_numbers`], with [`InterpreterOptions`].
+/// Separate function so the signatures above stay stable for existing Rust
+/// callers.
+#[allow(clippy::too_many_arguments)]
+pub fn nc_to_batch_stream_with_options(
+    input: &str,
+    initial_state: Option<&str>,
+    axis_identifiers: Option<Vec<String>>,
+    extra_axes: Option<Vec<String>>,
+    iteration_limit: usize,
+    disable_forward_fill: bool,
+    axis_index_map: Option<HashMap<String, usize>>,
+    allow_undefined_variables: bool,
+    flatten_tolerance: Option<f64>,
+    batch_size: usize,
+    emit_line_no: bool,
+    options: InterpreterOptions,
+    sender: std::sync::mpsc::SyncSender<Table>,
+    include_variables: bool,
+    events_sender: std::sync::mpsc::Sender<crate::output::VariableEvents>,
+) -> Result<state::State, ParsingError> {
+    let keep_contour = options.keep_contour;
     let mut state = build_state(
         axis_identifiers,
         extra_axes,
         iteration_limit,
         axis_index_map,
         allow_undefined_variables,
+        options,
     );
     if let Some(initial_state) = initial_state {
         let mut discard = OutputRows::collect();
@@ -218,13 +395,15 @@ pub fn nc_to_batch_stream_with_line_numbers(
         emit_line_no,
     );
     install_flattener(&mut output, &state, flatten_tolerance)?;
+    install_compensator(&mut output, &mut state, keep_contour);
     interpret_file(input, &mut state, &mut output)?;
     output.finish()?;
     Ok(state)
 }
 
-/// Interpret a file, pushing rows into `output`.
-fn interpret_file(input: &str, state: &mut State, output: &mut OutputRows) -> Result<(), ParsingError> {
+/// Interpret a file, pushing rows into `output`. Also runs the blocks a
+/// built-in cycle generates (see [`crate::subprogram::run_pending_call`]).
+pub(crate) fn interpret_file(input: &str, state: &mut State, output: &mut OutputRows) -> Result<(), ParsingError> {
     // Store input for error messages
     state.set_input(input);
 
@@ -242,10 +421,18 @@ fn interpret_file(input: &str, state: &mut State, output: &mut OutputRows) -> Re
         match crate::line_driver::interpret_lines(input, &mut padded_lines, output, state)? {
             Some(BlockFlow::Continue) | Some(BlockFlow::EndProgram) => return Ok(()),
             Some(BlockFlow::Jump(request)) => return Err(request.into_not_found_error(state)),
+            Some(BlockFlow::Repeat(_)) => unreachable!("the line driver resolves section repetitions"),
             None => {}
         }
     }
 
+    let blocks = parse_blocks(input, state)?;
+    interpret_parsed(blocks, output, state)
+}
+
+/// Parse a whole file into its `blocks`, for [`interpret_parsed`]. The line
+/// numbers of a parse error refer to the input `state` holds.
+pub(crate) fn parse_blocks<'i>(input: &'i str, state: &State) -> Result<Pair<'i, Rule>, ParsingError> {
     let file = NCParser::parse(Rule::file, input)
         .map_err(|e| {
             let (line, col) = match &e.line_col {
@@ -266,10 +453,17 @@ fn interpret_file(input: &str, state: &mut State, output: &mut OutputRows) -> Re
             message: "No blocks found".to_string(),
         })?;
 
-    let blocks = file.into_inner().next().ok_or_else(|| ParsingError::ParseError {
+    file.into_inner().next().ok_or_else(|| ParsingError::ParseError {
         message: "No inner blocks found".to_string(),
-    })?;
+    })
+}
 
+/// Interpret the parsed `blocks` of a file (see [`parse_blocks`]).
+pub(crate) fn interpret_parsed(
+    blocks: Pair<Rule>,
+    output: &mut OutputRows,
+    state: &mut State,
+) -> Result<(), ParsingError> {
     // Experimental: the explicit-stack VM (#47) runs the same structured path
     // when NC_VM=1, for differential parity against the recursive walker.
     let flow = if crate::interpret_rules::vm::vm_enabled() {
@@ -282,6 +476,7 @@ fn interpret_file(input: &str, state: &mut State, output: &mut OutputRows) -> Re
         // A jump that no scope could resolve: the destination does not exist
         // in the programmed search direction (alarm 14080 on a real control).
         BlockFlow::Jump(request) => Err(request.into_not_found_error(state)),
+        BlockFlow::Repeat(_) => unreachable!("scope runners resolve section repetitions"),
     }
 }
 
@@ -318,6 +513,9 @@ fn describe_rule(rule: Rule) -> &'static str {
         Rule::assignment => "an assignment",
         Rule::assignment_multi => "an array assignment (SET/REP)",
         Rule::axis_increment => "an incremental value IC(...)",
+        Rule::axis_absolute => "an absolute value AC(...)",
+        Rule::axis_diameter => "a diameter or radius DAC(...)/DIC(...)/RAC(...)/RIC(...)",
+        Rule::diameter_form => "DAC, DIC, RAC or RIC",
         Rule::expression | Rule::primary => "an expression",
         Rule::value | Rule::float | Rule::integer => "a number",
         Rule::identifier | Rule::variable => "a name",
@@ -332,6 +530,17 @@ fn describe_rule(rule: Rule) -> &'static str {
         Rule::tool_selection => "a tool selection (T=\"...\")",
         Rule::definition => "a variable definition (DEF)",
         Rule::data_type => "a data type (REAL/INT/BOOL)",
+        Rule::proc_definition | Rule::proc_kw => "a subprogram definition (PROC)",
+        Rule::proc_parameter | Rule::extern_parameter => "a subprogram parameter (e.g. REAL A)",
+        Rule::var_kw => "VAR",
+        Rule::proc_attribute => "a PROC attribute (e.g. SAVE)",
+        Rule::extern_declaration | Rule::extern_kw => "an EXTERN declaration",
+        Rule::call_statement | Rule::call_kw => "an indirect subprogram call (CALL)",
+        Rule::mcall_statement | Rule::mcall_kw => "a modal subprogram call (MCALL)",
+        Rule::execstring_statement | Rule::execstring_kw => "a dynamic block (EXECSTRING)",
+        Rule::define_statement | Rule::define_kw => "a macro definition (DEFINE ... AS)",
+        Rule::as_kw => "AS",
+        Rule::macro_text => "the macro text",
         Rule::control => "a control statement",
         Rule::condition => "a condition",
         Rule::relational_operator => "a comparison operator",
@@ -572,7 +781,14 @@ mod interpret_speed {
 
         // interpret + materialize into a Vec<Row> collect sink (no table build)
         let start = Instant::now();
-        let mut state = build_state(None, extra.clone(), 10_000, aim.clone(), true);
+        let mut state = build_state(
+            None,
+            extra.clone(),
+            10_000,
+            aim.clone(),
+            true,
+            InterpreterOptions::default(),
+        );
         let mut output = OutputRows::collect();
         interpret_file(&input, &mut state, &mut output).expect("interpret");
         let rows = output.finish().expect("finish");
@@ -662,6 +878,18 @@ mod tests {
             .unwrap_or_else(|| panic!("column {name} missing; have {:?}", column_names(table)))
     }
 
+    fn strings<'a>(table: &'a Table, name: &str) -> &'a [Option<String>] {
+        table
+            .columns
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, c)| match c {
+                Column::Str(v) => v.as_slice(),
+                other => panic!("column {name} is not a str column: {other:?}"),
+            })
+            .unwrap_or_else(|| panic!("column {name} missing; have {:?}", column_names(table)))
+    }
+
     fn column_names(table: &Table) -> Vec<&str> {
         table.columns.iter().map(|(n, _)| n.as_str()).collect()
     }
@@ -1289,6 +1517,1523 @@ mod tests {
         assert_eq!(floats(&table, "X").len(), 1 + 100_000);
     }
 
+    /// G91 makes path axis words incremental from the current position until
+    /// G90; feed and spindle values stay absolute, and the mode applies to
+    /// the whole block it is programmed in.
+    #[test]
+    fn g91_incremental_dimensioning() {
+        let table = interpret(
+            "G1 X10 Y10 F100\n\
+             G91\n\
+             X5 Y5 F200\n\
+             X5 Y-5\n\
+             G90 X0\n\
+             X1 G91\n\
+             X1\n",
+        );
+        assert_eq!(
+            floats(&table, "X"),
+            &[
+                Some(10.0),
+                Some(10.0),
+                Some(15.0),
+                Some(20.0),
+                Some(0.0),
+                Some(1.0),
+                Some(2.0)
+            ]
+        );
+        assert_eq!(
+            floats(&table, "Y"),
+            &[
+                Some(10.0),
+                Some(10.0),
+                Some(15.0),
+                Some(10.0),
+                Some(10.0),
+                Some(10.0),
+                Some(10.0)
+            ]
+        );
+        assert_eq!(floats(&table, "F")[2], Some(200.0));
+
+        // Increments are in workpiece coordinates; a frame is not an increment.
+        let (table, state) = nc_to_table(
+            "TRANS X100\nG1 X10\nG91\nTRANS X50\nX5\n",
+            None,
+            None,
+            None,
+            10000,
+            false,
+            None,
+            false,
+            None,
+        )
+        .expect("program should interpret");
+        assert_eq!(state.translation["X"], 50.0);
+        assert_eq!(floats(&table, "X").last().unwrap(), &Some(65.0));
+    }
+
+    /// AC() and IC() override the modal mode for one word. On the arc centre
+    /// I/J/K, AC() is the absolute centre: emitted as the usual offset
+    /// relative to the block's start point, even when the axis word of the
+    /// same block already moved.
+    #[test]
+    fn ac_and_ic_override_the_dimensioning_mode() {
+        let table = interpret(
+            "G1 X10 Y0 F100\n\
+             X=IC(5)\n\
+             G91 X=AC(2) Y=IC(3)\n\
+             G90 G2 X22 Y3 I=AC(12) J=AC(3)\n\
+             G91 G3 X-10 Y0 I=AC(17) J=IC(0)\n",
+        );
+        assert_eq!(
+            floats(&table, "X"),
+            &[Some(10.0), Some(15.0), Some(2.0), Some(22.0), Some(12.0)]
+        );
+        assert_eq!(floats(&table, "Y")[2], Some(3.0));
+        assert_eq!(floats(&table, "I"), &[None, None, None, Some(10.0), Some(-5.0)]);
+        assert_eq!(floats(&table, "J"), &[None, None, None, Some(0.0), Some(0.0)]);
+
+        let err = nc_to_table("R1=AC(5)\n", None, None, None, 10000, false, None, false, None)
+            .expect_err("AC() on a variable must fail");
+        assert!(err.to_string().contains("AC() is only allowed"), "{err}");
+    }
+
+    /// An arc programmed under G91 flattens from its true start point.
+    #[test]
+    fn g91_arc_flattens_on_the_right_circle() {
+        let (table, _state) = nc_to_table(
+            "G1 X10 Y0 F1000\nG91 G2 X100 Y0 I50 J0\n",
+            None,
+            None,
+            None,
+            10000,
+            false,
+            None,
+            false,
+            Some(0.1),
+        )
+        .expect("program should interpret");
+        let x = floats(&table, "X");
+        let y = floats(&table, "Y");
+        assert_eq!(x.last().unwrap(), &Some(110.0));
+        for (xv, yv) in x.iter().zip(y).filter_map(|(a, b)| a.zip(*b)) {
+            let r = ((xv - 60.0).powi(2) + yv.powi(2)).sqrt();
+            assert!((r - 50.0).abs() < 1e-6, "sample ({xv}, {yv}) off the circle: r={r}");
+        }
+    }
+
+    fn assert_near(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("value present");
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    /// Rotation frames: AROT turns the coordinate system about the current
+    /// frame origin, so a move along the local X axis comes out on machine Y,
+    /// and every coupled machine axis is emitted. ATRANS after a rotation
+    /// shifts along the rotated axes; ROT (absolute) deletes the translation.
+    #[test]
+    fn rotation_frames_rotate_axis_output() {
+        let table = interpret(
+            "G0 X0 Y0 Z0\n\
+             AROT Z=90\n\
+             G1 X10 F100\n\
+             Y5\n\
+             X=IC(2)\n\
+             ATRANS X5\n\
+             X0 Y0\n\
+             ROT Z=-90\n\
+             X1 Y0\n",
+        );
+        let (x, y, z) = (floats(&table, "X"), floats(&table, "Y"), floats(&table, "Z"));
+        let expected = [
+            (0.0, 0.0),
+            (0.0, 10.0),
+            (-5.0, 10.0),
+            (-5.0, 12.0),
+            (0.0, 5.0),
+            (0.0, -1.0),
+        ];
+        assert_eq!(x.len(), expected.len());
+        for (i, (ex, ey)) in expected.into_iter().enumerate() {
+            assert_near(x[i], ex);
+            assert_near(y[i], ey);
+        }
+        // Z is not coupled by a rotation about Z: it is never re-emitted.
+        assert_eq!(z.iter().filter(|v| v.is_some()).count(), expected.len());
+    }
+
+    /// While a rotation or mirror is active a single-axis move restates the
+    /// whole machine position, also the axis it does not couple into: under
+    /// ROT Z=90 a move along X only moves machine Y, yet the row carries
+    /// machine X too. Z, never positioned, stays out.
+    #[test]
+    fn rotated_single_axis_move_restates_the_machine_position() {
+        let (table, _state) = nc_to_table(
+            "G0 X0 Y0\nROT Z=90\nX10\nMIRROR Y0\nY5\n",
+            None,
+            None,
+            None,
+            10000,
+            true,
+            None,
+            false,
+            None,
+        )
+        .expect("program should interpret");
+        let moves: Vec<_> = floats(&table, "X")
+            .iter()
+            .copied()
+            .zip(floats(&table, "Y").iter().copied())
+            .filter(|(x, y)| x.is_some() || y.is_some())
+            .collect();
+        assert_eq!(moves.len(), 3);
+        assert_near(moves[1].0, 0.0);
+        assert_near(moves[1].1, 10.0);
+        assert_eq!(moves[2], (Some(10.0), Some(-5.0)));
+        assert!(!column_names(&table).contains(&"Z"));
+    }
+
+    /// RPL= turns about the applicate of the working plane; ROTS gives the
+    /// plane by solid angles; $AA_IM reads the rotated machine position.
+    #[test]
+    fn plane_and_solid_angle_rotations() {
+        let run = |src: &str| {
+            nc_to_table(src, None, None, None, 10000, false, None, false, None).expect("program should interpret")
+        };
+        // G18: RPL rotates about Y, turning Z onto X.
+        let (table, _) = run("G18\nROT RPL=90\nG0 X0 Y0 Z1\n");
+        assert_near(*floats(&table, "X").last().unwrap(), 1.0);
+        assert_near(*floats(&table, "Z").last().unwrap(), 0.0);
+
+        // A single solid angle is the plain rotation about that axis.
+        let (rots, _) = run("ROTS Y=30\nG0 X10 Y0 Z0\n");
+        let (rot, _) = run("ROT Y=30\nG0 X10 Y0 Z0\n");
+        for axis in ["X", "Y", "Z"] {
+            assert_near(floats(&rots, axis)[0], floats(&rot, axis)[0].unwrap());
+        }
+
+        let (_, state) = run("G0 X0 Y0 Z0\nTRANS X1\nAROT Z=90\nX3\nR1=$AA_IW[X]\nR2=$AA_IM[Y]\n");
+        assert_eq!(state.symbol_table["R1"], 3.0);
+        assert!((state.symbol_table["R2"] - 3.0).abs() < 1e-9);
+
+        for (src, message) in [
+            ("ROT A=10\n", "geometry axes"),
+            ("ROT RPL=10 Z=5\n", "cannot be combined"),
+            ("ROTS Z=10\n", "solid angles"),
+        ] {
+            let err = nc_to_table(src, None, None, None, 10000, false, None, false, None)
+                .expect_err("invalid frame instruction must fail");
+            assert!(err.to_string().contains(message), "{src}: {err}");
+        }
+    }
+
+    /// Arcs in a rotated frame flatten in the rotated working plane, with
+    /// I/J/K taken along the rotated axes.
+    #[test]
+    fn rotated_arc_flattens_in_the_rotated_plane() {
+        let (table, _state) = nc_to_table(
+            "G1 X0 Y0 Z0 F1000\nAROT Z=90\nG2 X20 Y0 I10 J0\n",
+            None,
+            None,
+            None,
+            10000,
+            false,
+            None,
+            false,
+            Some(0.1),
+        )
+        .expect("program should interpret");
+        let (x, y) = (floats(&table, "X"), floats(&table, "Y"));
+        assert!(x.len() > 10, "arc was not flattened: {} row(s)", x.len());
+        assert_eq!((x.last().unwrap(), y.last().unwrap()), (&Some(0.0), &Some(20.0)));
+        for (xv, yv) in x.iter().zip(y).filter_map(|(a, b)| a.zip(*b)) {
+            let r = (xv.powi(2) + (yv - 10.0).powi(2)).sqrt();
+            assert!((r - 10.0).abs() < 1e-6, "sample ({xv}, {yv}) off the circle: r={r}");
+            // Clockwise in the frame: through local (10, 10), machine (-10, 10).
+            assert!(xv <= 1e-9, "sample ({xv}, {yv}) on the wrong side");
+        }
+    }
+
+    /// An arc that is not flattened carries its I/J/K centre offsets in
+    /// machine coordinates under a rotation, like its end point: the centre
+    /// is as far from the start as from the end point. (A frame change moves
+    /// the machine position, so each arc starts from a move in its frame.)
+    #[test]
+    fn rotated_arc_outputs_machine_centre_offsets() {
+        let table = interpret(
+            "G1 X0 Y0 Z0 F100\n\
+             AROT Z=90\n\
+             G2 X20 Y0 I10 J0\n\
+             ROT X=30\n\
+             G1 X20 Y0\n\
+             G3 X20 Y10 I0 J5\n\
+             ROT Y=90 Z=45\n\
+             G1 X20 Z0\n\
+             G18 G2 X30 Z0 K0 I5\n",
+        );
+        let column = |name| floats(&table, name);
+        let (x, y, z) = (column("X"), column("Y"), column("Z"));
+        let centre = ["I", "J", "K"].map(column);
+        let mut position = [0.0; 3];
+        let mut arcs = 0;
+        for row in 0..x.len() {
+            let end = [x[row], y[row], z[row]];
+            let end: [f64; 3] = std::array::from_fn(|i| end[i].unwrap_or(position[i]));
+            if centre.iter().any(|offsets| offsets[row].is_some()) {
+                let centre: [f64; 3] = std::array::from_fn(|i| position[i] + centre[i][row].unwrap_or(0.0));
+                let distance = |p: [f64; 3]| (0..3).map(|i| (p[i] - centre[i]).powi(2)).sum::<f64>().sqrt();
+                let (from_start, from_end) = (distance(position), distance(end));
+                assert!(
+                    (from_start - from_end).abs() < 1e-9,
+                    "row {row}: {from_start} != {from_end}"
+                );
+                arcs += 1;
+            }
+            position = end;
+        }
+        assert_eq!(arcs, 3);
+        // G2 X0 Y20 I0 J10, not I10 J0.
+        assert_near(centre[0][1], 0.0);
+        assert_near(centre[1][1], 10.0);
+    }
+
+    /// SCALE/ASCALE multiply the local coordinates, increments and later
+    /// ATRANS offsets included; the components apply in the fixed order
+    /// translation, rotation, scale whatever order they were programmed in.
+    #[test]
+    fn scale_frames_scale_axis_output() {
+        let table = interpret(
+            "G0 X1 Y1 E1\n\
+             SCALE X2 Y3 E2\n\
+             X1 Y1 E1\n\
+             X=IC(1)\n\
+             ATRANS X1\n\
+             X0\n\
+             ROT Z=90\n\
+             ASCALE X2\n\
+             X1 Y0\n",
+        );
+        let (x, y) = (floats(&table, "X"), floats(&table, "Y"));
+        assert_eq!(&x[..4], &[Some(1.0), Some(2.0), Some(4.0), Some(2.0)]);
+        assert_eq!(&y[..2], &[Some(1.0), Some(3.0)]);
+        assert_eq!(floats(&table, "E")[1], Some(2.0));
+        assert_near(x[4], 0.0);
+        assert_near(y[4], 2.0);
+
+        let err = nc_to_table("SCALE X0\n", None, None, None, 10000, false, None, false, None)
+            .expect_err("a zero scale factor must fail");
+        assert!(err.to_string().contains("must not be 0"), "{err}");
+    }
+
+    /// MIRROR reverses the sign of an axis and, mirroring one axis of the
+    /// working plane, the arc direction: G2 is output as G3, also for the
+    /// modal motion at the frame change. AMIRROR of a mirrored axis undoes it.
+    #[test]
+    fn mirror_frames_flip_axes_and_arc_direction() {
+        let table = interpret(
+            "G1 X10 Y5 F100\n\
+             MIRROR X0\n\
+             X10\n\
+             G2 X-10 Y5 I-10 J0\n\
+             AMIRROR X0\n\
+             X3 Y5 I2 J0\n",
+        );
+        assert_eq!(
+            floats(&table, "X"),
+            &[Some(10.0), Some(-10.0), Some(10.0), Some(10.0), Some(3.0)]
+        );
+        let motion: Vec<_> = strings(&table, "gg01_motion").iter().map(|m| m.as_deref()).collect();
+        assert_eq!(motion, [Some("G1"), Some("G1"), Some("G3"), Some("G2"), Some("G2")]);
+        // Mirroring both plane axes keeps the direction.
+        let table = interpret("G1 X0 Y0 F100\nMIRROR X0 Y0\nG2 X10 Y0 I5 J0\n");
+        assert_eq!(strings(&table, "gg01_motion").last().unwrap().as_deref(), Some("G2"));
+    }
+
+    /// Arcs under a mirrored or scaled frame flatten on the machine circle.
+    #[test]
+    fn mirrored_and_scaled_arcs_flatten_on_the_machine_circle() {
+        let run = |src: &str| {
+            nc_to_table(src, None, None, None, 10000, false, None, false, Some(0.01))
+                .expect("program should interpret")
+                .0
+        };
+        // Mirrored: local centre (10, 0) is machine (-10, 0); clockwise in the
+        // frame passes local (10, 10), machine (-10, 10).
+        let table = run("G1 X0 Y0 F1000\nMIRROR X0\nG2 X20 Y0 I10 J0\n");
+        let (x, y) = (floats(&table, "X"), floats(&table, "Y"));
+        assert!(x.len() > 10, "arc was not flattened: {} row(s)", x.len());
+        assert_eq!(x.last().unwrap(), &Some(-20.0));
+        for (xv, yv) in x.iter().zip(y).filter_map(|(a, b)| a.zip(*b)) {
+            assert!(((xv + 10.0).hypot(yv) - 10.0).abs() < 1e-6, "({xv}, {yv})");
+            assert!(yv >= -1e-9, "sample ({xv}, {yv}) on the wrong side");
+        }
+        // Scaled: radius 10 becomes 20, and the tolerance holds in machine
+        // space (more samples than the unscaled arc).
+        let scaled = run("G1 X0 Y0 F1000\nSCALE X2 Y2\nG2 X20 Y0 I10 J0\n");
+        let plain = run("G1 X0 Y0 F1000\nG2 X20 Y0 I10 J0\n");
+        let (x, y) = (floats(&scaled, "X"), floats(&scaled, "Y"));
+        assert_eq!(x.last().unwrap(), &Some(40.0));
+        for (xv, yv) in x.iter().zip(y).filter_map(|(a, b)| a.zip(*b)) {
+            assert!(((xv - 20.0).hypot(yv) - 20.0).abs() < 1e-6, "({xv}, {yv})");
+        }
+        assert!(x.len() > floats(&plain, "X").len());
+        // The scaled CR comes back to the programmed radius in the frame.
+        let table = run("G1 X0 Y0 F1000\nSCALE X2 Y2\nG3 X20 Y0 CR=10\n");
+        let (x, y) = (floats(&table, "X"), floats(&table, "Y"));
+        assert!(x.len() > 10, "arc was not flattened: {} row(s)", x.len());
+        for (xv, yv) in x.iter().zip(y).filter_map(|(a, b)| a.zip(*b)) {
+            assert!(((xv - 20.0).hypot(yv) - 20.0).abs() < 1e-6, "({xv}, {yv})");
+        }
+    }
+
+    /// G54..G57/G505..G599 select a settable frame from the `$P_UIFR` table
+    /// (written with CTRANS, typically in the initial-state file): its offset
+    /// shifts the machine output ahead of the programmable frame until G500.
+    #[test]
+    fn work_offsets_shift_axis_output() {
+        let run = |src: &str| {
+            nc_to_table(
+                src,
+                Some("$P_UIFR[1]=CTRANS(X,10,Y,20)\n$P_UIFR[5]=CTRANS(Z,-5)\n"),
+                None,
+                None,
+                10000,
+                false,
+                None,
+                false,
+                None,
+            )
+            .expect("program should interpret")
+        };
+        let (table, state) = run("G0 X1 Y1\nG54 X1\nY1\nTRANS X1\nX0\nG505 Z0\nG500 X0 Y0\n");
+        assert_eq!(
+            floats(&table, "X"),
+            &[Some(1.0), Some(11.0), Some(11.0), Some(11.0), Some(11.0), Some(1.0)]
+        );
+        assert_eq!(
+            floats(&table, "Y"),
+            &[Some(1.0), Some(1.0), Some(21.0), Some(21.0), Some(21.0), Some(0.0)]
+        );
+        assert_eq!(floats(&table, "Z")[4], Some(-5.0));
+        assert_eq!(state.work_offset(), None);
+
+        // The final state reports the active offset; $AA_IM includes it.
+        let (_, state) = run("G56\nG54 X2\nR1=$AA_IM[X]\n");
+        assert_eq!(state.symbol_table["R1"], 12.0);
+        let final_state = state.final_state();
+        assert_eq!(final_state.work_offset.as_deref(), Some("G54"));
+        assert_eq!(final_state.work_offset_translation["Y"], 20.0);
+
+        for (src, message) in [
+            ("$P_UIFR[0]=CTRANS(X,1)\n", "settable frame number"),
+            ("$P_UIFR[1]=5\n", "only the translation form"),
+            ("$P_UIFR[1]=CTRANS(Q,1)\n", "'Q' is not an axis"),
+            ("$P_UIFR[1]=CTRANS(X,1,Y)\n", "pairs"),
+        ] {
+            let err = nc_to_table(src, None, None, None, 10000, false, None, false, None)
+                .expect_err("invalid settable frame must fail");
+            assert!(err.to_string().contains(message), "{src}: {err}");
+        }
+    }
+
+    /// G53/G153/SUPA suppress the work offset and the programmable frame in
+    /// their block only: the axis values are machine coordinates (increments
+    /// too), and the local position follows so later blocks continue from
+    /// there.
+    #[test]
+    fn frame_suppression_programs_machine_coordinates() {
+        let run = |src: &str| {
+            nc_to_table(
+                src,
+                Some("$P_UIFR[1]=CTRANS(Z,100)\n"),
+                None,
+                None,
+                10000,
+                true,
+                None,
+                false,
+                None,
+            )
+            .expect("program should interpret")
+        };
+        let emitted = |table: &Table, name: &str| floats(table, name).iter().flatten().copied().collect::<Vec<_>>();
+        let (table, state) = run("G54\nTRANS X5 Z10\nG0 X1 Z1\nG53 Z0\nX2\nSUPA G0 Z=IC(-5)\nG153 X0\nZ1\n");
+        assert_eq!(emitted(&table, "X"), [6.0, 7.0, 0.0]);
+        assert_eq!(emitted(&table, "Z"), [111.0, 0.0, -5.0, 111.0]);
+        assert_eq!(state.get_axis_local("X"), Some(-5.0));
+
+        // Rotated: only the programmed machine axis moves; the local
+        // position turns back through the frame.
+        let (table, state) = run("G0 X10 Y0\nROT Z=90\nG53 X5\nR1=$AA_IW[X]\nR2=$AA_IW[Y]\n");
+        let (x, y) = (floats(&table, "X"), floats(&table, "Y"));
+        assert_eq!((x.last().unwrap(), y.last().unwrap()), (&Some(5.0), &None));
+        assert_near(Some(state.symbol_table["R1"]), 10.0);
+        assert_near(Some(state.symbol_table["R2"]), -5.0);
+    }
+
+    /// A fresh directory holding the given subprogram files.
+    fn subprogram_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nc-subprograms-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        for (file, text) in files {
+            std::fs::write(dir.join(file), text).expect("write subprogram");
+        }
+        dir
+    }
+
+    fn interpret_with_subprograms(input: &str, dir: &std::path::Path) -> Result<(Table, state::State), ParsingError> {
+        nc_to_table_with_options(
+            input,
+            None,
+            None,
+            None,
+            10000,
+            true,
+            None,
+            false,
+            None,
+            InterpreterOptions::default().with_subprogram_dirs(vec![dir.to_path_buf()]),
+        )
+    }
+
+    /// `L100` and `NAME(args)` run the program file from the search path,
+    /// after the rest of the calling block; value parameters are copies, VAR
+    /// parameters write back, and the callee's locals do not leak.
+    #[test]
+    fn subprogram_calls_pass_parameters_and_return() {
+        let dir = subprogram_dir(
+            "params",
+            &[
+                ("L100.SPF", "G1 X=R1\nM17\nX999\n"),
+                (
+                    "contour.spf",
+                    "; contour\nPROC CONTOUR(REAL DEPTH, VAR REAL TOTAL, REAL LEVEL)\nDEF REAL TMP=DEPTH*2\nG1 Y=TMP Z=LEVEL\nTOTAL=TOTAL+DEPTH\nDEPTH=0\nRET\n",
+                ),
+            ],
+        );
+        let (table, state) = interpret_with_subprograms("R1=7\nG0 X1 L100\nR2=5\nCONTOUR(3, R2)\nX2\n", &dir)
+            .expect("program should interpret");
+        let emitted = |name: &str| floats(&table, name).iter().flatten().copied().collect::<Vec<_>>();
+        assert_eq!(emitted("X"), [1.0, 7.0, 2.0]);
+        assert_eq!(emitted("Y"), [6.0]);
+        // The omitted LEVEL is 0.
+        assert_eq!(emitted("Z"), [0.0]);
+        assert_eq!(state.symbol_table["R2"], 8.0);
+        assert!(!state.symbol_table.contains_key("DEPTH"));
+        assert!(!state.symbol_table.contains_key("TMP"));
+    }
+
+    /// `P<n>` runs the called program `n` times, each pass a whole call: a
+    /// VAR parameter carries over from one pass to the next.
+    #[test]
+    fn subprogram_pass_count_repeats_the_call() {
+        let dir = subprogram_dir(
+            "passes",
+            &[
+                ("L100.SPF", "G91 G1 X1\nM17\n"),
+                ("COUNT.SPF", "PROC COUNT(VAR REAL TIMES)\nTIMES=TIMES+1\nM17\n"),
+            ],
+        );
+        let (table, state) = interpret_with_subprograms("G90 G0 X0\nL100 P3\nG90 X10\nR1=0\nCOUNT(R1) P4\n", &dir)
+            .expect("program should interpret");
+        assert_eq!(
+            floats(&table, "X").iter().flatten().copied().collect::<Vec<_>>(),
+            [0.0, 1.0, 2.0, 3.0, 10.0]
+        );
+        let calls: Vec<_> = strings(&table, "non_returning_function_call")
+            .iter()
+            .flatten()
+            .cloned()
+            .collect();
+        assert_eq!(calls, ["L100", "COUNT(R1)"]);
+        assert_eq!(state.symbol_table["R1"], 4.0);
+
+        let err = interpret_with_subprograms("L100 P0\n", &dir).expect_err("P0 must fail");
+        assert!(err.to_string().contains("pass count P0"), "{err}");
+    }
+
+    /// A parameter shadows the caller's variable of the same name for the
+    /// call only.
+    #[test]
+    fn subprogram_parameter_shadows_caller_variable() {
+        let dir = subprogram_dir("shadow", &[("SUB.SPF", "PROC SUB(REAL LEN)\nG1 X=LEN\nLEN=0\nM17\n")]);
+        let (_, state) =
+            interpret_with_subprograms("DEF REAL LEN=50\nSUB(4)\nR1=LEN\n", &dir).expect("program should interpret");
+        assert_eq!(state.symbol_table["R1"], 50.0);
+        assert_eq!(state.get_axis_local("X"), Some(4.0));
+    }
+
+    /// `SAVE` restores the caller's modal G functions and frame on return,
+    /// restating the changed G words on the calling block's row.
+    #[test]
+    fn subprogram_save_restores_modes_and_frame() {
+        let dir = subprogram_dir(
+            "save",
+            &[
+                ("SAVED.SPF", "PROC SAVED SAVE\nG91 G1 X1\nTRANS X100\nM17\n"),
+                ("PLAIN.SPF", "PROC PLAIN\nG91\nM17\n"),
+            ],
+        );
+        let (table, state) =
+            interpret_with_subprograms("G90 G0 X10\nSAVED\nX20\n", &dir).expect("program should interpret");
+        assert_eq!(
+            floats(&table, "X").iter().flatten().copied().collect::<Vec<_>>(),
+            [10.0, 11.0, 20.0]
+        );
+        assert_eq!(state.get_axis_local("X"), Some(20.0));
+        let modes: Vec<_> = strings(&table, "gg14_wp_measure_mode")
+            .iter()
+            .flatten()
+            .cloned()
+            .collect();
+        assert_eq!(modes, ["G90", "G91", "G90"]);
+
+        // Without SAVE the modes stay as the subprogram left them.
+        let (_, state) = interpret_with_subprograms("G90 G0 X10\nPLAIN\nX5\n", &dir).expect("program should interpret");
+        assert_eq!(state.get_axis_local("X"), Some(15.0));
+    }
+
+    /// `CALL "NAME"` takes the program name from a string; a missing program
+    /// raises the subprogram-not-found alarm, as does a missing `L` program.
+    #[test]
+    fn subprogram_lookup_errors() {
+        let dir = subprogram_dir(
+            "lookup",
+            &[
+                ("HOLE.SPF", "G1 Z-1\nM17\n"),
+                ("DEEP.SPF", "DEEP\n"),
+                ("TWO.SPF", "PROC TWO(REAL A)\nM17\n"),
+                ("OUTER.SPF", "PROC OUTER(REAL A)\nG1 X=A\nBROKEN\nM17\n"),
+                ("BROKEN.SPF", "G1 Y1\nX=UNDEF1\nM17\n"),
+            ],
+        );
+        let (table, _) = interpret_with_subprograms("DEF STRING[8] P=\"HO\"\nCALL P << \"LE\"\n", &dir)
+            .expect("program should interpret");
+        assert_eq!(floats(&table, "Z").iter().flatten().collect::<Vec<_>>(), [&-1.0]);
+
+        for (src, kind) in [
+            ("CALL \"NOPE\"\n", "subprogram_not_found"),
+            ("L200\n", "subprogram_not_found"),
+            ("TWO(1, 2)\n", "invalid_function_arity"),
+        ] {
+            let err = interpret_with_subprograms(src, &dir).expect_err("call must fail");
+            assert_eq!(err.kind(), kind, "{src}: {err}");
+        }
+        let err = interpret_with_subprograms("DEEP\n", &dir).expect_err("recursion must fail");
+        assert!(err.to_string().contains("nesting depth"), "{err}");
+
+        // An error inside a subprogram names the program and the calling
+        // block, for every level of the call chain.
+        let err = interpret_with_subprograms("G0 X0\nOUTER(1)\n", &dir).expect_err("the callee must fail");
+        let message = err.to_string();
+        assert_eq!(err.location().map(|l| l.line), Some(2), "{message}");
+        assert!(message.contains("Line: X=UNDEF1"), "{message}");
+        assert!(
+            message.contains(
+                "In subprogram BROKEN, called on line 3: BROKEN\nIn subprogram OUTER, called on line 2: OUTER(1)"
+            ),
+            "{message}"
+        );
+
+        // Without a search path unknown names stay uninterpreted calls.
+        let table = interpret("VENDOR_CYCLE(1)\n");
+        assert!(column_names(&table).contains(&"non_returning_function_call"));
+    }
+
+    /// `MCALL` repeats the call after every block that positions an axis,
+    /// with the arguments of the `MCALL` block, until a bare `MCALL`; the
+    /// called program's own moves do not trigger it again.
+    #[test]
+    fn modal_call_repeats_after_positioning_blocks() {
+        let dir = subprogram_dir(
+            "mcall",
+            &[(
+                "DRILL.SPF",
+                "PROC DRILL(REAL DEPTH, VAR REAL COUNT)\nG1 Z=-DEPTH\nG0 Z2\nCOUNT=COUNT+1\nM17\n",
+            )],
+        );
+        let (table, state) = interpret_with_subprograms(
+            "R1=0\nR2=3\nG0 X0 Y0 Z2\nMCALL DRILL(R2, R1)\nR2=9\nX10\nF100\nY10 ; hole 2\nMCALL\nX0 Y0\n",
+            &dir,
+        )
+        .expect("program should interpret");
+        assert_eq!(state.symbol_table["R1"], 2.0);
+        let z: Vec<f64> = floats(&table, "Z").iter().flatten().copied().collect();
+        assert_eq!(z, [2.0, -3.0, 2.0, -3.0, 2.0]);
+        // The subprogram's rows keep their own line numbers.
+        let (tx, rx) = std::sync::mpsc::sync_channel(64);
+        let (events_tx, _events_rx) = std::sync::mpsc::channel();
+        nc_to_batch_stream_with_options(
+            "R1=0\nG0 X0 Y0 Z2\nMCALL DRILL(1, R1)\nX10\n",
+            None,
+            None,
+            None,
+            10000,
+            false,
+            None,
+            false,
+            None,
+            1_000_000,
+            true,
+            InterpreterOptions::default().with_subprogram_dirs(vec![dir]),
+            tx,
+            false,
+            events_tx,
+        )
+        .expect("program should interpret");
+        let table = rx.into_iter().next().expect("one batch for a small program");
+        assert_eq!(ints(&table, "line_no"), &[Some(2), Some(4), Some(2), Some(3), Some(5)]);
+    }
+
+    /// The built-in cycles need no search path: `MCALL CYCLE81(...)` drills
+    /// at every position, each expansion's rows carrying the line number of
+    /// the block that triggered it, and the caller's G91 comes back after it.
+    #[test]
+    fn builtin_cycles_expand_at_the_calling_block() {
+        let (tx, rx) = std::sync::mpsc::sync_channel(64);
+        let (events_tx, _events_rx) = std::sync::mpsc::channel();
+        nc_to_batch_stream_with_line_numbers(
+            "G0 X0 Y0 Z20\nG91\nMCALL CYCLE81(10, 0, 2, 0, 5)\nX10\nY10\nMCALL\nX5\n",
+            None,
+            None,
+            None,
+            10000,
+            true,
+            None,
+            false,
+            None,
+            1_000_000,
+            true,
+            tx,
+            false,
+            events_tx,
+        )
+        .expect("program should interpret");
+        let table = rx.into_iter().next().expect("one batch for a small program");
+        let lines: Vec<_> = ints(&table, "line_no").iter().flatten().copied().collect();
+        assert_eq!(lines, [1, 2, 4, 4, 4, 4, 4, 5, 5, 5, 5, 5, 7]);
+        let z: Vec<f64> = floats(&table, "Z").iter().flatten().copied().collect();
+        assert_eq!(z, [20.0, 2.0, -5.0, 10.0, 2.0, -5.0, 10.0]);
+        let x: Vec<f64> = floats(&table, "X").iter().flatten().copied().collect();
+        assert_eq!(x, [0.0, 10.0, 15.0]);
+    }
+
+    /// A registered cycle replaces the built-in one of that name; bad
+    /// arguments of a built-in cycle are reported at the calling block.
+    #[test]
+    fn registered_cycles_take_precedence() {
+        let cycles = HashMap::from([(
+            "cycle81".to_string(),
+            "PROC CYCLE81(REAL RTP, REAL RFP, REAL SDIS, REAL DP)\nG1 Z=DP\nG0 Z=RTP\nM17\n".to_string(),
+        )]);
+        let (table, _) = nc_to_table_with_options(
+            "G0 X0 Z20\nCYCLE81(10, 0, 2, -3)\n",
+            None,
+            None,
+            None,
+            10000,
+            true,
+            None,
+            false,
+            None,
+            InterpreterOptions::default().with_cycles(cycles),
+        )
+        .expect("program should interpret");
+        assert_eq!(
+            floats(&table, "Z").iter().flatten().copied().collect::<Vec<_>>(),
+            [20.0, -3.0, 10.0]
+        );
+
+        let err = nc_to_table(
+            "G0 X0\nN20 CYCLE84(10, 0, 2, -3)\n",
+            None,
+            None,
+            None,
+            10000,
+            false,
+            None,
+            false,
+            None,
+        )
+        .expect_err("tapping needs a pitch");
+        assert!(err.to_string().contains("CYCLE84: neither the thread pitch"), "{err}");
+        assert!(err.to_string().contains("N20 CYCLE84"), "{err}");
+    }
+
+    /// `DEFINE ... AS` macros expand in the blocks that use them, macros
+    /// inside macros included; the block keeps its number and comment.
+    #[test]
+    fn macros_expand_in_place() {
+        let program = "DEFINE M_SPIN AS M3 S1000\nDEFINE LIFT AS G0 Z=IC(5)\nDEFINE UP AS LIFT M_SPIN ; both\nG1 X0 Z0 F100\nX10 m_spin\nLIFT\nN40 UP ; again\nX20\n";
+        let (table, state) =
+            nc_to_table(program, None, None, None, 10000, true, None, false, None).expect("program should interpret");
+        // The comment of the third DEFINE is not part of the macro text.
+        assert_eq!(strings(&table, "comment")[0].as_deref(), Some("; both"));
+        assert_eq!(
+            floats(&table, "S"),
+            &[None, None, Some(1000.0), None, Some(1000.0), None]
+        );
+        assert_eq!(
+            floats(&table, "Z"),
+            &[None, Some(0.0), None, Some(5.0), Some(10.0), None]
+        );
+        assert_eq!(
+            strings(&table, "gg01_motion"),
+            &[
+                None,
+                Some("G1".to_string()),
+                None,
+                Some("G0".to_string()),
+                Some("G0".to_string()),
+                None
+            ]
+        );
+        assert_eq!(strings(&table, "comment")[4].as_deref(), Some("; again"));
+        assert_eq!(state.axes["X"], 20.0);
+    }
+
+    /// A macro that reaches itself is rejected where it is defined.
+    #[test]
+    fn recursive_macros_are_errors() {
+        let err = nc_to_table(
+            "DEFINE GO_UP AS Z1 GO_ON\nDEFINE GO_ON AS X1 GO_UP\n",
+            None,
+            None,
+            None,
+            10000,
+            false,
+            None,
+            false,
+            None,
+        )
+        .expect_err("recursive macro");
+        assert_eq!(err.kind(), "recursive_macro");
+        assert!(err.to_string().contains("GO_ON -> GO_UP -> GO_ON"), "{err}");
+        assert_eq!(err.location().map(|l| l.line), Some(2));
+    }
+
+    /// REPEAT/REPEATB run a labelled section and return to the block after
+    /// the repetition; sections nest, and the structured path (forced by the
+    /// WHILE) agrees with the line-by-line one.
+    #[test]
+    fn section_repetition_returns_to_the_caller() {
+        let program = "G1 F100\nLAB1: X1\nLAB2: X2\nREPEAT LAB1 P=2\nREPEATB LAB1 P=2\nSTART: X3\nREPEAT LAB1 LAB2\nENDLABEL: X4\nREPEAT START ENDLABEL\nN90 X5\n";
+        let expected = [1., 2., 1., 2., 1., 2., 1., 1., 3., 1., 2., 4., 3., 1., 2., 4., 5.];
+        for suffix in ["", "WHILE FALSE\nENDWHILE\n"] {
+            let table = interpret(&format!("{program}{suffix}"));
+            let x: Vec<f64> = floats(&table, "X").iter().flatten().copied().collect();
+            assert_eq!(x, expected, "{suffix:?}");
+        }
+    }
+
+    #[test]
+    fn section_repetition_errors() {
+        let error = |program: &str| {
+            nc_to_table(program, None, None, None, 10000, false, None, false, None)
+                .expect_err("invalid repetition")
+                .to_string()
+        };
+        assert!(error("LAB1: X1\nREPEAT LAB1 LAB2\nLAB2: X2\n").contains("contains the repeating block"));
+        assert!(error("LAB1: X1\nREPEAT LAB1 P=0\n").contains("P=0 is not a repetition count"));
+        assert!(error("X1\nREPEAT LAB9\nLAB9: X2\n").contains("toward the beginning of the program"));
+        assert!(error("LAB1: X1\nREPEATB LAB1 P=20000\n").contains("Loop limit"));
+    }
+
+    /// `EXECSTRING` runs a block assembled at run time in place of the
+    /// calling one, jumps included.
+    #[test]
+    fn execstring_runs_generated_blocks() {
+        let program = "DEF REAL POS = 12.5\nDEF STRING[40] CMD\nCMD = \"G1 X\" << POS << \" F\" << 100\nEXECSTRING(CMD)\nexecstring(\"Y\" << POS * 2)\nEXECSTRING(\"GOTOF SKIP\")\nX99\nSKIP: EXECSTRING(\"X1\")\n";
+        let (table, state) =
+            nc_to_table(program, None, None, None, 10000, true, None, false, None).expect("program should interpret");
+        assert_eq!(floats(&table, "X"), &[Some(12.5), None, Some(1.0)]);
+        assert_eq!(floats(&table, "Y"), &[None, Some(25.0), None]);
+        assert_eq!(floats(&table, "F")[0], Some(100.0));
+        assert_eq!(state.axes["X"], 1.0);
+        // Motion tables are not modelled, so EXECTAB must not run its text.
+        let err = nc_to_table(
+            "G1 X0\nEXECTAB(\"X1\")\n",
+            None,
+            None,
+            None,
+            10000,
+            false,
+            None,
+            false,
+            None,
+        )
+        .expect_err("EXECTAB is unsupported");
+        assert_eq!(err.kind(), "unsupported_statement", "{err}");
+    }
+
+    /// Errors in a generated block name both the calling line and the text.
+    #[test]
+    fn execstring_errors_show_the_generated_block() {
+        for (program, generated) in [
+            ("G1 X0\nEXECSTRING(\"G1 Q=\")\n", "'G1 Q='"),
+            (
+                "G1 X0\nEXECSTRING(\"X=\" << \"UNDEF1\")\n",
+                "Generated by EXECSTRING: X=UNDEF1",
+            ),
+        ] {
+            let err = nc_to_table(program, None, None, None, 10000, false, None, false, None)
+                .expect_err("the generated block must fail");
+            let message = err.to_string();
+            assert_eq!(err.location().map(|l| l.line), Some(2), "{message}");
+            assert!(message.contains("Line: EXECSTRING("), "{message}");
+            assert!(message.contains(generated), "{message}");
+        }
+        let err = nc_to_table(
+            "DEF STRING[40] AGAIN = \"EXECSTRING(AGAIN)\"\nEXECSTRING(AGAIN)\n",
+            None,
+            None,
+            None,
+            10000,
+            false,
+            None,
+            false,
+            None,
+        )
+        .expect_err("self-executing string must fail");
+        assert!(err.to_string().contains("nest deeper"), "{err}");
+    }
+
+    /// AP/RP program the end point in polar coordinates about the pole that
+    /// G110/G111/G112 define; the output stays Cartesian, and G2/G3 circle
+    /// around the pole.
+    #[test]
+    fn polar_coordinates_about_the_pole() {
+        let program = "G17 G0 X0 Y0\nG111 X50 Y50\nG1 AP=0 RP=20 F100\nAP=90\nG91 AP=90\nG90 G3 AP=270\nG112 AP=90 RP=10\nG1 AP=0 RP=5\nG110 X1\nRP=2\nG18 G111 Z10 X0\nAP=90 RP=4\n";
+        let (table, _state) =
+            nc_to_table(program, None, None, None, 10000, true, None, false, None).expect("program should interpret");
+        let moves: Vec<(f64, f64)> = floats(&table, "X")
+            .iter()
+            .zip(floats(&table, "Y"))
+            .filter_map(|(x, y)| Some(((*x)?, (*y)?)))
+            .collect();
+        let expected = [
+            (0.0, 0.0),
+            (70.0, 50.0),
+            (50.0, 70.0),
+            (30.0, 50.0),
+            (50.0, 30.0),
+            (55.0, 60.0),
+            (58.0, 60.0),
+        ];
+        assert_eq!(moves.len(), expected.len(), "{moves:?}");
+        for ((x, y), (ex, ey)) in moves.iter().zip(expected) {
+            assert!((x - ex).abs() < 1e-9 && (y - ey).abs() < 1e-9, "{moves:?}");
+        }
+        // The arc row carries its centre (the pole) relative to the start.
+        let arc = strings(&table, "gg01_motion")
+            .iter()
+            .position(|m| m.as_deref() == Some("G3"))
+            .unwrap();
+        assert_eq!(
+            (floats(&table, "I")[arc], floats(&table, "J")[arc]),
+            (Some(20.0), Some(0.0))
+        );
+        // G18: the angle runs from Z towards X.
+        let last = table.height() - 1;
+        assert!((floats(&table, "Z")[last].unwrap() - 10.0).abs() < 1e-9);
+        assert!((floats(&table, "X")[last].unwrap() - 4.0).abs() < 1e-9);
+    }
+
+    /// The polar end point sets both plane axes; programming one of them as
+    /// well is an error.
+    #[test]
+    fn polar_end_point_excludes_plane_axes() {
+        let err = nc_to_table(
+            "G111 X0 Y0\nG1 X10 AP=20 RP=3 Z1\n",
+            None,
+            None,
+            None,
+            10000,
+            false,
+            None,
+            false,
+            None,
+        )
+        .expect_err("X with AP/RP must fail");
+        assert_eq!(err.location().map(|l| l.line), Some(2), "{err}");
+        assert!(
+            err.to_string().contains("cannot be programmed together with AP/RP"),
+            "{err}"
+        );
+    }
+
+    /// `AR=` arcs resolve to the usual end point and I/J centre offsets,
+    /// from the end point or from the centre.
+    #[test]
+    fn opening_angle_arcs_resolve_centre_and_end_point() {
+        let program =
+            "G17 G1 X0 Y0 F100\nG3 X10 Y0 AR=90\nG2 X20 Y0 AR=270\nG3 I5 J0 AR=180\nG2 I=AC(15) J=AC(0) AR=90\n";
+        let (table, state) =
+            nc_to_table(program, None, None, None, 10000, true, None, false, None).expect("program should interpret");
+        let near = |a: &[Option<f64>], b: &[Option<f64>]| {
+            a.len() == b.len()
+                && a.iter().zip(b).all(|(a, b)| match (a, b) {
+                    (Some(a), Some(b)) => (a - b).abs() < 1e-9,
+                    (a, b) => a.is_none() && b.is_none(),
+                })
+        };
+        let (i, j) = (floats(&table, "I"), floats(&table, "J"));
+        assert!(near(i, &[None, Some(5.0), Some(5.0), Some(5.0), Some(-15.0)]), "{i:?}");
+        assert!(near(j, &[None, Some(5.0), Some(5.0), Some(0.0), Some(0.0)]), "{j:?}");
+        assert!(near(
+            floats(&table, "X"),
+            &[Some(0.0), Some(10.0), Some(20.0), Some(30.0), Some(15.0)]
+        ));
+        assert!((state.axes["Y"] + 15.0).abs() < 1e-9);
+        assert!(!column_names(&table).contains(&"AR"));
+
+        // A centre on the chord is at 0, not at a round-off -0.000, turned
+        // by a frame or not.
+        let table = interpret("G1 X0 Y0 F100\nG2 X10 Y0 AR=180\nAROT Z=90\nG1 X0 Y0\nG2 X10 Y0 AR=180\n");
+        let (i, j) = (floats(&table, "I"), floats(&table, "J"));
+        assert_eq!((i[1], j[1]), (Some(5.0), Some(0.0)));
+        assert_eq!((i[3], j[3]), (Some(0.0), Some(5.0)));
+        assert!(j[1].unwrap().is_sign_positive() && i[3].unwrap().is_sign_positive());
+    }
+
+    /// An opening angle needs G2/G3 and exactly one of end point and centre.
+    #[test]
+    fn opening_angle_arc_errors() {
+        for (program, message) in [
+            ("G1 X0 Y0\nG1 X10 AR=90\n", "only allowed with G2 or G3"),
+            ("G1 X0 Y0\nG2 X10 I5 AR=90\n", "not both"),
+            ("G1 X0 Y0\nG2 AR=90\n", "needs the end point or the centre"),
+            ("G1 X0 Y0\nG2 X10 AR=360\n", "between 0 and 360"),
+            ("G1 X0 Y0\nG2 X0 Y0 AR=90\n", "distinct start and end points"),
+        ] {
+            let err = nc_to_table(program, None, None, None, 10000, false, None, false, None)
+                .expect_err("invalid AR arc must fail");
+            assert_eq!(err.location().map(|l| l.line), Some(2), "{err}");
+            assert!(err.to_string().contains(message), "{program}: {err}");
+        }
+    }
+
+    /// The CIP intermediate point follows G90/G91 and AC/IC like an end
+    /// point, defaults to the start point and is output in machine
+    /// coordinates.
+    #[test]
+    fn cip_intermediate_point_in_machine_coordinates() {
+        let program = "G1 X0 Y0 Z5 F100\nCIP X20 Y0 I1=10 J1=5\nG91 CIP X10 I1=5 J1=IC(-5) K1=AC(0)\nG90\nTRANS X100\nCIP X20 Y0 I1=10 J1=5\n";
+        let (table, _) =
+            nc_to_table(program, None, None, None, 10000, true, None, false, None).expect("program should interpret");
+        assert_eq!(floats(&table, "I1"), &[None, Some(10.0), Some(25.0), None, Some(110.0)]);
+        assert_eq!(floats(&table, "J1"), &[None, Some(5.0), Some(-5.0), None, Some(5.0)]);
+        assert_eq!(floats(&table, "K1"), &[None, Some(5.0), Some(0.0), None, Some(5.0)]);
+    }
+
+    /// PO[] resolves the block's polynomials: the linear terms reach the end
+    /// point, and the coefficients are machine coordinates under the frame.
+    #[test]
+    fn poly_coefficients_resolve_in_machine_coordinates() {
+        let program = "G1 X10 Y0 F100\nPOLY PO[X]=(0,-10) PO[Y]=(10) PO[]=(2,1)\nG91 PO[X]=(20, 1, 2) PL=2\nG90\nROT RPL=90\nPO[Y]=(0, 5)\n";
+        let (table, _) =
+            nc_to_table(program, None, None, None, 10000, true, None, false, None).expect("program should interpret");
+        assert_eq!(
+            floats(&table, "X"),
+            &[Some(10.0), Some(0.0), Some(20.0), None, Some(0.0)]
+        );
+        // Under the rotation the local Y polynomial moves machine X.
+        assert_eq!(
+            floats(&table, "PO_X_1"),
+            &[None, Some(0.0), Some(0.0), None, Some(15.0)]
+        );
+        assert_eq!(
+            floats(&table, "PO_X_2"),
+            &[None, Some(-10.0), Some(1.0), None, Some(-5.0)]
+        );
+        assert_eq!(floats(&table, "PO_Y_1"), &[None, Some(20.0), None, None, None]);
+        assert_eq!(floats(&table, "PO_2"), &[None, Some(1.0), None, None, None]);
+        // The denominator's linear term makes it end at 2.
+        assert_eq!(floats(&table, "PO_1"), &[None, Some(0.0), None, None, None]);
+        assert!(!column_names(&table).contains(&"PO_Z_1"));
+
+        for (program, message) in [
+            ("G1 X0\nPO[X]=(10)\n", "only allowed with POLY"),
+            ("POLY X0\nPO[Q]=(10)\n", "needs an axis"),
+            ("POLY X0\nPO[X]=(1,2,3,4,5,6)\n", "at most five values"),
+            ("POLY X0\nPO[X]=(1) PO[]=(0)\n", "must not end at 0"),
+        ] {
+            let err = nc_to_table(program, None, None, None, 10000, false, None, false, None)
+                .expect_err("invalid PO[] must fail");
+            assert_eq!(err.location().map(|l| l.line), Some(2), "{err}");
+            assert!(err.to_string().contains(message), "{program}: {err}");
+        }
+    }
+
+    /// INVCW/INVCCW: AR= resolves to the end point on the involute, a mirror
+    /// swaps the direction like G2/G3, and the alarms name the problem.
+    #[test]
+    fn involute_end_points_and_alarms() {
+        let program = "G1 X10 Y0 F100\nINVCCW I=AC(0) J=AC(0) CR=10 AR=90\nINVCW X10 Y0 I=AC(0) J=AC(0) CR=10\nMIRROR X0\nINVCCW X15.707963 Y10 I=AC(0) J=AC(0) CR=10\n";
+        let (table, _) =
+            nc_to_table(program, None, None, None, 10000, true, None, false, None).expect("program should interpret");
+        let x = floats(&table, "X");
+        assert!((x[1].unwrap() - 5.0 * std::f64::consts::PI).abs() < 1e-9);
+        assert!((floats(&table, "Y")[1].unwrap() - 10.0).abs() < 1e-9);
+        assert_eq!(x[2], Some(10.0));
+        assert_eq!(x[4], Some(-15.707963));
+        assert_eq!(
+            strings(&table, "gg01_motion"),
+            &[
+                Some("G1".to_string()),
+                Some("INVCCW".to_string()),
+                Some("INVCW".to_string()),
+                Some("INVCCW".to_string()),
+                Some("INVCW".to_string()),
+            ]
+        );
+
+        for (program, message) in [
+            ("G1 X10 Y0\nINVCW X20 I-10\n", "needs the base circle radius"),
+            ("G1 X10 Y0\nINVCW X20 I-10 CR=-1\n", "must be positive"),
+            ("G1 X10 Y0\nINVCW X20 CR=10\n", "needs the base circle centre"),
+            ("G1 X5 Y0\nINVCW X20 I-5 CR=10\n", "start point lies inside"),
+            ("G1 X10 Y0\nINVCW X5 Y0 I-10 CR=10\n", "end point lies inside"),
+            ("G1 X10 Y0\nINVCW X20 Y0 I-10 CR=10\n", "away from the involute"),
+            (
+                "G1 X10 Y0\nINVCW X20 I-10 CR=10 AR=90\n",
+                "takes the place of the end point",
+            ),
+            ("G1 X10 Y0\nINVCW I-10 CR=10 AR=-10\n", "winds past the base circle"),
+            ("G1 X10 Y0\nINVCW I-10 CR=10 AR=0\n", "must not be 0"),
+        ] {
+            let err = nc_to_table(program, None, None, None, 10000, false, None, false, None)
+                .expect_err("invalid involute must fail");
+            assert_eq!(err.location().map(|l| l.line), Some(2), "{err}");
+            assert!(err.to_string().contains(message), "{program}: {err}");
+        }
+    }
+
+    /// G33/G34/G35: the lead words become the thread columns, F in G34/G35
+    /// is the lead change, and with the spindle turning the thread row
+    /// carries the derived path feed until the next row restates the
+    /// programmed one.
+    #[test]
+    fn thread_blocks_carry_lead_revolutions_and_feed() {
+        let program =
+            "G1 X20 Z5 F300 S1000 M3\nG33 Z-30 K1.5 SF=90\nG34 Z-40 K2 F0.1\nG1 X30\nG35 Z-50 X35 I2 K2.5 F0.05\nG1 X40\n";
+        let (table, _) =
+            nc_to_table(program, None, None, None, 10000, false, None, false, None).expect("program should interpret");
+        assert_eq!(
+            floats(&table, "THREAD_LEAD"),
+            &[None, Some(1.5), Some(2.0), None, Some(2.5), None]
+        );
+        assert_eq!(
+            floats(&table, "THREAD_LEAD_CHANGE"),
+            &[None, None, Some(0.1), None, Some(-0.05), None]
+        );
+        let revolutions = floats(&table, "THREAD_REVOLUTIONS");
+        assert!((revolutions[1].unwrap() - 35.0 / 1.5).abs() < 1e-9);
+        // 2n + 0.05n^2 = 10
+        assert!((revolutions[2].unwrap() - 10.0 / (1.0 + 1.5f64.sqrt())).abs() < 1e-9);
+        assert_eq!(floats(&table, "SF"), &[None, Some(90.0), None, None, None, None]);
+        let feed = floats(&table, "F");
+        assert!((feed[1].unwrap() - 1500.0).abs() < 1e-9);
+        assert_eq!(feed[3], Some(300.0));
+        // Lead axis Z (longer travel), over the X/Z path.
+        let path = 10.0f64.hypot(5.0);
+        assert!((feed[4].unwrap() - path * 1000.0 / revolutions[4].unwrap()).abs() < 1e-9);
+        assert_eq!(feed[5], Some(300.0));
+        assert!(!column_names(&table).contains(&"K"));
+
+        // The F of a dwell is no feed: the row after it restates the feed.
+        let program = "G1 X0 Z0 F300 S1000 M3\nG33 Z-15 K1.5\nG4 F2\nG1 X10\n";
+        let (table, _) =
+            nc_to_table(program, None, None, None, 10000, false, None, false, None).expect("program should interpret");
+        assert_eq!(floats(&table, "dwell"), &[None, None, Some(2.0), None]);
+        assert_eq!(floats(&table, "F")[3], Some(300.0));
+
+        for (program, message) in [
+            ("G1 X0 Z0\nG33 Z-10\n", "needs the lead"),
+            ("G1 X0 Z0\nG33 Z-10 K0\n", "must not be 0"),
+            ("G1 X0 Z0\nG33 Z-10 I1\n", "which the block does not move"),
+            ("G1 X0 Z0\nG34 Z-10 K1\n", "lead change F="),
+            ("G1 X0 Z0\nG35 Z-100 K1 F0.1\n", "decreases to 0"),
+        ] {
+            let err = nc_to_table(program, None, None, None, 10000, false, None, false, None)
+                .expect_err("invalid thread must fail");
+            assert_eq!(err.location().map(|l| l.line), Some(2), "{err}");
+            assert!(err.to_string().contains(message), "{program}: {err}");
+        }
+    }
+
+    /// SPLINEPATH picks the spline axes; the others follow linearly, and
+    /// the declaration itself leaves no call behind.
+    #[test]
+    fn spline_path_declares_the_spline_axes() {
+        let program = "G1 X0 Y0 Z0 F100\nSPLINEPATH(1, X, Y)\nCSPLINE X10 Y10 Z2\nX20 Y0 Z4\nG1 X30\n";
+        let (table, _) = nc_to_table(program, None, None, None, 10000, false, None, false, Some(0.01))
+            .expect("program should interpret");
+        assert!(!column_names(&table).contains(&"non_returning_function_call"));
+        let (xs, ys, zs) = (floats(&table, "X"), floats(&table, "Y"), floats(&table, "Z"));
+        assert!(xs.len() > 5);
+        // Z climbs steadily to its end value while X/Y trace the spline.
+        let mut travelled = 0.0;
+        for k in 2..xs.len() - 1 {
+            travelled += (xs[k].unwrap() - xs[k - 1].unwrap()).hypot(ys[k].unwrap() - ys[k - 1].unwrap());
+            assert!(zs[k].unwrap() > zs[k - 1].unwrap() - 1e-12 && zs[k].unwrap() <= 4.0 + 1e-12);
+        }
+        assert!(travelled > 28.0);
+        assert_eq!(zs[zs.len() - 2], Some(4.0));
+
+        for (program, message) in [
+            ("SPLINEPATH(2, X, Y)\n", "the spline group must be 1"),
+            ("SPLINEPATH(1, X, Q)\n", "'Q' is not a path axis"),
+            ("SPLINEPATH(1, X, X)\n", "named twice"),
+            ("SPLINEPATH(1)\n", "at least one axis"),
+            ("BSPLINE X1 PL=-1\n", "PL must be positive"),
+        ] {
+            let err = nc_to_table(program, None, None, None, 10000, false, None, false, None)
+                .expect_err("invalid spline declaration must fail");
+            assert!(err.to_string().contains(message), "{program}: {err}");
+        }
+    }
+
+    /// G41/G42 move the rows onto the tool-centre path at the radius of the
+    /// active D from the `$TC_DP6` table: inner corners are trimmed to the
+    /// intersection, outer corners get a G450 circle or the G451 tangent
+    /// intersection, and NORM approaches and retracts perpendicular to the
+    /// contour.
+    #[test]
+    fn tool_radius_compensation_offsets_the_contour() {
+        let run = |src: &str| {
+            nc_to_table(
+                src,
+                Some("$TC_DP6[1,1]=5\n"),
+                None,
+                None,
+                10000,
+                false,
+                None,
+                false,
+                None,
+            )
+            .expect("program should interpret")
+        };
+        let point = |table: &Table, k: usize| [floats(table, "X")[k].unwrap(), floats(table, "Y")[k].unwrap()];
+
+        // Inside a counter-clockwise square: every corner is an inner corner.
+        let (table, state) = run("G0 X0 Y-20\nG41 D1 G1 X0 Y0 F100\nX50\nY50\nX0\nY0\nG40 X-20 Y-20\n");
+        let path: Vec<_> = (0..table.height()).map(|k| point(&table, k)).collect();
+        assert_eq!(
+            path,
+            [
+                [0.0, -20.0],
+                [0.0, 5.0],
+                [45.0, 5.0],
+                [45.0, 45.0],
+                [5.0, 45.0],
+                [5.0, 0.0],
+                [-20.0, -20.0]
+            ]
+        );
+        assert_eq!(state.symbol_table["$TC_DP6[1,1]"], 5.0);
+
+        // Outside (G42) the corners are outer: G451 runs to the intersection,
+        // G450 inserts a circle around the corner point.
+        let (table, _) = run("G0 X0 Y-20\nG42 D1 G1 X0 Y0 F100\nG451 X50\nY50\nG450 X0\nG40 X0 Y80\n");
+        let motion = strings(&table, "gg01_motion");
+        assert_eq!(point(&table, 2), [55.0, -5.0]);
+        assert_eq!(point(&table, 3), [55.0, 50.0]);
+        assert_eq!(motion[4].as_deref(), Some("G3"));
+        assert_eq!(point(&table, 4), [50.0, 55.0]);
+        assert_eq!(
+            (floats(&table, "I")[4], floats(&table, "J")[4]),
+            (Some(-5.0), Some(0.0))
+        );
+        assert_eq!(motion[5].as_deref(), Some("G1"));
+        assert_eq!(point(&table, 5), [0.0, 55.0]);
+
+        // A compensated circle keeps its centre: its radius shrinks by the
+        // tool radius on the inner side.
+        let (table, _) = run("G0 X0 Y-20\nG41 D1 G1 X0 Y0 F100\nX50\nG3 X50 Y50 CR=25\nG40 G1 X70 Y25\n");
+        assert_eq!(point(&table, 2), [50.0, 5.0]);
+        assert_eq!(point(&table, 3), [50.0, 45.0]);
+        assert_eq!(
+            (floats(&table, "I")[3], floats(&table, "J")[3]),
+            (Some(0.0), Some(20.0))
+        );
+        assert!(!column_names(&table).contains(&"CR"));
+
+        // D0 compensates nothing.
+        let (table, _) = run("G0 X0 Y0\nG41 D0 G1 X10\nG40 X20\n");
+        assert_eq!(floats(&table, "Y"), &[Some(0.0), Some(0.0), Some(0.0)]);
+    }
+
+    /// KONT approaches around the first contour point when it is an outer
+    /// corner; `keep_contour` keeps the programmed path in the CONTOUR_*
+    /// columns.
+    #[test]
+    fn tool_radius_compensation_keeps_the_contour() {
+        let (table, _) = nc_to_table_with_options(
+            "G0 X-20 Y0\nKONT G42 D1 G1 X0 Y0 F100\nY50\nG40 X-20\n",
+            Some("$TC_DP6[1,1]=5\n"),
+            None,
+            None,
+            10000,
+            true,
+            None,
+            false,
+            None,
+            InterpreterOptions::default().with_keep_contour(true),
+        )
+        .expect("program should interpret");
+        // Approach to the contour point (0, 0) and around it to the side of
+        // Y50; the retract goes around (0, 50) likewise.
+        let emitted = |name: &str| floats(&table, name).iter().map(|v| v.unwrap()).collect::<Vec<_>>();
+        assert_eq!(emitted("X"), [-20.0, 0.0, 5.0, 5.0, 0.0, -20.0]);
+        assert_eq!(emitted("Y"), [0.0, -5.0, 0.0, 50.0, 55.0, 50.0]);
+        assert_eq!(emitted("CONTOUR_X"), [-20.0, 0.0, 0.0, 0.0, 0.0, -20.0]);
+        assert_eq!(emitted("CONTOUR_Y"), [0.0, 0.0, 0.0, 50.0, 50.0, 50.0]);
+    }
+
+    #[test]
+    fn tool_radius_compensation_errors() {
+        for (program, message) in [
+            ("G0 X0 Y0\nG41 D3 X10\n", "D3 has no tool offset data"),
+            (
+                "G0 X0 Y0\nG41 D1 G2 X10 CR=5\n",
+                "approaches the contour must be a G0/G1 line",
+            ),
+            (
+                "G0 X0 Y0\nG41 D1 G1 X10\nG40 G2 X20 CR=5\n",
+                "retracts from the contour (G40)",
+            ),
+            ("G0 X0 Y0\nG41 D1 G1 X10\nG18 X20\n", "working plane cannot change"),
+            ("G0 X0 Y0\nROT Z30\nG41 D1 G1 X10\n", "rotated, scaled or mirrored"),
+            (
+                "G0 X0 Y0\nKONTC G41 D1 G1 X10\n",
+                "KONTC approach and retract is not supported",
+            ),
+            ("G0 X0 Y0\nG41 D1.5 G1 X10\n", "not a tool offset number"),
+            ("$TC_DP6[0,1]=2\n", "both from 1"),
+            (
+                "$TC_DP6[2,1]=2\nG0 X0 Y0\nG41 D1 G1 X10\n",
+                "D1 is in the table for tools 1, 2",
+            ),
+        ] {
+            let err = nc_to_table(
+                program,
+                Some("$TC_DP6[1,1]=5\n"),
+                None,
+                None,
+                10000,
+                false,
+                None,
+                false,
+                None,
+            )
+            .expect_err("invalid compensation must fail");
+            assert!(err.to_string().contains(message), "{program}: {err}");
+        }
+    }
+
+    /// T selects and M6 loads a tool; its D offset shifts the machine
+    /// positions by the tool lengths `$TC_DP3`..`$TC_DP5` along the axes of
+    /// the working plane, as each axis next moves.
+    #[test]
+    fn tool_length_compensation_shifts_the_machine_positions() {
+        let tools = "$TC_DP3[1,1]=100\n$TC_DP3[2,1]=50\n$TC_DP4[2,1]=3\n$TC_DP5[2,1]=-2\n$TC_TP2[2]=\"MILL\"\n";
+        let run = |src: &str| {
+            nc_to_table(src, Some(tools), None, None, 10000, true, None, false, None).expect("program should interpret")
+        };
+        let emitted = |table: &Table, name: &str| floats(table, name).iter().flatten().copied().collect::<Vec<_>>();
+
+        let (table, state) = run("G0 X0 Y0 Z10\nT1 M6\nD1 Z10\nR1=$AA_IM[Z]\nR2=$AA_IW[Z]\nD0 Z10\n");
+        assert_eq!(emitted(&table, "Z"), [10.0, 110.0, 10.0]);
+        assert_eq!(strings(&table, "T")[1].as_deref(), Some("1"));
+        assert_eq!((state.symbol_table["R1"], state.symbol_table["R2"]), (110.0, 10.0));
+
+        // The length follows the plane: under G18 length 1 is along Y,
+        // length 2 along X and length 3 along Z. An axis the block does not
+        // move keeps its position until it moves.
+        let (table, _) = run("G0 X0 Y0 Z0\nT=\"MILL\" M6 D1 Z0\nX0\nG18 Y0\nX0 Z0\n");
+        assert_eq!(emitted(&table, "Z"), [0.0, 50.0, -2.0]);
+        assert_eq!(emitted(&table, "X"), [0.0, -2.0, 3.0]);
+        assert_eq!(emitted(&table, "Y"), [0.0, 50.0]);
+
+        // Under frame suppression the tool length stays.
+        let (table, _) = run("T1 M6 D1\nG54 G0 Z0\nG53 Z0\n");
+        assert_eq!(emitted(&table, "Z"), [100.0, 100.0]);
+
+        for (program, message) in [
+            ("T1 M6 D2 Z0\n", "D2 has no tool offset data"),
+            ("T=\"DRILL\" M6 D1 Z0\n", "tool \"DRILL\" has no tool number"),
+            ("D1 Z0\n", "D1 is in the table for tools 1, 2"),
+            ("T1 M6 D1.5\n", "not a tool offset number"),
+            ("$TC_DP3[1]=5\n", "both from 1"),
+        ] {
+            let err = nc_to_table(program, Some(tools), None, None, 10000, false, None, false, None)
+                .expect_err("invalid tool offset must fail");
+            assert!(err.to_string().contains(message), "{program}: {err}");
+        }
+    }
+
+    /// G70/G71 convert the geometry to the output unit, G700/G710 the feed,
+    /// the tool and settable offsets and the actual positions read back too.
+    #[test]
+    fn inch_and_metric_programs_convert_to_the_output_unit() {
+        let emitted = |table: &Table, name: &str| floats(table, name).iter().flatten().copied().collect::<Vec<_>>();
+
+        let (table, state) = nc_to_table(
+            "G0 X0 Y0\nG70 G1 X1 F10\nG2 X2 Y0 CR=0.5\nG700 G1 A90 X4 F10\nG4 F2\nR1=$AA_IW[X]\nG70 R2=$AA_IW[X]\nTRANS X1\nX0\nG71 X10\n",
+            None,
+            None,
+            None,
+            10000,
+            true,
+            None,
+            false,
+            None,
+        )
+        .expect("program should interpret");
+        assert_eq!(emitted(&table, "X"), [0.0, 25.4, 50.8, 101.6, 25.4, 35.4]);
+        assert_eq!(emitted(&table, "CR"), [12.7]);
+        assert_eq!(emitted(&table, "A"), [90.0]);
+        assert_eq!(emitted(&table, "F"), [10.0, 254.0]);
+        assert_eq!(emitted(&table, "dwell"), [2.0]);
+        assert_eq!((state.symbol_table["R1"], state.symbol_table["R2"]), (4.0, 101.6));
+        assert_eq!(state.final_state().unit, "mm");
+
+        let (table, state) = nc_to_table_with_options(
+            "G0 X0\nG71 X25.4 F100\nG710 X50.8 F254\nT1 M6 D1 Z0\n",
+            Some("G710 $TC_DP3[1,1]=127\n$P_UIFR[1]=CTRANS(X,25.4)\nG54\n"),
+            None,
+            None,
+            10000,
+            true,
+            None,
+            false,
+            None,
+            InterpreterOptions::default().with_unit(Unit::Inch),
+        )
+        .expect("program should interpret");
+        assert_eq!(emitted(&table, "X"), [1.0, 2.0, 3.0]);
+        assert_eq!(emitted(&table, "F"), [100.0, 10.0]);
+        assert_eq!(emitted(&table, "Z"), [5.0]);
+        assert_eq!(state.final_state().unit, "inch");
+    }
+
+    /// The spindle columns follow S, M3/M4/M5 and SPOS; under G96 the speed
+    /// follows the transverse axis up to LIMS, and G97 keeps the last one.
+    #[test]
+    fn spindle_speed_and_direction_are_derived_per_row() {
+        let run = |src: &str| {
+            nc_to_table(src, None, None, None, 10000, false, None, false, None).expect("program should interpret")
+        };
+        let (table, _) =
+            run("R1=5\nG0 X50 Z0\nG96 S200 LIMS=3000 M3\nG1 X10\nR2=1\nG97 X20\nS500\nSPOS=90\nM4 X30\nG4 S2\nM5\n");
+        let rpm: Vec<_> = floats(&table, "spindle_rpm")
+            .iter()
+            .map(|v| v.map(|v| (v * 1000.0).round() / 1000.0))
+            .collect();
+        assert_eq!(
+            rpm,
+            [
+                None,
+                Some(636.62),
+                Some(3000.0),
+                Some(3000.0),
+                Some(500.0),
+                Some(0.0),
+                Some(500.0),
+                Some(500.0),
+                Some(0.0)
+            ]
+        );
+        let direction: Vec<_> = strings(&table, "spindle_dir").iter().map(|d| d.as_deref()).collect();
+        assert_eq!(
+            direction,
+            [
+                None,
+                Some("CW"),
+                Some("CW"),
+                Some("CW"),
+                Some("CW"),
+                Some("POS"),
+                Some("CCW"),
+                Some("CCW"),
+                Some("STOP")
+            ]
+        );
+        assert_eq!(floats(&table, "S").last().unwrap(), &Some(500.0));
+
+        // Without the spindle the columns stay out of the table.
+        let (table, _) = run("G0 X1\nG1 X2 F100\n");
+        assert!(table.columns.iter().all(|(name, _)| !name.starts_with("spindle")));
+    }
+
+    /// A thread's feed follows the speed of the spindle model: under G96 the
+    /// speed the cutting rate gives at the end point, and none while the
+    /// spindle is stopped.
+    #[test]
+    fn thread_feed_follows_the_spindle_speed() {
+        let run = |src: &str| {
+            nc_to_table(src, None, None, None, 10000, false, None, false, None)
+                .expect("program should interpret")
+                .0
+        };
+        // 200 m/min at radius 50: 636.6 rpm, 2 mm per revolution.
+        let table = run("G0 X50 Z0 F300\nG96 S200 LIMS=3000 M3\nG33 Z-20 K2\nG1 X60\n");
+        let feed = floats(&table, "F");
+        assert_eq!(feed[2].map(f64::round), Some(1273.0));
+        assert_eq!(feed[3], Some(300.0));
+
+        let table = run("G0 X50 Z0 F300 S1000 M3\nM5\nG33 Z-20 K2\nSPOS=0\nG33 Z-40 K2\n");
+        // The programmed feed, forward-filled: no row has a derived one.
+        assert_eq!(floats(&table, "F"), &[Some(300.0); 5]);
+    }
+
+    /// Under DIAMON the transverse axis X is programmed as a diameter and
+    /// output as its radius; DIAM90 does so only under G90, and DAC/DIC/
+    /// RAC/RIC override the mode for one word.
+    #[test]
+    fn diameter_programming_halves_the_transverse_axis() {
+        let emitted = |table: &Table, name: &str| floats(table, name).iter().flatten().copied().collect::<Vec<_>>();
+
+        let table = interpret(
+            "DIAMON G18 G1 X20 Z5\nX=DAC(30)\nX=RAC(30)\nG91 X4\nX=DIC(4)\nX=RIC(4)\nDIAM90 X2\nG90 X50\nDIAMOF X50\nX=DAC(50)\n",
+        );
+        assert_eq!(
+            emitted(&table, "X"),
+            [10.0, 15.0, 30.0, 32.0, 34.0, 38.0, 40.0, 25.0, 50.0, 25.0]
+        );
+        let modes: Vec<_> = strings(&table, "gg29_rad_diam_prog")
+            .iter()
+            .map(|m| m.as_deref())
+            .collect();
+        assert_eq!(modes[0], Some("DIAMON"));
+        assert_eq!(modes[8], Some("DIAMOF"));
+
+        // The arc's end point is a diameter, its centre offset a radius:
+        // centre (X0, Z20), radius 20.
+        for program in [
+            "DIAMON G18 G0 X0 Z0\nG2 X40 Z20 I0 K20\n",
+            "DIAMON G18 G0 X0 Z0\nG2 X40 Z20 I=AC(0) K=AC(20)\n",
+        ] {
+            let (table, _) = nc_to_table(program, None, None, None, 10000, false, None, false, Some(0.01))
+                .expect("program should interpret");
+            let (x, z) = (floats(&table, "X"), floats(&table, "Z"));
+            assert!(x.len() > 10, "arc was not flattened: {} row(s)", x.len());
+            assert_eq!(x.last().unwrap(), &Some(20.0));
+            for (xv, zv) in x.iter().zip(z).filter_map(|(a, b)| a.zip(*b)) {
+                assert!((xv.hypot(zv - 20.0) - 20.0).abs() < 1e-6, "({xv}, {zv})");
+            }
+        }
+
+        let error = nc_to_table("G0 Y=DAC(3)\n", None, None, None, 10000, false, None, false, None)
+            .expect_err("DAC() on Y should fail");
+        assert!(error.to_string().contains("transverse axis X"), "{error}");
+    }
+
     /// The CR= radius form is likewise a per-block interpolation parameter and
     /// is accepted both bare (I50) and with `=` (CR=20).
     #[test]
@@ -0,0 +1,170 @@
+//! Involute interpolation (`INVCW`/`INVCCW`, manual 3.9.8): the path
+//! unwinds from (or winds onto) a base circle in the working plane.
+//!
+//! * `I`/`J`/`K` give the base circle's centre like a circle centre (relative
+//!   to the start point, or `AC()`), `CR=` its radius, which must be
+//!   positive.
+//! * The end point is either programmed, or follows from the opening angle
+//!   `AR=`: the angle the generating line turns through about the centre,
+//!   unwinding away from the base circle for `AR > 0` and winding towards it
+//!   for `AR < 0`. The row then carries the end point; `AR` never appears
+//!   as a column.
+//! * `INVCW` travels clockwise around the centre, `INVCCW` counter-clockwise.
+//!   Whether the path moves away from or towards the base circle follows
+//!   from the start and end point's distances to the centre.
+//!
+//! Alarms: start or end point inside the base circle, and an end point
+//! farther than [`END_POINT_TOLERANCE`] from the involute through the start
+//! point. An end point within it is reached the way the control does,
+//! blending between the involutes through the start and the end point (see
+//! [`crate::flatten`]).
+
+use crate::arcs::{move_plane_axes, CENTRE_OFFSETS, GEOMETRY_AXES};
+use crate::errors::ParsingError;
+use crate::output::OutputRows;
+use crate::state::State;
+use crate::types::Value;
+
+/// How far the programmed end point may lie from the involute through the
+/// start point: the default of MD21015 `$MC_INVOLUTE_RADIUS_DELTA` (mm).
+pub(crate) const END_POINT_TOLERANCE: f64 = 0.01;
+
+/// The involutes of one base circle that run in one direction, in the
+/// working plane. A point on one is given by its phase (which involute) and
+/// its unwound length in base-circle radii.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Involute {
+    centre: [f64; 2],
+    radius: f64,
+    /// +1 if the path turns counter-clockwise while unwinding, -1 otherwise.
+    sign: f64,
+}
+
+impl Involute {
+    /// The involutes about `centre` travelled `clockwise` or not, moving
+    /// `away` from the base circle or towards it.
+    pub(crate) fn new(centre: [f64; 2], radius: f64, clockwise: bool, away: bool) -> Self {
+        let sign = if clockwise != away { 1.0 } else { -1.0 };
+        Involute { centre, radius, sign }
+    }
+
+    /// The unwound length of `point` in base-circle radii, `None` inside
+    /// the base circle.
+    pub(crate) fn unwound(&self, point: [f64; 2]) -> Option<f64> {
+        let ratio = (point[0] - self.centre[0]).hypot(point[1] - self.centre[1]) / self.radius;
+        (ratio >= 1.0 - 1e-9).then(|| (ratio * ratio - 1.0).max(0.0).sqrt())
+    }
+
+    /// The phase of the involute through `point` (outside the base circle):
+    /// the angle at which it leaves the base circle.
+    pub(crate) fn phase(&self, point: [f64; 2]) -> f64 {
+        let unwound = self.unwound(point).unwrap_or(0.0);
+        let polar = (point[1] - self.centre[1]).atan2(point[0] - self.centre[0]);
+        polar + self.sign * (unwound.atan() - unwound)
+    }
+
+    /// The point `unwound` radii along the involute of `phase`.
+    pub(crate) fn point(&self, phase: f64, unwound: f64) -> [f64; 2] {
+        let (sin, cos) = (phase + self.sign * unwound).sin_cos();
+        let along = self.sign * unwound;
+        [
+            self.centre[0] + self.radius * (cos + along * sin),
+            self.centre[1] + self.radius * (sin - along * cos),
+        ]
+    }
+
+    /// The unit direction of increasing unwound length at a point of the
+    /// involute of `phase`: along the generating line.
+    pub(crate) fn direction(&self, phase: f64, unwound: f64) -> [f64; 2] {
+        let (sin, cos) = (phase + self.sign * unwound).sin_cos();
+        [cos, sin]
+    }
+}
+
+/// Resolve an involute block once all its other words ran: check the base
+/// circle and the end point, or compute the end point from the opening
+/// angle `AR=`.
+pub(crate) fn finish_block(
+    opening_angle: Option<f64>,
+    line_no: usize,
+    output: &mut OutputRows,
+    state: &mut State,
+) -> Result<(), ParsingError> {
+    let Some(clockwise) = state.involute_direction() else {
+        return Ok(());
+    };
+    let error = |message: String| {
+        ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or("").to_string(),
+            "involute (INVCW/INVCCW)".to_string(),
+            message,
+        )
+    };
+    let [abscissa, ordinate, _] = state.plane.axes();
+    let last = output.last_mut().expect("row started");
+    let cell = |key: &str| match last.get(key) {
+        Some(Value::Float(value)) => Some(*value),
+        _ => None,
+    };
+    let offsets = [cell(CENTRE_OFFSETS[abscissa]), cell(CENTRE_OFFSETS[ordinate])];
+    let end_programmed = state.moved_in_block(abscissa) || state.moved_in_block(ordinate);
+    let radius = cell("CR");
+    if !end_programmed && opening_angle.is_none() && offsets.iter().all(Option::is_none) && radius.is_none() {
+        // A block in modal involute mode that moves nothing in the plane.
+        return Ok(());
+    }
+    let Some(radius) = radius else {
+        return Err(error("an involute needs the base circle radius CR=".to_string()));
+    };
+    if radius.is_nan() || radius <= 0.0 {
+        return Err(error("the base circle radius CR= must be positive".to_string()));
+    }
+    if offsets.iter().all(Option::is_none) {
+        return Err(error("an involute needs the base circle centre I/J/K".to_string()));
+    }
+    let start = [abscissa, ordinate].map(|slot| state.block_start_local(GEOMETRY_AXES[slot]).unwrap_or(0.0));
+    let centre = [
+        start[0] + offsets[0].unwrap_or(0.0),
+        start[1] + offsets[1].unwrap_or(0.0),
+    ];
+    let probe = Involute::new(centre, radius, clockwise, true);
+    let Some(start_unwound) = probe.unwound(start) else {
+        return Err(error("the start point lies inside the base circle".to_string()));
+    };
+
+    if let Some(opening_angle) = opening_angle {
+        if end_programmed {
+            return Err(error(
+                "AR= takes the place of the end point; program one of them".to_string(),
+            ));
+        }
+        if opening_angle == 0.0 || !opening_angle.is_finite() {
+            return Err(error("the opening angle AR= must not be 0".to_string()));
+        }
+        let end_unwound = start_unwound + opening_angle.to_radians();
+        if end_unwound < 0.0 {
+            return Err(error(format!(
+                "the opening angle AR={opening_angle} winds past the base circle (AR= must be at least {:.4})",
+                -start_unwound.to_degrees()
+            )));
+        }
+        let involute = Involute::new(centre, radius, clockwise, opening_angle > 0.0);
+        let end = involute.point(involute.phase(start), end_unwound);
+        return move_plane_axes([(abscissa, end[0]), (ordinate, end[1])], last, state);
+    }
+
+    let end = [abscissa, ordinate].map(|slot| state.get_axis_local(GEOMETRY_AXES[slot]).unwrap_or(0.0));
+    let Some(end_unwound) = probe.unwound(end) else {
+        return Err(error("the end point lies inside the base circle".to_string()));
+    };
+    let involute = Involute::new(centre, radius, clockwise, end_unwound > start_unwound);
+    let reached = involute.point(involute.phase(start), end_unwound);
+    let miss = (end[0] - reached[0]).hypot(end[1] - reached[1]);
+    if miss > END_POINT_TOLERANCE {
+        return Err(error(format!(
+            "the end point is {miss:.4} away from the involute through the start point (at most {END_POINT_TOLERANCE})"
+        )));
+    }
+    Ok(())
+}
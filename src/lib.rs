@@ -3,15 +3,29 @@
 extern crate pest_derive;
 mod types;
 
+mod arcs;
+mod cycles;
 mod errors;
 pub mod flatten;
+mod frame;
 mod interpret_rules;
 pub mod interpreter;
+mod involute;
 mod line_driver;
+mod macros;
 mod modal_groups;
 pub mod output;
+mod polar;
+mod poly;
+mod spindle;
+mod splines;
 mod state;
 mod structure_scan;
+mod subprogram;
+mod threads;
+mod tool_radius;
+mod tools;
+pub mod units;
 
 #[cfg(feature = "python")]
 mod python_bindings {
@@ -21,6 +35,7 @@ mod python_bindings {
     use pyo3::types::{PyDict, PyList};
     use pyo3::wrap_pyfunction;
     use std::collections::HashMap;
+    use std::path::PathBuf;
     use std::sync::Arc;
 
     /// One output batch as an Arrow struct array, exported to Python through the
@@ -59,10 +74,11 @@ mod python_bindings {
     use std::sync::{mpsc, Mutex};
 
     use crate::errors::ErrorLocation;
-    use crate::interpreter::{nc_to_batch_stream_with_line_numbers, nc_to_row_stream};
+    use crate::interpreter::{nc_to_batch_stream_with_options, nc_to_row_stream_with_options, InterpreterOptions};
     use crate::output::{is_forward_filled_column, is_string_column, Column, Row, Table, VariableEvents};
     use crate::state::FinalState;
     use crate::types::Value;
+    use crate::units::Unit;
 
     pyo3::create_exception!(
         _internal,
@@ -115,15 +131,20 @@ mod python_bindings {
     }
 
     /// Render a [`FinalState`] as the Python `.state` dict:
-    /// `{axes, symbol_table, translation, string_table}`. The three numeric
-    /// sub-tables become `dict[str, float]`; `string_table` (DEF STRING
-    /// variables) becomes `dict[str, str]`. Shared by both iterators.
+    /// `{axes, symbol_table, translation, string_table, work_offset,
+    /// work_offset_translation, unit}`. The numeric sub-tables become
+    /// `dict[str, float]`; `string_table` (DEF STRING variables) becomes
+    /// `dict[str, str]`, `work_offset` the active G word or `None` and `unit`
+    /// the output unit (`"mm"` or `"inch"`). Shared by both iterators.
     fn final_state_to_py<'py>(py: Python<'py>, state: &FinalState) -> PyResult<Bound<'py, PyDict>> {
         let dict = PyDict::new(py);
         dict.set_item("axes", state.axes.clone())?;
         dict.set_item("symbol_table", state.symbol_table.clone())?;
         dict.set_item("translation", state.translation.clone())?;
         dict.set_item("string_table", state.string_table.clone())?;
+        dict.set_item("work_offset", state.work_offset.clone())?;
+        dict.set_item("work_offset_translation", state.work_offset_translation.clone())?;
+        dict.set_item("unit", state.unit)?;
         Ok(dict)
     }
 
@@ -198,6 +219,26 @@ mod python_bindings {
         })
     }
 
+    /// The output unit named `name` (`"mm"` or `"inch"`).
+    fn parse_unit(name: &str) -> PyResult<Unit> {
+        Unit::from_name(name)
+            .ok_or_else(|| PyErr::new::<PyValueError, _>(format!("unit must be \"mm\" or \"inch\", not {name:?}")))
+    }
+
+    /// The [`InterpreterOptions`] from the Python keyword arguments.
+    fn interpreter_options(
+        subprogram_dirs: Option<Vec<PathBuf>>,
+        cycles: Option<HashMap<String, String>>,
+        keep_contour: bool,
+        unit: &str,
+    ) -> PyResult<InterpreterOptions> {
+        Ok(InterpreterOptions::default()
+            .with_subprogram_dirs(subprogram_dirs.unwrap_or_default())
+            .with_cycles(cycles.unwrap_or_default())
+            .with_keep_contour(keep_contour)
+            .with_unit(parse_unit(unit)?))
+    }
+
     /// Spawn the interpreter on a worker thread, pushing finished rows into a
     /// bounded channel. Shared by the row and batch streaming iterators.
     #[allow(clippy::too_many_arguments)]
@@ -210,6 +251,7 @@ mod python_bindings {
         axis_index_map: Option<HashMap<String, usize>>,
         allow_undefined_variables: bool,
         flatten_tolerance: Option<f64>,
+        options: InterpreterOptions,
     ) -> PyResult<(
         mpsc::Receiver<Row>,
         mpsc::Receiver<Result<FinalState, ErrInfo>>,
@@ -221,7 +263,7 @@ mod python_bindings {
         let handle = std::thread::Builder::new()
             .name("nc-interpreter".to_string())
             .spawn(move || {
-                let outcome = nc_to_row_stream(
+                let outcome = nc_to_row_stream_with_options(
                     &input,
                     initial_state.as_deref(),
                     axis_identifiers,
@@ -230,6 +272,7 @@ mod python_bindings {
                     axis_index_map,
                     allow_undefined_variables,
                     flatten_tolerance,
+                    options,
                     row_sender,
                 );
                 let message = match outcome {
@@ -262,6 +305,7 @@ mod python_bindings {
         flatten_tolerance: Option<f64>,
         emit_line_no: bool,
         include_variables: bool,
+        options: InterpreterOptions,
     ) -> PyResult<(
         mpsc::Receiver<Table>,
         mpsc::Receiver<Result<FinalState, ErrInfo>>,
@@ -281,7 +325,7 @@ mod python_bindings {
         let handle = std::thread::Builder::new()
             .name("nc-interpreter".to_string())
             .spawn(move || {
-                let outcome = nc_to_batch_stream_with_line_numbers(
+                let outcome = nc_to_batch_stream_with_options(
                     &input,
                     initial_state.as_deref(),
                     axis_identifiers,
@@ -293,6 +337,7 @@ mod python_bindings {
                     flatten_tolerance,
                     batch_size,
                     emit_line_no,
+                    options,
                     batch_sender,
                     include_variables,
                     events_sender,
@@ -480,7 +525,7 @@ mod python_bindings {
     /// worker thread. Rows are forward-filled like the batch DataFrame
     /// unless `forward_fill` is false.
     #[pyfunction]
-    #[pyo3(signature = (input, initial_state = None, axis_identifiers = None, extra_axes = None, iteration_limit = 10000, forward_fill = true, include_variables = false, axis_index_map = None, allow_undefined_variables = false, input_is_path = false, flatten_tolerance = None))]
+    #[pyo3(signature = (input, initial_state = None, axis_identifiers = None, extra_axes = None, iteration_limit = 10000, forward_fill = true, include_variables = false, axis_index_map = None, allow_undefined_variables = false, input_is_path = false, flatten_tolerance = None, subprogram_dirs = None, cycles = None, keep_contour = false, unit = "mm"))]
     #[allow(clippy::too_many_arguments)]
     fn nc_to_rows(
         input: String,
@@ -494,6 +539,10 @@ mod python_bindings {
         allow_undefined_variables: bool,
         input_is_path: bool,
         flatten_tolerance: Option<f64>,
+        subprogram_dirs: Option<Vec<PathBuf>>,
+        cycles: Option<HashMap<String, String>>,
+        keep_contour: bool,
+        unit: &str,
     ) -> PyResult<NcRowIterator> {
         // When `input_is_path` is set, `input` is a filesystem path: read the
         // program here (once) instead of copying a 1.1 GB Python str across the
@@ -514,6 +563,7 @@ mod python_bindings {
             axis_index_map,
             allow_undefined_variables,
             flatten_tolerance,
+            interpreter_options(subprogram_dirs, cycles, keep_contour, unit)?,
         )?;
 
         Ok(NcRowIterator {
@@ -657,7 +707,7 @@ mod python_bindings {
     /// thread. Wrapping each with `pl.DataFrame` and concatenating reconstructs
     /// `nc_to_dataframe`.
     #[pyfunction]
-    #[pyo3(signature = (input, batch_size = 500_000, initial_state = None, axis_identifiers = None, extra_axes = None, iteration_limit = 10000, disable_forward_fill = false, axis_index_map = None, allow_undefined_variables = false, input_is_path = false, flatten_tolerance = None, include_line_numbers = false, include_variables = false))]
+    #[pyo3(signature = (input, batch_size = 500_000, initial_state = None, axis_identifiers = None, extra_axes = None, iteration_limit = 10000, disable_forward_fill = false, axis_index_map = None, allow_undefined_variables = false, input_is_path = false, flatten_tolerance = None, include_line_numbers = false, include_variables = false, subprogram_dirs = None, cycles = None, keep_contour = false, unit = "mm"))]
     #[allow(clippy::too_many_arguments)]
     fn nc_to_batches(
         input: String,
@@ -673,6 +723,10 @@ mod python_bindings {
         flatten_tolerance: Option<f64>,
         include_line_numbers: bool,
         include_variables: bool,
+        subprogram_dirs: Option<Vec<PathBuf>>,
+        cycles: Option<HashMap<String, String>>,
+        keep_contour: bool,
+        unit: &str,
     ) -> PyResult<NcBatchIterator> {
         if batch_size == 0 {
             return Err(PyErr::new::<PyValueError, _>("batch_size must be greater than 0"));
@@ -697,6 +751,7 @@ mod python_bindings {
             flatten_tolerance,
             include_line_numbers,
             include_variables,
+            interpreter_options(subprogram_dirs, cycles, keep_contour, unit)?,
         )?;
 
         Ok(NcBatchIterator {
@@ -22,11 +22,12 @@
 
 use crate::errors::ParsingError;
 use crate::interpret_rules::{
-    canonical_block_number, insert_m_key, interpret_block, is_end_of_program_m_code, resolve_jump, scan_jump_targets,
-    BlockFlow,
+    canonical_block_number, finish_block, g_command_output, insert_m_key, interpret_block, interpret_generated_block,
+    is_end_of_program_m_code, resolve_jump, scan_jump_targets, schedule_subprogram_call, select_block_mode, BlockFlow,
+    Sections, MACRO_EXPANSION,
 };
 use crate::modal_groups::classify_g_command;
-use crate::state::{ColKind, State};
+use crate::state::{ColKind, Dimensioning, State};
 use crate::types::{NCParser, Pair, Rule, Value};
 use pest::Parser;
 use rayon::prelude::*;
@@ -79,8 +80,7 @@ enum Word<'a> {
     },
     /// A bare identifier that is neither a vocabulary command, a frame or
     /// reserved keyword, nor a word operator: a parameterless subprogram
-    /// call, stored exactly like `interpret_non_returning_function_call`.
-    /// `value` preserves the trailing whitespace that pest's span includes;
+    /// call, stored like the grammar path stores it: the word alone.
     /// `typo_warn` mirrors `looks_like_axis_word_typo` and is emitted at
     /// execution time (like the pest path, once per execution, never for
     /// lines that are decoded but not run).
@@ -326,15 +326,21 @@ fn run_lines(
 ) -> Result<BlockFlow, ParsingError> {
     let mut index = 0;
     let mut jumps_taken = 0;
+    let mut sections = Sections::default();
     while index < execs.len() {
         let flow = match &execs[index] {
             LineExec::Blank => BlockFlow::Continue,
-            LineExec::Decoded(line, chunk_idx) => execute_decoded(line, &chunk_arenas[*chunk_idx], output, state)?,
+            // A line using a macro was decoded from its unexpanded text.
+            LineExec::Decoded(line, chunk_idx) => match expand_line(line.line_no, state)? {
+                Some(expanded) => interpret_generated_block(&expanded, line.line_no, MACRO_EXPANSION, output, state)?,
+                None => execute_decoded(line, &chunk_arenas[*chunk_idx], output, state)?,
+            },
             LineExec::Parsed(block) => interpret_block(block.clone(), output, state)?,
         };
         match flow {
-            BlockFlow::Continue => index += 1,
+            BlockFlow::Continue => index = sections.advance(index),
             BlockFlow::EndProgram => return Ok(BlockFlow::EndProgram),
+            BlockFlow::Repeat(request) => index = sections.enter(targets, index, request, state)?,
             BlockFlow::Jump(request) => match resolve_jump(targets, index, &request) {
                 Some(destination) => {
                     // Only backward jumps can form cycles; bound them like
@@ -348,6 +354,7 @@ fn run_lines(
                             });
                         }
                     }
+                    sections.jumped(destination);
                     index = destination;
                 }
                 None => return Ok(BlockFlow::Jump(request)),
@@ -357,6 +364,14 @@ fn run_lines(
     Ok(BlockFlow::Continue)
 }
 
+fn expand_line(line_no: usize, state: &State) -> Result<Option<String>, ParsingError> {
+    if state.macros.is_empty() {
+        return Ok(None);
+    }
+    let text = state.get_line(line_no).unwrap_or("").trim_end_matches('\r');
+    crate::macros::expand_block(text, line_no, state)
+}
+
 /// Execute a decoded trivial line: the exact effects of `interpret_block`
 /// on the same line, without the parse tree.
 fn execute_decoded(
@@ -369,23 +384,36 @@ fn execute_decoded(
         return Ok(BlockFlow::Continue);
     }
     output.start_row(line.line_no)?;
+    let words = &arena[line.words.clone()];
+    // G90/G91 (and G17/G18/G19, G54..., G53, DIAMON...) apply to the whole block, like
+    // `begin_block` on the grammar path.
+    state.begin_block();
+    for word in words {
+        if let Word::GCommand(_, code) = word {
+            select_block_mode(code, state);
+        }
+    }
     let mut flow = BlockFlow::Continue;
     // Split borrows: row insertion vs axis-state updates.
-    for word in &arena[line.words.clone()] {
+    for word in words {
         match word {
             Word::Assign(key, value) => {
                 match state.resolve_output_key(key) {
                     Some((ColKind::Axis, skey)) => {
-                        let machine_value = state.update_axis(skey, *value)?;
+                        let value = crate::units::geometry(skey, *value, state);
+                        let local_value = state.resolve_programmed(skey, value, Dimensioning::Modal);
+                        state.update_axis(skey, local_value)?;
                         let last = output.last_mut().expect("row was just pushed");
-                        last.insert(skey, Value::Float(machine_value));
+                        state.machine_outputs(skey, local_value, |axis, machine_value| {
+                            last.insert(axis, Value::Float(machine_value));
+                        });
                     }
                     Some((ColKind::Block, skey)) => {
+                        let value = crate::units::geometry(skey, *value, state);
                         let last = output.last_mut().expect("row was just pushed");
-                        last.insert(skey, Value::Float(*value));
+                        last.insert(skey, Value::Float(value));
                     }
                     None => {
-                        state.warn_unsupported_address(key, line.line_no);
                         // User variable: identifiers are case-insensitive, so
                         // the symbol key is the uppercased name (manual 3.3.2),
                         // and every name has exactly one type - a STRING
@@ -442,53 +470,54 @@ fn execute_decoded(
                     }
                     None => *factor,
                 };
-                // IC(): new LOCAL coordinate = current local + increment (or the
-                // bare increment, with a warning, if never set) - exactly
-                // interpret_axis_increment. The lookup name is the resolved key
-                // (uppercased interned name for axes/blocks, as-written for
-                // variables), matching the pre-interning normalize_key behavior.
-                let increment_local = |state: &State, name: &str, value: f64| -> f64 {
-                    if !*incremental {
-                        return value;
-                    }
-                    match state.get_axis_local(name) {
-                        Some(local) => local + value,
-                        None => {
-                            crate::state::emit_warning(format_args!(
-                                "Warning: axis '{}' is incremented with IC() before any absolute position was set; assuming it starts at 0 (a real control would start from the actual axis position).",
-                                name
-                            ));
-                            value
-                        }
-                    }
+                // IC() or the active G90/G91 mode: exactly the
+                // `State::resolve_programmed` call of interpret_assignment. The
+                // lookup name is the resolved key (uppercased interned name for
+                // axes/blocks, as-written for variables), matching the
+                // pre-interning normalize_key behavior.
+                let dimensioning = if *incremental {
+                    Dimensioning::Incremental
+                } else {
+                    Dimensioning::Modal
                 };
                 match state.resolve_output_key(key) {
                     Some((ColKind::Axis, skey)) => {
-                        let local_value = increment_local(state, skey, value);
-                        let machine_value = state.update_axis(skey, local_value)?;
+                        let value = crate::units::geometry(skey, value, state);
+                        let local_value = state.resolve_programmed(skey, value, dimensioning);
+                        state.update_axis(skey, local_value)?;
                         let last = output.last_mut().expect("row was just pushed");
-                        last.insert(skey, Value::Float(machine_value));
+                        state.machine_outputs(skey, local_value, |axis, machine_value| {
+                            last.insert(axis, Value::Float(machine_value));
+                        });
                     }
                     Some((ColKind::Block, skey)) => {
-                        let local_value = increment_local(state, skey, value);
+                        let value = crate::units::geometry(skey, value, state);
+                        let local_value = state.resolve_programmed(skey, value, dimensioning);
                         let last = output.last_mut().expect("row was just pushed");
                         last.insert(skey, Value::Float(local_value));
                     }
                     None => {
-                        state.warn_unsupported_address(key, line.line_no);
                         let key = key.to_uppercase();
                         reject_string_variable(&key, state, line.line_no)?;
-                        let local_value = increment_local(state, &key, value);
+                        let local_value = state.resolve_programmed(&key, value, dimensioning);
                         output.record_variable_change(&key, local_value);
                         state.symbol_table.insert(key, local_value);
                     }
                 }
             }
             Word::Call { value, typo_warn } => {
-                if *typo_warn {
+                if crate::subprogram::take_pass_count(value, line.line_no, state)? {
+                    continue;
+                }
+                let program = crate::subprogram::find(value, line.line_no, state)?;
+                let resolved = program.is_some();
+                if let Some(program) = program {
+                    schedule_subprogram_call(program, None, line.line_no, state)?;
+                }
+                if *typo_warn && !resolved {
                     crate::state::emit_warning(format_args!(
                         "Warning: '{}' (line {}) is interpreted as a subprogram call; did you mean one or more axis words (e.g. Y20)?",
-                        value.trim(),
+                        value,
                         line.line_no
                     ));
                 }
@@ -498,7 +527,10 @@ fn execute_decoded(
             Word::GCommand(group, as_written) => {
                 let last = output.last_mut().expect("row was just pushed");
                 // Uppercase like the grammar path: g2 IS G2.
-                last.insert(group, Value::Str(as_written.to_uppercase()));
+                last.insert(
+                    group,
+                    Value::Str(g_command_output(group, as_written.to_uppercase(), state)),
+                );
             }
             Word::MCode(code) => {
                 let last = output.last_mut().expect("row was just pushed");
@@ -517,6 +549,7 @@ fn execute_decoded(
     if let Some(comment) = line.comment {
         last.insert("comment", Value::Str(comment.to_string()));
     }
+    finish_block(line.line_no, output, state)?;
     Ok(flow)
 }
 
@@ -587,7 +620,7 @@ fn parse_number(line: &str, i: &mut usize) -> Option<f64> {
 /// to parse), so the fast path must reject it to the grammar.
 const RESERVED_WORDS: &[&str] = &[
     "IF", "ELSE", "ENDIF", "GOTOB", "GOTOF", "GOTOC", "GOTOS", "GOTO", "CASE", "OF", "DEFAULT", "LOOP", "FOR", "WHILE",
-    "REPEAT", "LABEL", "TO", "UNTIL", "ENDWHILE", "ENDFOR", "ENDLOOP",
+    "REPEATB", "REPEAT", "ENDLABEL", "LABEL", "TO", "UNTIL", "ENDWHILE", "ENDFOR", "ENDLOOP",
 ];
 
 fn is_reserved_word(word: &str) -> bool {
@@ -863,15 +896,21 @@ fn decode_line_inner<'a>(
                     arena.push(Word::MCode(code));
                 } else {
                     match classify_g_command(&code.to_uppercase()) {
+                        // Pole definitions (G110..G112) take the block's
+                        // plane axes; the grammar path handles them.
+                        Some(("gg03_frame_area_limit", _)) if matches!(&code[1..], "110" | "111" | "112") => {
+                            return DecodeResult::NeedsGrammar
+                        }
                         Some((group, _modal)) => arena.push(Word::GCommand(group, code)),
                         // Unknown G code: let the full path produce its error.
                         None => return DecodeResult::NeedsGrammar,
                     }
                 }
             } else {
-                // Axis-address letters: exactly the grammar's
-                // variable_single_char set (normalized to the uppercase
-                // column key). Anything else needs the grammar.
+                // Axis-address letters: the grammar's variable_single_char
+                // set (normalized to the uppercase column key) but for the
+                // tool number T, which acts at the end of the block
+                // (crate::tools). Anything else needs the grammar.
                 let key = match letter {
                     b'X' => "X",
                     b'Y' => "Y",
@@ -885,7 +924,6 @@ fn decode_line_inner<'a>(
                     b'I' => "I",
                     b'J' => "J",
                     b'K' => "K",
-                    b'T' => "T",
                     b'S' => "S",
                     b'F' => "F",
                     b'D' => "D",
@@ -896,8 +934,9 @@ fn decode_line_inner<'a>(
                 let Some(value) = parse_number(line, &mut i) else {
                     return DecodeResult::NeedsGrammar;
                 };
-                // The grammar's axis_word rejects a following '[' (arrays).
-                if i < n_len && bytes[i] == b'[' {
+                // The grammar's axis_word rejects a following '[' (arrays)
+                // and '=' (`I1=10` assigns the CIP intermediate point).
+                if i < n_len && matches!(bytes[i], b'[' | b'=') {
                     return DecodeResult::NeedsGrammar;
                 }
                 arena.push(Word::Assign(key, value));
@@ -914,8 +953,16 @@ fn decode_line_inner<'a>(
             skip_ws(&mut eq);
             if eq < n_len && bytes[eq] == b'=' {
                 // A reserved word is not an `identifier`, so `IF=5` is a
-                // parse error in the full grammar, not an assignment.
-                if is_reserved_word(word) {
+                // parse error in the full grammar, not an assignment. The
+                // polar AP/RP, opening-angle AR, tool T and spindle LIMS/SPOS
+                // words act at the end of the block (crate::polar,
+                // crate::arcs, crate::tools, crate::spindle), which only the
+                // grammar path does.
+                if is_reserved_word(word)
+                    || ["AP", "RP", "AR", "T", "LIMS", "SPOS", "SPOSA"]
+                        .iter()
+                        .any(|kw| kw.eq_ignore_ascii_case(word))
+                {
                     return DecodeResult::NeedsGrammar;
                 }
                 i = eq + 1;
@@ -975,26 +1022,25 @@ fn decode_line_inner<'a>(
                         // call in the full grammar - unless it is reserved
                         // (control flow), a frame keyword (frame_op at block
                         // start, a loud error mid-block), DEF (opens a
-                        // definition), or the word operators DIV/MOD (which
+                        // definition) or DEFINE (a macro), the subprogram
+                        // keywords PROC/EXTERN/CALL/MCALL/RET, or the word
+                        // operators DIV/MOD (which
                         // would continue a preceding expression, e.g.
                         // `X=5 DIV R1`); those take the full grammar.
                         if is_reserved_word(word)
                             || crate::interpret_rules::FRAME_KEYWORDS
                                 .iter()
                                 .any(|kw| kw.eq_ignore_ascii_case(word))
-                            || word.eq_ignore_ascii_case("DEF")
+                            || ["DEF", "DEFINE", "PROC", "EXTERN", "CALL", "MCALL", "RET"]
+                                .iter()
+                                .any(|kw| kw.eq_ignore_ascii_case(word))
                             || word.eq_ignore_ascii_case("DIV")
                             || word.eq_ignore_ascii_case("MOD")
                         {
                             return DecodeResult::NeedsGrammar;
                         }
-                        // pest's non_returning_function_call span includes
-                        // the whitespace skipped while attempting the
-                        // optional argument list; replicate it in the value.
-                        let mut ws_end = i;
-                        skip_ws(&mut ws_end);
-                        let value = &line[word_start..ws_end];
-                        i = ws_end;
+                        let value = &line[word_start..i];
+                        skip_ws(&mut i);
                         let typo_warn = crate::interpret_rules::looks_like_axis_word_typo(word);
                         arena.push(Word::Call { value, typo_warn });
                     }
@@ -1140,6 +1186,110 @@ mod tests {
         }
     }
 
+    #[test]
+    fn g91_dimensioning_matches_full_parse() {
+        for program in [
+            // mode switch on its own line, then plain and dynamic words
+            "G1 X10 Y10 F100\nG91\nX5 Y5\nF_M = 2\nX=(F_M*2) F200\nG90 X0",
+            // G91 after the axis words still applies to the whole block
+            "G1 X10 E0\nX5 E1 G91\nX5\nX1 G90",
+            // IC() under G91, AC() (grammar-only) on a decoded program
+            "G1 X10\nG91 X=IC(1)\nX=AC(3)\nX2",
+            // G91 before any absolute position (warning path)
+            "G91 G1 X5\nX5",
+        ] {
+            assert_paths_agree(program, false);
+        }
+    }
+
+    #[test]
+    fn rotated_frame_matches_full_parse() {
+        for program in [
+            "G1 X0 Y0 Z0 F100\nAROT Z=30\nX10\nY5 Z1\nX=IC(2)\nATRANS X5\nX0 Y0",
+            // RPL in a plane selected on a decoded line; Y is not yet positioned
+            "G18 G1 X0 Z0\nROT RPL=45\nX10\nZ=F_M*2\nROT\nX1",
+            // scale and mirror, with the G2/G3 swap on decoded motion words
+            "G1 X0 Y0 E0\nSCALE X2 E3\nX1 E1\nAMIRROR Y0\nG2 X4 Y0 I1 J0\nX=IC(1)\nMIRROR\nG3 X0",
+        ] {
+            assert_paths_agree(program, true);
+        }
+    }
+
+    #[test]
+    fn tool_radius_compensation_matches_full_parse() {
+        for program in [
+            // decoded lines around a compensated contour, with an infeed
+            "$TC_DP6[1,1]=4\nG0 X0 Y-10 Z5\nG42 D1 G1 X0 Y0 F100\nZ-1\nX20\nG2 X30 Y10 I0 J10\nG1 G451 Y30\nG40 X40 Y40\n",
+            // KONT approach, G41 switched to G42 on the contour
+            "$TC_DP6[1,1]=2\nG0 X-5 Y0\nKONT G41 D1 G1 X0 F100\nY10\nG42 X10\nG40 G0 X20\n",
+        ] {
+            assert_paths_agree(program, false);
+        }
+    }
+
+    #[test]
+    fn inch_programs_match_full_parse() {
+        for program in [
+            // decoded axis, circle and feed words under each unit
+            "G0 X0 Y0\nG70 G1 X1 Y1 F10\nG2 X2 Y1 I0.5 J0\nG700 G1 X3 F10\nG4 F2\nR1=2\nX=R1*2 Y=IC(R1)\nG71 X10 A90\n",
+            "G700\nG0 X1\nG33 Z-1 K0.05 S100 M3\nG710 G1 X2 F500\n",
+        ] {
+            assert_paths_agree(program, false);
+        }
+    }
+
+    #[test]
+    fn spindle_state_matches_full_parse() {
+        for program in [
+            // S and M3/M4/M5 decoded, LIMS/SPOS through the grammar
+            "G0 X50 Z0\nG96 S200 LIMS=3000 M3\nG1 X10\nR2=1\nG97 X20\nS500\nSPOS=90\nM4 X30\nG4 S2\nM5\n",
+            "DIAMON G0 X100 M4 S1000\nG96 S150\nX50\nG94 X80\n",
+        ] {
+            assert_paths_agree(program, false);
+        }
+    }
+
+    #[test]
+    fn diameter_programming_matches_full_parse() {
+        for program in [
+            // DIAMON/DIAM90/DIAMOF on decoded lines, DAC() through the grammar
+            "DIAMON G18 G1 X20 Z5\nG91 X4\nX=IC(2)\nDIAM90 X2\nG90 X50\nX=DAC(30)\nDIAMOF X50\n",
+            "DIAMON G18 G0 X0 Z0\nG2 X40 Z20 I0 K20\nG3 X0 Z40 I-20 K0\n",
+        ] {
+            assert_paths_agree(program, false);
+        }
+    }
+
+    #[test]
+    fn tool_length_compensation_matches_full_parse() {
+        for program in [
+            // T words on decoded lines, the length taken with the next move
+            "$TC_DP3[3,1]=80\n$TC_DP4[3,1]=1\nG0 X0 Y0 Z5\nT3\nM6\nD1 X1\nZ5\nG18 Y2 T=4\nD0 Z1\n",
+            // frame-suppressed blocks keep the tool length
+            "$TC_DP3[1,2]=40\nT1 M6 D2\nG54 G0 Z1\nG53 Z1\nSUPA X2 Z3\n",
+        ] {
+            assert_paths_agree(program, false);
+        }
+    }
+
+    #[test]
+    fn work_offset_matches_full_parse() {
+        // G54 selected on decoded lines, in the block it moves (either order),
+        // under a rotation, and deselected by G500.
+        assert_paths_agree(
+            "$P_UIFR[1]=CTRANS(X,10,Y,20)\nG0 X1 Y1\nX1 G54\nAROT Z=90\nG55 Y2\nG54 X0\nG500 Y1",
+            true,
+        );
+    }
+
+    #[test]
+    fn frame_suppression_matches_full_parse() {
+        assert_paths_agree(
+            "TRANS X5 Z10\nG0 X1 Z1\nG53 Z0\nX2\nsupa Z-5\nG91 G153 X1\nG90 Y0\nAROT Z=90\nG53 X5\nY1",
+            true,
+        );
+    }
+
     #[test]
     fn ic_undefined_multiplier_matches_full_parse() {
         // Undefined IDENT: identical error without allow_undefined_variables,
@@ -1199,6 +1349,27 @@ mod tests {
             "Y2O\nX10Y20",
             // calls that collide with special words are not claimed
             "DEF REAL DEPTH=2.5\nX=DEPTH\nTRANS X10\nX1\nTRANS\nX2",
+            // section repetitions return to the block after the REPEAT
+            "LAB1: X1\nX2\nREPEAT LAB1 P=2\nREPEATB LAB1\nST: Y1\nENDLABEL: Y2\nREPEAT ST ENDLABEL P=2\nX3",
+            // macros: the DEFINE takes the grammar, the decoded uses expand
+            "DEFINE LIFT AS G0 Z=IC(5)\nDEFINE SPIN AS M3 S1000\nG1 X1 Z0\nLIFT\nN20 X2 LIFT SPIN ;up\nX3",
+            // polar coordinates: AP/RP and pole definitions take the grammar
+            "G0 X1 Y1\nG111 X5 Y5\nG1 AP=90 RP=2\nX3\nG110 AP=0 RP=1\nG2 AP=180\nY2",
+            // opening-angle arcs resolve at the end of the block
+            "G1 X0 Y0\nG3 X10 Y0 AR=90\nG2 I5 AR=180\nX30",
+            // CIP intermediate points, also after G91 and under a frame
+            "G1 X0 Y0 Z5\nCIP X20 Y0 I1=10 J1=5\nG91\nCIP X10 I1 = 5 K1=AC(0)\nG90\nTRANS X100\nCIP X20 I1=10 J1=5\nX9",
+            // polynomial coefficients resolve at the end of the block
+            "G1 X10 Y0\nPOLY PO[X]=(0,-10) PO[Y]=(10) PO[]=(2,1)\nPO[X]=(20, 1, 2) PL=2\nX5\nG1 X0",
+            // involutes are checked, and AR= resolved, at the end of the block
+            "G1 X10 Y0\nINVCCW I=AC(0) J=AC(0) CR=10 AR=90\nINVCW X10 Y0 I=AC(0) J=AC(0) CR=10\nINVCCW\nX15.707963 Y10 I-10 CR=10\nG1 X0",
+            "G1 X10 Y0\nINVCCW X15.707963 Y10 I-10 CR=10\nMIRROR X0\nINVCW X10 Y0 I=AC(0) J=AC(0) CR=10",
+            // thread blocks resolve their lead and feed at the end of the block
+            "G1 X20 Z5 F300 S1000 M3\nG33 Z-30 K1.5 SF=90\nG34 Z-40 K2 F0.1\nZ-45 K2 F0.2\nG1 X30\nX40",
+            // spline blocks check their PL at the end of the block
+            "SPLINEPATH(1, X, Y)\nASPLINE X1 Y1 PL=2\nX2 Y0 PL=0",
+            // generated blocks, jumps out of them included
+            "R1=4\nEXECSTRING(\"G1 X\" << R1)\nEXECSTRING(\"GOTOF END\")\nX9\nEND: EXECSTRING(\"Y\" << R1 * 2)",
         ] {
             assert_paths_agree(program, false);
         }
@@ -1206,6 +1377,40 @@ mod tests {
         assert_paths_agree("G1 CROTS X0", false);
     }
 
+    /// Bare calls resolved on a search path (also with a pass count), and
+    /// the modal call, run the subprogram from both paths after the rest of
+    /// the calling block.
+    #[test]
+    fn resolved_subprogram_calls_match_full_parse() {
+        let dir = std::env::temp_dir().join(format!("nc-stage1-subprograms-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        std::fs::write(dir.join("L10.SPF"), "G91 X1\nM17\n").expect("write subprogram");
+        std::fs::write(dir.join("STEP.SPF"), "PROC STEP SAVE\nG91 Y1 L10\nRET\n").expect("write subprogram");
+        let program = "G1 X0 Y0 F100\nX5 L10\nL10 P2\nN20 STEP ;twice\nSTEP\nX=R1 OTHER_CALL\nMCALL L10\nG90 Y5\nF200\nY6 STEP\nMCALL\nY7\n";
+        let _guard = env_lock();
+        let [(full_table, full_state), (fast_table, fast_state)] = [false, true].map(|stage1| {
+            std::env::set_var("NC_STAGE1", if stage1 { "1" } else { "0" });
+            let result = crate::interpreter::nc_to_table_with_options(
+                program,
+                None,
+                None,
+                None,
+                10_000,
+                false,
+                None,
+                true,
+                None,
+                crate::interpreter::InterpreterOptions::default().with_subprogram_dirs(vec![dir.clone()]),
+            );
+            std::env::remove_var("NC_STAGE1");
+            result.expect("program should interpret")
+        });
+        assert_eq!(sorted_columns(&full_table), sorted_columns(&fast_table));
+        assert_eq!(full_state.axes, fast_state.axes);
+        assert_eq!(full_state.axes["X"], 12.0);
+        assert_eq!(full_state.axes["Y"], 19.0);
+    }
+
     #[test]
     fn havoc_shaped_program_matches_full_parse() {
         // A miniature of the real Havoc file exercising every new form.
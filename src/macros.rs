@@ -0,0 +1,196 @@
+//! Macros: `DEFINE M_SPIN AS M3 S1000` binds a name to replacement text, and
+//! a later block using the name as a word (`G1 X10 M_SPIN`) runs as if the
+//! text had been written in its place.
+//!
+//! Expansion is textual and happens per block, before interpretation: the
+//! block's source line is scanned for identifiers naming a macro, each is
+//! replaced by its text (expanded in turn, so macros may use macros), and
+//! the result is parsed again as a single block (see
+//! [`crate::interpret_rules::interpret_generated_block`]). Quoted strings and the
+//! trailing comment are left alone, as are the block number and jump label.
+//! A macro that reaches itself, directly or through others, is an error both
+//! where it is defined and where it is used.
+//!
+//! Macros are global: one defined in a subprogram stays defined after it
+//! returns, like the definitions a control reads from its macro files.
+//! Blocks spanning several lines (the headers of IF/WHILE/... structures)
+//! are not expanded.
+
+use crate::errors::ParsingError;
+use crate::state::State;
+use std::collections::HashMap;
+
+/// Record a `DEFINE name AS text` statement. The definition is rejected when
+/// expanding it would never terminate.
+pub(crate) fn define(name: &str, text: &str, line_no: usize, state: &mut State) -> Result<(), ParsingError> {
+    let name = name.to_uppercase();
+    let previous = state.macros.insert(name.clone(), text.trim_end().to_string());
+    let mut chain = Vec::new();
+    if let Err(chain) = expand_into(&name, &state.macros, &mut chain, &mut String::new()) {
+        match previous {
+            Some(text) => state.macros.insert(name, text),
+            None => state.macros.remove(&name),
+        };
+        return Err(recursive_macro(chain, line_no, state));
+    }
+    Ok(())
+}
+
+/// The text of the block `text` with its macros expanded, or `None` when it
+/// uses none (or is a `DEFINE` itself, whose name must not be replaced).
+pub(crate) fn expand_block(text: &str, line_no: usize, state: &State) -> Result<Option<String>, ParsingError> {
+    if state.macros.is_empty() || text.contains('\n') {
+        return Ok(None);
+    }
+    let body_start = skip_block_prefix(text);
+    let body = &text[body_start..];
+    let first_word = body
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or("");
+    if first_word.eq_ignore_ascii_case("DEFINE") {
+        return Ok(None);
+    }
+    let mut expanded = text[..body_start].to_string();
+    let mut chain = Vec::new();
+    match expand_into(body, &state.macros, &mut chain, &mut expanded) {
+        Ok(false) => Ok(None),
+        Ok(true) => Ok(Some(expanded)),
+        Err(chain) => Err(recursive_macro(chain, line_no, state)),
+    }
+}
+
+/// Append `text` to `out` with every macro name replaced by its expansion.
+/// Returns whether anything was replaced, or the chain of names that led
+/// back to a macro already being expanded.
+fn expand_into(
+    text: &str,
+    macros: &HashMap<String, String>,
+    chain: &mut Vec<String>,
+    out: &mut String,
+) -> Result<bool, Vec<String>> {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    let mut copied = 0;
+    let mut replaced = false;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += 1;
+                }
+                i = (i + 1).min(bytes.len());
+            }
+            b';' => break,
+            b if is_word_byte(b) => {
+                let start = i;
+                while i < bytes.len() && is_word_byte(bytes[i]) {
+                    i += 1;
+                }
+                // Numbers and $-variables are never macro names.
+                if !(bytes[start].is_ascii_alphabetic() || bytes[start] == b'_') {
+                    continue;
+                }
+                let name = text[start..i].to_uppercase();
+                let Some(replacement) = macros.get(&name) else {
+                    continue;
+                };
+                if chain.contains(&name) {
+                    chain.push(name);
+                    return Err(std::mem::take(chain));
+                }
+                out.push_str(&text[copied..start]);
+                chain.push(name);
+                expand_into(replacement, macros, chain, out)?;
+                chain.pop();
+                copied = i;
+                replaced = true;
+            }
+            _ => i += 1,
+        }
+    }
+    out.push_str(&text[copied..]);
+    Ok(replaced)
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'$'
+}
+
+/// Byte offset just past the block number and jump label, if any.
+fn skip_block_prefix(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let skip_ws = |mut i: usize| {
+        while i < bytes.len() && (bytes[i] == b' ' || bytes[i] == b'\t') {
+            i += 1;
+        }
+        i
+    };
+    let mut i = skip_ws(0);
+    if bytes.get(i) == Some(&b'N') && bytes.get(i + 1).is_some_and(u8::is_ascii_digit) {
+        i += 1;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        i = skip_ws(i);
+    }
+    let word = bytes[i..]
+        .iter()
+        .take_while(|b| b.is_ascii_alphanumeric() || **b == b'_')
+        .count();
+    if word >= 2 && bytes.get(i + word) == Some(&b':') {
+        i = skip_ws(i + word + 1);
+    }
+    i
+}
+
+fn recursive_macro(chain: Vec<String>, line_no: usize, state: &State) -> ParsingError {
+    ParsingError::RecursiveMacro {
+        line_no,
+        preview: state.get_line(line_no).unwrap_or("").to_string(),
+        name: chain.last().cloned().unwrap_or_default(),
+        chain: chain.join(" -> "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(definitions: &[(&str, &str)]) -> HashMap<String, String> {
+        definitions
+            .iter()
+            .map(|(name, text)| (name.to_string(), text.to_string()))
+            .collect()
+    }
+
+    fn expand(text: &str, macros: &HashMap<String, String>) -> Result<String, Vec<String>> {
+        let mut out = String::new();
+        expand_into(text, macros, &mut Vec::new(), &mut out).map(|_| out)
+    }
+
+    #[test]
+    fn replaces_whole_words_outside_strings_and_comments() {
+        let macros = table(&[("LIFT", "G0 Z=IC(5)"), ("SPIN", "M3 S1000"), ("UP", "LIFT SPIN")]);
+        assert_eq!(expand("G1 X10 lift", &macros).unwrap(), "G1 X10 G0 Z=IC(5)");
+        assert_eq!(expand("UP ; LIFT", &macros).unwrap(), "G0 Z=IC(5) M3 S1000 ; LIFT");
+        assert_eq!(
+            expand("MSG(\"LIFT\") LIFTER=1 $LIFT", &macros).unwrap(),
+            "MSG(\"LIFT\") LIFTER=1 $LIFT"
+        );
+    }
+
+    #[test]
+    fn reports_the_cycle() {
+        let macros = table(&[("A1", "X1 B1"), ("B1", "C1"), ("C1", "A1")]);
+        assert_eq!(expand("G1 A1", &macros).unwrap_err(), ["A1", "B1", "C1", "A1"]);
+    }
+
+    #[test]
+    fn block_number_and_label_are_kept() {
+        assert_eq!(skip_block_prefix("N10 LIFT"), 4);
+        assert_eq!(skip_block_prefix("N10 LIFT: LIFT"), 10);
+        assert_eq!(skip_block_prefix("LIFT"), 0);
+    }
+}
@@ -6,19 +6,34 @@ use clap::{Arg, ArgAction, Command};
 use std::collections::HashMap;
 use std::io::{self};
 
+mod arcs;
+mod cycles;
 mod errors;
 mod flatten;
+mod frame;
 mod interpret_rules;
 mod interpreter;
+mod involute;
 mod line_driver;
+mod macros;
 mod modal_groups;
 mod output;
+mod polar;
+mod poly;
+mod spindle;
+mod splines;
 mod state;
 mod structure_scan;
+mod subprogram;
+mod threads;
+mod tool_radius;
+mod tools;
 mod types;
+mod units;
 
-use interpreter::nc_to_table;
+use interpreter::{nc_to_table_with_options, InterpreterOptions};
 use std::path::PathBuf;
+use units::Unit;
 
 fn main() -> io::Result<()> {
     // Define and interpret the command-line arguments using `clap`
@@ -87,16 +102,39 @@ fn main() -> io::Result<()> {
             Arg::new("flatten_tolerance")
                 .long("flatten-tolerance")
                 .value_name("TOLERANCE")
-                .help("Flatten arcs (G2/G3) and splines (ASPLINE/BSPLINE/CSPLINE) into G1 moves, keeping the polyline within TOLERANCE (max deviation, in path units) of the true curve")
+                .help("Flatten arcs (G2/G3), CIP/CT circles, splines (ASPLINE/BSPLINE/CSPLINE), POLY polynomials and INVCW/INVCCW involutes into G1 moves, keeping the polyline within TOLERANCE (max deviation, in path units) of the true curve")
                 .num_args(1)
                 .value_parser(clap::value_parser!(f64)),
         )
+        .arg(
+            Arg::new("keep_contour")
+                .long("keep-contour")
+                .help("With tool radius compensation (G41/G42), keep the programmed contour in the CONTOUR_X/CONTOUR_Y/CONTOUR_Z columns next to the tool-centre path")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("unit")
+                .long("unit")
+                .value_name("UNIT")
+                .help("Unit of the output lengths; G70/G71 (G700/G710) programs are converted to it")
+                .num_args(1)
+                .value_parser(["mm", "inch"])
+                .default_value("mm"),
+        )
         .arg(
             Arg::new("allow_undefined_variables")
                 .long("allow-undefined-variables")
                 .help("Allow undefined variables in the input file (this will initialize these variables as 0.0), default is false")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("subprogram_dir")
+                .long("subprogram-dir")
+                .value_name("DIR")
+                .help("Directory searched for called subprograms (NAME.SPF, then NAME.MPF); repeat for several, searched in order")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
         .get_matches();
 
     // Retrieve the input file
@@ -129,18 +167,27 @@ fn main() -> io::Result<()> {
     let disable_forward_fill = matches.get_flag("disable_forward_fill");
 
     let input = std::fs::read_to_string(matches.get_one::<String>("input").unwrap())
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Error reading input file: {}", e)))?;
+        .map_err(|e| io::Error::other(format!("Error reading input file: {}", e)))?;
 
     let initial_state = matches
         .get_one::<String>("initial_state")
         .map(std::fs::read_to_string)
         .transpose()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Error reading initial state file: {}", e)))?;
+        .map_err(|e| io::Error::other(format!("Error reading initial state file: {}", e)))?;
 
     let allow_undefined_variables = matches.get_flag("allow_undefined_variables");
     let flatten_tolerance = matches.get_one::<f64>("flatten_tolerance").copied();
+    let keep_contour = matches.get_flag("keep_contour");
+    let unit = matches
+        .get_one::<String>("unit")
+        .and_then(|name| Unit::from_name(name))
+        .unwrap_or_default();
+    let subprogram_dirs: Vec<PathBuf> = matches
+        .get_many::<PathBuf>("subprogram_dir")
+        .map(|dirs| dirs.cloned().collect())
+        .unwrap_or_default();
 
-    match nc_to_table(
+    match nc_to_table_with_options(
         &input,
         initial_state.as_deref(),
         axes_override.clone(),
@@ -150,6 +197,10 @@ fn main() -> io::Result<()> {
         axis_index_map,
         allow_undefined_variables,
         flatten_tolerance,
+        InterpreterOptions::default()
+            .with_keep_contour(keep_contour)
+            .with_unit(unit)
+            .with_subprogram_dirs(subprogram_dirs),
     ) {
         Ok((table, _state)) => {
             let mut output_path = PathBuf::from(input_path.clone());
@@ -202,6 +202,9 @@ impl VariableEvents {
 /// Where finished rows go: collected for the batch table, or pushed into a
 /// bounded channel that a streaming consumer drains while interpretation is
 /// still running.
+// One sink per run, never moved in a hot loop: boxing the batch variant would
+// only add an indirection to every row delivery.
+#[allow(clippy::large_enum_variant)]
 enum RowSink {
     Collect(Vec<Row>),
     #[allow(dead_code)] // constructed by the python-feature bindings, not the bin
@@ -336,8 +339,12 @@ pub struct OutputRows {
     /// sink: arc and spline rows are replaced by sampled runs of G1 rows
     /// before they reach the sink (see [`crate::flatten`]).
     flattener: Option<crate::flatten::Flattener>,
-    /// Whether the once-per-run G91 warning has fired (see `flush`).
-    warned_g91: bool,
+    /// Optional tool radius compensation ahead of the flattener: moves the
+    /// rows onto the tool-centre path (see [`crate::tool_radius`]).
+    compensator: Option<crate::tool_radius::Compensator>,
+    /// Line number given to every row started while set (see
+    /// [`OutputRows::pin_line`]).
+    pinned_line: Option<usize>,
 }
 
 impl OutputRows {
@@ -347,7 +354,8 @@ impl OutputRows {
             sink: RowSink::Collect(Vec::new()),
             record_variables: false,
             flattener: None,
-            warned_g91: false,
+            compensator: None,
+            pinned_line: None,
         }
     }
 
@@ -358,7 +366,8 @@ impl OutputRows {
             sink: RowSink::Stream(sender),
             record_variables: true,
             flattener: None,
-            warned_g91: false,
+            compensator: None,
+            pinned_line: None,
         }
     }
 
@@ -416,7 +425,24 @@ impl OutputRows {
             // log: on only when the caller opted in via `include_variables`.
             record_variables,
             flattener: None,
-            warned_g91: false,
+            compensator: None,
+            pinned_line: None,
+        }
+    }
+
+    /// Tell the flattener (if any) the frame active for the rows that follow
+    /// (see [`crate::flatten::Flattener::set_frame`]).
+    pub fn set_frame(&mut self, linear: Option<crate::frame::Matrix3>, mirrored: [bool; 3]) {
+        if let Some(flattener) = &mut self.flattener {
+            flattener.set_frame(linear, mirrored);
+        }
+    }
+
+    /// Tell the flattener (if any) the axes `SPLINEPATH` declared (see
+    /// [`crate::flatten::Flattener::set_spline_path`]).
+    pub fn set_spline_path(&mut self, axes: &[String]) {
+        if let Some(flattener) = &mut self.flattener {
+            flattener.set_spline_path(axes);
         }
     }
 
@@ -426,6 +452,25 @@ impl OutputRows {
         self.flattener = Some(flattener);
     }
 
+    /// Install the tool radius compensation stage (see
+    /// [`crate::tool_radius::Compensator`]); every subsequent row passes
+    /// through it before the flattener.
+    pub(crate) fn set_compensator(&mut self, compensator: crate::tool_radius::Compensator) {
+        self.compensator = Some(compensator);
+    }
+
+    pub(crate) fn has_compensator(&self) -> bool {
+        self.compensator.is_some()
+    }
+
+    /// Tell the compensation stage the compensation for the rows that follow
+    /// (`None`: G40).
+    pub(crate) fn set_radius_compensation(&mut self, setting: Option<crate::tool_radius::Setting>) {
+        if let Some(compensator) = &mut self.compensator {
+            compensator.set_setting(setting);
+        }
+    }
+
     /// Number of committed rows in a `Collect` sink (0 for streaming sinks).
     /// Lets the experimental resumable VM (#47) pause at a row boundary.
     #[allow(dead_code)]
@@ -436,15 +481,14 @@ impl OutputRows {
         }
     }
 
-    /// Deep-clone a checkpointable `Collect`-sink output: the committed rows,
-    /// the in-flight row, and the warn latches. `None` when the sink is a
-    /// streaming channel or a flattener is installed (their state isn't
-    /// snapshottable this simply). For the experimental resumable VM (#47);
+    /// Deep-clone a checkpointable `Collect`-sink output: the committed rows
+    /// and the in-flight row. `None` when the sink is a streaming channel or a
+    /// flattener or compensator is installed (their state isn't snapshottable this simply). For the experimental resumable VM (#47);
     /// the whole-file forward-fill happens later in `Table::from_rows`, so a
     /// Collect snapshot needs no columnar carry.
     #[allow(dead_code)]
     pub(crate) fn snapshot_collect(&self) -> Option<OutputRows> {
-        if self.flattener.is_some() {
+        if self.flattener.is_some() || self.compensator.is_some() {
             return None;
         }
         match &self.sink {
@@ -453,7 +497,8 @@ impl OutputRows {
                 sink: RowSink::Collect(rows.clone()),
                 record_variables: self.record_variables,
                 flattener: None,
-                warned_g91: self.warned_g91,
+                compensator: None,
+                pinned_line: self.pinned_line,
             }),
             _ => None,
         }
@@ -470,11 +515,36 @@ impl OutputRows {
         }
     }
 
-    /// Route a row to the sink, passing it through the flattener first when one
-    /// is installed, and returning an emptied row the sink no longer needs
-    /// (batch path) so the caller can reuse its allocation as the next
-    /// `current`. `None` when nothing is available to recycle.
+    /// Route a row to the sink, passing it through the compensator and the
+    /// flattener first when installed, and returning an emptied row the sink
+    /// no longer needs (batch path) so the caller can reuse its allocation as
+    /// the next `current`. `None` when nothing is available to recycle.
     fn deliver(&mut self, row: Row) -> Result<Option<Row>, ParsingError> {
+        if let Some(mut compensator) = self.compensator.take() {
+            let mut compensated = Vec::new();
+            compensator.push(row, &mut compensated);
+            self.compensator = Some(compensator);
+            return self.deliver_all(compensated);
+        }
+        self.deliver_flattened(row)
+    }
+
+    /// Deliver `rows` past the compensator, keeping one recycled row for the
+    /// caller and handing the others back to the pool.
+    fn deliver_all(&mut self, rows: Vec<Row>) -> Result<Option<Row>, ParsingError> {
+        let mut recycled = None;
+        for row in rows {
+            if let Some(r) = self.deliver_flattened(row)? {
+                if let Some(prev) = recycled.replace(r) {
+                    self.reclaim(prev);
+                }
+            }
+        }
+        Ok(recycled)
+    }
+
+    /// Route a row to the sink through the flattener, if one is installed.
+    fn deliver_flattened(&mut self, row: Row) -> Result<Option<Row>, ParsingError> {
         if let Some(mut flattener) = self.flattener.take() {
             let mut flattened = Vec::new();
             flattener.push(row, &mut flattened);
@@ -516,18 +586,6 @@ impl OutputRows {
         }
         let mut row = std::mem::take(&mut self.current);
         rekey_g4_dwell(&mut row);
-        // G91 is parsed but incremental dimensioning is NOT applied: every
-        // axis value is emitted as if absolute, so positions are wrong from
-        // this block on. Loud once-per-run warning - never butcher silently.
-        if !self.warned_g91 {
-            if matches!(row.cells.get("gg14_wp_measure_mode"), Some(Value::Str(code)) if code == "G91") {
-                crate::state::emit_warning(format_args!(
-                    "Warning [line {}]: G91 incremental dimensioning is not interpreted - axis values are emitted as absolute positions and will be wrong from this block on",
-                    row.line_no
-                ));
-                self.warned_g91 = true;
-            }
-        }
         // `mem::take` above left `current` as an empty (unallocated) default; if
         // the sink handed back a pooled row, adopt its retained allocation for
         // the next block instead of allocating fresh on the first cell insert.
@@ -540,10 +598,17 @@ impl OutputRows {
     /// Begin the row for the block at `line_no`, flushing the previous row.
     pub fn start_row(&mut self, line_no: usize) -> Result<(), ParsingError> {
         self.flush()?;
-        self.current.line_no = line_no;
+        self.current.line_no = self.pinned_line.unwrap_or(line_no);
         Ok(())
     }
 
+    /// Give every row started from now on the line number `line_no` instead
+    /// of its own (`None` lifts this), returning the previous setting: the
+    /// rows a built-in cycle generates belong to the block calling it.
+    pub(crate) fn pin_line(&mut self, line_no: Option<usize>) -> Option<usize> {
+        std::mem::replace(&mut self.pinned_line, line_no)
+    }
+
     /// The row currently being filled. Named after `Vec::last_mut`, which
     /// this type replaced; always `Some`.
     pub fn last_mut(&mut self) -> Option<&mut CellMap> {
@@ -564,6 +629,14 @@ impl OutputRows {
     /// streaming).
     pub fn finish(mut self) -> Result<Vec<Row>, ParsingError> {
         self.flush()?;
+        // A compensated element waits for the next one, which never comes.
+        if let Some(mut compensator) = self.compensator.take() {
+            let mut compensated = Vec::new();
+            compensator.finish(&mut compensated);
+            for row in compensated {
+                self.deliver_flattened(row)?;
+            }
+        }
         // A program ending inside a spline still owes its buffered curve.
         if let Some(mut flattener) = self.flattener.take() {
             let mut flattened = Vec::new();
@@ -699,7 +772,10 @@ const KNOWN_AXIS_COLUMNS: &[&str] = &[
 pub fn is_string_column(name: &str) -> bool {
     MODAL_G_GROUPS.contains(&name)
         || NON_MODAL_G_GROUPS.contains(&name)
-        || matches!(name, "T" | "non_returning_function_call" | "comment")
+        || matches!(
+            name,
+            "T" | SPINDLE_DIR_COLUMN | "non_returning_function_call" | "comment"
+        )
 }
 
 /// Whether a column is forward-filled in the sanitized table: value columns
@@ -712,7 +788,7 @@ pub fn is_forward_filled_column(name: &str) -> bool {
         && name != DWELL_COLUMN
         && !is_string_column(name)
         && !BLOCK_ADDRESSES.contains(&name);
-    is_value || MODAL_G_GROUPS.contains(&name)
+    is_value || MODAL_G_GROUPS.contains(&name) || name == SPINDLE_DIR_COLUMN
 }
 
 /// Per-block dwell-time column: on a `G4` block the `F` word is the dwell
@@ -740,6 +816,12 @@ fn rekey_g4_dwell(row: &mut Row) {
     }
 }
 
+/// Spindle speed in rpm and state (`CW`, `CCW`, `STOP`, `POS`) derived by
+/// [`crate::spindle`] for every row once the program uses the spindle.
+/// Both are forward-filled like the modal columns.
+pub const SPINDLE_RPM_COLUMN: &str = "spindle_rpm";
+pub const SPINDLE_DIR_COLUMN: &str = "spindle_dir";
+
 /// Marker column emitted by the curve flattener: `1.0` on rows it generated
 /// (intermediate polyline samples), absent on programmed positions - so
 /// filtering on null recovers the original toolpath points. Per-row like the
@@ -775,6 +857,8 @@ fn canonical_order(present: &HashSet<&'static str>) -> Vec<&'static str> {
     for &name in KNOWN_AXIS_COLUMNS {
         push_if_present(name, &mut ordered);
     }
+    push_if_present(intern_column(SPINDLE_RPM_COLUMN), &mut ordered);
+    push_if_present(intern_column(SPINDLE_DIR_COLUMN), &mut ordered);
     // Any remaining value columns (e.g. user-configured extra axes), in
     // deterministic alphabetical order.
     let mut extra: Vec<&'static str> = present
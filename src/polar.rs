@@ -0,0 +1,173 @@
+//! Polar coordinates (manual 3.5.5): `AP=` (polar angle) and `RP=` (polar
+//! radius) program the end point of a G0/G1/G2/G3 move in the working plane
+//! relative to a pole, which G110/G111/G112 define.
+//!
+//! * `G111 X.. Y..` sets the pole in the workpiece coordinate system, `G110`
+//!   relative to the last programmed point and `G112` relative to the last
+//!   pole. The pole may also be given in polar form (`G112 AP=30 RP=10`).
+//!   Without a definition the pole is the workpiece zero.
+//! * The angle is measured counter-clockwise from the plane's abscissa
+//!   (X for G17, Z for G18, Y for G19). `AP` follows G90/G91 and `AC()` /
+//!   `IC()`; `RP` is always absolute. Both are modal.
+//! * G2/G3 with polar coordinates circle around the pole: the row gets the
+//!   centre offsets I/J/K besides the Cartesian end point.
+//!
+//! The output only holds Cartesian machine coordinates; AP/RP themselves
+//! never appear as columns.
+
+use crate::arcs::{move_plane_axes, CENTRE_OFFSETS, GEOMETRY_AXES};
+use crate::errors::ParsingError;
+use crate::output::OutputRows;
+use crate::state::{Dimensioning, State};
+use crate::types::Value;
+
+/// What a G110/G111/G112 pole definition is relative to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PoleReference {
+    /// G110: the last programmed point.
+    LastPoint,
+    /// G111: the workpiece zero.
+    Zero,
+    /// G112: the last pole.
+    LastPole,
+}
+
+/// The pole, the modal polar angle and radius, and the polar words of the
+/// block being interpreted.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Polar {
+    /// The pole in local X/Y/Z coordinates (only the working-plane
+    /// components are used).
+    pole: [f64; 3],
+    angle: f64,
+    radius: f64,
+    /// The pole definition programmed in this block.
+    reference: Option<PoleReference>,
+    block_angle: Option<(f64, Dimensioning)>,
+    block_radius: Option<f64>,
+    /// The geometry axis words of a pole-definition block, by X/Y/Z slot.
+    block_axes: [Option<f64>; 3],
+}
+
+impl Polar {
+    /// Forget the previous block's polar words.
+    pub(crate) fn begin_block(&mut self) {
+        self.reference = None;
+        self.block_angle = None;
+        self.block_radius = None;
+        self.block_axes = [None; 3];
+    }
+
+    /// Note a pole definition in this block if `code` is G110, G111 or G112.
+    pub(crate) fn select_pole(&mut self, code: &str) {
+        let reference = match code.to_ascii_uppercase().as_str() {
+            "G110" => PoleReference::LastPoint,
+            "G111" => PoleReference::Zero,
+            "G112" => PoleReference::LastPole,
+            _ => return,
+        };
+        self.reference = Some(reference);
+    }
+}
+
+/// Take a programmed `key = value` word that belongs to the polar
+/// coordinates: `AP`/`RP`, and the plane axes of a pole definition (which
+/// define the pole rather than move). Returns `false` for any other word.
+pub(crate) fn take_word(key: &str, value: f64, dimensioning: Dimensioning, state: &mut State) -> bool {
+    if key.eq_ignore_ascii_case("AP") {
+        state.polar.block_angle = Some((value, dimensioning));
+    } else if key.eq_ignore_ascii_case("RP") {
+        state.polar.block_radius = Some(value);
+    } else {
+        if state.polar.reference.is_none() {
+            return false;
+        }
+        let [abscissa, ordinate, _] = state.plane.axes();
+        match GEOMETRY_AXES.iter().position(|axis| *axis == key) {
+            Some(slot) if slot == abscissa || slot == ordinate => state.polar.block_axes[slot] = Some(value),
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Apply the block's polar words once all its other words ran: define the
+/// pole, or move the plane axes to the programmed polar end point.
+pub(crate) fn finish_block(line_no: usize, output: &mut OutputRows, state: &mut State) -> Result<(), ParsingError> {
+    let [abscissa, ordinate, _] = state.plane.axes();
+    if let Some(reference) = state.polar.reference {
+        define_pole(reference, abscissa, ordinate, state);
+        return Ok(());
+    }
+    if state.polar.block_angle.is_none() && state.polar.block_radius.is_none() {
+        return Ok(());
+    }
+    if let Some(slot) = [abscissa, ordinate]
+        .into_iter()
+        .find(|&slot| state.moved_in_block(slot))
+    {
+        return Err(ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or("").to_string(),
+            "polar coordinates".to_string(),
+            format!(
+                "{} cannot be programmed together with AP/RP; the polar end point sets both plane axes",
+                GEOMETRY_AXES[slot]
+            ),
+        ));
+    }
+    let incremental = state.incremental;
+    let polar = &mut state.polar;
+    if let Some((angle, dimensioning)) = polar.block_angle {
+        let incremental = match dimensioning {
+            Dimensioning::Modal => incremental,
+            Dimensioning::Incremental => true,
+            Dimensioning::Absolute => false,
+            Dimensioning::Diameter { incremental } | Dimensioning::Radius { incremental } => incremental,
+        };
+        polar.angle = if incremental { polar.angle + angle } else { angle };
+    }
+    if let Some(radius) = polar.block_radius {
+        polar.radius = radius;
+    }
+
+    let (sin, cos) = polar.angle.to_radians().sin_cos();
+    let end = [
+        (abscissa, polar.pole[abscissa] + polar.radius * cos),
+        (ordinate, polar.pole[ordinate] + polar.radius * sin),
+    ];
+    let pole = polar.pole;
+    let last = output.last_mut().expect("row started");
+    // An arc in polar coordinates circles around the pole.
+    let programs_centre = CENTRE_OFFSETS.iter().chain(&["CR"]).any(|key| last.get(key).is_some());
+    if state.arc_direction().is_some() && !programs_centre {
+        for (slot, _) in end {
+            let start = state.get_axis_local(GEOMETRY_AXES[slot]).unwrap_or(0.0);
+            last.insert(CENTRE_OFFSETS[slot], Value::Float(pole[slot] - start));
+        }
+    }
+    move_plane_axes(end, last, state)
+}
+
+fn define_pole(reference: PoleReference, abscissa: usize, ordinate: usize, state: &mut State) {
+    let origin = |slot: usize| match reference {
+        PoleReference::LastPoint => state.get_axis_local(GEOMETRY_AXES[slot]).unwrap_or(0.0),
+        PoleReference::Zero => 0.0,
+        PoleReference::LastPole => state.polar.pole[slot],
+    };
+    let (u, v) = (origin(abscissa), origin(ordinate));
+    let polar = &mut state.polar;
+    let (u, v) = if polar.block_angle.is_some() || polar.block_radius.is_some() {
+        let angle = polar.block_angle.map_or(0.0, |(angle, _)| angle);
+        let radius = polar.block_radius.unwrap_or(0.0);
+        let (sin, cos) = angle.to_radians().sin_cos();
+        (u + radius * cos, v + radius * sin)
+    } else {
+        (
+            u + polar.block_axes[abscissa].unwrap_or(0.0),
+            v + polar.block_axes[ordinate].unwrap_or(0.0),
+        )
+    };
+    polar.pole[abscissa] = u;
+    polar.pole[ordinate] = v;
+}
@@ -0,0 +1,192 @@
+//! Polynomial interpolation (`POLY`): per block, every path axis follows a
+//! polynomial of degree up to 5 in the parameter `p`, which runs from 0 to
+//! the block's `PL` (default 1).
+//!
+//! * `PO[X]=(xe,a2,a3,a4,a5)` programs the end point `xe` of an axis (like an
+//!   axis word, following G90/G91) and the coefficients `a2..a5`; the
+//!   constant term is the start point and the linear term follows from the
+//!   end point. Missing coefficients are 0.
+//! * `PO[]=(ge,c2,c3,c4,c5)` programs a denominator polynomial shared by the
+//!   geometry axes, which then move along the quotient of two polynomials
+//!   (conic sections such as circles are exact). Its constant term is 1 and
+//!   `ge` its value at the end of the block.
+//! * An axis programmed with a plain axis word moves linearly in `p` (a
+//!   geometry axis still shares the denominator).
+//!
+//! The interpreter resolves the geometry axes' polynomials into machine
+//! coordinates under the active frame, so the row carries them complete:
+//! `PO_X_1..PO_X_5` (and Y, Z) are the numerator coefficients from the linear
+//! term on (the constant term is the start point), `PO_1..PO_5` those of the
+//! denominator. An axis that stands still gets no coefficients. The
+//! flattener evaluates them (see [`crate::flatten`]). Coefficients of other
+//! axes are not evaluated; those axes move linearly.
+
+use crate::arcs::GEOMETRY_AXES;
+use crate::errors::ParsingError;
+use crate::output::{CellMap, OutputRows};
+use crate::state::{emit_warning, ColKind, Dimensioning, State};
+use crate::types::Value;
+
+/// The numerator coefficient columns of X, Y and Z (linear term first).
+pub(crate) const NUMERATOR_COLUMNS: [[&str; 5]; 3] = [
+    ["PO_X_1", "PO_X_2", "PO_X_3", "PO_X_4", "PO_X_5"],
+    ["PO_Y_1", "PO_Y_2", "PO_Y_3", "PO_Y_4", "PO_Y_5"],
+    ["PO_Z_1", "PO_Z_2", "PO_Z_3", "PO_Z_4", "PO_Z_5"],
+];
+/// The denominator coefficient columns (linear term first).
+pub(crate) const DENOMINATOR_COLUMNS: [&str; 5] = ["PO_1", "PO_2", "PO_3", "PO_4", "PO_5"];
+
+/// The polynomial words of the block being interpreted.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PolyWords {
+    /// `PO[X]`, `PO[Y]`, `PO[Z]`: the coefficients `a2..a5`.
+    numerators: [Option<[f64; 4]>; 3],
+    /// `PO[]`: the end value and the coefficients `c2..c5`.
+    denominator: Option<[f64; 5]>,
+}
+
+impl PolyWords {
+    /// Forget the previous block's polynomial words.
+    pub(crate) fn begin_block(&mut self) {
+        self.numerators = [None; 3];
+        self.denominator = None;
+    }
+}
+
+/// Take a `PO[axis]=(...)` word (`axis` is `None` for the denominator): move
+/// the axis to its end point, writing its machine coordinates into the
+/// block's row `last`, and keep the coefficients for [`finish_block`].
+pub(crate) fn take_coefficients(
+    axis: Option<&str>,
+    values: &[f64],
+    line_no: usize,
+    last: &mut CellMap,
+    state: &mut State,
+) -> Result<(), ParsingError> {
+    let error = |message: String| {
+        ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or("").to_string(),
+            "polynomial interpolation (PO[])".to_string(),
+            message,
+        )
+    };
+    if values.len() > 5 {
+        return Err(error(
+            "PO[] takes at most five values: the end point and a2..a5".to_string(),
+        ));
+    }
+    let coefficients: [f64; 4] = std::array::from_fn(|n| values.get(n + 1).copied().unwrap_or(0.0));
+    let Some(axis) = axis else {
+        state.poly_words.denominator = Some([
+            values[0],
+            coefficients[0],
+            coefficients[1],
+            coefficients[2],
+            coefficients[3],
+        ]);
+        return Ok(());
+    };
+    let Some((ColKind::Axis, key)) = state.resolve_output_key(axis) else {
+        return Err(error(format!("PO[{axis}] needs an axis, and '{axis}' is none")));
+    };
+    // The end point and the coefficients are lengths (see crate::units).
+    let coefficients = coefficients.map(|coefficient| crate::units::geometry(key, coefficient, state));
+    let end = crate::units::geometry(key, values[0], state);
+    let local_value = state.resolve_programmed(key, end, Dimensioning::Modal);
+    state.update_axis(key, local_value)?;
+    state.machine_outputs(key, local_value, |axis, machine_value| {
+        last.insert(axis, Value::Float(machine_value));
+    });
+    match GEOMETRY_AXES.iter().position(|geometry| *geometry == key) {
+        Some(slot) => state.poly_words.numerators[slot] = Some(coefficients),
+        None if values.len() > 1 => emit_warning(format_args!(
+            "Warning [line {line_no}]: the PO[{key}] polynomial coefficients are not evaluated; {key} moves linearly"
+        )),
+        None => {}
+    }
+    Ok(())
+}
+
+/// Resolve the block's polynomials once all its other words ran.
+pub(crate) fn finish_block(line_no: usize, output: &mut OutputRows, state: &mut State) -> Result<(), ParsingError> {
+    let words = std::mem::take(&mut state.poly_words);
+    if words.numerators.iter().all(Option::is_none) && words.denominator.is_none() {
+        return Ok(());
+    }
+    let error = |message: &str| {
+        ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or(""),
+            "polynomial interpolation (PO[])",
+            message,
+        )
+    };
+    if state.modal_word("gg01_motion") != Some("POLY") {
+        return Err(error("PO[] is only allowed with POLY"));
+    }
+    let last = output.last_mut().expect("row started");
+    let length = match last.get("PL") {
+        Some(Value::Float(length)) => *length,
+        _ => 1.0,
+    };
+    if length.is_nan() || length <= 0.0 {
+        return Err(error("the parameter interval PL must be positive"));
+    }
+    let powers: [f64; 6] = std::array::from_fn(|n| length.powi(n as i32));
+
+    // Denominator 1 + c1 p + ... + c5 p^5 with the value `ge` at p = PL.
+    let mut denominator = [1.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+    let mut end_value = 1.0;
+    if let Some([ge, c2, c3, c4, c5]) = words.denominator {
+        denominator[2..].copy_from_slice(&[c2, c3, c4, c5]);
+        let higher: f64 = (2..6).map(|n| denominator[n] * powers[n]).sum();
+        denominator[1] = (ge - 1.0 - higher) / length;
+        end_value = ge;
+    }
+    if end_value.abs() < 1e-12 {
+        return Err(error("the denominator polynomial PO[] must not end at 0"));
+    }
+
+    // Local numerators: the start point, the linear term that reaches the
+    // end point, then the programmed coefficients.
+    let mut numerators = [[0.0; 6]; 3];
+    for (slot, numerator) in numerators.iter_mut().enumerate() {
+        let start = state.block_start_local(GEOMETRY_AXES[slot]).unwrap_or(0.0);
+        let end = state.get_axis_local(GEOMETRY_AXES[slot]).unwrap_or(start);
+        numerator[0] = start;
+        if let Some(coefficients) = words.numerators[slot] {
+            numerator[2..].copy_from_slice(&coefficients);
+        }
+        let higher: f64 = (2..6).map(|n| numerator[n] * powers[n]).sum();
+        numerator[1] = (end * end_value - start - higher) / length;
+    }
+
+    // Machine coordinates: M·f(p) + t·g(p) over g(p), term by term.
+    let origin = state.machine_point([0.0; 3]);
+    let machine: [[f64; 6]; 3] = {
+        let terms: [[f64; 3]; 6] = std::array::from_fn(|n| {
+            let linear = state.machine_point(numerators.map(|numerator| numerator[n]));
+            std::array::from_fn(|slot| linear[slot] - origin[slot] + origin[slot] * denominator[n])
+        });
+        std::array::from_fn(|slot| terms.map(|term| term[slot]))
+    };
+    for (slot, numerator) in machine.iter().enumerate() {
+        let Some((ColKind::Axis, _)) = state.resolve_output_key(GEOMETRY_AXES[slot]) else {
+            continue;
+        };
+        // An axis standing still has the numerator start·g(p).
+        if (1..6).all(|n| (numerator[n] - numerator[0] * denominator[n]).abs() < 1e-12) {
+            continue;
+        }
+        for (column, value) in NUMERATOR_COLUMNS[slot].into_iter().zip(&numerator[1..]) {
+            last.insert(column, Value::Float(*value));
+        }
+    }
+    if words.denominator.is_some() {
+        for (column, value) in DENOMINATOR_COLUMNS.into_iter().zip(&denominator[1..]) {
+            last.insert(column, Value::Float(*value));
+        }
+    }
+    Ok(())
+}
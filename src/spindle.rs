@@ -0,0 +1,194 @@
+//! Spindle state: speed `S`, direction `M3`/`M4`/`M5`, constant cutting
+//! rate `G96` and positioning `SPOS` (manual 5.1, 5.3, 6.5).
+//!
+//! * `S` programs the speed in rpm, `M3` starts the spindle clockwise, `M4`
+//!   counter-clockwise and `M5` stops it. The `S` of a G4 block is a dwell,
+//!   not a speed.
+//! * `G96` (and `G961`/`G962`) turns `S` into the cutting rate, in m/min
+//!   (ft/min with inch output): the speed follows the position of the
+//!   transverse axis X, `n = S / (2π·X)`, up to the limit `LIMS=`. It is
+//!   taken at the end point of each block. `G97` (and any other feed type of
+//!   G-group 15) keeps the speed last reached, and `S` is in rpm again.
+//! * `SPOS=<angle>` (`SPOSA`, `SPOS[<n>]`) and `M19` stop the spindle in
+//!   position control until the next `M3`/`M4`.
+//!
+//! Once the program speaks of the spindle, each row gets the speed it turns
+//! at in [`SPINDLE_RPM_COLUMN`] (0 while stopped or positioned) and its state
+//! in [`SPINDLE_DIR_COLUMN`]: `CW`, `CCW`, `STOP` or `POS`. Only the master
+//! spindle is modelled.
+
+use crate::errors::ParsingError;
+use crate::output::{OutputRows, SPINDLE_DIR_COLUMN, SPINDLE_RPM_COLUMN};
+use crate::state::{emit_warning, State, TRANSVERSE_AXIS};
+use crate::types::Value;
+use crate::units::Unit;
+
+/// The feed types of G-group 15 with a constant cutting rate.
+const CONSTANT_CUTTING_RATE: [&str; 3] = ["G96", "G961", "G962"];
+
+/// What the spindle does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Direction {
+    #[default]
+    Stop,
+    Clockwise,
+    CounterClockwise,
+    /// Stopped in position control (`SPOS`, `M19`).
+    Positioned,
+}
+
+impl Direction {
+    /// The direction an M code selects, `None` for any other.
+    fn from_m_code(code: &str) -> Option<Direction> {
+        match code {
+            "M3" | "M03" => Some(Direction::Clockwise),
+            "M4" | "M04" => Some(Direction::CounterClockwise),
+            "M5" | "M05" => Some(Direction::Stop),
+            "M19" => Some(Direction::Positioned),
+            _ => None,
+        }
+    }
+
+    /// The value of [`SPINDLE_DIR_COLUMN`].
+    fn name(self) -> &'static str {
+        match self {
+            Direction::Stop => "STOP",
+            Direction::Clockwise => "CW",
+            Direction::CounterClockwise => "CCW",
+            Direction::Positioned => "POS",
+        }
+    }
+}
+
+/// The master spindle.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Spindle {
+    /// True once the program programmed the spindle: only then do rows get
+    /// the spindle columns.
+    used: bool,
+    /// True if the current block programmed `LIMS` or `SPOS`.
+    programmed: bool,
+    /// True if the current block positions the spindle (`SPOS`).
+    positioned: bool,
+    direction: Direction,
+    /// The commanded speed in rpm, whether or not the spindle turns.
+    speed: f64,
+    /// The cutting rate under G96.
+    cutting_rate: f64,
+    /// The speed limit under G96 (`LIMS=`).
+    limit: Option<f64>,
+    /// True while a constant cutting rate is active.
+    constant: bool,
+}
+
+/// Take the spindle word `LIMS=<rpm>` or `SPOS=<angle>` (`SPOSA`,
+/// `SPOS[<n>]`) of the current block, which [`finish_block`] applies; false
+/// for any other word.
+pub(crate) fn take_word(key: &str, value: f64, state: &mut State) -> bool {
+    let key = key.to_ascii_uppercase();
+    if key == "LIMS" {
+        state.spindle.limit = Some(value);
+        state.spindle.programmed = true;
+        return true;
+    }
+    let (name, spindle) = match key.split_once('[') {
+        Some((name, index)) => (name, index.trim_end_matches(']').trim()),
+        None => (key.as_str(), "1"),
+    };
+    if name != "SPOS" && name != "SPOSA" {
+        return false;
+    }
+    if spindle.parse::<f64>() != Ok(1.0) {
+        emit_warning(format_args!(
+            "Warning: {key}={value} positions spindle {spindle}; only the master spindle 1 is modelled"
+        ));
+        return true;
+    }
+    state.spindle.positioned = true;
+    state.spindle.programmed = true;
+    true
+}
+
+/// Apply the block's S, M3/M4/M5/M19, SPOS and G96/G97 once all its words
+/// ran, and give the row the spindle columns.
+pub(crate) fn finish_block(line_no: usize, output: &mut OutputRows, state: &mut State) -> Result<(), ParsingError> {
+    let last = output.last_mut().expect("row started");
+    let dwell = matches!(last.get("gg02_wait"), Some(Value::Str(code)) if code == "G4");
+    let programmed = match last.get("S") {
+        Some(&Value::Float(speed)) if !dwell => Some(speed),
+        _ => None,
+    };
+    let direction = match last.get("M") {
+        Some(Value::StrList(codes)) => codes.iter().filter_map(|code| Direction::from_m_code(code)).next_back(),
+        _ => None,
+    };
+    let constant = state
+        .modal_word("gg15_feed_type")
+        .is_some_and(|code| CONSTANT_CUTTING_RATE.contains(&code));
+    let spindle = &mut state.spindle;
+    let changed = std::mem::take(&mut spindle.programmed)
+        || programmed.is_some()
+        || direction.is_some()
+        || constant != spindle.constant;
+    spindle.used |= changed;
+    if !spindle.used {
+        return Ok(());
+    }
+
+    if let Some(direction) = direction {
+        spindle.direction = direction;
+    }
+    if std::mem::take(&mut spindle.positioned) {
+        spindle.direction = Direction::Positioned;
+    }
+    // G97 keeps the speed G96 last reached; an S of the same block replaces it.
+    spindle.constant = constant;
+    if let Some(value) = programmed {
+        if constant {
+            spindle.cutting_rate = value;
+        } else {
+            spindle.speed = value;
+        }
+    }
+    if constant {
+        // The cutting rate is per minute; the radius in the output unit.
+        let per_unit = match state.units.output {
+            Unit::Millimetre => 1000.0,
+            Unit::Inch => 12.0,
+        };
+        let radius = state.get_axis_local(TRANSVERSE_AXIS).unwrap_or(0.0).abs();
+        let speed = state.spindle.cutting_rate * per_unit / (2.0 * std::f64::consts::PI * radius);
+        state.spindle.speed = match state.spindle.limit {
+            Some(limit) => speed.min(limit),
+            None if speed.is_finite() => speed,
+            None => {
+                emit_warning(format_args!(
+                    "Warning [line {line_no}]: G96 at the turning centre (X0) without a speed limit LIMS=; keeping {} rpm",
+                    state.spindle.speed
+                ));
+                state.spindle.speed
+            }
+        };
+    }
+
+    let last = output.last_mut().expect("row started");
+    if last.is_empty() && !changed {
+        // A block that only assigns variables stays without a row.
+        return Ok(());
+    }
+    last.insert(SPINDLE_RPM_COLUMN, Value::Float(turning_speed(state).unwrap_or(0.0)));
+    last.insert(
+        SPINDLE_DIR_COLUMN,
+        Value::Str(state.spindle.direction.name().to_string()),
+    );
+    Ok(())
+}
+
+/// The speed in rpm the spindle turns at after the current block, `None`
+/// while it is stopped or positioned.
+pub(crate) fn turning_speed(state: &State) -> Option<f64> {
+    match state.spindle.direction {
+        Direction::Clockwise | Direction::CounterClockwise => Some(state.spindle.speed),
+        Direction::Stop | Direction::Positioned => None,
+    }
+}
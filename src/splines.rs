@@ -0,0 +1,72 @@
+//! The spline declarations of the interpreter (manual 4.7.2); the curve
+//! itself is evaluated by the flattener (see [`crate::flatten`]).
+//!
+//! * `SPLINEPATH(1, X, Y, ...)` names the axes spline group 1 (the only one)
+//!   interpolates. The other axes programmed in spline blocks follow
+//!   linearly. Without a declaration the control interpolates the geometry
+//!   axes X, Y and Z.
+//! * `PL=` on an `ASPLINE`/`BSPLINE`/`CSPLINE` block is the parameter
+//!   interval of its spline span and must be positive.
+
+use crate::errors::ParsingError;
+use crate::output::OutputRows;
+use crate::state::{State, NON_GEOMETRIC_AXES};
+use crate::types::Value;
+
+/// The spline group `SPLINEPATH` can declare.
+const SPLINE_GROUP: f64 = 1.0;
+
+/// Declare the spline path: `group` and the `axes` as programmed.
+pub(crate) fn declare_path(
+    group: f64,
+    axes: Vec<String>,
+    line_no: usize,
+    output: &mut OutputRows,
+    state: &mut State,
+) -> Result<(), ParsingError> {
+    let error = |message: String| {
+        ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or("").to_string(),
+            "SPLINEPATH".to_string(),
+            message,
+        )
+    };
+    if group != SPLINE_GROUP {
+        return Err(error(format!("the spline group must be 1, got {group}")));
+    }
+    if axes.is_empty() {
+        return Err(error("SPLINEPATH needs at least one axis".to_string()));
+    }
+    let mut path: Vec<String> = Vec::with_capacity(axes.len());
+    for axis in axes {
+        let axis = axis.to_uppercase();
+        if !state.is_axis(&axis) || NON_GEOMETRIC_AXES.contains(&axis.as_str()) {
+            return Err(error(format!("'{axis}' is not a path axis")));
+        }
+        if path.contains(&axis) {
+            return Err(error(format!("the axis {axis} is named twice")));
+        }
+        path.push(axis);
+    }
+    output.set_spline_path(&path);
+    state.spline_path = Some(path);
+    Ok(())
+}
+
+/// Check the parameter interval of a spline block once all its words ran.
+pub(crate) fn finish_block(line_no: usize, output: &mut OutputRows, state: &State) -> Result<(), ParsingError> {
+    if !matches!(state.modal_word("gg01_motion"), Some("ASPLINE" | "BSPLINE" | "CSPLINE")) {
+        return Ok(());
+    }
+    let last = output.last_mut().expect("row started");
+    match last.get("PL") {
+        Some(Value::Float(length)) if length.is_nan() || *length <= 0.0 => Err(ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or("").to_string(),
+            "spline interpolation".to_string(),
+            "the parameter interval PL must be positive".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
@@ -1,5 +1,9 @@
 use crate::errors::ParsingError;
+use crate::frame::{self, Matrix3, WorkingPlane};
+use crate::subprogram::{ModalCall, PendingCall, SubprogramLibrary};
+use std::cell::Cell;
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
 
 /// A `HashMap` using the non-cryptographic FxHash instead of the default
@@ -29,31 +33,124 @@ pub fn emit_warning(args: std::fmt::Arguments) {
 ///
 /// Three families share these semantics:
 /// * the circular/helical interpolation parameters `I`, `J`, `K` (arc-centre
-///   offsets relative to the start point), `CR` (the arc-radius form) and
-///   `TURN` (additional full helix turns), programmed on G2/G3 (and CIP/CT)
-///   blocks;
+///   offsets relative to the start point, turned by the frame like the axes),
+///   `I1`, `J1`, `K1` (the CIP intermediate point, in absolute machine
+///   coordinates like the axes),
+///   `CR` (the arc-radius form) and `TURN` (additional full helix turns),
+///   programmed on G2/G3 and CIP blocks;
 /// * the spline programming addresses `PW` (point weight), `SD` (spline
-///   degree) and `PL` (parameter interval length).
+///   degree) and `PL` (parameter interval length, also of a POLY block);
+/// * the resolved POLY polynomials (see [`crate::poly`]): `PO_X_1` ..
+///   `PO_Z_5`, the numerator coefficients of the geometry axes in machine
+///   coordinates, and `PO_1` .. `PO_5`, those of the common denominator;
+/// * the thread start angle `SF` and the spindle-synchronised path
+///   information of a G33/G34/G35 block (see [`crate::threads`]):
+///   `THREAD_LEAD`, `THREAD_LEAD_CHANGE` and `THREAD_REVOLUTIONS`.
 ///
 /// Before these were listed here the arc-centre offsets were silently dropped
 /// from the output (they fell through to the user-variable branch), so arcs
 /// came out as bare straight-line endpoints.
-pub const BLOCK_ADDRESSES: &[&str] = &["I", "J", "K", "CR", "TURN", "PW", "SD", "PL"];
-
-/// NC addresses the interpreter recognizes but does not implement: an
-/// assignment to one of these parses as a plain user variable, so the
-/// construct it belongs to is NOT interpreted and the resulting motion is
-/// wrong. Each gets a loud once-per-run warning - an out-of-scope construct
-/// must never be butchered silently.
-const UNSUPPORTED_ADDRESSES: &[(&str, &str)] = &[
-    ("AR", "arc opening angle (G2/G3 ... AR=)"),
-    ("AP", "polar angle (G0..G3 AP= RP=)"),
-    ("RP", "polar radius (G0..G3 AP= RP=)"),
-    ("I1", "CIP intermediate point (I1= J1= K1=)"),
-    ("J1", "CIP intermediate point (I1= J1= K1=)"),
-    ("K1", "CIP intermediate point (I1= J1= K1=)"),
+pub const BLOCK_ADDRESSES: &[&str] = &[
+    "I",
+    "J",
+    "K",
+    "I1",
+    "J1",
+    "K1",
+    "CR",
+    "TURN",
+    "PW",
+    "SD",
+    "PL",
+    "PO_X_1",
+    "PO_X_2",
+    "PO_X_3",
+    "PO_X_4",
+    "PO_X_5",
+    "PO_Y_1",
+    "PO_Y_2",
+    "PO_Y_3",
+    "PO_Y_4",
+    "PO_Y_5",
+    "PO_Z_1",
+    "PO_Z_2",
+    "PO_Z_3",
+    "PO_Z_4",
+    "PO_Z_5",
+    "PO_1",
+    "PO_2",
+    "PO_3",
+    "PO_4",
+    "PO_5",
+    "SF",
+    "THREAD_LEAD",
+    "THREAD_LEAD_CHANGE",
+    "THREAD_REVOLUTIONS",
 ];
 
+/// Axis-identifier names that are output value columns but not path
+/// coordinates (block number, feed, spindle speed, tool data): never
+/// interpolated, never part of a curve, and never dimensioned incrementally
+/// under G91.
+pub(crate) const NON_GEOMETRIC_AXES: &[&str] = &["N", "F", "S", "D", "T"];
+
+/// How a programmed axis value is dimensioned (manual 3.5.1/3.5.2): by the
+/// modal G90/G91 mode, or forced for one word by `AC(...)` / `IC(...)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimensioning {
+    /// A plain address value: follows the active G90/G91 mode.
+    Modal,
+    /// `IC(...)`: incremental, whatever the mode.
+    Incremental,
+    /// `AC(...)`: absolute, whatever the mode.
+    Absolute,
+    /// `DAC(...)`/`DIC(...)`: a diameter of the transverse axis, absolute or
+    /// incremental, whatever the G90/G91 and DIAMON/DIAM90/DIAMOF modes.
+    Diameter { incremental: bool },
+    /// `RAC(...)`/`RIC(...)`: a radius of the transverse axis, likewise.
+    Radius { incremental: bool },
+}
+
+/// The transverse axis of a lathe, whose positions diameter programming
+/// gives as diameters.
+pub const TRANSVERSE_AXIS: &str = "X";
+
+/// Diameter programming of the transverse axis (G-group 29, manual 10.6):
+/// the positions `X` programs are diameters and the machine moves to half
+/// of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiameterMode {
+    /// `DIAMOF` (and `DIAMCYCOF`): radii.
+    #[default]
+    Off,
+    /// `DIAMON`: diameters, absolute (G90) and incremental (G91) alike.
+    On,
+    /// `DIAM90`: diameters under G90, radii under G91.
+    Absolute,
+}
+
+impl DiameterMode {
+    /// The mode for a G-group 29 word, `None` for anything else.
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code.to_ascii_uppercase().as_str() {
+            "DIAMOF" | "DIAMCYCOF" => Some(DiameterMode::Off),
+            "DIAMON" => Some(DiameterMode::On),
+            "DIAM90" => Some(DiameterMode::Absolute),
+            _ => None,
+        }
+    }
+
+    /// True if a value of the transverse axis is a diameter, absolute or
+    /// `incremental`.
+    pub fn diameter(self, incremental: bool) -> bool {
+        match self {
+            DiameterMode::Off => false,
+            DiameterMode::On => true,
+            DiameterMode::Absolute => !incremental,
+        }
+    }
+}
+
 /// Which kind of output column an assignment key resolves to. Variables (which
 /// never appear as output cells) are represented by the absence of a resolution
 /// (see [`State::resolve_output_key`]).
@@ -74,6 +171,29 @@ pub struct State {
     /// symbol_table - using one in a numeric expression is a loud error.
     pub string_table: HashMap<String, String>,
     pub translation: FxMap<String, f64>,
+    /// Rotation part of the programmable frame (`ROT`, `AROT`, `ROTS`, ...),
+    /// mapping local X/Y/Z directions to machine directions; `None` while
+    /// unrotated.
+    rotation: Option<Matrix3>,
+    /// `SCALE`/`ASCALE` factors of the programmable frame, per axis (an axis
+    /// that is absent has factor 1).
+    scale: FxMap<String, f64>,
+    /// Axes mirrored by `MIRROR`/`AMIRROR` (sign reversal of the axis).
+    mirrored: HashSet<String>,
+    /// The geometry part of the frame, `rotation · diag(scale · mirror)` over
+    /// X/Y/Z, or `None` while that is the identity. The components apply in
+    /// the control's fixed order whatever order they were programmed in
+    /// (manual 3.12.1): a local position `p` is at the machine position
+    /// `translation + rotation · scale · mirror · p`.
+    linear: Option<Matrix3>,
+    /// The settable frames `$P_UIFR[n]` (n = 1..99) as per-axis offsets,
+    /// written with `CTRANS` (see [`settable_frame_number`]).
+    settable_frames: HashMap<usize, FxMap<String, f64>>,
+    /// The selected work offset (G54..G57, G505..G599) and the offsets it
+    /// applies ahead of the programmable frame; `None` under G500. A copy of
+    /// the table entry: like on the control, a later write to the settable
+    /// frame takes effect when it is selected again.
+    work_offset: Option<(String, FxMap<String, f64>)>,
     pub axis_identifiers: Vec<String>,
     pub iteration_limit: usize,
     pub axis_index_map: Option<HashMap<String, usize>>,
@@ -85,8 +205,6 @@ pub struct State {
     /// Every jump target seen anywhere during the run (never popped), used
     /// for "did you mean" suggestions when a jump destination is not found.
     pub seen_jump_targets: HashSet<String>,
-    /// Unsupported NC addresses already warned about (once per run).
-    warned_addresses: HashSet<String>,
     /// Store line offsets for efficient error reporting. Shared (`Arc`) to
     /// avoid re-copying the offset table when the state is cloned.
     line_offsets: Arc<[usize]>,
@@ -100,6 +218,118 @@ pub struct State {
     /// hit misses. Built once at construction from the axis identifiers and the
     /// fixed block addresses.
     output_keys: FxMap<String, (ColKind, &'static str)>,
+    /// The gg14 dimensioning mode: `true` while G91 (incremental) is active.
+    /// Set before a block's axis words are evaluated, since G90/G91 act on
+    /// the whole block they are programmed in.
+    pub incremental: bool,
+    /// Local X/Y/Z positions at the start of the current block, captured on
+    /// each axis' first move in the block (`block_moved` bit set), so an
+    /// absolute arc centre `I=AC(...)` can be turned into the usual offset
+    /// relative to the start point.
+    block_start: [Option<f64>; 3],
+    block_moved: u8,
+    /// `true` in a block that programs G53, G153 or SUPA (G-group 9): its
+    /// axis values are machine coordinates, bypassing the work offset and
+    /// the programmable frame. Set like `incremental`, but for that block
+    /// only. With no basic frame, DRF or external offsets modeled, the three
+    /// suppression levels act alike.
+    pub frame_suppressed: bool,
+    /// The active working plane (G17/G18/G19), set like `incremental`. Frame
+    /// rotations with `RPL=` turn about its applicate.
+    pub plane: WorkingPlane,
+    /// Diameter programming of [`TRANSVERSE_AXIS`] (DIAMON/DIAM90/DIAMOF),
+    /// set like `incremental`.
+    pub diameter: DiameterMode,
+    /// The programmed direction while G2/G3 or INVCW/INVCCW is the active
+    /// motion (`Some(true)` for G2 and INVCW), so a frame change that
+    /// reverses arcs can restate the machine direction.
+    arc_clockwise: Option<bool>,
+    /// True if `arc_clockwise` belongs to an involute (INVCW/INVCCW).
+    involute: bool,
+    /// Interned output keys of the geometry axes X/Y/Z (`None` if not
+    /// configured): a rotated frame writes machine values for all of them.
+    geometry_keys: [Option<&'static str>; 3],
+    /// Geometry axes already warned about being coupled into a rotated
+    /// machine position before they had a position (bit per axis).
+    warned_unpositioned: Cell<u8>,
+    /// The subprogram search path and the programs loaded from it.
+    pub(crate) subprograms: SubprogramLibrary,
+    /// A subprogram call programmed in the current block; it runs once the
+    /// rest of the block is executed.
+    pub(crate) pending_call: Option<PendingCall>,
+    /// The modal call selected by `MCALL`, repeated after every block that
+    /// positions an axis.
+    pub(crate) modal_call: Option<ModalCall>,
+    /// `true` once the current block has programmed an axis position (not
+    /// F, S, N, D or T).
+    pub(crate) block_positioned: bool,
+    /// `DEFINE ... AS` macros: uppercased name to replacement text.
+    pub(crate) macros: HashMap<String, String>,
+    /// Nesting depth of blocks generated by `EXECSTRING` running right now.
+    pub(crate) exec_depth: usize,
+    /// The polar-coordinate pole, angle and radius (AP/RP, G110..G112).
+    pub(crate) polar: crate::polar::Polar,
+    /// The circle words (AR=) of the current block.
+    pub(crate) arc_words: crate::arcs::ArcWords,
+    /// The polynomial coefficients (PO[..]=) of the current block.
+    pub(crate) poly_words: crate::poly::PolyWords,
+    /// The feed around thread blocks (G33/G34/G35).
+    pub(crate) threads: crate::threads::Threads,
+    /// The axes declared by `SPLINEPATH` (see [`crate::splines`]); `None`:
+    /// the control default X, Y, Z.
+    pub(crate) spline_path: Option<Vec<String>>,
+    /// The direction, speed and constant cutting rate of the spindle (see
+    /// [`crate::spindle`]).
+    pub(crate) spindle: crate::spindle::Spindle,
+    /// The tool table and the active tool radius compensation (see
+    /// [`crate::tool_radius`]).
+    pub(crate) tool_radius: crate::tool_radius::ToolRadius,
+    /// The tool table, the selected and loaded tool and the active tool
+    /// length (see [`crate::tools`]).
+    pub(crate) tools: crate::tools::Tools,
+    /// The output unit and the one the program is written in (G70/G71,
+    /// G700/G710; see [`crate::units`]).
+    pub(crate) units: crate::units::Units,
+    /// Local variables of the active subprogram calls, innermost last.
+    call_frames: Vec<CallFrame>,
+    /// The last word programmed in each modal G group, which a `SAVE`
+    /// subprogram restores on return.
+    modal_words: FxMap<&'static str, String>,
+}
+
+/// The variables one subprogram call declared (its parameters and `DEF`s),
+/// with the caller's values they hide until the call returns.
+#[derive(Debug, Clone, Default)]
+struct CallFrame {
+    locals: HashSet<String>,
+    hidden_numbers: Vec<(String, f64)>,
+    hidden_strings: Vec<(String, String)>,
+}
+
+/// The modal settings a `SAVE` subprogram hands back to its caller: the G
+/// functions, the work offset and the programmable frame.
+#[derive(Debug, Clone)]
+pub(crate) struct SavedModes {
+    incremental: bool,
+    units: crate::units::Units,
+    plane: WorkingPlane,
+    diameter: DiameterMode,
+    work_offset: Option<(String, FxMap<String, f64>)>,
+    translation: FxMap<String, f64>,
+    rotation: Option<Matrix3>,
+    scale: FxMap<String, f64>,
+    mirrored: HashSet<String>,
+    arc_clockwise: Option<bool>,
+    involute: bool,
+    modal_words: FxMap<&'static str, String>,
+}
+
+/// The program text errors are reported against, kept aside while a
+/// subprogram runs.
+#[derive(Debug, Clone)]
+pub(crate) struct Source {
+    line_offsets: Arc<[usize]>,
+    input: Arc<str>,
 }
 
 impl State {
@@ -151,24 +381,80 @@ impl State {
                 .or_insert((ColKind::Block, crate::output::intern_column(block)));
         }
 
+        let geometry_keys = ["X", "Y", "Z"].map(|axis| match output_keys.get(axis) {
+            Some(&(ColKind::Axis, key)) => Some(key),
+            _ => None,
+        });
+
         State {
             axes: FxMap::default(),
             symbol_table: symbols,
             string_table: HashMap::new(),
             translation,
+            rotation: None,
+            scale: FxMap::default(),
+            mirrored: HashSet::new(),
+            linear: None,
+            settable_frames: HashMap::new(),
+            work_offset: None,
             axis_identifiers,
             iteration_limit,
             axis_index_map,
             allow_undefined_variables,
             jump_scopes: Vec::new(),
             seen_jump_targets: HashSet::new(),
-            warned_addresses: HashSet::new(),
             line_offsets: Arc::from(Vec::new()),
             input: Arc::from(""),
             output_keys,
+            incremental: false,
+            frame_suppressed: false,
+            block_start: [None; 3],
+            block_moved: 0,
+            plane: WorkingPlane::default(),
+            diameter: DiameterMode::default(),
+            arc_clockwise: None,
+            involute: false,
+            geometry_keys,
+            warned_unpositioned: Cell::new(0),
+            subprograms: SubprogramLibrary::default(),
+            pending_call: None,
+            modal_call: None,
+            block_positioned: false,
+            macros: HashMap::new(),
+            exec_depth: 0,
+            polar: Default::default(),
+            arc_words: Default::default(),
+            poly_words: Default::default(),
+            threads: Default::default(),
+            spline_path: None,
+            tool_radius: Default::default(),
+            spindle: Default::default(),
+            tools: Default::default(),
+            units: Default::default(),
+            call_frames: Vec::new(),
+            modal_words: FxMap::default(),
         }
     }
 
+    /// Sets the directories searched for subprograms (`NAME.SPF`, then
+    /// `NAME.MPF`, in each directory in turn)
+    pub fn set_subprogram_dirs(&mut self, dirs: Vec<PathBuf>) {
+        self.subprograms.set_dirs(dirs);
+    }
+
+    /// Sets the unit of the output (see [`crate::units`]); the program is
+    /// written in it until it selects another one.
+    pub fn set_output_unit(&mut self, unit: crate::units::Unit) {
+        self.units = crate::units::Units::new(unit);
+    }
+
+    /// Registers the NC source of cycle `name` (a program with a PROC
+    /// statement), used instead of a program file or built-in cycle of that
+    /// name
+    pub fn register_cycle(&mut self, name: &str, source: String) {
+        self.subprograms.register(name, source);
+    }
+
     /// Resolve an assignment key to its output column: `Some((kind, interned
     /// name))` for an axis or block address, or `None` for a user variable
     /// (which is never emitted as an output cell). The common case - a key
@@ -181,26 +467,6 @@ impl State {
         self.output_keys.get(&key.to_uppercase()).copied()
     }
 
-    /// Warn (once per run per address) when an assignment targets a known
-    /// but unsupported NC address: the value lands in the symbol table as a
-    /// user variable and the construct it programs is not interpreted.
-    /// Cheap on the hot variable path: unsupported addresses are all two
-    /// characters, so longer keys return before any lookup.
-    pub fn warn_unsupported_address(&mut self, key: &str, line_no: usize) {
-        if key.len() != 2 {
-            return;
-        }
-        let upper = key.to_uppercase();
-        if let Some((name, what)) = UNSUPPORTED_ADDRESSES.iter().find(|(n, _)| *n == upper) {
-            if self.warned_addresses.insert(upper) {
-                emit_warning(format_args!(
-                    "Warning [line {}]: address '{}' - {} - is not interpreted and is treated as a user variable; the motion it programs will be wrong",
-                    line_no, name, what
-                ));
-            }
-        }
-    }
-
     /// True if a jump target (canonical key) is defined in any scope on the
     /// currently active scope chain.
     pub fn jump_target_visible(&self, key: &str) -> bool {
@@ -218,6 +484,19 @@ impl State {
         self.input = Arc::from(input);
     }
 
+    /// The current program text, to restore after a subprogram ran
+    pub(crate) fn source(&self) -> Source {
+        Source {
+            line_offsets: Arc::clone(&self.line_offsets),
+            input: Arc::clone(&self.input),
+        }
+    }
+
+    pub(crate) fn restore_source(&mut self, source: Source) {
+        self.line_offsets = source.line_offsets;
+        self.input = source.input;
+    }
+
     /// Gets a line from the input by line number (1-based indexing)
     pub fn get_line(&self, line_no: usize) -> Option<&str> {
         if line_no == 0 {
@@ -268,17 +547,340 @@ impl State {
         *self.translation.get(axis).unwrap_or(&0.0)
     }
 
-    /// Resets all translation values to zero (bare `TRANS` deletes the
-    /// programmable frame)
-    pub fn reset_translations(&mut self) {
+    /// Stores settable frame `number` (`$P_UIFR[number] = CTRANS(...)`)
+    pub fn set_settable_frame(&mut self, number: usize, offsets: FxMap<String, f64>) {
+        self.settable_frames.insert(number, offsets);
+    }
+
+    /// Selects the work offset of a G-group 8 word: G500 deselects it, G54
+    /// and the others activate their settable frame (a frame that was never
+    /// written is all zero, as on a control with an empty zero-offset table)
+    pub fn select_work_offset(&mut self, code: &str) {
+        self.work_offset = match settable_frame_number(code) {
+            None | Some(0) => None,
+            Some(number) => {
+                let offsets = self.settable_frames.get(&number).cloned().unwrap_or_default();
+                Some((code.to_ascii_uppercase(), offsets))
+            }
+        };
+    }
+
+    /// The G word of the selected work offset, `None` under G500
+    pub fn work_offset(&self) -> Option<&str> {
+        self.work_offset.as_ref().map(|(code, _)| code.as_str())
+    }
+
+    /// Machine position of the local origin of `axis`: the work offset plus
+    /// the programmable translation, plus the tool length
+    fn origin(&self, axis: &str) -> f64 {
+        let translation = self.get_translation(axis) + self.tool_length(axis);
+        match &self.work_offset {
+            Some((_, offsets)) => translation + offsets.get(axis).copied().unwrap_or(0.0),
+            None => translation,
+        }
+    }
+
+    /// The active tool length along `axis` (see [`crate::tools`]): frame
+    /// suppression leaves it in place
+    fn tool_length(&self, axis: &str) -> f64 {
+        geometry_slot(axis).map_or(0.0, |slot| self.tools.length[slot])
+    }
+
+    /// Deletes the programmable frame: all translations, the rotation, the
+    /// scale factors and the mirrors (a bare `TRANS`, and every absolute
+    /// frame instruction, does this)
+    pub fn reset_frame(&mut self) {
         for value in self.translation.values_mut() {
             *value = 0.0;
         }
+        self.rotation = None;
+        self.scale.clear();
+        self.mirrored.clear();
+        self.linear = None;
+    }
+
+    /// The frame rotation, `None` while unrotated
+    pub fn frame_rotation(&self) -> Option<Matrix3> {
+        self.rotation
+    }
+
+    /// Sets the frame rotation; an identity matrix clears it
+    pub fn set_frame_rotation(&mut self, rotation: Matrix3) {
+        self.rotation = (!frame::is_identity(&rotation)).then_some(rotation);
+        self.update_linear();
+    }
+
+    /// Multiplies the scale factor of `axis` by `factor` (`SCALE` after a
+    /// reset, `ASCALE` on the current frame)
+    pub fn multiply_scale(&mut self, axis: &str, factor: f64) {
+        let current = self.scale.get(axis).copied().unwrap_or(1.0);
+        self.scale.insert(axis.to_string(), current * factor);
+        self.update_linear();
+    }
+
+    /// Toggles the mirror of `axis`: mirroring a mirrored axis again undoes it
+    pub fn toggle_mirror(&mut self, axis: &str) {
+        if !self.mirrored.remove(axis) {
+            self.mirrored.insert(axis.to_string());
+        }
+        self.update_linear();
+    }
+
+    /// The combined scale and mirror sign of `axis` (1 without either)
+    pub fn axis_factor(&self, axis: &str) -> f64 {
+        if self.scale.is_empty() && self.mirrored.is_empty() {
+            return 1.0;
+        }
+        let sign = if self.mirrored.contains(axis) { -1.0 } else { 1.0 };
+        sign * self.scale.get(axis).copied().unwrap_or(1.0)
+    }
+
+    /// The geometry part of the frame (see `linear`), `None` for identity
+    pub fn frame_linear(&self) -> Option<Matrix3> {
+        self.linear
+    }
+
+    /// Which of X/Y/Z the frame mirrors (a negative combined factor)
+    pub fn mirrored_geometry(&self) -> [bool; 3] {
+        ["X", "Y", "Z"].map(|axis| self.axis_factor(axis) < 0.0)
+    }
+
+    /// True while the frame mirrors the working plane an odd number of
+    /// times: the machine traverses G2 arcs counter-clockwise and vice versa.
+    pub fn arcs_reversed(&self) -> bool {
+        let [abscissa, ordinate, _] = self.plane.axes();
+        let mirrored = self.mirrored_geometry();
+        mirrored[abscissa] != mirrored[ordinate]
+    }
+
+    /// The G-group 1 word to output for the programmed motion `word`
+    /// (uppercase): G2 and G3, like INVCW and INVCCW, swap while the frame
+    /// reverses arcs.
+    pub fn output_motion(&mut self, word: String) -> String {
+        (self.arc_clockwise, self.involute) = match word.as_str() {
+            "G2" => (Some(true), false),
+            "G3" => (Some(false), false),
+            "INVCW" => (Some(true), true),
+            "INVCCW" => (Some(false), true),
+            _ => (None, false),
+        };
+        match self.arc_clockwise {
+            Some(clockwise) => arc_word(clockwise != self.arcs_reversed(), self.involute).to_string(),
+            None => word,
+        }
+    }
+
+    /// The programmed direction of the active G2/G3 motion (`Some(true)`
+    /// for G2), `None` for any other motion.
+    pub(crate) fn arc_direction(&self) -> Option<bool> {
+        self.arc_clockwise.filter(|_| !self.involute)
+    }
+
+    /// The programmed direction of the active INVCW/INVCCW motion
+    /// (`Some(true)` for INVCW), `None` for any other motion.
+    pub(crate) fn involute_direction(&self) -> Option<bool> {
+        self.arc_clockwise.filter(|_| self.involute)
+    }
+
+    /// The machine direction of the active G2/G3 (or INVCW/INVCCW) motion,
+    /// if a frame change from `reversed_before` (the previous
+    /// [`State::arcs_reversed`]) turned it around and it must be output
+    /// again.
+    pub fn restated_arc_motion(&self, reversed_before: bool) -> Option<&'static str> {
+        let clockwise = self.arc_clockwise?;
+        (self.arcs_reversed() != reversed_before).then(|| arc_word(clockwise != self.arcs_reversed(), self.involute))
+    }
+
+    fn update_linear(&mut self) {
+        let factors = ["X", "Y", "Z"].map(|axis| self.axis_factor(axis));
+        let mut linear = self.rotation.unwrap_or(frame::IDENTITY);
+        for row in linear.iter_mut() {
+            for (cell, factor) in row.iter_mut().zip(factors) {
+                *cell *= factor;
+            }
+        }
+        self.linear = (!frame::is_identity(&linear)).then_some(linear);
+    }
+
+    /// Adds an `ATRANS` offset given in the current frame coordinates: it is
+    /// rotated, scaled and mirrored like a position first, so the origin moves
+    /// along the frame's axes (manual 3.12.2.2: an offset programmed after
+    /// SCALE is scaled too).
+    pub fn add_translation(
+        &mut self,
+        offsets: &[(String, f64)],
+        line_no: usize,
+        preview: &str,
+    ) -> Result<(), ParsingError> {
+        let mut geometry = [0.0; 3];
+        for (axis, value) in offsets {
+            match (geometry_slot(axis), self.linear) {
+                (Some(slot), Some(_)) => geometry[slot] += value,
+                _ => {
+                    let current = self.get_translation(axis);
+                    let delta = value * self.axis_factor(axis);
+                    self.update_translation(axis, current + delta, line_no, preview)?;
+                }
+            }
+        }
+        if let Some(linear) = self.linear {
+            for (slot, delta) in frame::apply(&linear, geometry).into_iter().enumerate() {
+                if let Some(value) = self.translation.get_mut(["X", "Y", "Z"][slot]) {
+                    *value += delta;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Start interpreting a new block: forget the previous block's start
+    /// positions (see [`State::block_start_local`]) and frame suppression.
+    pub fn begin_block(&mut self) {
+        self.block_moved = 0;
+        self.frame_suppressed = false;
+        self.block_positioned = false;
+        self.polar.begin_block();
+        self.arc_words.begin_block();
+        self.poly_words.begin_block();
+    }
+
+    /// The geometry axes moved so far in the block (bit per X/Y/Z slot), to
+    /// be reinstated with [`State::restore_block_moves`] after words that
+    /// must not move them.
+    pub(crate) fn block_moves(&self) -> u8 {
+        self.block_moved
+    }
+
+    /// Reinstates the moved geometry axes of [`State::block_moves`].
+    pub(crate) fn restore_block_moves(&mut self, moved: u8) {
+        self.block_moved = moved;
+    }
+
+    /// True once the current block has moved geometry axis `slot` (X=0,
+    /// Y=1, Z=2).
+    pub(crate) fn moved_in_block(&self, slot: usize) -> bool {
+        self.block_moved & (1 << slot) != 0
+    }
+
+    /// The local position `axis` had when the current block started, even if
+    /// an earlier word of the block already moved it.
+    pub fn block_start_local(&self, axis: &str) -> Option<f64> {
+        match geometry_slot(axis) {
+            Some(slot) if self.block_moved & (1 << slot) != 0 => self.block_start[slot],
+            _ => self.get_axis_local(axis),
+        }
     }
 
-    /// Updates an axis value in local coordinates (without translation).
-    /// Returns the machine coordinate (local + translation) for output purposes.
-    pub fn update_axis(&mut self, key: &str, local_value: f64) -> Result<f64, ParsingError> {
+    /// Resolve a programmed value for `key` to what is stored: the new LOCAL
+    /// coordinate for an axis, the value itself for block addresses and
+    /// variables. Path axes follow G91 unless overridden by `AC()`; the
+    /// arc-centre offsets I/J/K are always relative to the start point, so
+    /// `AC()` converts an absolute centre into that offset. A diameter of the
+    /// transverse axis becomes its radius (see [`State::as_radius`]). In a
+    /// block with [`State::frame_suppressed`] an axis resolves to its MACHINE
+    /// coordinate instead (increments included), which [`State::update_axis`]
+    /// and [`State::machine_outputs`] expect there.
+    pub fn resolve_programmed(&self, key: &str, value: f64, dimensioning: Dimensioning) -> f64 {
+        if let Some(axis) = centre_offset_axis(key) {
+            if dimensioning != Dimensioning::Absolute {
+                return value;
+            }
+            let value = self.as_radius(axis, value, dimensioning);
+            return match self.block_start_local(axis) {
+                Some(start) => value - start,
+                None => {
+                    emit_warning(format_args!(
+                        "Warning: arc centre '{}=AC(...)' is programmed before axis '{}' has a position; assuming it starts at 0.",
+                        key, axis
+                    ));
+                    value
+                }
+            };
+        }
+        let value = self.as_radius(key, value, dimensioning);
+        match dimensioning {
+            Dimensioning::Absolute
+            | Dimensioning::Diameter { incremental: false }
+            | Dimensioning::Radius { incremental: false } => value,
+            Dimensioning::Incremental => self.incremented(key, value, "IC()"),
+            Dimensioning::Diameter { incremental: true } => self.incremented(key, value, "DIC()"),
+            Dimensioning::Radius { incremental: true } => self.incremented(key, value, "RIC()"),
+            Dimensioning::Modal => {
+                if self.incremental && self.is_axis(key) && !NON_GEOMETRIC_AXES.contains(&key) {
+                    self.incremented(key, value, "G91")
+                } else {
+                    value
+                }
+            }
+        }
+    }
+
+    /// A value programmed for `key` as a radius: half of it if `key` is the
+    /// [`TRANSVERSE_AXIS`] and the value a diameter, by `DAC()`/`DIC()` or
+    /// by the [`DiameterMode`] for its absolute or incremental
+    /// `dimensioning`.
+    pub fn as_radius(&self, key: &str, value: f64, dimensioning: Dimensioning) -> f64 {
+        if !key.eq_ignore_ascii_case(TRANSVERSE_AXIS) {
+            return value;
+        }
+        let diameter = match dimensioning {
+            Dimensioning::Modal => self.diameter.diameter(self.incremental),
+            Dimensioning::Incremental => self.diameter.diameter(true),
+            Dimensioning::Absolute => self.diameter.diameter(false),
+            Dimensioning::Diameter { .. } => true,
+            Dimensioning::Radius { .. } => false,
+        };
+        if diameter {
+            value / 2.0
+        } else {
+            value
+        }
+    }
+
+    /// The coordinate `increment` away from the current position of `key`
+    /// (machine under frame suppression, local otherwise), or the bare
+    /// increment (with a warning) if it was never set.
+    fn incremented(&self, key: &str, increment: f64, how: &str) -> f64 {
+        let current = if self.frame_suppressed && self.is_axis(key) {
+            self.get_axis_machine(key)
+        } else {
+            self.get_axis_local(key)
+        };
+        match current {
+            Some(local) => local + increment,
+            None => {
+                emit_warning(format_args!(
+                    "Warning: axis '{}' is incremented with {} before any absolute position was set; assuming it starts at 0 (a real control would start from the actual axis position).",
+                    key, how
+                ));
+                increment
+            }
+        }
+    }
+
+    /// Updates an axis value in local coordinates (without the frame); see
+    /// [`State::machine_outputs`] for the machine coordinates to output.
+    /// Under frame suppression the value is a machine coordinate of the tool
+    /// tip: the local position that reaches it under the active frame is
+    /// stored instead (for a rotated geometry axis, that moves the coupled
+    /// local axes too).
+    pub fn update_axis(&mut self, key: &str, local_value: f64) -> Result<(), ParsingError> {
+        self.block_positioned |= !NON_GEOMETRIC_AXES.contains(&key);
+        if self.frame_suppressed {
+            self.store_machine(key, local_value + self.tool_length(key));
+            return Ok(());
+        }
+        self.store_local(key, local_value);
+        Ok(())
+    }
+
+    fn store_local(&mut self, key: &str, local_value: f64) {
+        if let Some(slot) = geometry_slot(key) {
+            if self.block_moved & (1 << slot) == 0 {
+                self.block_start[slot] = self.axes.get(key).copied();
+                self.block_moved |= 1 << slot;
+            }
+        }
         // Store the local coordinate. Get-mut first: after the first block that
         // moves an axis, the key already exists, so the common path overwrites
         // in place and allocates no String (HashMap::insert would take the key
@@ -289,9 +891,112 @@ impl State {
                 self.axes.insert(key.to_string(), local_value);
             }
         }
-        // Return the machine coordinate for output
-        let translation_value = self.get_translation(key);
-        Ok(local_value + translation_value)
+    }
+
+    /// Store the local coordinates that put `key` at machine coordinate
+    /// `machine` under the active frame, leaving every other machine
+    /// coordinate where it is.
+    fn store_machine(&mut self, key: &str, machine: f64) {
+        let rotated = geometry_slot(key)
+            .zip(self.linear)
+            .and_then(|(slot, linear)| Some((slot, linear, frame::inverse(&linear)?)));
+        let Some((slot, linear, inverse)) = rotated else {
+            self.store_local(key, (machine - self.origin(key)) / self.axis_factor(key));
+            return;
+        };
+        // Machine position relative to the frame origin; an axis without a
+        // position is taken to be at the origin.
+        let mut offset = [0.0; 3];
+        for (row, axis) in ["X", "Y", "Z"].into_iter().enumerate() {
+            if row == slot {
+                offset[row] = machine - self.origin(axis);
+            } else if self.axes.contains_key(axis) {
+                offset[row] = self.framed_machine(&linear, row) - self.origin(axis);
+            }
+        }
+        for (row, local) in frame::apply(&inverse, offset).into_iter().enumerate() {
+            let axis = ["X", "Y", "Z"][row];
+            // Coupled axes move in local coordinates; ones without a position
+            // (and unconfigured ones) are left alone.
+            if row == slot || (self.geometry_keys[row].is_some() && self.axes.contains_key(axis)) {
+                self.store_local(axis, local);
+            }
+        }
+    }
+
+    /// Report the machine coordinates an axis write of `local_value` to `key`
+    /// changes: just `key` (local + translation) in a plain frame, and every
+    /// geometry axis a rotation couples `key` into otherwise. While a rotation
+    /// or mirror is active, a geometry axis write restates every machine
+    /// geometry axis whose local axes have a position, so a row under a
+    /// rotated frame (`ROT Z=90`, then `X20`) carries the whole machine
+    /// position. Called after [`State::update_axis`]. Under frame suppression
+    /// the value already is the machine coordinate of `key` (bar the tool
+    /// length), and only `key` moves.
+    pub fn machine_outputs(&self, key: &'static str, local_value: f64, mut emit: impl FnMut(&'static str, f64)) {
+        if self.frame_suppressed {
+            emit(key, local_value + self.tool_length(key));
+            return;
+        }
+        let (Some(linear), Some(slot)) = (self.linear, geometry_slot(key)) else {
+            emit(key, local_value * self.axis_factor(key) + self.origin(key));
+            return;
+        };
+        let restate = self.rotation.is_some() || self.mirrored_geometry().contains(&true);
+        for (row, &machine_key) in self.geometry_keys.iter().enumerate() {
+            let coupled = linear[row][slot].abs() >= frame::EPSILON;
+            let positioned = || {
+                ["X", "Y", "Z"]
+                    .into_iter()
+                    .enumerate()
+                    .all(|(slot, axis)| linear[row][slot].abs() < frame::EPSILON || self.axes.contains_key(axis))
+            };
+            if let Some(machine_key) = machine_key.filter(|_| coupled || restate && positioned()) {
+                emit(machine_key, self.framed_machine(&linear, row));
+            }
+        }
+    }
+
+    /// Machine coordinates of the local X/Y/Z point `point` under the active
+    /// frame (the point itself plus the tool length in a block with frame
+    /// suppression).
+    pub(crate) fn machine_point(&self, point: [f64; 3]) -> [f64; 3] {
+        if self.frame_suppressed {
+            return std::array::from_fn(|slot| point[slot] + self.tools.length[slot]);
+        }
+        let linear = self.linear.unwrap_or(frame::IDENTITY);
+        std::array::from_fn(|row| {
+            let rotated: f64 = (0..3).map(|slot| linear[row][slot] * point[slot]).sum();
+            self.origin(["X", "Y", "Z"][row]) + rotated
+        })
+    }
+
+    /// Machine coordinate of geometry axis `row` under the frame's `linear`
+    /// part. A coupled axis without a position counts as 0, with a
+    /// once-per-axis warning.
+    fn framed_machine(&self, linear: &Matrix3, row: usize) -> f64 {
+        let mut value = self.origin(["X", "Y", "Z"][row]);
+        for (slot, axis) in ["X", "Y", "Z"].into_iter().enumerate() {
+            let coefficient = linear[row][slot];
+            if coefficient.abs() < frame::EPSILON {
+                continue;
+            }
+            match self.axes.get(axis) {
+                Some(local) => value += coefficient * local,
+                None => {
+                    let warned = self.warned_unpositioned.get();
+                    if warned & (1 << slot) == 0 {
+                        self.warned_unpositioned.set(warned | (1 << slot));
+                        emit_warning(format_args!(
+                            "Warning: axis '{}' has no position yet but the rotated frame couples it into machine axis '{}'; assuming it is at 0.",
+                            axis,
+                            ["X", "Y", "Z"][row]
+                        ));
+                    }
+                }
+            }
+        }
+        value
     }
 
     /// Gets the current local coordinate for an axis
@@ -299,9 +1004,14 @@ impl State {
         self.axes.get(key).copied()
     }
 
-    /// Gets the current machine coordinate for an axis (local + translation)
+    /// Gets the current machine coordinate for an axis (the frame applied to
+    /// the local coordinates)
     pub fn get_axis_machine(&self, key: &str) -> Option<f64> {
-        self.axes.get(key).map(|local| local + self.get_translation(key))
+        let local = self.axes.get(key)?;
+        match (self.linear, geometry_slot(key)) {
+            (Some(linear), Some(slot)) => Some(self.framed_machine(&linear, slot)),
+            _ => Some(local * self.axis_factor(key) + self.origin(key)),
+        }
     }
 
     /// Gets the array index for an axis, if a mapping exists
@@ -321,11 +1031,126 @@ impl State {
         }
     }
 
+    /// Opens the local-variable scope of a subprogram call
+    pub(crate) fn enter_call(&mut self) {
+        self.call_frames.push(CallFrame::default());
+    }
+
+    /// Closes the innermost call scope: its locals are dropped and the
+    /// caller's values they hid come back
+    pub(crate) fn leave_call(&mut self) {
+        let Some(frame) = self.call_frames.pop() else { return };
+        let is_local = |key: &str| frame.locals.iter().any(|name| names_variable(key, name));
+        self.symbol_table.retain(|key, _| !is_local(key));
+        self.string_table.retain(|key, _| !is_local(key));
+        self.symbol_table.extend(frame.hidden_numbers);
+        self.string_table.extend(frame.hidden_strings);
+    }
+
+    /// Number of subprogram calls currently active
+    pub(crate) fn call_depth(&self) -> usize {
+        self.call_frames.len()
+    }
+
+    /// Declares `name` (and its array elements) local to the active
+    /// subprogram call, hiding a caller variable of the same name. Outside a
+    /// subprogram every variable is global and this does nothing.
+    pub(crate) fn declare_local(&mut self, name: &str) {
+        let Some(frame) = self.call_frames.last_mut() else {
+            return;
+        };
+        if !frame.locals.insert(name.to_string()) {
+            return;
+        }
+        let numbers: Vec<String> = self
+            .symbol_table
+            .keys()
+            .filter(|key| names_variable(key, name))
+            .cloned()
+            .collect();
+        for key in numbers {
+            let value = self.symbol_table.remove(&key).expect("key was just listed");
+            frame.hidden_numbers.push((key, value));
+        }
+        let strings: Vec<String> = self
+            .string_table
+            .keys()
+            .filter(|key| names_variable(key, name))
+            .cloned()
+            .collect();
+        for key in strings {
+            let value = self.string_table.remove(&key).expect("key was just listed");
+            frame.hidden_strings.push((key, value));
+        }
+    }
+
+    /// The active word of the modal G group `group`, if one was programmed.
+    pub(crate) fn modal_word(&self, group: &str) -> Option<&str> {
+        self.modal_words.get(group).map(String::as_str)
+    }
+
+    /// Remembers `word` as the active member of its G group, if that group
+    /// is modal
+    pub(crate) fn record_modal_word(&mut self, group: &'static str, word: &str) {
+        if self.modal_words.get(group).is_some_and(|active| active == word) {
+            return;
+        }
+        if crate::modal_groups::classify_g_command(word).is_some_and(|(_, modal)| modal) {
+            self.modal_words.insert(group, word.to_string());
+        }
+    }
+
+    /// Snapshot of the modal settings for a `SAVE` subprogram
+    pub(crate) fn save_modes(&self) -> SavedModes {
+        SavedModes {
+            incremental: self.incremental,
+            units: self.units,
+            plane: self.plane,
+            diameter: self.diameter,
+            work_offset: self.work_offset.clone(),
+            translation: self.translation.clone(),
+            rotation: self.rotation,
+            scale: self.scale.clone(),
+            mirrored: self.mirrored.clone(),
+            arc_clockwise: self.arc_clockwise,
+            involute: self.involute,
+            modal_words: self.modal_words.clone(),
+        }
+    }
+
+    /// Reinstates the settings of [`State::save_modes`] and returns the G
+    /// words (by group) that differ from the ones the subprogram left
+    /// active, in group order, to be output again
+    pub(crate) fn restore_modes(&mut self, saved: SavedModes) -> Vec<(&'static str, String)> {
+        let mut changed: Vec<(&'static str, String)> = saved
+            .modal_words
+            .iter()
+            .filter(|(group, word)| self.modal_words.get(*group) != Some(*word))
+            .map(|(group, word)| (*group, word.clone()))
+            .collect();
+        changed.sort_unstable();
+        self.incremental = saved.incremental;
+        self.units = saved.units;
+        self.plane = saved.plane;
+        self.diameter = saved.diameter;
+        self.work_offset = saved.work_offset;
+        self.translation = saved.translation;
+        self.rotation = saved.rotation;
+        self.scale = saved.scale;
+        self.mirrored = saved.mirrored;
+        self.arc_clockwise = saved.arc_clockwise;
+        self.involute = saved.involute;
+        self.modal_words = saved.modal_words;
+        self.update_linear();
+        changed
+    }
+
     /// Snapshot the end-of-run state for the Python-facing `.state` dict. The
     /// numeric maps (`axes`, `symbol_table`, `translation`) and the string
     /// variables (`string_table`, from `DEF STRING`) are carried apart because
     /// they have different value types; the Python binding renders them as one
-    /// dict `{axes, symbol_table, translation, string_table}`.
+    /// dict `{axes, symbol_table, translation, string_table, work_offset,
+    /// work_offset_translation, unit}`.
     #[allow(dead_code)]
     pub fn final_state(&self) -> FinalState {
         FinalState {
@@ -333,14 +1158,80 @@ impl State {
             symbol_table: self.symbol_table.clone(),
             translation: self.translation.clone(),
             string_table: self.string_table.clone(),
+            work_offset: self.work_offset().map(str::to_string),
+            work_offset_translation: self
+                .work_offset
+                .as_ref()
+                .map(|(_, offsets)| offsets.clone())
+                .unwrap_or_default(),
+            unit: self.units.output.name(),
         }
     }
 }
 
+/// The G-group 1 word for a circular or involute motion.
+fn arc_word(clockwise: bool, involute: bool) -> &'static str {
+    match (clockwise, involute) {
+        (true, false) => "G2",
+        (false, false) => "G3",
+        (true, true) => "INVCW",
+        (false, true) => "INVCCW",
+    }
+}
+
+/// The settable frame a G-group 8 word selects: 1..4 for G54..G57, 5..99 for
+/// G505..G599, and 0 for G500 (no work offset). `None` for any other word.
+pub(crate) fn settable_frame_number(code: &str) -> Option<usize> {
+    let number: usize = code.strip_prefix(['G', 'g'])?.parse().ok()?;
+    match number {
+        500 => Some(0),
+        54..=57 => Some(number - 53),
+        505..=599 => Some(number - 500),
+        _ => None,
+    }
+}
+
+/// True for the G-group 9 words that suppress the frames in their block:
+/// G53 (work offset and programmable frame), G153 (also the basic frame) and
+/// SUPA (also DRF and external offsets). SUPD is not a frame suppression.
+pub(crate) fn suppresses_frames(code: &str) -> bool {
+    ["G53", "G153", "SUPA"]
+        .iter()
+        .any(|word| word.eq_ignore_ascii_case(code))
+}
+
+/// True if the symbol-table `key` belongs to the variable `name`: the
+/// variable itself or one of its array elements (`NAME[2]`).
+fn names_variable(key: &str, name: &str) -> bool {
+    key.strip_prefix(name)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('['))
+}
+
+/// Index of a geometry axis in [`State::block_start`].
+fn geometry_slot(axis: &str) -> Option<usize> {
+    match axis {
+        "X" => Some(0),
+        "Y" => Some(1),
+        "Z" => Some(2),
+        _ => None,
+    }
+}
+
+/// The geometry axis an arc-centre offset address belongs to.
+fn centre_offset_axis(key: &str) -> Option<&'static str> {
+    match key {
+        "I" => Some("X"),
+        "J" => Some("Y"),
+        "K" => Some("Z"),
+        _ => None,
+    }
+}
+
 /// End-of-run interpreter state handed to Python as the `.state` dict. The
 /// numeric sub-tables keep their `f64` values; `string_table` carries the
 /// `DEF STRING` variables (a numeric sub-dict cannot hold them). The Python
-/// binding turns this into `{axes, symbol_table, translation, string_table}`.
+/// binding turns this into `{axes, symbol_table, translation, string_table,
+/// work_offset, work_offset_translation}`.
 #[derive(Debug, Clone, Default)]
 #[allow(dead_code)] // fields read only by the python-feature bindings, not the bin
 pub struct FinalState {
@@ -348,4 +1239,10 @@ pub struct FinalState {
     pub symbol_table: HashMap<String, f64>,
     pub translation: FxMap<String, f64>,
     pub string_table: HashMap<String, String>,
+    /// The G word of the active work offset (e.g. `G54`), `None` under G500.
+    pub work_offset: Option<String>,
+    /// The offsets the active work offset applies (empty under G500).
+    pub work_offset_translation: FxMap<String, f64>,
+    /// The unit of the lengths (`mm` or `inch`, see [`crate::units`]).
+    pub unit: &'static str,
 }
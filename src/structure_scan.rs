@@ -72,11 +72,9 @@ pub fn check_structures(input: &str) -> Result<ProgramShape, ParsingError> {
         match word.as_str() {
             // IF opens a block unless it is the single-line conditional
             // jump form (IF <condition> GOTO... <target>).
-            "IF" => {
-                if !contains_goto_word(line) {
-                    has_block_structures = true;
-                    stack.push((Structure::If, line_no, raw_line.to_string()));
-                }
+            "IF" if !contains_goto_word(line) => {
+                has_block_structures = true;
+                stack.push((Structure::If, line_no, raw_line.to_string()));
             }
             "WHILE" => {
                 has_block_structures = true;
@@ -90,7 +88,14 @@ pub fn check_structures(input: &str) -> Result<ProgramShape, ParsingError> {
                 has_block_structures = true;
                 stack.push((Structure::Loop, line_no, raw_line.to_string()));
             }
-            "REPEAT" => {
+            // REPEAT opens a block when it stands alone; with a target it
+            // is a single-line section repetition (REPEAT LAB1 P=2).
+            "REPEAT"
+                if line
+                    .split_whitespace()
+                    .last()
+                    .is_some_and(|w| w.eq_ignore_ascii_case("REPEAT")) =>
+            {
                 has_block_structures = true;
                 stack.push((Structure::Repeat, line_no, raw_line.to_string()));
             }
@@ -0,0 +1,518 @@
+//! Subprograms (NC programming manual 2.3): a block naming a program
+//! (`L100`, `CONTOUR_A(5, 2)`) or `CALL "NAME"` runs the file `NAME.SPF` (or
+//! `NAME.MPF`) from the configured search path, then carries on after the
+//! calling block.
+//!
+//! * `PROC NAME(REAL A, VAR REAL B) SAVE` in the first block declares the
+//!   formal parameters. A value parameter gets a copy of its argument; a
+//!   `VAR` parameter writes its final value back to the caller's variable.
+//! * Parameters and the subprogram's own `DEF`s are local to the call. Every
+//!   other variable (R parameters, names the caller defined) is shared.
+//! * `SAVE` restores the caller's modal G functions, work offset and
+//!   programmable frame on return.
+//! * `M17`, `RET`, `M2` and `M30` return to the caller.
+//! * `P<n>` after the name (`L100 P3`) runs the program `n` times in a row,
+//!   1 to [`MAX_PASSES`].
+//! * Calls nest up to [`MAX_CALL_DEPTH`] levels deep.
+//! * `MCALL NAME(...)` makes the call modal: it repeats after every later
+//!   block that positions an axis, until a bare `MCALL` deselects it. The
+//!   arguments are evaluated once, in the `MCALL` block. Inside the called
+//!   program the modal call is not in effect, and an `MCALL` programmed
+//!   there ends with its level.
+//!
+//! A name is looked up among the cycles registered with the interpreter
+//! (NC source with a PROC statement, like a program file), then on the search
+//! path, then among the built-in cycles of [`crate::cycles`].
+//!
+//! The call runs when the calling block is done, so the rest of that block
+//! (e.g. `G1 X10 L100`) acts first. Output rows from a subprogram carry its
+//! own line numbers, those of a built-in cycle the calling block's. A program
+//! is parsed on its first call, and later calls reuse the parse.
+
+use crate::cycles::{Cycle, CycleContext};
+use crate::errors::ParsingError;
+use crate::interpret_rules::g_command_output;
+use crate::output::OutputRows;
+use crate::state::State;
+use crate::types::{NCParser, Rule, Value};
+use pest::iterators::Pair;
+use pest::Parser;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Subprogram levels below the main program: the control runs 16 program
+/// levels, the main program being the first.
+pub(crate) const MAX_CALL_DEPTH: usize = 15;
+
+/// The largest pass count `P` of a call.
+pub(crate) const MAX_PASSES: usize = 9999;
+
+type Blocks<'i> = Pair<'i, Rule>;
+
+self_cell::self_cell!(
+    /// The parsed `blocks` of a program, borrowing its text.
+    struct ParsedProgram {
+        owner: Rc<str>,
+        #[covariant]
+        dependent: Blocks,
+    }
+    impl {Debug}
+);
+
+/// A subprogram loaded from the search path.
+#[derive(Debug)]
+pub(crate) struct Subprogram {
+    /// The program name, uppercased.
+    pub(crate) name: String,
+    text: Rc<str>,
+    /// The text parsed, on the first call.
+    parsed: OnceCell<ParsedProgram>,
+    /// The formal parameters of its PROC statement, in order.
+    pub(crate) parameters: Vec<Parameter>,
+    /// The PROC statement carries the `SAVE` attribute.
+    save: bool,
+    /// A built-in cycle, whose blocks are generated for each call.
+    cycle: Option<&'static Cycle>,
+}
+
+impl Subprogram {
+    /// A program from NC source, its parameters from its PROC statement.
+    fn from_source(name: &str, text: String) -> Self {
+        let (parameters, save) = parse_proc_statement(&text);
+        Subprogram {
+            name: name.to_string(),
+            text: Rc::from(text),
+            parsed: OnceCell::new(),
+            parameters,
+            save,
+            cycle: None,
+        }
+    }
+
+    fn builtin(cycle: &'static Cycle) -> Self {
+        let parameters = cycle
+            .parameters
+            .iter()
+            .map(|&name| Parameter {
+                name: name.to_string(),
+                by_reference: false,
+                is_string: cycle.strings.contains(&name),
+            })
+            .collect();
+        Subprogram {
+            name: cycle.name.to_string(),
+            text: Rc::from(""),
+            parsed: OnceCell::new(),
+            parameters,
+            save: cycle.save,
+            cycle: Some(cycle),
+        }
+    }
+
+    /// The parsed blocks of the program, parsed on the first call. Its text
+    /// must be the input of `state`, for the line numbers of a parse error.
+    fn blocks(&self, state: &State) -> Result<Pair<'_, Rule>, ParsingError> {
+        if let Some(parsed) = self.parsed.get() {
+            return Ok(parsed.borrow_dependent().clone());
+        }
+        crate::structure_scan::check_structures(&self.text)?;
+        let parsed = ParsedProgram::try_new(self.text.clone(), |text| crate::interpreter::parse_blocks(text, state))?;
+        Ok(self.parsed.get_or_init(|| parsed).borrow_dependent().clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Parameter {
+    /// The parameter name, uppercased like every variable.
+    pub(crate) name: String,
+    /// `VAR`: call-by-reference.
+    pub(crate) by_reference: bool,
+    /// A `STRING[n]` parameter (lives in the string table).
+    pub(crate) is_string: bool,
+}
+
+/// The value a call passes to one parameter.
+#[derive(Debug, Clone)]
+pub(crate) enum Argument {
+    Number(f64),
+    Text(String),
+}
+
+/// A call programmed in the current block, run when the block ends.
+#[derive(Debug, Clone)]
+pub(crate) struct PendingCall {
+    pub(crate) program: Rc<Subprogram>,
+    /// One value per parameter, in order.
+    pub(crate) arguments: Vec<Argument>,
+    /// The caller's variable behind each `VAR` parameter, by parameter index.
+    pub(crate) references: Vec<(usize, String)>,
+    /// The calling block.
+    pub(crate) line_no: usize,
+    /// How many times the program runs (`P`).
+    pub(crate) passes: usize,
+}
+
+/// The modal call selected by `MCALL`.
+#[derive(Debug, Clone)]
+pub(crate) struct ModalCall {
+    call: PendingCall,
+    /// `false` until the end of the `MCALL` block: the call first runs after
+    /// the next block that positions an axis.
+    armed: bool,
+}
+
+impl ModalCall {
+    pub(crate) fn new(call: PendingCall) -> Self {
+        ModalCall { call, armed: false }
+    }
+}
+
+/// The subprogram search path, the registered cycles, and the programs
+/// looked up so far (by uppercased name; `None` remembers that a name was not
+/// found).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SubprogramLibrary {
+    dirs: Vec<PathBuf>,
+    registered: HashMap<String, Rc<Subprogram>>,
+    loaded: HashMap<String, Option<Rc<Subprogram>>>,
+}
+
+impl SubprogramLibrary {
+    pub(crate) fn set_dirs(&mut self, dirs: Vec<PathBuf>) {
+        self.dirs = dirs;
+        self.loaded.clear();
+    }
+
+    /// Register the NC source of the cycle `name`, which then takes
+    /// precedence over a program file or built-in cycle of that name.
+    pub(crate) fn register(&mut self, name: &str, source: String) {
+        let name = name.to_ascii_uppercase();
+        self.loaded.remove(&name);
+        self.registered
+            .insert(name.clone(), Rc::new(Subprogram::from_source(&name, source)));
+    }
+
+    /// The search path for error messages.
+    fn describe(&self) -> String {
+        if self.dirs.is_empty() {
+            return "(no subprogram directory configured)".to_string();
+        }
+        self.dirs
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The file `name` refers to: the first directory holding `NAME.SPF` or
+    /// `NAME.MPF`, file names compared case-insensitively like the control
+    /// does.
+    fn locate(&self, name: &str) -> Option<PathBuf> {
+        for dir in &self.dirs {
+            let Ok(entries) = std::fs::read_dir(dir) else { continue };
+            let files: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|e| e.path())).collect();
+            for extension in ["SPF", "MPF"] {
+                let wanted = format!("{name}.{extension}");
+                let found = files.iter().find(|path| {
+                    path.is_file()
+                        && path
+                            .file_name()
+                            .and_then(|file| file.to_str())
+                            .is_some_and(|file| file.eq_ignore_ascii_case(&wanted))
+                });
+                if let Some(path) = found {
+                    return Some(path.clone());
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Look up the subprogram a call in block `line_no` names. `Ok(None)` when
+/// nothing has that name - the word then stays an uninterpreted call, as it
+/// was before subprograms were supported (`MSG(...)`, vendor cycles) - except
+/// for an `L<number>` program when a search path is configured: that can be
+/// nothing but a subprogram and is reported missing.
+pub(crate) fn find(name: &str, line_no: usize, state: &mut State) -> Result<Option<Rc<Subprogram>>, ParsingError> {
+    let key = name.to_ascii_uppercase();
+    let library = &state.subprograms;
+    if let Some(program) = library.registered.get(&key) {
+        return Ok(Some(program.clone()));
+    }
+    let found = match library.loaded.get(&key) {
+        Some(found) => found.clone(),
+        None => {
+            let found = match library.locate(&key) {
+                Some(path) => Some(Rc::new(load(&key, &path, line_no, state)?)),
+                None => crate::cycles::builtin(&key).map(|cycle| Rc::new(Subprogram::builtin(cycle))),
+            };
+            state.subprograms.loaded.insert(key.clone(), found.clone());
+            found
+        }
+    };
+    if found.is_none() && is_numbered_program(&key) && !state.subprograms.dirs.is_empty() {
+        return Err(not_found(name, line_no, state));
+    }
+    Ok(found)
+}
+
+/// The error for a call to a subprogram that is not on the search path.
+pub(crate) fn not_found(name: &str, line_no: usize, state: &State) -> ParsingError {
+    ParsingError::SubprogramNotFound {
+        line_no,
+        preview: state.get_line(line_no).unwrap_or("").to_string(),
+        name: name.to_ascii_uppercase(),
+        searched: state.subprograms.describe(),
+    }
+}
+
+/// `L` followed by digits: the numbered subprogram form.
+fn is_numbered_program(name: &str) -> bool {
+    name.strip_prefix('L')
+        .is_some_and(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Take the pass count `P<n>` of the call programmed earlier in block
+/// `line_no`; false for any other word, or if the block calls nothing.
+pub(crate) fn take_pass_count(word: &str, line_no: usize, state: &mut State) -> Result<bool, ParsingError> {
+    let Some(digits) = word.strip_prefix(['P', 'p']) else {
+        return Ok(false);
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(false);
+    }
+    let Some(call) = state.pending_call.as_mut() else {
+        return Ok(false);
+    };
+    match digits.parse::<usize>() {
+        Ok(passes) if (1..=MAX_PASSES).contains(&passes) => {
+            call.passes = passes;
+            Ok(true)
+        }
+        _ => {
+            let message = format!(
+                "the pass count {word} of {} must be between 1 and {MAX_PASSES}",
+                call.program.name
+            );
+            Err(ParsingError::with_context(
+                line_no,
+                state.get_line(line_no).unwrap_or("").to_string(),
+                "subprogram call".to_string(),
+                message,
+            ))
+        }
+    }
+}
+
+fn load(name: &str, path: &Path, line_no: usize, state: &State) -> Result<Subprogram, ParsingError> {
+    let text = std::fs::read_to_string(path).map_err(|e| {
+        ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or("").to_string(),
+            "subprogram call".to_string(),
+            format!("cannot read subprogram {}: {e}", path.display()),
+        )
+    })?;
+    Ok(Subprogram::from_source(name, text))
+}
+
+/// The formal parameters and the `SAVE` attribute of the PROC statement in
+/// the first block (comments and blank lines may precede it). A program
+/// without one takes no parameters. A first block that does not parse is
+/// left for the interpreter to report once the program runs.
+fn parse_proc_statement(text: &str) -> (Vec<Parameter>, bool) {
+    let mut parameters = Vec::new();
+    let mut save = false;
+    let first_block = text.lines().find(|line| {
+        let line = line.trim();
+        !line.is_empty() && !line.starts_with(';')
+    });
+    let Some(Ok(parsed)) = first_block.map(|line| NCParser::parse(Rule::line_entry, line)) else {
+        return (parameters, save);
+    };
+    let Some(proc_definition) = parsed.flatten().find(|pair| pair.as_rule() == Rule::proc_definition) else {
+        return (parameters, save);
+    };
+    for pair in proc_definition.into_inner() {
+        match pair.as_rule() {
+            Rule::proc_parameter => {
+                let mut parameter = Parameter {
+                    name: String::new(),
+                    by_reference: false,
+                    is_string: false,
+                };
+                for part in pair.into_inner() {
+                    match part.as_rule() {
+                        Rule::var_kw => parameter.by_reference = true,
+                        Rule::data_type => parameter.is_string = part.as_str().to_uppercase().starts_with("STRING"),
+                        Rule::identifier => parameter.name = part.as_str().to_uppercase(),
+                        _ => {}
+                    }
+                }
+                parameters.push(parameter);
+            }
+            Rule::proc_attribute => save |= pair.as_str().eq_ignore_ascii_case("SAVE"),
+            _ => {}
+        }
+    }
+    (parameters, save)
+}
+
+/// Run the subprogram call programmed in block `line_no` just interpreted,
+/// if any, else the modal call if that block positioned an axis.
+pub(crate) fn run_pending_call(line_no: usize, output: &mut OutputRows, state: &mut State) -> Result<(), ParsingError> {
+    let modal = match state.modal_call.as_mut() {
+        Some(modal) if !modal.armed => {
+            modal.armed = true;
+            None
+        }
+        Some(modal) if state.block_positioned => Some(PendingCall {
+            line_no,
+            ..modal.call.clone()
+        }),
+        _ => None,
+    };
+    // A call programmed in the block takes the place of the modal one.
+    let Some(call) = state.pending_call.take().or(modal) else {
+        return Ok(());
+    };
+    if state.call_depth() >= MAX_CALL_DEPTH {
+        return Err(ParsingError::with_context(
+            call.line_no,
+            state.get_line(call.line_no).unwrap_or("").to_string(),
+            "subprogram call".to_string(),
+            format!(
+                "calling {} exceeds the maximum nesting depth of {MAX_CALL_DEPTH} subprogram levels",
+                call.program.name
+            ),
+        ));
+    }
+    for _ in 0..call.passes {
+        run_call(&call, output, state)?;
+    }
+    Ok(())
+}
+
+/// Run one pass of `call`.
+fn run_call(call: &PendingCall, output: &mut OutputRows, state: &mut State) -> Result<(), ParsingError> {
+    let program = &call.program;
+    // A VAR parameter starts from its variable's value now: a modal call
+    // runs long after its arguments were bound.
+    let mut arguments = call.arguments.clone();
+    for (index, key) in &call.references {
+        let current = match arguments[*index] {
+            Argument::Number(_) => state.symbol_table.get(key).copied().map(Argument::Number),
+            Argument::Text(_) => state.string_table.get(key).cloned().map(Argument::Text),
+        };
+        if let Some(current) = current {
+            arguments[*index] = current;
+        }
+    }
+    // A built-in cycle's blocks, generated for these arguments.
+    let expansion = match program.cycle {
+        Some(cycle) => {
+            let context = CycleContext {
+                plane: state.plane,
+                feed: state.axes.get("F").copied(),
+                iteration_limit: state.iteration_limit,
+            };
+            let blocks = cycle.expand(&arguments, &context).map_err(|message| {
+                ParsingError::with_context(
+                    call.line_no,
+                    state.get_line(call.line_no).unwrap_or("").to_string(),
+                    "cycle call".to_string(),
+                    message,
+                )
+            })?;
+            Some(blocks)
+        }
+        None => None,
+    };
+    let saved = program.save.then(|| state.save_modes());
+    let source = state.source();
+    // Jumps never leave a subprogram: its labels are its own.
+    let jump_scopes = std::mem::take(&mut state.jump_scopes);
+    // The called program runs without the caller's modal call, and its own
+    // MCALL ends with it.
+    let modal_call = state.modal_call.take();
+    state.enter_call();
+    // A built-in cycle has its arguments in its blocks already.
+    let arguments = if expansion.is_some() { Vec::new() } else { arguments };
+    for (parameter, argument) in program.parameters.iter().zip(arguments) {
+        state.declare_local(&parameter.name);
+        match argument {
+            Argument::Number(value) => {
+                state.symbol_table.insert(parameter.name.clone(), value);
+            }
+            Argument::Text(text) => {
+                state.string_table.insert(parameter.name.clone(), text);
+            }
+        }
+    }
+    let result = match &expansion {
+        Some(blocks) => {
+            let pinned = output.pin_line(Some(call.line_no));
+            let result = crate::interpreter::interpret_file(blocks, state, output);
+            output.pin_line(pinned);
+            result
+        }
+        None => {
+            state.set_input(&program.text);
+            program
+                .blocks(state)
+                .and_then(|blocks| crate::interpreter::interpret_parsed(blocks, output, state))
+        }
+    };
+    // Read the VAR parameters before their scope goes.
+    let returned: Vec<(String, Option<Argument>)> = call
+        .references
+        .iter()
+        .map(|(index, key)| {
+            let parameter = &program.parameters[*index];
+            let value = if parameter.is_string {
+                state.string_table.get(&parameter.name).cloned().map(Argument::Text)
+            } else {
+                state.symbol_table.get(&parameter.name).copied().map(Argument::Number)
+            };
+            (key.clone(), value)
+        })
+        .collect();
+    state.leave_call();
+    state.modal_call = modal_call;
+    state.jump_scopes = jump_scopes;
+    state.restore_source(source);
+    result.map_err(|e| e.in_subprogram(&program.name, call.line_no, state.get_line(call.line_no).unwrap_or("")))?;
+
+    // What the return changes lands on a row of the calling block.
+    output.start_row(call.line_no)?;
+    for (key, value) in returned {
+        match value {
+            Some(Argument::Number(value)) => {
+                output.record_variable_change(&key, value);
+                state.symbol_table.insert(key, value);
+            }
+            Some(Argument::Text(text)) => {
+                state.string_table.insert(key, text);
+            }
+            None => {}
+        }
+    }
+    if let Some(saved) = saved {
+        let arcs_reversed = state.arcs_reversed();
+        let restored = state.restore_modes(saved);
+        let last = output.last_mut().expect("row was just started");
+        for (group, word) in restored {
+            last.insert(group, Value::Str(g_command_output(group, word, state)));
+        }
+        if last.get("gg01_motion").is_none() {
+            if let Some(word) = state.restated_arc_motion(arcs_reversed) {
+                last.insert("gg01_motion", Value::Str(word.to_string()));
+            }
+        }
+        output.set_frame(state.frame_linear(), state.mirrored_geometry());
+    }
+    Ok(())
+}
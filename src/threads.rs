@@ -0,0 +1,182 @@
+//! Thread cutting (`G33`/`G34`/`G35`, manual 3.10): the tool moves on a
+//! straight line, synchronised to the spindle, so the rotating workpiece
+//! gets a helical thread.
+//!
+//! * `I`/`J`/`K` give the thread lead (mm per revolution) along X/Y/Z. For a
+//!   taper thread the lead belongs to the axis with the longer travel; of
+//!   several leads, that axis' one is used.
+//! * `G34` increases and `G35` decreases the lead per revolution by `F=`
+//!   (mm/rev²). There `F` is no feed: the modal path feed stays what it was.
+//! * `SF=` (start angle offset, degrees) is an output column of the block
+//!   that programs it.
+//!
+//! The row carries the spindle-synchronised path information instead of the
+//! lead words: `THREAD_LEAD` (lead at the start of the block),
+//! `THREAD_LEAD_CHANGE` (signed, G34/G35 only) and `THREAD_REVOLUTIONS`
+//! (spindle revolutions over the block). While the spindle turns at `n` rpm
+//! (see [`crate::spindle`]; under G96 the speed at the end point) the
+//! block's `F` becomes the path feed that cuts the thread in
+//! `THREAD_REVOLUTIONS / n` minutes, and the next non-thread row restates
+//! the programmed feed, so the forward-filled `F` stays meaningful.
+
+use crate::arcs::{CENTRE_OFFSETS, GEOMETRY_AXES};
+use crate::errors::ParsingError;
+use crate::output::OutputRows;
+use crate::state::{emit_warning, State};
+use crate::types::Value;
+
+/// The thread columns of a row (see the module docs).
+pub(crate) const THREAD_LEAD: &str = "THREAD_LEAD";
+pub(crate) const THREAD_LEAD_CHANGE: &str = "THREAD_LEAD_CHANGE";
+pub(crate) const THREAD_REVOLUTIONS: &str = "THREAD_REVOLUTIONS";
+
+/// The feed bookkeeping around thread blocks.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Threads {
+    /// The programmed path feed, which a G34/G35 lead change `F` must not
+    /// replace.
+    path_feed: Option<f64>,
+    /// True while the last row's `F` is a derived thread feed.
+    feed_derived: bool,
+}
+
+/// Resolve a thread block once all its other words ran; after one, restate
+/// the programmed feed.
+pub(crate) fn finish_block(line_no: usize, output: &mut OutputRows, state: &mut State) -> Result<(), ParsingError> {
+    // The F of a G4 block is its dwell time (see crate::output), no feed.
+    let last = output.last_mut().expect("row started");
+    if matches!(last.get("gg02_wait"), Some(Value::Str(code)) if code == "G4") {
+        return Ok(());
+    }
+    let lead_change_sign = match state.modal_word("gg01_motion") {
+        Some("G33") => 0.0,
+        Some("G34") => 1.0,
+        Some("G35") => -1.0,
+        _ => {
+            let programmed = match last.get("F") {
+                Some(Value::Float(feed)) => Some(*feed),
+                _ => None,
+            };
+            record_path_feed(programmed, state);
+            restate_feed(output, state);
+            return Ok(());
+        }
+    };
+    let last = output.last_mut().expect("row started");
+    let cell = |key: &str| match last.get(key) {
+        Some(Value::Float(value)) => Some(*value),
+        _ => None,
+    };
+    let leads = CENTRE_OFFSETS.map(cell);
+    // In G34/G35, F is the lead change; the path feed is the one before.
+    let lead_change = if lead_change_sign != 0.0 { cell("F") } else { None };
+    if lead_change.is_some() {
+        last.remove("F");
+        match state.threads.path_feed {
+            Some(feed) => state.update_axis("F", feed)?,
+            None => {
+                state.axes.remove("F");
+            }
+        }
+    } else {
+        record_path_feed(cell("F"), state);
+    }
+    let error = |message: String| {
+        ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or("").to_string(),
+            "thread cutting (G33/G34/G35)".to_string(),
+            message,
+        )
+    };
+    let travel: [f64; 3] = std::array::from_fn(|slot| {
+        let start = state.block_start_local(GEOMETRY_AXES[slot]).unwrap_or(0.0);
+        state.get_axis_local(GEOMETRY_AXES[slot]).unwrap_or(start) - start
+    });
+    let moves = travel.iter().any(|distance| distance.abs() > 1e-12);
+    if !moves && leads.iter().all(Option::is_none) {
+        // A block in modal thread mode that moves nothing.
+        restate_feed(output, state);
+        return Ok(());
+    }
+
+    let Some(slot) = (0..3)
+        .filter(|&slot| leads[slot].is_some())
+        .max_by(|&a, &b| travel[a].abs().total_cmp(&travel[b].abs()))
+    else {
+        return Err(error("a thread block needs the lead I/J/K".to_string()));
+    };
+    let lead = leads[slot].unwrap_or(0.0).abs();
+    if lead < 1e-12 {
+        return Err(error(format!("the thread lead {} must not be 0", CENTRE_OFFSETS[slot])));
+    }
+    let distance = travel[slot].abs();
+    if distance < 1e-12 {
+        return Err(error(format!(
+            "the thread lead {} belongs to {}, which the block does not move",
+            CENTRE_OFFSETS[slot], GEOMETRY_AXES[slot]
+        )));
+    }
+    let change = match (lead_change_sign, lead_change) {
+        (0.0, _) => 0.0,
+        (sign, Some(change)) => sign * change.abs(),
+        (_, None) => return Err(error("G34/G35 need the lead change F=".to_string())),
+    };
+    // The revolutions n over which the lead L + change·n covers the
+    // distance: L·n + change·n²/2 = distance.
+    let discriminant = lead * lead + 2.0 * change * distance;
+    if discriminant <= 0.0 {
+        return Err(error(
+            "the lead decreases to 0 before the thread reaches its end point".to_string(),
+        ));
+    }
+    let revolutions = 2.0 * distance / (lead + discriminant.sqrt());
+
+    for offset in CENTRE_OFFSETS {
+        last.remove(offset);
+    }
+    last.insert(THREAD_LEAD, Value::Float(lead));
+    if lead_change_sign != 0.0 {
+        last.insert(THREAD_LEAD_CHANGE, Value::Float(change));
+    }
+    last.insert(THREAD_REVOLUTIONS, Value::Float(revolutions));
+    match crate::spindle::turning_speed(state).filter(|speed| *speed > 0.0) {
+        Some(speed) => {
+            let path = travel.iter().map(|distance| distance * distance).sum::<f64>().sqrt();
+            last.insert("F", Value::Float(path * speed / revolutions));
+            state.threads.feed_derived = true;
+        }
+        None => {
+            emit_warning(format_args!(
+                "Warning [line {line_no}]: the spindle does not turn (M3/M4 with a speed S) for the thread; its feed is not derived"
+            ));
+            restate_feed(output, state);
+        }
+    }
+    Ok(())
+}
+
+/// Remember the feed a block programs. Only the row's own F counts: after
+/// a G4 block the modal F holds the dwell time.
+fn record_path_feed(programmed: Option<f64>, state: &mut State) {
+    match programmed {
+        Some(feed) => state.threads.path_feed = Some(feed),
+        None if state.threads.path_feed.is_none() => state.threads.path_feed = state.get_axis_local("F"),
+        None => {}
+    }
+}
+
+/// After a row with a derived thread feed, output the programmed feed again
+/// (unless the block programs its own).
+fn restate_feed(output: &mut OutputRows, state: &mut State) {
+    if !state.threads.feed_derived {
+        return;
+    }
+    state.threads.feed_derived = false;
+    let last = output.last_mut().expect("row started");
+    if last.get("F").is_none() {
+        if let Some(feed) = state.threads.path_feed {
+            last.insert("F", Value::Float(feed));
+        }
+    }
+}
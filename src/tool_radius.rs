@@ -0,0 +1,738 @@
+//! Tool radius compensation (`G40`/`G41`/`G42`, manual 8.6): with `G41` the
+//! tool centre travels left of the programmed contour, with `G42` right of
+//! it, at the radius of the active tool offset `D`; `G40` ends it.
+//!
+//! * The radius is `$TC_DP6[<t>, <d>]` of the tool table (see
+//!   [`crate::tools`]) for the loaded tool `<t>` and the active `D<d>`.
+//!   `D0`, or no `D`, compensates nothing.
+//! * Lines (G0/G1) and circles (G2/G3) are offset by the radius, in the
+//!   working plane. Where two of them meet, the output stage looks ahead one
+//!   element: at an inner corner both are trimmed to the intersection of
+//!   their offset paths; an outer corner gets a transition circle around the
+//!   corner point (`G450`), or runs to the intersection of the two tangents
+//!   (`G451`, a transition circle where they do not meet).
+//! * The first block that moves in the plane after `G41`/`G42` approaches
+//!   the contour, and the first one after `G40` retracts from it; both must
+//!   be lines. With `NORM` the tool stands perpendicular to the contour at
+//!   its first/last point; with `KONT` it travels around that point like
+//!   around an outer corner.
+//! * Blocks that move nothing in the plane (an infeed, M codes) leave the
+//!   tool where it is; the corner is made between the elements around them.
+//!
+//! The rows then carry the tool-centre path. A compensated circle carries
+//! its centre as I/J/K offsets from its new start point (`CR` is replaced),
+//! and a corner transition is a row of its own, with the line number of the
+//! block after the corner. With `keep_contour`, the `CONTOUR_X`/`CONTOUR_Y`/
+//! `CONTOUR_Z` columns keep the programmed end point of every row that
+//! positions the axis.
+//!
+//! Alarms: a D number without a radius, an approach or retract that is no
+//! line, another interpolation while compensating, a plane change and a
+//! rotated, scaled or mirrored frame while compensating, and `KONTT`/`KONTC`.
+//! An element that the offset turns around (the tool does not fit the
+//! contour) and offset paths that do not meet at an inner corner give a
+//! warning; the output then shows the gouge.
+
+use crate::arcs::{CENTRE_OFFSETS, GEOMETRY_AXES};
+use crate::errors::ParsingError;
+use crate::output::{intern_column, OutputRows, Row};
+use crate::state::{emit_warning, State};
+use crate::types::Value;
+
+/// The columns holding the programmed contour with `keep_contour`.
+pub(crate) const CONTOUR_COLUMNS: [&str; 3] = ["CONTOUR_X", "CONTOUR_Y", "CONTOUR_Z"];
+
+/// Points closer than this coincide (mm).
+const EPSILON: f64 = 1e-9;
+
+/// The compensation the output was last given.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ToolRadius {
+    sent: Option<Setting>,
+    /// True once a block moved in the plane under G41/G42: the tool has
+    /// left the contour and must retract from it.
+    engaged: bool,
+}
+
+impl ToolRadius {
+    /// Forget the compensation sent to a previous output: the next G41/G42
+    /// block approaches the contour anew.
+    pub(crate) fn restart(&mut self) {
+        self.sent = None;
+        self.engaged = false;
+    }
+}
+
+/// An active compensation, as the output stage applies it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Setting {
+    /// The distance of the tool centre from the contour, to the left of the
+    /// direction of travel: the radius for G41, its negative for G42.
+    offset: f64,
+    /// The abscissa and ordinate of the working plane, as X/Y/Z slots.
+    plane: [usize; 2],
+    /// `KONT`: approach and retract around the contour point.
+    around: bool,
+    /// `G451`: outer corners run to the intersection of the tangents.
+    intersect: bool,
+}
+
+/// Check a block under tool radius compensation once all its words ran,
+/// and hand a changed compensation to the output.
+pub(crate) fn finish_block(line_no: usize, output: &mut OutputRows, state: &mut State) -> Result<(), ParsingError> {
+    let side = match state.modal_word("gg07_tool_radius") {
+        Some("G41") => 1.0,
+        Some("G42") => -1.0,
+        _ => 0.0,
+    };
+    if side == 0.0 && state.tool_radius.sent.is_none() && !state.tool_radius.engaged {
+        return Ok(());
+    }
+    let error = |message: String| {
+        ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or("").to_string(),
+            "tool radius compensation (G40/G41/G42)".to_string(),
+            message,
+        )
+    };
+    let [abscissa, ordinate, _] = state.plane.axes();
+    let setting = if side == 0.0 {
+        None
+    } else {
+        let radius = match state.get_axis_local("D") {
+            None | Some(0.0) => 0.0,
+            Some(number) if number < 0.0 || number.fract() != 0.0 => {
+                return Err(error(format!("D{number} is not a tool offset number")))
+            }
+            Some(number) => match state.tools.offset(number as usize).map_err(&error)? {
+                Some(offset) => offset.radius,
+                None => return Err(error(crate::tools::missing_offset(number))),
+            },
+        };
+        let around = match state.modal_word("gg17_approach_retract_tool") {
+            Some("KONT") => true,
+            Some(word @ ("KONTT" | "KONTC")) => {
+                return Err(error(format!(
+                    "{word} approach and retract is not supported; use NORM or KONT"
+                )))
+            }
+            _ => false,
+        };
+        (radius != 0.0).then(|| Setting {
+            offset: side * radius,
+            plane: [abscissa, ordinate],
+            around,
+            intersect: state.modal_word("gg18_corner_behavior") == Some("G451"),
+        })
+    };
+    if let Some(setting) = setting {
+        if state.frame_linear().is_some() {
+            return Err(error(
+                "tool radius compensation under a rotated, scaled or mirrored frame is not supported".to_string(),
+            ));
+        }
+        if state.tool_radius.engaged && state.tool_radius.sent.is_some_and(|sent| sent.plane != setting.plane) {
+            return Err(error(
+                "the working plane cannot change while tool radius compensation is active (program G40 first)"
+                    .to_string(),
+            ));
+        }
+    }
+
+    let last = output.last_mut().expect("row started");
+    let moves = state.moved_in_block(abscissa)
+        || state.moved_in_block(ordinate)
+        || [abscissa, ordinate]
+            .iter()
+            .any(|&slot| last.get(CENTRE_OFFSETS[slot]).is_some());
+    if moves {
+        let motion = state.modal_word("gg01_motion").unwrap_or("G0");
+        let line = matches!(motion, "G0" | "G1");
+        match (setting.is_some(), state.tool_radius.engaged) {
+            (true, false) if !line => {
+                return Err(error(format!(
+                    "the block that approaches the contour must be a G0/G1 line, not {motion}"
+                )))
+            }
+            (true, false) => {
+                for slot in [abscissa, ordinate] {
+                    if state.block_start_local(GEOMETRY_AXES[slot]).is_none() {
+                        return Err(error(format!(
+                            "the approach needs the start position of {}",
+                            GEOMETRY_AXES[slot]
+                        )));
+                    }
+                }
+            }
+            (true, true) if !line && !matches!(motion, "G2" | "G3") => {
+                return Err(error(format!(
+                    "only G0/G1 lines and G2/G3 circles can be compensated, not {motion}"
+                )))
+            }
+            (false, true) if !line => {
+                return Err(error(format!(
+                    "the block that retracts from the contour (G40) must be a G0/G1 line, not {motion}"
+                )))
+            }
+            _ => {}
+        }
+        state.tool_radius.engaged = setting.is_some();
+    }
+
+    if state.tool_radius.sent != setting {
+        if setting.is_some() && !output.has_compensator() {
+            // The stage starts where the rows so far left the tool: at the
+            // start of this block.
+            let local = GEOMETRY_AXES.map(|axis| state.block_start_local(axis));
+            let machine = state.machine_point(local.map(|value| value.unwrap_or(0.0)));
+            let contour = std::array::from_fn(|slot| local[slot].map(|_| machine[slot]));
+            let motion = state.modal_word("gg01_motion").map(str::to_string);
+            output.set_compensator(Compensator::new(false, contour, motion));
+        }
+        output.set_radius_compensation(setting);
+        state.tool_radius.sent = setting;
+    }
+    Ok(())
+}
+
+/// The output stage between the interpreter and the curve flattener that
+/// moves the rows onto the tool-centre path (see the module docs).
+#[derive(Debug)]
+pub(crate) struct Compensator {
+    keep_contour: bool,
+    /// The programmed X/Y/Z position in machine coordinates: the contour.
+    contour: [Option<f64>; 3],
+    setting: Option<Setting>,
+    /// The active G-group 1 word, and the last one passed on (which a
+    /// generated transition row may have changed).
+    motion: Option<String>,
+    output_motion: Option<String>,
+    /// The compensated element whose end waits for the next element.
+    pending: Option<Pending>,
+    axes: [&'static str; 3],
+    offsets: [&'static str; 3],
+    contour_columns: [&'static str; 3],
+}
+
+/// A compensated element whose end is not known yet.
+#[derive(Debug)]
+struct Pending {
+    row: Row,
+    element: Element,
+    setting: Setting,
+    motion: Option<String>,
+    /// Where the tool starts the element.
+    start: [f64; 2],
+    /// The element approaches the contour: its start is not compensated.
+    approach: bool,
+    /// The rows after it that move nothing in the plane.
+    trailing: Vec<Row>,
+}
+
+/// A programmed line or circle in the working plane.
+#[derive(Debug, Clone, Copy)]
+struct Element {
+    start: [f64; 2],
+    end: [f64; 2],
+    /// The centre and direction (`true`: clockwise) of a circle, `None` for
+    /// a line.
+    arc: Option<([f64; 2], bool)>,
+}
+
+/// What follows a pending element.
+enum Next<'a> {
+    Element(&'a Element, Setting),
+    /// The line retracting from the contour.
+    Retract(&'a Element),
+    /// Nothing: the program ended.
+    End,
+}
+
+/// A generated move between two compensated elements.
+enum Transition {
+    Line([f64; 2]),
+    Arc { to: [f64; 2], centre: [f64; 2], cw: bool },
+}
+
+/// A compensated path through a point, extended without end.
+enum Curve {
+    Line([f64; 2], [f64; 2]),
+    Circle([f64; 2], f64),
+}
+
+impl Element {
+    /// The unit direction of travel at `point` (a point of the element).
+    fn tangent(&self, point: [f64; 2]) -> [f64; 2] {
+        match self.arc {
+            None => unit(sub(self.end, self.start)),
+            Some((centre, cw)) => {
+                let radial = unit(sub(point, centre));
+                if cw {
+                    [radial[1], -radial[0]]
+                } else {
+                    [-radial[1], radial[0]]
+                }
+            }
+        }
+    }
+
+    /// `point` moved `offset` to the left of the direction of travel there.
+    fn shifted(&self, point: [f64; 2], offset: f64) -> [f64; 2] {
+        let [x, y] = self.tangent(point);
+        [point[0] - offset * y, point[1] + offset * x]
+    }
+
+    /// The compensated path through `point`.
+    fn path(&self, point: [f64; 2]) -> Curve {
+        match self.arc {
+            None => Curve::Line(point, self.tangent(self.start)),
+            Some((centre, _)) => Curve::Circle(centre, distance(point, centre)),
+        }
+    }
+}
+
+impl Compensator {
+    /// A stage starting at the machine X/Y/Z position `contour` with the
+    /// G-group 1 word `motion` active.
+    pub(crate) fn new(keep_contour: bool, contour: [Option<f64>; 3], motion: Option<String>) -> Self {
+        Compensator {
+            keep_contour,
+            contour,
+            setting: None,
+            output_motion: motion.clone(),
+            motion,
+            pending: None,
+            axes: GEOMETRY_AXES.map(intern_column),
+            offsets: CENTRE_OFFSETS.map(intern_column),
+            contour_columns: CONTOUR_COLUMNS.map(intern_column),
+        }
+    }
+
+    /// Set the compensation for the rows that follow (`None`: G40).
+    pub(crate) fn set_setting(&mut self, setting: Option<Setting>) {
+        self.setting = setting;
+    }
+
+    /// Process one interpreter row, appending the rows that are complete to
+    /// `out`. A compensated element is held back until the next one.
+    pub(crate) fn push(&mut self, mut row: Row, out: &mut Vec<Row>) {
+        if let Some(Value::Str(word)) = row.cells.get("gg01_motion") {
+            self.motion = Some(word.clone());
+        }
+        let plane = self
+            .setting
+            .map(|setting| setting.plane)
+            .or(self.pending.as_ref().map(|pending| pending.setting.plane));
+        let element = plane.and_then(|plane| self.element(&row, plane));
+        for slot in 0..3 {
+            if let Some(value) = cell(&row, self.axes[slot]) {
+                self.contour[slot] = Some(value);
+            }
+        }
+        if self.keep_contour {
+            for slot in 0..3 {
+                let placed = row.cells.get(self.axes[slot]).is_some()
+                    || (element.is_some() && plane.is_some_and(|plane| plane.contains(&slot)));
+                if let (true, Some(value)) = (placed, self.contour[slot]) {
+                    row.cells.insert(self.contour_columns[slot], Value::Float(value));
+                }
+            }
+        }
+
+        match (element, self.setting, self.pending.take()) {
+            (None, _, Some(mut pending)) => {
+                // The tool stays where the pending element leaves it.
+                for slot in pending.setting.plane {
+                    row.cells.remove(self.axes[slot]);
+                }
+                pending.trailing.push(row);
+                self.pending = Some(pending);
+            }
+            (Some(element), Some(setting), pending) => {
+                let approach = pending.is_none();
+                let start = match pending {
+                    Some(pending) => self.join(pending, Next::Element(&element, setting), row.line_no, out),
+                    None => element.start,
+                };
+                self.pending = Some(Pending {
+                    row,
+                    element,
+                    setting,
+                    motion: self.motion.clone(),
+                    start,
+                    approach,
+                    trailing: Vec::new(),
+                });
+            }
+            (Some(element), None, Some(pending)) => {
+                let [u, v] = pending.setting.plane;
+                self.join(pending, Next::Retract(&element), row.line_no, out);
+                // The retract ends on the programmed point, in both axes.
+                row.cells.insert(self.axes[u], Value::Float(element.end[0]));
+                row.cells.insert(self.axes[v], Value::Float(element.end[1]));
+                let motion = self.motion.clone();
+                self.emit(row, motion.as_deref(), out);
+            }
+            (_, _, None) => {
+                let motion = self.motion.clone();
+                self.emit(row, motion.as_deref(), out);
+            }
+        }
+    }
+
+    /// Emit the element still waiting at the end of the program.
+    pub(crate) fn finish(&mut self, out: &mut Vec<Row>) {
+        if let Some(pending) = self.pending.take() {
+            let line_no = pending.row.line_no;
+            self.join(pending, Next::End, line_no, out);
+        }
+    }
+
+    /// The element `row` programs in `plane`, from the contour position;
+    /// `None` if it moves nothing there.
+    fn element(&self, row: &Row, plane: [usize; 2]) -> Option<Element> {
+        let start = [self.contour[plane[0]]?, self.contour[plane[1]]?];
+        let end = [0, 1].map(|k| cell(row, self.axes[plane[k]]).unwrap_or(start[k]));
+        let arc = match self.motion.as_deref() {
+            Some(word @ ("G2" | "G3")) => {
+                let cw = word == "G2";
+                let offsets = plane.map(|slot| cell(row, self.offsets[slot]));
+                let centre = if offsets.iter().any(Option::is_some) {
+                    Some([0, 1].map(|k| start[k] + offsets[k].unwrap_or(0.0)))
+                } else {
+                    cell(row, "CR").and_then(|radius| radius_centre(start, end, radius, cw))
+                };
+                centre
+                    .filter(|&centre| distance(start, centre) > EPSILON)
+                    .map(|centre| (centre, cw))
+            }
+            _ => None,
+        };
+        (distance(start, end) > EPSILON || arc.is_some()).then_some(Element { start, end, arc })
+    }
+
+    /// Emit `pending` ending where `next` lets it, the rows after it and the
+    /// transition to `next`; returns where `next` starts.
+    fn join(&mut self, pending: Pending, next: Next, line_no: usize, out: &mut Vec<Row>) -> [f64; 2] {
+        let element = pending.element;
+        let offset = pending.setting.offset;
+        let corner = element.end;
+        let a = element.shifted(corner, offset);
+        let (end, transitions, start) = match next {
+            Next::End => (a, Vec::new(), a),
+            Next::Retract(after) => {
+                let b = after.shifted(corner, offset);
+                if pending.setting.around && is_outer(element.tangent(corner), after.tangent(corner), offset) {
+                    around_corner(&element, after, corner, a, b, offset, pending.setting.intersect)
+                } else {
+                    (a, Vec::new(), a)
+                }
+            }
+            Next::Element(after, setting) if pending.approach => {
+                let b = after.shifted(corner, setting.offset);
+                if pending.setting.around && is_outer(element.tangent(corner), after.tangent(corner), setting.offset) {
+                    let a = element.shifted(corner, setting.offset);
+                    around_corner(&element, after, corner, a, b, setting.offset, setting.intersect)
+                } else {
+                    (b, Vec::new(), b)
+                }
+            }
+            Next::Element(after, setting) => {
+                let b = after.shifted(corner, setting.offset);
+                if setting.offset != offset {
+                    // A new radius or side: straight across.
+                    (a, vec![Transition::Line(b)], b)
+                } else if distance(a, b) <= EPSILON {
+                    (a, Vec::new(), a)
+                } else if is_outer(element.tangent(corner), after.tangent(corner), offset) {
+                    around_corner(&element, after, corner, a, b, offset, setting.intersect)
+                } else {
+                    match nearest(intersections(element.path(a), after.path(b)), corner) {
+                        Some(meet) => (meet, Vec::new(), meet),
+                        None => {
+                            warn(
+                                line_no,
+                                "the offset paths do not meet at the inner corner; the tool radius is too large",
+                            );
+                            (a, vec![Transition::Line(b)], b)
+                        }
+                    }
+                }
+            }
+        };
+
+        let Pending {
+            mut row,
+            setting,
+            motion,
+            start: from,
+            approach,
+            trailing,
+            ..
+        } = pending;
+        if !approach {
+            check_element(&element, from, end, offset, row.line_no);
+        }
+        let [u, v] = setting.plane;
+        row.cells.insert(self.axes[u], Value::Float(end[0]));
+        row.cells.insert(self.axes[v], Value::Float(end[1]));
+        if let Some((centre, _)) = element.arc {
+            row.cells.remove("CR");
+            row.cells.insert(self.offsets[u], Value::Float(centre[0] - from[0]));
+            row.cells.insert(self.offsets[v], Value::Float(centre[1] - from[1]));
+        }
+        self.emit(row, motion.as_deref(), out);
+        for row in trailing {
+            self.emit(row, None, out);
+        }
+        let mut from = end;
+        for transition in transitions {
+            let mut cells = crate::output::CellMap::default();
+            let to = match transition {
+                Transition::Line(to) => {
+                    cells.insert(intern_column("gg01_motion"), Value::Str("G1".to_string()));
+                    to
+                }
+                Transition::Arc { to, centre, cw } => {
+                    let word = if cw { "G2" } else { "G3" };
+                    cells.insert(intern_column("gg01_motion"), Value::Str(word.to_string()));
+                    cells.insert(self.offsets[u], Value::Float(centre[0] - from[0]));
+                    cells.insert(self.offsets[v], Value::Float(centre[1] - from[1]));
+                    to
+                }
+            };
+            cells.insert(self.axes[u], Value::Float(to[0]));
+            cells.insert(self.axes[v], Value::Float(to[1]));
+            if self.keep_contour {
+                cells.insert(self.contour_columns[u], Value::Float(corner[0]));
+                cells.insert(self.contour_columns[v], Value::Float(corner[1]));
+            }
+            let row = Row {
+                line_no,
+                cells,
+                variable_changes: Vec::new(),
+            };
+            self.emit(row, None, out);
+            from = to;
+        }
+        start
+    }
+
+    /// Pass `row` on, restating the G-group 1 word `motion` it moves under
+    /// if a generated row changed the one in effect.
+    fn emit(&mut self, mut row: Row, motion: Option<&str>, out: &mut Vec<Row>) {
+        match row.cells.get("gg01_motion") {
+            Some(Value::Str(word)) => self.output_motion = Some(word.clone()),
+            _ => {
+                if let Some(motion) = motion.filter(|&motion| self.output_motion.as_deref() != Some(motion)) {
+                    row.cells
+                        .insert(intern_column("gg01_motion"), Value::Str(motion.to_string()));
+                    self.output_motion = Some(motion.to_string());
+                }
+            }
+        }
+        out.push(row);
+    }
+}
+
+/// True if the turn from direction `before` to `after` is an outer corner
+/// for the tool at `offset` (left of the path if positive).
+fn is_outer(before: [f64; 2], after: [f64; 2], offset: f64) -> bool {
+    let turn = cross(before, after);
+    if turn.abs() < EPSILON {
+        dot(before, after) < 0.0
+    } else {
+        offset * turn < 0.0
+    }
+}
+
+/// The way around the outer corner `corner` from `a`, the compensated end of
+/// `before`, to `b`, the compensated start of `after`: the end of `before`,
+/// the transitions and the start of `after`.
+fn around_corner(
+    before: &Element,
+    after: &Element,
+    corner: [f64; 2],
+    a: [f64; 2],
+    b: [f64; 2],
+    offset: f64,
+    intersect: bool,
+) -> ([f64; 2], Vec<Transition>, [f64; 2]) {
+    if intersect {
+        let tangents = (
+            Curve::Line(a, before.tangent(corner)),
+            Curve::Line(b, after.tangent(corner)),
+        );
+        if let Some(meet) = intersections(tangents.0, tangents.1).first().copied() {
+            let mut transitions = Vec::new();
+            let end = match before.arc {
+                None => meet,
+                Some(_) => {
+                    transitions.push(Transition::Line(meet));
+                    a
+                }
+            };
+            let start = match after.arc {
+                None => meet,
+                Some(_) => {
+                    transitions.push(Transition::Line(b));
+                    b
+                }
+            };
+            return (end, transitions, start);
+        }
+    }
+    let arc = Transition::Arc {
+        to: b,
+        centre: corner,
+        cw: offset > 0.0,
+    };
+    (a, vec![arc], b)
+}
+
+/// Warn if compensating `element` from `start` to `end` turned it around.
+fn check_element(element: &Element, start: [f64; 2], end: [f64; 2], offset: f64, line_no: usize) {
+    match element.arc {
+        None => {
+            if dot(sub(end, start), sub(element.end, element.start)) < 0.0 {
+                warn(
+                    line_no,
+                    "the compensated line runs backwards; the tool does not fit the contour",
+                );
+            }
+        }
+        Some((centre, cw)) => {
+            let radius = distance(element.start, centre);
+            // Left of a clockwise circle is outwards.
+            if radius + if cw { offset } else { -offset } <= EPSILON {
+                warn(line_no, "the circle is smaller than the tool radius on its inner side");
+            } else if sweep(start, end, centre, cw) > sweep(element.start, element.end, centre, cw) + EPSILON {
+                warn(
+                    line_no,
+                    "the compensated circle runs backwards; the tool does not fit the contour",
+                );
+            }
+        }
+    }
+}
+
+fn warn(line_no: usize, message: &str) {
+    emit_warning(format_args!(
+        "Warning: tool radius compensation (line {line_no}): {message}"
+    ));
+}
+
+/// The centre of the circle from `start` to `end` with the radius `CR=`
+/// (negative: more than a semicircle).
+fn radius_centre(start: [f64; 2], end: [f64; 2], radius: f64, cw: bool) -> Option<[f64; 2]> {
+    let chord = distance(start, end);
+    let half = chord / 2.0;
+    if chord < EPSILON || radius.abs() < half - EPSILON {
+        return None;
+    }
+    let height = (radius * radius - half * half).max(0.0).sqrt();
+    let [x, y] = unit(sub(end, start));
+    // The centre of the minor arc lies right of the chord for G2.
+    let side = if cw == (radius >= 0.0) { -1.0 } else { 1.0 };
+    Some([
+        (start[0] + end[0]) / 2.0 - side * height * y,
+        (start[1] + end[1]) / 2.0 + side * height * x,
+    ])
+}
+
+/// The angle travelled from `start` to `end` around `centre`, a full turn
+/// if they coincide.
+fn sweep(start: [f64; 2], end: [f64; 2], centre: [f64; 2], cw: bool) -> f64 {
+    if distance(start, end) <= EPSILON {
+        return std::f64::consts::TAU;
+    }
+    let angle = |point: [f64; 2]| (point[1] - centre[1]).atan2(point[0] - centre[0]);
+    let turned = angle(end) - angle(start);
+    if cw { -turned } else { turned }.rem_euclid(std::f64::consts::TAU)
+}
+
+/// The points where two compensated paths meet.
+fn intersections(first: Curve, second: Curve) -> Vec<[f64; 2]> {
+    match (first, second) {
+        (Curve::Line(p, d), Curve::Line(q, e)) => {
+            let denominator = cross(d, e);
+            if denominator.abs() < EPSILON {
+                return Vec::new();
+            }
+            let along = cross(sub(q, p), e) / denominator;
+            vec![add(p, scale(d, along))]
+        }
+        (Curve::Line(p, d), Curve::Circle(centre, radius)) | (Curve::Circle(centre, radius), Curve::Line(p, d)) => {
+            // |p + s·d − centre| = radius, with d a unit vector.
+            let w = sub(p, centre);
+            let half_b = dot(d, w);
+            let discriminant = half_b * half_b - (dot(w, w) - radius * radius);
+            if discriminant < 0.0 {
+                return Vec::new();
+            }
+            let root = discriminant.sqrt();
+            vec![add(p, scale(d, -half_b - root)), add(p, scale(d, -half_b + root))]
+        }
+        (Curve::Circle(c, r), Curve::Circle(k, s)) => {
+            let between = distance(c, k);
+            if between < EPSILON || between > r + s || between < (r - s).abs() {
+                return Vec::new();
+            }
+            let along = (r * r - s * s + between * between) / (2.0 * between);
+            let height = (r * r - along * along).max(0.0).sqrt();
+            let [x, y] = unit(sub(k, c));
+            let foot = add(c, [x * along, y * along]);
+            vec![
+                [foot[0] - height * y, foot[1] + height * x],
+                [foot[0] + height * y, foot[1] - height * x],
+            ]
+        }
+    }
+}
+
+fn nearest(points: Vec<[f64; 2]>, target: [f64; 2]) -> Option<[f64; 2]> {
+    points
+        .into_iter()
+        .min_by(|a, b| distance(*a, target).total_cmp(&distance(*b, target)))
+}
+
+fn cell(row: &Row, key: &str) -> Option<f64> {
+    match row.cells.get(key) {
+        Some(Value::Float(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+fn add(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: [f64; 2], factor: f64) -> [f64; 2] {
+    [a[0] * factor, a[1] * factor]
+}
+
+fn dot(a: [f64; 2], b: [f64; 2]) -> f64 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: [f64; 2], b: [f64; 2]) -> f64 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+fn unit(a: [f64; 2]) -> [f64; 2] {
+    let length = a[0].hypot(a[1]);
+    if length < 1e-12 {
+        [0.0, 0.0]
+    } else {
+        scale(a, 1.0 / length)
+    }
+}
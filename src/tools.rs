@@ -0,0 +1,207 @@
+//! Tool selection and tool length compensation (manual 8.1..8.5).
+//!
+//! * The tool table (typically in the initial-state file) holds the offset
+//!   data of each tool `<t>` and offset number `D<d>`: `$TC_DP3[<t>, <d>]`
+//!   .. `$TC_DP5[<t>, <d>]` are the lengths 1..3, `$TC_DP6[<t>, <d>]` the
+//!   radius (see [`crate::tool_radius`]). `$TC_TP2[<t>] = "<name>"` names
+//!   tool `<t>`, so that `T="<name>"` selects it.
+//! * `T<t>` or `T="<name>"` selects a tool, and `M6` loads it. Both forms
+//!   appear in the `T` column. `T0` unloads the tool with the next `M6`.
+//! * `D<d>` activates an offset of the loaded tool, `D0` deselects it.
+//!   Before the first `M6` a D number refers to the one tool the table
+//!   gives it.
+//! * The active lengths shift the machine positions of the geometry axes:
+//!   length 1 along the infeed axis of the working plane (Z under G17),
+//!   length 2 along its ordinate and length 3 along its abscissa. The
+//!   output therefore gives the tool carrier reference point (the
+//!   flange), while the program and `$AA_IW` speak of the tool tip. As on
+//!   the control, an axis takes a new length along with its next
+//!   programmed move.
+//!
+//! With an empty tool table, T, D and M6 change no position.
+
+use crate::arcs::GEOMETRY_AXES;
+use crate::errors::ParsingError;
+use crate::output::OutputRows;
+use crate::state::State;
+use crate::types::Value;
+use std::collections::HashMap;
+
+/// The tool offset system variables and the offset parameter they set.
+pub(crate) const OFFSET_VARIABLES: [&str; 4] = ["$TC_DP3", "$TC_DP4", "$TC_DP5", "$TC_DP6"];
+
+/// The offset data of one D number of a tool.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Offset {
+    /// The lengths 1..3 (`$TC_DP3`..`$TC_DP5`).
+    pub(crate) lengths: [f64; 3],
+    /// The radius (`$TC_DP6`).
+    pub(crate) radius: f64,
+}
+
+/// A tool as `T` programs it.
+#[derive(Debug, Clone, PartialEq)]
+enum Selection {
+    Number(usize),
+    Name(String),
+}
+
+/// The tool table and the tool the program selected and loaded.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Tools {
+    /// The offset data by tool and D number.
+    offsets: HashMap<(usize, usize), Offset>,
+    /// The tool numbers by name (`$TC_TP2`).
+    names: HashMap<String, usize>,
+    /// The T word of the current block.
+    programmed: Option<Selection>,
+    /// The tool the last T word selected, and the one M6 loaded.
+    selected: Option<Selection>,
+    loaded: Option<Selection>,
+    /// The tool length offset of the machine positions, per X/Y/Z slot.
+    pub(crate) length: [f64; 3],
+}
+
+impl Tools {
+    /// Set offset parameter `variable` (one of [`OFFSET_VARIABLES`]) of
+    /// `D<number>` of tool `tool`.
+    pub(crate) fn set_offset(&mut self, variable: usize, tool: usize, number: usize, value: f64) {
+        let offset = self.offsets.entry((tool, number)).or_default();
+        match variable {
+            3 => offset.radius = value,
+            length => offset.lengths[length] = value,
+        }
+    }
+
+    /// Name tool `tool` (`$TC_TP2[tool] = "name"`).
+    pub(crate) fn set_name(&mut self, tool: usize, name: String) {
+        self.names.insert(name, tool);
+    }
+
+    /// Select tool `T<number>` (a T word of the current block).
+    pub(crate) fn select_number(&mut self, number: usize) {
+        self.programmed = Some(Selection::Number(number));
+    }
+
+    /// Select the tool named `name` (`T="name"` in the current block).
+    pub(crate) fn select_name(&mut self, name: String) {
+        self.programmed = Some(Selection::Name(name));
+    }
+
+    /// The offset data of `D<number>` (from 1) of the loaded tool, `None` if
+    /// the table has none. Without a loaded tool, that of the one tool the
+    /// table gives `D<number>`.
+    pub(crate) fn offset(&self, number: usize) -> Result<Option<Offset>, String> {
+        let tool = match &self.loaded {
+            Some(Selection::Number(tool)) => *tool,
+            Some(Selection::Name(name)) => match self.names.get(name) {
+                Some(&tool) => tool,
+                None => {
+                    return Err(format!(
+                        "tool \"{name}\" has no tool number; name one with $TC_TP2[<t>]=\"{name}\""
+                    ))
+                }
+            },
+            None => {
+                let mut owners: Vec<usize> = self
+                    .offsets
+                    .keys()
+                    .filter(|&&(_, d)| d == number)
+                    .map(|&(tool, _)| tool)
+                    .collect();
+                owners.sort_unstable();
+                match owners.as_slice() {
+                    [] => return Ok(None),
+                    [tool] => *tool,
+                    _ => {
+                        let tools: Vec<String> = owners.iter().map(usize::to_string).collect();
+                        return Err(format!(
+                            "D{number} is in the table for tools {}; load one with T<t> M6",
+                            tools.join(", ")
+                        ));
+                    }
+                }
+            }
+        };
+        Ok(self.offsets.get(&(tool, number)).copied())
+    }
+}
+
+/// Take the tool number `T<value>` (or `T=<value>`) of the current block,
+/// which [`finish_block`] applies; false for any other word.
+pub(crate) fn take_word(key: &str, value: f64, state: &mut State) -> bool {
+    if !key.eq_ignore_ascii_case("T") || state.is_axis("T") {
+        return false;
+    }
+    if value >= 0.0 && value.fract() == 0.0 {
+        state.tools.select_number(value as usize);
+    }
+    true
+}
+
+/// The error for a D number without offset data.
+pub(crate) fn missing_offset(number: f64) -> String {
+    format!(
+        "D{number} has no tool offset data; set $TC_DP3..$TC_DP6[<tool>, {number}] (e.g. in the initial-state file)"
+    )
+}
+
+/// Apply the block's T, M6 and D once all its words ran: a changed tool
+/// length moves the axes the block programmed by the difference.
+pub(crate) fn finish_block(line_no: usize, output: &mut OutputRows, state: &mut State) -> Result<(), ParsingError> {
+    let last = output.last_mut().expect("row started");
+    let programmed = state.tools.programmed.take();
+    let change =
+        matches!(last.get("M"), Some(Value::StrList(codes)) if codes.iter().any(|code| code == "M6" || code == "M06"));
+    if programmed.is_none() && !change && state.tools.offsets.is_empty() && state.tools.length == [0.0; 3] {
+        return Ok(());
+    }
+    if let Some(selection) = programmed {
+        if let Selection::Number(number) = selection {
+            last.insert("T", Value::Str(number.to_string()));
+        }
+        state.tools.selected = Some(selection);
+    }
+    if change {
+        state.tools.loaded = match state.tools.selected.clone() {
+            Some(Selection::Number(0)) => None,
+            selected => selected,
+        };
+    }
+
+    let error = |message: String| {
+        ParsingError::with_context(
+            line_no,
+            state.get_line(line_no).unwrap_or("").to_string(),
+            "tool offset (T/D)".to_string(),
+            message,
+        )
+    };
+    let lengths = match state.get_axis_local("D") {
+        None | Some(0.0) => [0.0; 3],
+        Some(number) if number < 0.0 || number.fract() != 0.0 => {
+            return Err(error(format!("D{number} is not a tool offset number")))
+        }
+        Some(number) => match state.tools.offset(number as usize).map_err(&error)? {
+            Some(offset) => offset.lengths,
+            None if state.tools.offsets.is_empty() => [0.0; 3],
+            None => return Err(error(missing_offset(number))),
+        },
+    };
+    let [abscissa, ordinate, applicate] = state.plane.axes();
+    let mut length = [0.0; 3];
+    length[applicate] = lengths[0];
+    length[ordinate] = lengths[1];
+    length[abscissa] = lengths[2];
+    if length != state.tools.length {
+        state.tools.length = length;
+        // The axes this block output already went out with the old length.
+        let last = output.last_mut().expect("row started");
+        for axis in GEOMETRY_AXES {
+            if let (Some(_), Some(machine)) = (last.get(axis), state.get_axis_machine(axis)) {
+                last.insert(axis, Value::Float(machine));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,185 @@
+//! Inch and metric dimensions (`G70`/`G71`/`G700`/`G710`, manual 10.5).
+//!
+//! The output is in one unit, the basic system: millimetres, or inches if
+//! the caller asks for them. The program states the unit it is written in,
+//! and the values it programs are converted to the basic system:
+//!
+//! * `G70` (inch) and `G71` (metric) convert the geometry: the positions of
+//!   the linear axes (the rotary axes A, B and C are in degrees), the circle
+//!   words `I`/`J`/`K` (also the thread lead), `I1`/`J1`/`K1` and `CR`, the
+//!   polar radius `RP`, the `PO[]` coefficients of an axis, the programmable
+//!   offsets of `TRANS`/`ATRANS` and the lead change `F` of G34/G35. The
+//!   feed `F` stays in the basic system (mm/min or inch/min).
+//! * `G700` (inch) and `G710` (metric) convert the technological data as
+//!   well: the feed `F`, the tool offsets `$TC_DP3`..`$TC_DP6`, the settable
+//!   offsets `$P_UIFR` and the actual positions read from `$AA_IW`/`$AA_IM`.
+//!
+//! The dwell time `F` of a G4 block is never converted. Until the program
+//! selects a unit, it is written in the basic system.
+
+use crate::output::OutputRows;
+use crate::state::{State, NON_GEOMETRIC_AXES};
+use crate::types::Value;
+
+/// The rotary axes, whose positions are angles.
+const ROTARY_AXES: [&str; 3] = ["A", "B", "C"];
+
+/// The lengths among the addresses other than axes.
+const LENGTH_ADDRESSES: [&str; 8] = ["I", "J", "K", "I1", "J1", "K1", "CR", "RP"];
+
+/// A unit of length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Unit {
+    #[default]
+    Millimetre,
+    Inch,
+}
+
+impl Unit {
+    /// The unit called `name` (`mm` or `inch`, any case).
+    pub fn from_name(name: &str) -> Option<Unit> {
+        match name.to_ascii_lowercase().as_str() {
+            "mm" => Some(Unit::Millimetre),
+            "inch" => Some(Unit::Inch),
+            _ => None,
+        }
+    }
+
+    /// The name [`Unit::from_name`] takes.
+    pub fn name(self) -> &'static str {
+        match self {
+            Unit::Millimetre => "mm",
+            Unit::Inch => "inch",
+        }
+    }
+
+    fn millimetres(self) -> f64 {
+        match self {
+            Unit::Millimetre => 1.0,
+            Unit::Inch => 25.4,
+        }
+    }
+}
+
+/// The basic system and the unit the program is written in.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Units {
+    /// The unit of the output.
+    pub(crate) output: Unit,
+    /// The unit of G70/G71 (G700/G710).
+    programmed: Unit,
+    /// True under G700/G710.
+    technology: bool,
+}
+
+impl Units {
+    /// Output in `unit`, with the program written in it too.
+    pub(crate) fn new(unit: Unit) -> Self {
+        Units {
+            output: unit,
+            programmed: unit,
+            technology: false,
+        }
+    }
+
+    /// Switch the program's unit if `code` is G70, G71, G700 or G710.
+    pub(crate) fn select(&mut self, code: &str) {
+        (self.programmed, self.technology) = match code.to_ascii_uppercase().as_str() {
+            "G70" => (Unit::Inch, false),
+            "G71" => (Unit::Millimetre, false),
+            "G700" => (Unit::Inch, true),
+            "G710" => (Unit::Millimetre, true),
+            _ => return,
+        };
+    }
+
+    /// `value`, a length in the programmed unit, in the output unit.
+    fn geometry(&self, value: f64) -> f64 {
+        if self.programmed == self.output {
+            return value;
+        }
+        value * self.programmed.millimetres() / self.output.millimetres()
+    }
+
+    /// `value`, a length in the technological data, in the output unit
+    /// (unchanged under G70/G71).
+    pub(crate) fn data(&self, value: f64) -> f64 {
+        if self.technology {
+            self.geometry(value)
+        } else {
+            value
+        }
+    }
+
+    /// `value`, a length in the output unit, as the technological data
+    /// gives it: the inverse of [`Units::data`].
+    fn data_read(&self, value: f64) -> f64 {
+        if !self.technology || self.programmed == self.output {
+            return value;
+        }
+        value * self.output.millimetres() / self.programmed.millimetres()
+    }
+}
+
+/// True if `key` programs a length: a linear axis or one of
+/// [`LENGTH_ADDRESSES`].
+fn is_length(key: &str, state: &State) -> bool {
+    LENGTH_ADDRESSES.iter().any(|address| address.eq_ignore_ascii_case(key))
+        || (state.is_axis(key)
+            && !NON_GEOMETRIC_AXES.iter().any(|axis| axis.eq_ignore_ascii_case(key))
+            && !ROTARY_AXES.iter().any(|axis| axis.eq_ignore_ascii_case(key)))
+}
+
+/// A programmed `key = value` word in the output unit (unchanged for
+/// anything but a length). The feed is converted by [`finish_block`].
+pub(crate) fn geometry(key: &str, value: f64, state: &State) -> f64 {
+    if state.units.programmed != state.units.output && is_length(key, state) {
+        state.units.geometry(value)
+    } else {
+        value
+    }
+}
+
+/// A length of axis `key` in the technological data (a settable offset) in
+/// the output unit.
+pub(crate) fn data(key: &str, value: f64, state: &State) -> f64 {
+    if is_length(key, state) {
+        state.units.data(value)
+    } else {
+        value
+    }
+}
+
+/// The position `value` of axis `key` as an actual-position variable
+/// reads it.
+pub(crate) fn data_read(key: &str, value: f64, state: &State) -> f64 {
+    if is_length(key, state) {
+        state.units.data_read(value)
+    } else {
+        value
+    }
+}
+
+/// Convert the block's `F` once all its words ran: a G34/G35 lead change
+/// with the geometry, a feed under G700/G710, a dwell time never.
+pub(crate) fn finish_block(output: &mut OutputRows, state: &mut State) -> Result<(), crate::errors::ParsingError> {
+    let last = output.last_mut().expect("row started");
+    let Some(&Value::Float(programmed)) = last.get("F") else {
+        return Ok(());
+    };
+    let converted = if matches!(last.get("gg02_wait"), Some(Value::Str(code)) if code == "G4") {
+        programmed
+    } else if matches!(state.modal_word("gg01_motion"), Some("G34" | "G35")) {
+        state.units.geometry(programmed)
+    } else {
+        state.units.data(programmed)
+    };
+    if converted == programmed {
+        return Ok(());
+    }
+    last.insert("F", Value::Float(converted));
+    if state.is_axis("F") {
+        state.update_axis("F", converted)?;
+    }
+    Ok(())
+}
@@ -18,9 +18,10 @@
 //! beside the recursive path and can be diffed against it on the golden corpus.
 
 use super::{
-    evaluate_condition, evaluate_expression, get_error_context, interpret_assignment, interpret_block_number,
-    interpret_case, interpret_definition, interpret_frame_op, interpret_goto, interpret_if_goto, interpret_statement,
-    resolve_jump, scan_jump_targets, BlockFlow,
+    begin_block, evaluate_condition, evaluate_expression, finish_block, get_error_context, interpret_assignment,
+    interpret_block_number, interpret_case, interpret_define, interpret_definition, interpret_frame_op, interpret_goto,
+    interpret_if_goto, interpret_repeat_section, interpret_statement, resolve_jump, scan_jump_targets, BlockFlow,
+    Sections,
 };
 use crate::errors::ParsingError;
 use crate::output::OutputRows as Output;
@@ -51,6 +52,8 @@ struct Frame<'i> {
     /// Backward-jump cycle counter (bounded by `iteration_limit`), per scope —
     /// mirrors `run_blocks`' local `jumps_taken`.
     jumps_taken: usize,
+    /// Active section repetitions — mirrors `run_blocks`' local `sections`.
+    sections: Sections,
     kind: FrameKind<'i>,
 }
 
@@ -84,6 +87,8 @@ enum Step<'i> {
     EndProgram,
     /// A pending GOTO to resolve against the scope stack.
     Jump(super::JumpRequest),
+    /// A section repetition, resolved in the current scope.
+    Repeat(super::RepeatRequest),
     /// A structured control opened a body scope; push this frame.
     Enter(Box<Frame<'i>>),
 }
@@ -133,7 +138,10 @@ impl<'i> Vm<'i> {
             let top = self.stack.last().unwrap();
             let block = top.blocks[top.index].clone();
             match exec_block(block, output, state)? {
-                Step::Continue => self.stack.last_mut().unwrap().index += 1,
+                Step::Continue => {
+                    let top = self.stack.last_mut().unwrap();
+                    top.index = top.sections.advance(top.index);
+                }
                 Step::EndProgram => {
                     pop_all(&mut self.stack, state);
                     return Ok(Outcome::Done(BlockFlow::EndProgram));
@@ -142,6 +150,10 @@ impl<'i> Vm<'i> {
                 Step::Jump(request) => {
                     resolve_across_stack(&mut self.stack, request, state)?;
                 }
+                Step::Repeat(request) => {
+                    let top = self.stack.last_mut().unwrap();
+                    top.index = top.sections.enter(&top.targets, top.index, request, state)?;
+                }
             }
 
             if let Some(k) = stop_at_rows {
@@ -182,6 +194,7 @@ fn new_frame<'i>(blocks: Vec<Pair<'i, Rule>>, kind: FrameKind<'i>, state: &mut S
         targets,
         index: 0,
         jumps_taken: 0,
+        sections: Sections::default(),
         kind,
     }
 }
@@ -205,7 +218,7 @@ fn pop_frame(stack: &mut Vec<Frame>, state: &mut State) {
 fn pop_and_advance(stack: &mut Vec<Frame>, state: &mut State) {
     pop_frame(stack, state);
     if let Some(parent) = stack.last_mut() {
-        parent.index += 1;
+        parent.index = parent.sections.advance(parent.index);
     }
 }
 
@@ -304,7 +317,19 @@ fn exec_block<'i>(block: Pair<'i, Rule>, output: &mut Output, state: &mut State)
             message: format!("Expected a block, found {:?}", block.as_rule()),
         });
     }
-    output.start_row(block.line_col().0)?;
+    let line_no = block.line_col().0;
+    if let Some(expanded) = crate::macros::expand_block(block.as_str(), line_no, state)? {
+        return Ok(
+            match super::interpret_generated_block(&expanded, line_no, super::MACRO_EXPANSION, output, state)? {
+                BlockFlow::Continue => Step::Continue,
+                BlockFlow::EndProgram => Step::EndProgram,
+                BlockFlow::Jump(r) => Step::Jump(r),
+                BlockFlow::Repeat(r) => Step::Repeat(r),
+            },
+        );
+    }
+    output.start_row(line_no)?;
+    begin_block(&block, state);
 
     // Mirror `interpret_block`: process EVERY item, letting the control-flow
     // signal accumulate (last write wins, like `flow = interpret_control(...)`),
@@ -314,15 +339,16 @@ fn exec_block<'i>(block: Pair<'i, Rule>, output: &mut Output, state: &mut State)
     let mut flow = BlockFlow::Continue;
     for item in block.into_inner() {
         match item.as_rule() {
-            Rule::statement => {
-                if let BlockFlow::EndProgram = interpret_statement(item, output, state)? {
-                    flow = BlockFlow::EndProgram;
-                }
-            }
+            Rule::statement => match interpret_statement(item, output, state)? {
+                BlockFlow::Continue => {}
+                other => flow = other,
+            },
             Rule::block_number => interpret_block_number(item, output),
             Rule::label_def => {}
             Rule::definition => interpret_definition(item, output, state)?,
-            Rule::frame_op => interpret_frame_op(item, state)?,
+            Rule::define_statement => interpret_define(item, state)?,
+            Rule::proc_definition | Rule::extern_declaration => {}
+            Rule::frame_op => interpret_frame_op(item, output, state)?,
             Rule::comment => {
                 let last = output.last_mut().expect("row started");
                 last.insert("comment", Value::Str(item.as_str().to_string()));
@@ -343,10 +369,12 @@ fn exec_block<'i>(block: Pair<'i, Rule>, output: &mut Output, state: &mut State)
             }
         }
     }
+    finish_block(line_no, output, state)?;
     Ok(match flow {
         BlockFlow::Continue => Step::Continue,
         BlockFlow::EndProgram => Step::EndProgram,
         BlockFlow::Jump(r) => Step::Jump(r),
+        BlockFlow::Repeat(r) => Step::Repeat(r),
     })
 }
 
@@ -376,6 +404,7 @@ fn exec_control<'i>(
             Rule::goto_statement => interpret_goto(stmt, state)?,
             Rule::if_goto_statement => interpret_if_goto(stmt, state)?,
             Rule::case_statement => interpret_case(stmt, state)?,
+            Rule::repeat_section => interpret_repeat_section(stmt, state)?,
             Rule::gotos_statement => {
                 let (line_no, _) = get_error_context(&stmt, state);
                 crate::state::emit_warning(format_args!(
@@ -535,6 +564,7 @@ fn resolve_across_stack(
                     });
                 }
             }
+            top.sections.jumped(dest);
             top.index = dest;
             return Ok(());
         }
@@ -564,7 +594,7 @@ mod resume_tests {
             .collect()
     }
 
-    fn parse_blocks(input: &str) -> Pair<Rule> {
+    fn parse_blocks(input: &str) -> Pair<'_, Rule> {
         let file = NCParser::parse(Rule::file, input).expect("parse").next().expect("file");
         file.into_inner().next().expect("blocks")
     }
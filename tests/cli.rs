@@ -123,3 +123,34 @@ fn string_ops_program_runs() {
     let csv = std::fs::read_to_string(dir.join("program.csv")).expect("CSV output should exist");
     assert!(csv.lines().count() >= 2, "no rows produced");
 }
+
+/// `--subprogram-dir` (repeatable) gives the search path for called
+/// subprograms; the moves they program land in the caller's CSV.
+#[test]
+fn subprogram_dir_flag_resolves_calls() {
+    let dir = std::env::temp_dir().join("nc-cli-test-subprograms");
+    let library = dir.join("library");
+    std::fs::create_dir_all(&library).expect("create temp dir");
+    std::fs::write(library.join("L20.SPF"), "G1 X20 Y5\nM17\n").expect("write subprogram");
+    let input = dir.join("program.mpf");
+    std::fs::write(&input, "G1 X0 Y0 F100\nL20\nX30\n").expect("write input");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_nc-gcode-interpreter"))
+        .arg(&input)
+        .args(["--subprogram-dir", dir.join("empty").to_str().unwrap()])
+        .args(["--subprogram-dir", library.to_str().unwrap()])
+        .status()
+        .expect("binary should run");
+    assert!(status.success(), "CLI exited with {status}");
+    let csv = std::fs::read_to_string(dir.join("program.csv")).expect("CSV output should exist");
+    assert!(csv.contains("20.000,5.000"), "subprogram moves missing:\n{csv}");
+
+    // Without the flag, L20 stays an uninterpreted call as before.
+    let status = Command::new(env!("CARGO_BIN_EXE_nc-gcode-interpreter"))
+        .arg(&input)
+        .status()
+        .expect("binary should run");
+    assert!(status.success(), "CLI exited with {status}");
+    let csv = std::fs::read_to_string(dir.join("program.csv")).expect("CSV output should exist");
+    assert!(!csv.contains("20.000,5.000"), "no subprogram should run:\n{csv}");
+}